@@ -0,0 +1,4 @@
+//! Builds the C ABI of makepkg as a shared library. See `include/makepkg.h` for the C
+//! declarations.
+
+pub use makepkg::ffi::*;
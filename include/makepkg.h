@@ -0,0 +1,58 @@
+#ifndef MAKEPKG_H
+#define MAKEPKG_H
+
+#include <stddef.h>
+#include <stdint.h>
+
+#ifdef __cplusplus
+extern "C" {
+#endif
+
+/* These functions are exported by libmakepkg_ffi.so, built from the makepkg-ffi crate. */
+
+typedef struct Makepkg Makepkg;
+typedef struct Pkgbuild Pkgbuild;
+
+#define MAKEPKG_CLEAN_BUILD (1u << 0)
+#define MAKEPKG_NO_CHECK (1u << 1)
+#define MAKEPKG_IGNORE_ARCH (1u << 2)
+#define MAKEPKG_SKIP_INTEG (1u << 3)
+#define MAKEPKG_FORCE (1u << 4)
+#define MAKEPKG_NO_ARCHIVE (1u << 5)
+#define MAKEPKG_LOG (1u << 6)
+
+#define MAKEPKG_LOG_DEBUG 0
+#define MAKEPKG_LOG_WARNING 1
+#define MAKEPKG_LOG_ERROR 2
+
+typedef void (*makepkg_event_fn)(void *data, const char *msg);
+typedef void (*makepkg_log_fn)(void *data, int level, const char *msg);
+typedef void (*makepkg_output_fn)(void *data, const uint8_t *output, size_t len);
+
+/* The message of the last error on this thread, valid until the next failing call. */
+const char *makepkg_last_error(void);
+
+/* config may be NULL to use the default makepkg.conf. Returns NULL on error. */
+Makepkg *makepkg_new(const char *config);
+void makepkg_free(Makepkg *makepkg);
+/* The callbacks are called on whichever thread the makepkg handle is used from. */
+void makepkg_set_callbacks(Makepkg *makepkg, makepkg_event_fn event, makepkg_log_fn log,
+                           makepkg_output_fn output, void *data);
+
+/* Returns NULL on error. */
+Pkgbuild *makepkg_pkgbuild_load(const Makepkg *makepkg, const char *dir);
+void makepkg_pkgbuild_free(Pkgbuild *pkgbuild);
+
+/* Free the result with makepkg_string_free. */
+char *makepkg_srcinfo(const Pkgbuild *pkgbuild);
+void makepkg_string_free(char *s);
+
+/* Return 0 on success and -1 on error. */
+int makepkg_verify_sources(const Makepkg *makepkg, const Pkgbuild *pkgbuild);
+int makepkg_build(const Makepkg *makepkg, Pkgbuild *pkgbuild, uint32_t flags);
+
+#ifdef __cplusplus
+}
+#endif
+
+#endif
@@ -0,0 +1,201 @@
+use std::{
+    fs::read_dir,
+    path::{Path, PathBuf},
+};
+
+use sha2::{Digest, Sha256};
+
+use crate::{
+    callback::Event,
+    error::{Context, IOContext, IOErrorExt, Result},
+    fs::{copy, mkdir, rename, rm_all},
+    integ::{finalize, get_sum_array, hash_file},
+    options::Options,
+    pacman::buildinfo_installed,
+    pkgbuild::{Function, Pkgbuild},
+    Makepkg,
+};
+
+// Installed packages whose version changes what the compiler produces.
+static TOOLCHAIN: &[&str] = &[
+    "binutils", "clang", "gcc", "gcc-libs", "glibc", "go", "lld", "llvm", "rust",
+];
+
+impl Makepkg {
+    /// Copies the packages of a previous build of the same PKGBUILD and toolchain from
+    /// [`Config::artifact_cache`](crate::config::Config::artifact_cache) into their
+    /// destination directories.
+    ///
+    /// Returns whether the packages were found.
+    pub(crate) fn restore_cached_packages(
+        &self,
+        options: &Options,
+        pkgbuild: &Pkgbuild,
+        entry: Option<&Path>,
+    ) -> Result<bool> {
+        let Some(entry) = entry else {
+            return Ok(false);
+        };
+        if options.rebuild || !entry.exists() {
+            return Ok(false);
+        }
+
+        let files =
+            read_dir(entry).context(Context::BuildPackage, IOContext::ReadDir(entry.into()))?;
+        for file in files {
+            let file = file.context(Context::BuildPackage, IOContext::ReadDir(entry.into()))?;
+            let name = file.file_name();
+            let name = name.to_string_lossy();
+            if !name.ends_with(".sig") {
+                self.event(Event::UsingCachedPackage(&name))?;
+            }
+            let dest = self.config.package_file_dest(pkgbuild, &name)?;
+            if let Some(dir) = dest.parent().filter(|d| !d.exists()) {
+                mkdir(dir, Context::BuildPackage)?;
+            }
+            copy(file.path(), dest, Context::BuildPackage)?;
+        }
+        Ok(true)
+    }
+
+    /// Stores the freshly built packages in the artifact cache.
+    pub(crate) fn cache_packages(&self, pkgbuild: &Pkgbuild, entry: Option<&Path>) -> Result<()> {
+        let Some(entry) = entry else {
+            return Ok(());
+        };
+
+        // fill a temporary directory first so an interrupted copy is never used
+        let tmp = entry.with_extension("tmp");
+        if tmp.exists() {
+            rm_all(&tmp, Context::BuildPackage)?;
+        }
+        mkdir(&tmp, Context::BuildPackage)?;
+        let packages = self.config.package_list(pkgbuild)?;
+        // the debug package is only created if there was something to put in it
+        for package in packages.iter().map(|p| &p.path).filter(|p| p.exists()) {
+            let name = file_name(package);
+            copy(package, tmp.join(name), Context::BuildPackage)?;
+            let sig = PathBuf::from(format!("{}.sig", package.display()));
+            if sig.exists() {
+                copy(
+                    &sig,
+                    tmp.join(format!("{}.sig", name)),
+                    Context::BuildPackage,
+                )?;
+            }
+        }
+
+        if entry.exists() {
+            rm_all(entry, Context::BuildPackage)?;
+        }
+        rename(&tmp, entry, Context::BuildPackage)
+    }
+
+    /// The cache directory for this build. Worked out before dependencies are installed so
+    /// the toolchain matches the one seen by later lookups.
+    ///
+    /// pkgver(), VCS sources and remote sources with a `SKIP` checksum can produce a different
+    /// package from the same PKGBUILD so those are never cached. Neither is anything when the
+    /// installed toolchain can not be read.
+    pub(crate) fn artifact_cache_entry(
+        &self,
+        options: &Options,
+        pkgbuild: &Pkgbuild,
+    ) -> Result<Option<PathBuf>> {
+        let Some(cache) = &self.config.artifact_cache else {
+            return Ok(None);
+        };
+        if options.no_build || options.no_package || options.no_archive {
+            return Ok(None);
+        }
+        if pkgbuild.has_function(Function::Pkgver) || self.has_unpinned_source(pkgbuild) {
+            return Ok(None);
+        }
+        Ok(self.artifact_key(pkgbuild)?.map(|key| cache.join(key)))
+    }
+
+    // sources whose content is not fixed by the PKGBUILD
+    fn has_unpinned_source(&self, pkgbuild: &Pkgbuild) -> bool {
+        let arch = &self.config.arch;
+        pkgbuild
+            .source
+            .values
+            .iter()
+            .filter(|v| v.enabled(arch))
+            .take(2)
+            .any(|sources| {
+                let sums = pkgbuild
+                    .get_all_checksums()
+                    .map(|(_, a)| get_sum_array(a, &sources.arch));
+                sources.values.iter().enumerate().any(|(n, source)| {
+                    source.vcs_kind().is_some()
+                        || source.is_remote()
+                            && sums.iter().filter_map(|v| v.get(n)).all(|v| v == "SKIP")
+                })
+            })
+    }
+
+    fn artifact_key(&self, pkgbuild: &Pkgbuild) -> Result<Option<String>> {
+        let config = &self.config;
+        let mut digest = Sha256::new();
+        digest.update(pkgbuild.srcinfo());
+
+        let dir = &pkgbuild.dir;
+        let local = pkgbuild
+            .source
+            .enabled(&config.arch)
+            .filter(|s| !s.is_remote())
+            .map(|s| s.file_name());
+        let files = pkgbuild
+            .packages()
+            .flat_map(|p| [&p.install, &p.changelog])
+            .chain([&pkgbuild.install, &pkgbuild.changelog])
+            .flatten()
+            .map(|f| f.as_str());
+        for file in [Pkgbuild::file_name()]
+            .into_iter()
+            .chain(local)
+            .chain(files)
+        {
+            let hash = hash_file::<Sha256>(&dir.join(file))?;
+            digest.update(format!("{}={}\n", file, hash));
+        }
+
+        for (name, value) in [
+            ("CARCH", &config.arch),
+            ("CHOST", &config.chost),
+            ("CPPFLAGS", &config.cppflags),
+            ("CFLAGS", &config.cflags),
+            ("CXXFLAGS", &config.cxxflags),
+            ("RUSTFLAGS", &config.rustflags),
+            ("LDFLAGS", &config.ldflags),
+            ("LTOFLAGS", &config.ltoflags),
+            ("PACKAGER", &config.packager),
+        ] {
+            digest.update(format!("{}={}\n", name, value));
+        }
+        digest.update(format!("BUILDENV={:?}\n", config.build_env.values));
+        digest.update(format!("OPTIONS={:?}\n", config.options.values));
+        digest.update(format!("PKGEXT={}\n", config.pkgext));
+
+        // without the toolchain versions a cached package could have been built by a
+        // different compiler
+        let Ok(installed) = buildinfo_installed(self, pkgbuild) else {
+            return Ok(None);
+        };
+        for package in installed {
+            let name = package.rsplitn(3, '-').nth(2).unwrap_or_default();
+            if TOOLCHAIN.contains(&name) {
+                digest.update(format!("installed={}\n", package));
+            }
+        }
+
+        Ok(Some(finalize(digest)))
+    }
+}
+
+fn file_name(path: &Path) -> &str {
+    path.file_name()
+        .and_then(|n| n.to_str())
+        .unwrap_or_default()
+}
@@ -0,0 +1,258 @@
+use std::{
+    future::Future,
+    io,
+    process::ExitStatus,
+    sync::{Arc, Mutex},
+};
+
+use tokio::{runtime::Handle, task::spawn_blocking};
+
+use crate::{
+    build_many::BatchReport,
+    callback::{
+        Answer, Callbacks, CommandInfo, CommandKind, CommandOutput, DownloadEvent, Event, LogLevel,
+        LogMessage, PackageEvent, Question,
+    },
+    error::Result,
+    options::Options,
+    pkgbuild::Pkgbuild,
+    report::BuildReport,
+    Makepkg,
+};
+
+/// The async version of [`Callbacks`] used by [`AsyncMakepkg`].
+///
+/// The operation waits for each future to complete before continuing.
+pub trait AsyncCallbacks: std::fmt::Debug + Send + 'static {
+    fn event(&mut self, _event: Event) -> impl Future<Output = io::Result<()>> {
+        async { Ok(()) }
+    }
+    fn log(&mut self, _level: LogLevel, _msg: LogMessage) -> impl Future<Output = io::Result<()>> {
+        async { Ok(()) }
+    }
+
+    fn command_new(
+        &mut self,
+        _id: usize,
+        _kind: CommandKind,
+        _command: &CommandInfo,
+    ) -> impl Future<Output = io::Result<CommandOutput>> {
+        async { Ok(Default::default()) }
+    }
+    fn command_exit(
+        &mut self,
+        _id: usize,
+        _kind: CommandKind,
+        _status: Option<ExitStatus>,
+    ) -> impl Future<Output = io::Result<()>> {
+        async { Ok(()) }
+    }
+    fn command_output(
+        &mut self,
+        _id: usize,
+        _kind: CommandKind,
+        _output: &[u8],
+    ) -> impl Future<Output = io::Result<()>> {
+        async { Ok(()) }
+    }
+    fn command_stdin(
+        &mut self,
+        _id: usize,
+        _kind: CommandKind,
+    ) -> impl Future<Output = io::Result<bool>> {
+        async { Ok(true) }
+    }
+
+    fn download(
+        &mut self,
+        _pkgbuild: &Pkgbuild,
+        _event: DownloadEvent,
+    ) -> impl Future<Output = io::Result<()>> {
+        async { Ok(()) }
+    }
+
+    fn package(&mut self, _event: PackageEvent) -> impl Future<Output = io::Result<()>> {
+        async { Ok(()) }
+    }
+
+    fn hashing(
+        &mut self,
+        _file_name: &str,
+        _hashed: u64,
+        _total: u64,
+    ) -> impl Future<Output = io::Result<()>> {
+        async { Ok(()) }
+    }
+
+    /// Asks the user to make a decision. The default implementation always answers no.
+    fn question(&mut self, _question: Question) -> impl Future<Output = io::Result<Answer>> {
+        async { Ok(Answer::No) }
+    }
+}
+
+// operations run on tokio's blocking threads where it is fine to block on the callbacks
+#[derive(Debug)]
+struct BlockOn<C> {
+    callbacks: C,
+    handle: Handle,
+}
+
+impl<C: AsyncCallbacks> Callbacks for BlockOn<C> {
+    fn event(&mut self, event: Event) -> io::Result<()> {
+        self.handle.block_on(self.callbacks.event(event))
+    }
+    fn log(&mut self, level: LogLevel, msg: LogMessage) -> io::Result<()> {
+        self.handle.block_on(self.callbacks.log(level, msg))
+    }
+    fn command_new(
+        &mut self,
+        id: usize,
+        kind: CommandKind,
+        command: &CommandInfo,
+    ) -> io::Result<CommandOutput> {
+        self.handle
+            .block_on(self.callbacks.command_new(id, kind, command))
+    }
+    fn command_exit(
+        &mut self,
+        id: usize,
+        kind: CommandKind,
+        status: Option<ExitStatus>,
+    ) -> io::Result<()> {
+        self.handle
+            .block_on(self.callbacks.command_exit(id, kind, status))
+    }
+    fn command_output(&mut self, id: usize, kind: CommandKind, output: &[u8]) -> io::Result<()> {
+        self.handle
+            .block_on(self.callbacks.command_output(id, kind, output))
+    }
+    fn command_stdin(&mut self, id: usize, kind: CommandKind) -> io::Result<bool> {
+        self.handle.block_on(self.callbacks.command_stdin(id, kind))
+    }
+    fn download(&mut self, pkgbuild: &Pkgbuild, event: DownloadEvent) -> io::Result<()> {
+        self.handle
+            .block_on(self.callbacks.download(pkgbuild, event))
+    }
+    fn package(&mut self, event: PackageEvent) -> io::Result<()> {
+        self.handle.block_on(self.callbacks.package(event))
+    }
+    fn hashing(&mut self, file_name: &str, hashed: u64, total: u64) -> io::Result<()> {
+        self.handle
+            .block_on(self.callbacks.hashing(file_name, hashed, total))
+    }
+    fn question(&mut self, question: Question) -> io::Result<Answer> {
+        self.handle.block_on(self.callbacks.question(question))
+    }
+}
+
+/// Runs [`Makepkg`] operations on tokio's blocking thread pool.
+///
+/// Operations on the same AsyncMakepkg run one at a time. Create one per PKGBUILD to build
+/// concurrently.
+#[derive(Debug, Clone)]
+pub struct AsyncMakepkg {
+    makepkg: Arc<Mutex<Makepkg>>,
+}
+
+impl AsyncMakepkg {
+    pub fn new(makepkg: Makepkg) -> Self {
+        AsyncMakepkg {
+            makepkg: Arc::new(Mutex::new(makepkg)),
+        }
+    }
+
+    /// Sets the callbacks, replacing any set on the [`Makepkg`].
+    ///
+    /// The callbacks are run on the current tokio runtime so this must be called from within
+    /// one.
+    pub fn with_callbacks<C: AsyncCallbacks>(makepkg: Makepkg, callbacks: C) -> Self {
+        let handle = Handle::current();
+        Self::new(makepkg.callbacks(BlockOn { callbacks, handle }))
+    }
+
+    async fn run<T, F>(&self, f: F) -> T
+    where
+        T: Send + 'static,
+        F: FnOnce(&Makepkg) -> T + Send + 'static,
+    {
+        let makepkg = self.makepkg.clone();
+        spawn_blocking(move || {
+            let makepkg = makepkg.lock().unwrap_or_else(|e| e.into_inner());
+            f(&makepkg)
+        })
+        .await
+        .unwrap_or_else(|e| std::panic::resume_unwind(e.into_panic()))
+    }
+
+    /// See [`Makepkg::download_sources`].
+    pub async fn download_sources(
+        &self,
+        options: &Options,
+        pkgbuild: &Pkgbuild,
+        all: bool,
+    ) -> Result<()> {
+        let options = options.clone();
+        let pkgbuild = pkgbuild.clone();
+        self.run(move |m| m.download_sources(&options, &pkgbuild, all))
+            .await
+    }
+
+    /// See [`Makepkg::extract_sources`].
+    pub async fn extract_sources(
+        &self,
+        options: &Options,
+        pkgbuild: &Pkgbuild,
+        all: bool,
+    ) -> Result<()> {
+        let options = options.clone();
+        let pkgbuild = pkgbuild.clone();
+        self.run(move |m| m.extract_sources(&options, &pkgbuild, all))
+            .await
+    }
+
+    /// See [`Makepkg::update_pkgver`].
+    pub async fn update_pkgver(&self, options: &Options, pkgbuild: &mut Pkgbuild) -> Result<()> {
+        let options = options.clone();
+        let mut owned = pkgbuild.clone();
+        let (owned, res) = self
+            .run(move |m| {
+                let res = m.update_pkgver(&options, &mut owned);
+                (owned, res)
+            })
+            .await;
+        *pkgbuild = owned;
+        res
+    }
+
+    /// See [`Makepkg::build`].
+    pub async fn build(&self, options: &Options, pkgbuild: &mut Pkgbuild) -> Result<BuildReport> {
+        let options = options.clone();
+        let mut owned = pkgbuild.clone();
+        let (owned, res) = self
+            .run(move |m| {
+                let res = m.build(&options, &mut owned);
+                (owned, res)
+            })
+            .await;
+        *pkgbuild = owned;
+        res
+    }
+
+    /// See [`Makepkg::build_many`].
+    pub async fn build_many(
+        &self,
+        options: &Options,
+        pkgbuilds: &mut [Pkgbuild],
+    ) -> Result<BatchReport> {
+        let options = options.clone();
+        let mut owned = pkgbuilds.to_vec();
+        let (owned, res) = self
+            .run(move |m| {
+                let res = m.build_many(&options, &mut owned);
+                (owned, res)
+            })
+            .await;
+        pkgbuilds.clone_from_slice(&owned);
+        res
+    }
+}
@@ -9,11 +9,11 @@ pkgbuild_functions=(pkgver verify prepare build check package)
 
 pkgbuild_vars=( "${base_pkgbuild_vars[@]}" )
 
-conf_vars=(DLAGENTS VCSCLIENTS CARCH CHOST CPPFLAGS CFLAGS CXXFLAGS RUSTFLAGS LDFLAGS
+conf_vars=(DLAGENTS BUILD_SHELL LINT_LEVELS PACKAGE_CHECKS DOWNLOAD_HEADERS VCSCLIENTS CARCH CHOST CPPFLAGS CFLAGS CXXFLAGS RUSTFLAGS LDFLAGS
            LTOFLAGS MAKEFLAGS DEBUG_CFLAGS DEBUG_CXXFLAGS DEBUG_RUSTFLAGS BUILDENV
-           DISTCC_HOSTS BUILDDIR GPGKEY OPTIONS INTEGRITY_CHECK STRIP_BINARIES
+           DISTCC_HOSTS BUILDDIR BUILDDIR_CANDIDATES BUILDDIR_MIN_FREE TMPFS_BUILDDIR VCS_RETRIES GPGKEY KEYSERVER SSH_ALLOWED_SIGNERS EXTRACT_CACHE ARTIFACT_CACHE OPTIONS INTEGRITY_CHECK STRIP_BINARIES
            STRIP_SHARED STRIP_STATIC MAN_DIRS DOC_DIRS PURGE_TARGETS DBGSRCDIR
-           PKGDEST SRCDEST SRCPKGDEST LOGDEST PACKAGER COMPRESSGZ COMPRESSBZ2
+           PKGDEST DEBUG_PKGDEST PKGDEST_OVERRIDES SRCDEST SRCPKGDEST LOGDEST PACKAGER COMPRESSGZ COMPRESSBZ2
            COMPRESSXZ COMPRESSZST COMPRESSLRZ COMPRESSLZO COMPRESSZ COMPRESSLZ4 COMPRESSLZ
            PKGEXT SRCEXT PACMAN_AUTH)
 
@@ -49,7 +49,7 @@ escape() {
 	local val="$1"
 	val="${val//\\/\\\\}"
 	val="${val//\"/\\\"}"
-	val="${val//$'\n'/\\\\n}"
+	val="${val//$'\n'/\\n}"
 	printf -- "%s" "$val"
 }
 
@@ -197,7 +197,9 @@ dump_function_name() {
 	local funcname="$1"
 
 	if have_function "$funcname"; then
-		printf -- "FUNCTION %s\n" "$funcname"
+		local name line file
+		read -r name line file < <(shopt -s extdebug; declare -F "$funcname")
+		printf -- 'FUNCTION %s %s "%s"\n' "$funcname" "$line" "$(escape "$(declare -f "$funcname")")"
 	fi
 }
 
@@ -232,7 +234,14 @@ run_function() {
 	export -n startdir srcdir pkgdir CARCH
 
 	cd_safe "$workingdir"
-	"$pkgfunc"
+
+	if [[ -n $MAKEPKG_STDIN_FD ]]; then
+		local stdin_fd=$MAKEPKG_STDIN_FD
+		unset MAKEPKG_STDIN_FD
+		"$pkgfunc" <&"$stdin_fd"
+	else
+		"$pkgfunc"
+	fi
 }
 
 dump_config() {
@@ -1,18 +1,53 @@
+use std::{
+    path::Path,
+    time::{Instant, SystemTime},
+};
+
 use nix::sys::stat::{umask, Mode};
 
 use crate::{
     callback::Event,
-    error::{AlreadyBuiltError, ArchitectureError, Context, Result},
-    fs::{mkdir, rm_all},
+    config::PackageCheck,
+    error::{AlreadyBuiltError, ArchitectureError, Context, IOContext, IOErrorExt, Result},
+    fs::mkdir,
+    host::HostEnvironment,
     options::Options,
     package::PackageKind,
     pkgbuild::{Function, Pkgbuild},
+    report::{BuildPhase, BuildReport},
     Makepkg,
 };
 
 impl Makepkg {
-    pub fn build(&self, options: &Options, pkgbuild: &mut Pkgbuild) -> Result<()> {
+    /// Builds the packages of a [`Pkgbuild`] and returns how long each phase of the build took
+    /// along with the packages it produced.
+    pub fn build(&self, options: &Options, pkgbuild: &mut Pkgbuild) -> Result<BuildReport> {
+        #[cfg(feature = "tracing")]
+        let _span = tracing::info_span!(
+            "build",
+            pkgbase = pkgbuild.pkgbase,
+            version = pkgbuild.version()
+        )
+        .entered();
+        let started = Instant::now();
+        *self.report.borrow_mut() = Some(BuildReport::default());
+        let res = self.build_inner(options, pkgbuild);
+        let mut report = self.report.borrow_mut().take().unwrap_or_default();
+        report.total = started.elapsed();
+
+        let res = res.and_then(|_| self.event(Event::BuildSummary(&report)));
+        if let Err(e) = &res {
+            let _ = self.build_log_line(&format!("error: {}", e));
+        }
+        self.close_build_log();
+        let unmounted = self.unmount_tmpfs();
+        res.and(unmounted)?;
+        Ok(report)
+    }
+
+    fn build_inner(&self, options: &Options, pkgbuild: &mut Pkgbuild) -> Result<()> {
         umask(Mode::from_bits_truncate(0o022));
+        let started = SystemTime::now();
 
         self.event(Event::BuildingPackage(
             &pkgbuild.pkgbase,
@@ -20,6 +55,7 @@ impl Makepkg {
         ))?;
 
         let config = &self.config;
+        *self.host.borrow_mut() = Some(HostEnvironment::collect());
 
         if !options.ignore_arch && !self.arch_supported(pkgbuild) {
             return Err(ArchitectureError {
@@ -33,56 +69,157 @@ impl Makepkg {
             self.err_if_built(options, pkgbuild)?;
         }
 
+        let cache_entry = self.artifact_cache_entry(options, pkgbuild)?;
+        if self.restore_cached_packages(options, pkgbuild, cache_entry.as_deref())? {
+            self.record_built_packages(pkgbuild)?;
+            self.event(Event::BuiltPackage(
+                &pkgbuild.pkgbase,
+                &config.package_version(pkgbuild),
+            ))?;
+            if options.install {
+                self.install_packages(options, pkgbuild)?;
+            }
+            return Ok(());
+        }
+
+        let installed_deps = if options.sync_deps && !options.no_deps {
+            self.sync_deps(options, pkgbuild)?
+        } else {
+            Vec::new()
+        };
+
+        // dependencies are removed even if the build failed
+        let res = self.build_with_deps(options, pkgbuild, started, cache_entry.as_deref());
+        let removed = if options.rm_deps {
+            self.remove_deps(options, &installed_deps)
+        } else {
+            Ok(())
+        };
+        res.and(removed)?;
+
+        if options.install && !options.no_archive {
+            self.install_packages(options, pkgbuild)?;
+        }
+
+        Ok(())
+    }
+
+    fn build_with_deps(
+        &self,
+        options: &Options,
+        pkgbuild: &mut Pkgbuild,
+        started: SystemTime,
+        cache_entry: Option<&Path>,
+    ) -> Result<()> {
+        let config = &self.config;
+
+        self.report_lints(pkgbuild, pkgbuild.lint_functions())?;
+
+        self.select_builddir(pkgbuild)?;
         let dirs = self.pkgbuild_dirs(pkgbuild)?;
+        if !options.no_space_check {
+            self.check_free_space(options, &dirs, pkgbuild)?;
+        }
+        if options.build_log {
+            self.open_build_log(pkgbuild)?;
+            self.build_log_line(&format!(
+                "building {} {} for {}",
+                pkgbuild.pkgbase,
+                pkgbuild.version(),
+                config.arch
+            ))
+            .context(Context::BuildPackage, IOContext::WriteBuffer)?;
+        }
 
         if options.no_extract {
             self.event(Event::UsingExistingSrcdir)?;
         }
 
-        if !options.no_download {
-            self.download_sources(options, pkgbuild, false)?;
-            self.check_integ(options, pkgbuild, false)?;
+        let mut checkpoint = self.load_checkpoint(options, &dirs)?;
+
+        if !options.no_download && !checkpoint.done(BuildPhase::Verify) {
+            self.time_phase(BuildPhase::Download, || {
+                self.download_sources(options, pkgbuild, options.all_arch)
+            })?;
+            self.time_phase(BuildPhase::Verify, || {
+                self.check_integ(options, pkgbuild, options.all_arch)
+            })?;
+            checkpoint.complete(BuildPhase::Verify)?;
         }
+        self.err_if_unchanged(options, &dirs, pkgbuild)?;
 
-        if options.clean_build && dirs.srcdir.exists() {
+        let extracted = checkpoint.done(BuildPhase::Extract);
+        if options.clean_build && !extracted && dirs.srcdir.exists() {
             self.event(Event::RemovingSrcdir)?;
-            rm_all(&dirs.srcdir, Context::BuildPackage)?;
+            self.remove_build_dir(&dirs, &dirs.srcdir, Context::BuildPackage)?;
         }
-        mkdir(&dirs.srcdir, Context::BuildPackage)?;
+        self.create_build_dir(&dirs.srcdir, Context::BuildPackage)?;
 
-        if !options.no_extract {
+        if !options.no_extract && !extracted {
             self.extract_sources(options, pkgbuild, false)?;
+            checkpoint.complete(BuildPhase::Extract)?;
         }
 
         self.update_pkgver(options, pkgbuild)?;
+        checkpoint.update_pkgbuild()?;
         self.err_if_built(options, pkgbuild)?;
 
         if dirs.pkgdir.exists() && !options.keep_pkg {
             self.event(Event::RemovingPkgdir)?;
-            rm_all(&dirs.pkgdir, Context::BuildPackage)?;
+            self.remove_build_dir(&dirs, &dirs.pkgdir, Context::BuildPackage)?;
         }
+        self.create_build_dir(&dirs.pkgdir, Context::BuildPackage)?;
         for pkg in pkgbuild.packages() {
-            mkdir(&dirs.pkgdir(pkg), Context::BuildPackage)?;
+            mkdir(dirs.pkgdir(pkg), Context::BuildPackage)?;
         }
 
+        let checked = !options.no_build
+            && (config.option(pkgbuild, "check").enabled()
+                || (config.build_option(pkgbuild, "check").enabled() && !options.no_check));
+
         if !options.no_build {
-            self.run_function(options, pkgbuild, Function::Build)?;
-            if config.option(pkgbuild, "check").enabled()
-                || (config.build_option(pkgbuild, "check").enabled() && !options.no_check)
-            {
-                self.run_function(options, pkgbuild, Function::Check)?;
+            if !checkpoint.done(BuildPhase::Build) {
+                self.time_phase(BuildPhase::Build, || {
+                    self.run_function(options, pkgbuild, Function::Build)
+                })?;
+                checkpoint.complete(BuildPhase::Build)?;
+            }
+            if checked && !checkpoint.done(BuildPhase::Check) {
+                self.time_phase(BuildPhase::Check, || {
+                    self.run_function(options, pkgbuild, Function::Check)
+                })?;
+                checkpoint.complete(BuildPhase::Check)?;
             }
         }
 
         if !options.no_package {
-            self.run_function(options, pkgbuild, Function::Package)?;
+            self.time_phase(BuildPhase::Package, || {
+                self.run_function(options, pkgbuild, Function::Package)
+            })?;
         }
 
         if !options.no_archive {
-            for pkg in pkgbuild.packages() {
-                self.create_package(&dirs, options, pkgbuild, pkg, false)?;
+            self.time_phase(BuildPhase::Archive, || {
+                for pkg in pkgbuild.packages() {
+                    self.create_package(&dirs, options, pkgbuild, pkg, false)?;
+                }
+                Ok(())
+            })?;
+            if config.package_check_enabled(PackageCheck::DuplicateFiles) {
+                self.report_duplicate_files(&dirs, pkgbuild)?;
             }
-            self.event(Event::BuiltPackage(&pkgbuild.pkgbase, &pkgbuild.version()))?;
+            self.event(Event::BuiltPackage(
+                &pkgbuild.pkgbase,
+                &config.package_version(pkgbuild),
+            ))?;
+            self.record_built_packages(pkgbuild)?;
+            self.save_build_key(&dirs, pkgbuild)?;
+            self.cache_packages(pkgbuild, cache_entry)?;
+        }
+        checkpoint.finish()?;
+
+        if !options.no_build && config.package_check_enabled(PackageCheck::UnusedDepends) {
+            self.suggest_unused_depends(&dirs, pkgbuild, started, checked)?;
         }
 
         Ok(())
@@ -104,19 +241,13 @@ impl Makepkg {
     }
 
     pub fn is_pkg_built(&self, pkgbuild: &Pkgbuild) -> Result<bool> {
-        let dirs = self.pkgbuild_dirs(pkgbuild)?;
-        let ver = pkgbuild.version();
-
-        for pkg in pkgbuild.pkgnames() {
-            let name = format!("{}-{}-{}{}", pkg, ver, self.config.arch, self.config.pkgext);
-            let path = dirs.pkgdest.join(name);
-
-            if !path.exists() {
-                return Ok(false);
-            }
-        }
-
-        Ok(true)
+        // the debug package may legitimately not exist
+        Ok(self
+            .config
+            .package_list(pkgbuild)?
+            .iter()
+            .filter(|p| p.kind == PackageKind::Package)
+            .all(|p| p.path.exists()))
     }
 
     pub fn err_if_srcpkg_built(&self, options: &Options, pkgbuild: &Pkgbuild) -> Result<()> {
@@ -0,0 +1,100 @@
+use std::path::Path;
+
+use walkdir::WalkDir;
+
+use crate::{
+    callback::Event,
+    config::PkgbuildDirs,
+    error::{Context, IOContext, IOErrorExt, Result, UnsafeDeleteError, UnsafeDeleteReason},
+    fs::{mkdir, rm_all, write},
+    pkgbuild::Pkgbuild,
+    Makepkg,
+};
+
+/// Written into srcdir and pkgdir when they are created so that makepkg only ever deletes
+/// directories it made. srcdir and pkgdir are also deleted without it when they are at their
+/// usual path inside the builddir.
+pub(crate) static DIR_MARKER: &str = ".makepkg-dir";
+
+impl Makepkg {
+    /// Deletes the srcdir and pkgdir of a previous build if they exist.
+    pub fn clean_build_dirs(&self, pkgbuild: &Pkgbuild) -> Result<()> {
+        let dirs = self.pkgbuild_dirs(pkgbuild)?;
+        for dir in [&dirs.srcdir, &dirs.pkgdir] {
+            if dir.exists() {
+                self.remove_build_dir(&dirs, dir, Context::CleanBuildDirs)?;
+            }
+        }
+        Ok(())
+    }
+
+    /// Creates srcdir or pkgdir and marks it as safe to delete.
+    pub(crate) fn create_build_dir(&self, path: &Path, context: Context) -> Result<()> {
+        mkdir(path, context.clone())?;
+        let marker = path.join(DIR_MARKER);
+        if !marker.exists() {
+            write(&marker, "", context)?;
+        }
+        Ok(())
+    }
+
+    /// Deletes srcdir or pkgdir after checking it is a directory makepkg created inside of
+    /// the builddir.
+    pub(crate) fn remove_build_dir(
+        &self,
+        dirs: &PkgbuildDirs,
+        path: &Path,
+        context: Context,
+    ) -> Result<()> {
+        check_removable(dirs, path, context.clone())?;
+
+        let size = dir_size(path);
+        rm_all(path, context)?;
+        self.event(Event::RemovedDir(path, size))?;
+        Ok(())
+    }
+}
+
+fn check_removable(dirs: &PkgbuildDirs, path: &Path, context: Context) -> Result<()> {
+    let err = |reason| UnsafeDeleteError {
+        path: path.to_path_buf(),
+        reason,
+    };
+
+    let metadata = path
+        .symlink_metadata()
+        .context(context.clone(), IOContext::Stat(path.into()))?;
+    if metadata.file_type().is_symlink() {
+        return Err(err(UnsafeDeleteReason::Symlink).into());
+    }
+
+    let builddir = dirs
+        .builddir
+        .canonicalize()
+        .context(context.clone(), IOContext::Stat(dirs.builddir.clone()))?;
+    let real = path
+        .canonicalize()
+        .context(context, IOContext::Stat(path.into()))?;
+    if real == builddir || !real.starts_with(&builddir) {
+        return Err(err(UnsafeDeleteReason::OutsideBuilddir).into());
+    }
+
+    // srcdir and pkgdir left by versions of makepkg that did not write the marker are
+    // still removed as long as they are at their usual path
+    let canonical = path == dirs.srcdir || path == dirs.pkgdir;
+    if !canonical && !path.join(DIR_MARKER).exists() {
+        return Err(err(UnsafeDeleteReason::MissingMarker).into());
+    }
+
+    Ok(())
+}
+
+fn dir_size(path: &Path) -> u64 {
+    WalkDir::new(path)
+        .into_iter()
+        .filter_map(|f| f.ok())
+        .filter_map(|f| f.metadata().ok())
+        .filter(|m| !m.is_dir())
+        .map(|m| m.len())
+        .sum()
+}
@@ -0,0 +1,17 @@
+use std::{collections::BTreeMap, ffi::OsString};
+
+use crate::Makepkg;
+
+impl Makepkg {
+    /// Environment telling child processes not to colorize their output when the color
+    /// BUILDENV option is not enabled.
+    pub(crate) fn color_env(&self, envs: &mut BTreeMap<String, OsString>) {
+        if self.config.build_env("color").enabled() {
+            return;
+        }
+
+        envs.insert("NO_COLOR".into(), "1".into());
+        envs.insert("CLICOLOR".into(), "0".into());
+        envs.insert("TERM".into(), "dumb".into());
+    }
+}
@@ -1,12 +1,39 @@
 mod buildflags;
+mod color;
 mod compiler;
 mod debug;
 
-use std::{collections::BTreeMap, ffi::OsString, process::Command};
+use std::{collections::BTreeMap, ffi::OsString, path::Path, process::Command};
 
-use crate::{config::PkgbuildDirs, pkgbuild::Pkgbuild, Makepkg};
+use crate::{config::PkgbuildDirs, error::Result, pkgbuild::Pkgbuild, Makepkg};
 
 impl Makepkg {
+    /// The environment build(), check() and package() are run with.
+    ///
+    /// This is the build flags and tools from makepkg.conf and the PKGBUILD's options plus the
+    /// variables set for every function. `pkgdir` is set for the first package.
+    pub fn build_environment(&self, pkgbuild: &Pkgbuild) -> Result<BTreeMap<String, OsString>> {
+        let dirs = self.pkgbuild_dirs(pkgbuild)?;
+        let pkgname = pkgbuild.pkgnames().next().unwrap_or(&pkgbuild.pkgbase);
+        let mut env = self.generate_build_env(&dirs, pkgbuild);
+        env.extend(self.function_env(&dirs, &dirs.pkgdir.join(pkgname)));
+        Ok(env)
+    }
+
+    /// Variables set for every PKGBUILD function.
+    pub(crate) fn function_env(
+        &self,
+        dirs: &PkgbuildDirs,
+        pkgdir: &Path,
+    ) -> BTreeMap<String, OsString> {
+        let mut env = BTreeMap::new();
+        env.insert("CARCH".into(), self.config.arch.clone().into());
+        env.insert("startdir".into(), dirs.startdir.clone().into());
+        env.insert("srcdir".into(), dirs.srcdir.clone().into());
+        env.insert("pkgdir".into(), pkgdir.into());
+        env
+    }
+
     pub(crate) fn build_env(
         &self,
         dirs: &PkgbuildDirs,
@@ -28,6 +55,7 @@ impl Makepkg {
         self.compiler(dirs, pkgbuild, &mut env);
         self.build_flags(dirs, pkgbuild, &mut env);
         self.debug_flags(dirs, pkgbuild, &mut env);
+        self.color_env(&mut env);
         env
     }
 }
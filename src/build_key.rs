@@ -0,0 +1,105 @@
+use std::path::PathBuf;
+
+use sha2::{Digest, Sha256};
+
+use crate::{
+    config::PkgbuildDirs,
+    error::{Context, Result, UnchangedError},
+    fs::{read_to_string, write},
+    integ::{finalize, hash_file},
+    options::Options,
+    pkgbuild::Pkgbuild,
+    Makepkg,
+};
+
+impl Makepkg {
+    /// Fails with [`UnchangedError`] if [`Options::skip_unchanged`] is set and the packages
+    /// in pkgdest were built from the same PKGBUILD, sources and configuration.
+    pub(crate) fn err_if_unchanged(
+        &self,
+        options: &Options,
+        dirs: &PkgbuildDirs,
+        pkgbuild: &Pkgbuild,
+    ) -> Result<()> {
+        if !options.skip_unchanged {
+            return Ok(());
+        }
+        let Some(key) = self.build_key(dirs, pkgbuild)? else {
+            return Ok(());
+        };
+
+        let path = key_file(dirs);
+        if path.exists()
+            && read_to_string(&path, Context::BuildPackage)? == key
+            && self.is_pkg_built(pkgbuild)?
+        {
+            return Err(UnchangedError {
+                pkgbase: pkgbuild.pkgbase.clone(),
+            }
+            .into());
+        }
+        Ok(())
+    }
+
+    /// Remembers what the packages were built from for [`Makepkg::err_if_unchanged`].
+    pub(crate) fn save_build_key(&self, dirs: &PkgbuildDirs, pkgbuild: &Pkgbuild) -> Result<()> {
+        if let Some(key) = self.build_key(dirs, pkgbuild)? {
+            write(key_file(dirs), key, Context::BuildPackage)?;
+        }
+        Ok(())
+    }
+
+    // a digest of everything that affects the built packages. VCS sources can change without
+    // the PKGBUILD changing so builds using them never match.
+    fn build_key(&self, dirs: &PkgbuildDirs, pkgbuild: &Pkgbuild) -> Result<Option<String>> {
+        let config = &self.config;
+        let mut digest = Sha256::new();
+        let mut add = |name: &str, value: &str| {
+            digest.update(name);
+            digest.update("=");
+            digest.update(value);
+            digest.update("\n");
+        };
+
+        add("PKGBUILD", &hash_file::<Sha256>(&dirs.pkgbuild)?);
+        for source in pkgbuild.source.enabled(&config.arch) {
+            if source.vcs_kind().is_some() {
+                return Ok(None);
+            }
+            let path = dirs.download_path(source);
+            if !path.exists() {
+                return Ok(None);
+            }
+            add(source.file_name(), &hash_file::<Sha256>(&path)?);
+        }
+        let files = pkgbuild
+            .packages()
+            .flat_map(|p| [&p.install, &p.changelog])
+            .chain([&pkgbuild.install, &pkgbuild.changelog]);
+        for file in files.flatten() {
+            add(file, &hash_file::<Sha256>(&dirs.startdir.join(file))?);
+        }
+
+        add("CARCH", &config.arch);
+        add("CHOST", &config.chost);
+        add("CPPFLAGS", &config.cppflags);
+        add("CFLAGS", &config.cflags);
+        add("CXXFLAGS", &config.cxxflags);
+        add("RUSTFLAGS", &config.rustflags);
+        add("LDFLAGS", &config.ldflags);
+        add("LTOFLAGS", &config.ltoflags);
+        add("DEBUG_CFLAGS", &config.debug_cflags);
+        add("DEBUG_CXXFLAGS", &config.debug_cxxflags);
+        add("DEBUG_RUSTFLAGS", &config.debug_rustflags);
+        add("BUILDENV", &format!("{:?}", config.build_env.values));
+        add("OPTIONS", &format!("{:?}", config.options.values));
+        add("PACKAGER", &config.packager);
+        add("PKGEXT", &config.pkgext.to_string());
+
+        Ok(Some(finalize(digest)))
+    }
+}
+
+fn key_file(dirs: &PkgbuildDirs) -> PathBuf {
+    dirs.srcdir.with_file_name(".makepkg-build-key")
+}
@@ -0,0 +1,114 @@
+use std::{
+    fs::File,
+    io::{self, Write},
+    time::{SystemTime, UNIX_EPOCH},
+};
+
+use crate::{
+    error::{Context, Result},
+    fs::open,
+    pkgbuild::Pkgbuild,
+    Makepkg,
+};
+
+/// A single timestamped log of everything that happens during a build.
+///
+/// Events, log messages and command output are interleaved in the order they happen.
+#[derive(Debug)]
+pub(crate) struct BuildLog {
+    file: File,
+    line_start: bool,
+}
+
+impl BuildLog {
+    pub(crate) fn write_line(&mut self, line: &str) -> io::Result<()> {
+        if !self.line_start {
+            self.file.write_all(b"\n")?;
+        }
+        for line in line.lines() {
+            writeln!(self.file, "[{}] {}", timestamp(), line)?;
+        }
+        self.line_start = true;
+        Ok(())
+    }
+
+    pub(crate) fn write_output(&mut self, mut data: &[u8]) -> io::Result<()> {
+        while !data.is_empty() {
+            if self.line_start {
+                write!(self.file, "[{}] ", timestamp())?;
+            }
+            let end = data
+                .iter()
+                .position(|&b| b == b'\n')
+                .map(|n| n + 1)
+                .unwrap_or(data.len());
+            self.file.write_all(&data[..end])?;
+            self.line_start = data[end - 1] == b'\n';
+            data = &data[end..];
+        }
+        Ok(())
+    }
+}
+
+impl Makepkg {
+    pub(crate) fn open_build_log(&self, pkgbuild: &Pkgbuild) -> Result<()> {
+        let dirs = self.pkgbuild_dirs(pkgbuild)?;
+        let path = dirs.logdest.join(format!(
+            "{}-{}-{}.log",
+            pkgbuild.pkgbase,
+            pkgbuild.version(),
+            self.config.arch,
+        ));
+
+        let mut file = File::options();
+        let file = file.create(true).truncate(true).write(true);
+        let file = open(file, path, Context::BuildPackage)?;
+        *self.build_log.borrow_mut() = Some(BuildLog {
+            file,
+            line_start: true,
+        });
+        Ok(())
+    }
+
+    pub(crate) fn close_build_log(&self) {
+        *self.build_log.borrow_mut() = None;
+    }
+
+    pub(crate) fn build_log_line(&self, line: &str) -> io::Result<()> {
+        if let Some(log) = &mut *self.build_log.borrow_mut() {
+            log.write_line(line)?;
+        }
+        Ok(())
+    }
+}
+
+// UTC in the form 2000-01-01T00:00:00Z
+fn timestamp() -> String {
+    let secs = SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|d| d.as_secs())
+        .unwrap_or(0);
+    let days = (secs / 86400) as i64;
+    let rem = secs % 86400;
+
+    // days to civil date, see http://howardhinnant.github.io/date_algorithms.html
+    let z = days + 719468;
+    let era = z.div_euclid(146097);
+    let doe = z.rem_euclid(146097);
+    let yoe = (doe - doe / 1460 + doe / 36524 - doe / 146096) / 365;
+    let doy = doe - (365 * yoe + yoe / 4 - yoe / 100);
+    let mp = (5 * doy + 2) / 153;
+    let day = doy - (153 * mp + 2) / 5 + 1;
+    let month = if mp < 10 { mp + 3 } else { mp - 9 };
+    let year = yoe + era * 400 + i64::from(month <= 2);
+
+    format!(
+        "{:04}-{:02}-{:02}T{:02}:{:02}:{:02}Z",
+        year,
+        month,
+        day,
+        rem / 3600,
+        rem / 60 % 60,
+        rem % 60
+    )
+}
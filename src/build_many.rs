@@ -0,0 +1,301 @@
+use std::{
+    collections::{BTreeSet, HashMap, HashSet},
+    fmt::Display,
+};
+
+use crate::{
+    callback::Event,
+    error::{DependencyCycleError, Error, Result},
+    options::Options,
+    pkgbuild::{Depend, Pkgbuild},
+    report::BuildReport,
+    Makepkg,
+};
+
+/// What happened to one PKGBUILD in [`Makepkg::build_many`].
+#[derive(Debug)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize))]
+pub enum BatchOutcome {
+    Built(BuildReport),
+    Failed(Error),
+    /// Not attempted because a PKGBUILD it depends on was not built. Holds that pkgbase.
+    Skipped(String),
+}
+
+#[derive(Debug)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize))]
+pub struct BatchBuild {
+    pub pkgbase: String,
+    pub outcome: BatchOutcome,
+}
+
+impl Display for BatchBuild {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match &self.outcome {
+            BatchOutcome::Built(report) => {
+                write!(
+                    f,
+                    "{}: built in {:.1}s",
+                    self.pkgbase,
+                    report.total.as_secs_f64()
+                )
+            }
+            BatchOutcome::Failed(err) => write!(f, "{}: failed: {}", self.pkgbase, err),
+            BatchOutcome::Skipped(dep) => {
+                write!(f, "{}: skipped as {} was not built", self.pkgbase, dep)
+            }
+        }
+    }
+}
+
+/// The outcome of every PKGBUILD passed to [`Makepkg::build_many`] in the order they were
+/// built.
+#[derive(Debug, Default)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize))]
+pub struct BatchReport {
+    pub builds: Vec<BatchBuild>,
+}
+
+impl BatchReport {
+    /// Whether every PKGBUILD was built.
+    pub fn is_success(&self) -> bool {
+        self.builds
+            .iter()
+            .all(|b| matches!(b.outcome, BatchOutcome::Built(_)))
+    }
+
+    /// The builds that failed or were skipped.
+    pub fn unbuilt(&self) -> impl Iterator<Item = &BatchBuild> {
+        self.builds
+            .iter()
+            .filter(|b| !matches!(b.outcome, BatchOutcome::Built(_)))
+    }
+}
+
+impl Display for BatchReport {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        for build in &self.builds {
+            writeln!(f, "{}", build)?;
+        }
+        Ok(())
+    }
+}
+
+impl Makepkg {
+    /// Builds several PKGBUILDs, building each one after the others in the batch that provide
+    /// its depends, makedepends or checkdepends.
+    ///
+    /// A failed build does not stop the batch but everything depending on it is skipped.
+    /// With [`Options::install_intermediates`] packages that later builds depend on are
+    /// installed as dependencies so the later builds can use them.
+    ///
+    /// Only fails if the PKGBUILDs depend on each other in a cycle or a callback cancels the
+    /// build, failures of single builds are in the returned [`BatchReport`].
+    pub fn build_many(&self, options: &Options, pkgbuilds: &mut [Pkgbuild]) -> Result<BatchReport> {
+        let deps = self.batch_deps(options, pkgbuilds);
+        let order = build_order(pkgbuilds, &deps)?;
+        let mut report = BatchReport::default();
+        let mut unbuilt = HashSet::new();
+
+        for i in order {
+            let pkgbase = pkgbuilds[i].pkgbase.clone();
+            if let Some(&dep) = deps[i].iter().find(|d| unbuilt.contains(*d)) {
+                let dep = pkgbuilds[dep].pkgbase.clone();
+                self.event(Event::BuildSkipped(&pkgbase, &dep))?;
+                unbuilt.insert(i);
+                report.builds.push(BatchBuild {
+                    pkgbase,
+                    outcome: BatchOutcome::Skipped(dep),
+                });
+                continue;
+            }
+
+            let mut build_options = options.clone();
+            if options.install_intermediates
+                && !options.install
+                && deps.iter().any(|d| d.contains(&i))
+            {
+                build_options.install = true;
+                build_options.as_deps = true;
+            }
+
+            let outcome = match self.build(&build_options, &mut pkgbuilds[i]) {
+                Ok(build) => BatchOutcome::Built(build),
+                Err(Error::Cancelled) => return Err(Error::Cancelled),
+                Err(e) => {
+                    unbuilt.insert(i);
+                    BatchOutcome::Failed(e)
+                }
+            };
+            report.builds.push(BatchBuild { pkgbase, outcome });
+        }
+
+        Ok(report)
+    }
+
+    /// The order [`Makepkg::build_many`] builds PKGBUILDs in as indexes into `pkgbuilds`.
+    ///
+    /// PKGBUILDs that don't depend on each other keep the order they were given in.
+    /// checkdepends are only followed for PKGBUILDs whose check function would run.
+    pub fn build_order(&self, options: &Options, pkgbuilds: &[Pkgbuild]) -> Result<Vec<usize>> {
+        build_order(pkgbuilds, &self.batch_deps(options, pkgbuilds))
+    }
+
+    // for each PKGBUILD the other PKGBUILDs in the batch that it depends on
+    fn batch_deps(&self, options: &Options, pkgbuilds: &[Pkgbuild]) -> Vec<BTreeSet<usize>> {
+        let config = &self.config;
+        let arch = config.arch.as_str();
+        let mut providers = HashMap::new();
+        for (i, pkgbuild) in pkgbuilds.iter().enumerate() {
+            for pkg in pkgbuild.packages() {
+                providers.insert(pkg.pkgname.as_str(), i);
+                for provide in pkg.provides.enabled(arch) {
+                    providers.insert(Depend::name_of(provide), i);
+                }
+            }
+        }
+
+        pkgbuilds
+            .iter()
+            .enumerate()
+            .map(|(i, pkgbuild)| {
+                let mut deps = pkgbuild
+                    .packages()
+                    .flat_map(|p| p.depends.enabled(arch))
+                    .chain(pkgbuild.depends.enabled(arch))
+                    .chain(pkgbuild.makedepends.enabled(arch))
+                    .collect::<Vec<_>>();
+                // the same condition build uses to decide whether to run check()
+                let check = config.option(pkgbuild, "check").enabled()
+                    || (config.build_option(pkgbuild, "check").enabled() && !options.no_check);
+                if check {
+                    deps.extend(pkgbuild.checkdepends.enabled(arch));
+                }
+                deps.into_iter()
+                    .filter_map(|d| providers.get(Depend::name_of(d)).copied())
+                    .filter(|&p| p != i)
+                    .collect()
+            })
+            .collect()
+    }
+}
+
+fn build_order(pkgbuilds: &[Pkgbuild], deps: &[BTreeSet<usize>]) -> Result<Vec<usize>> {
+    let mut order = Vec::new();
+    let mut done = vec![false; pkgbuilds.len()];
+
+    while order.len() < pkgbuilds.len() {
+        let ready = (0..pkgbuilds.len()).find(|&i| !done[i] && deps[i].iter().all(|&d| done[d]));
+        let Some(next) = ready else {
+            let pkgbases = (0..pkgbuilds.len())
+                .filter(|&i| !done[i])
+                .map(|i| pkgbuilds[i].pkgbase.clone())
+                .collect();
+            return Err(DependencyCycleError { pkgbases }.into());
+        };
+        done[next] = true;
+        order.push(next);
+    }
+
+    Ok(order)
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::{
+        config::Config,
+        pkgbuild::{ArchVec, ArchVecs, Package},
+    };
+
+    fn vecs(values: &[&str]) -> ArchVecs<String> {
+        let values = values.iter().map(|v| v.to_string()).collect();
+        ArchVecs {
+            values: vec![ArchVec::from_vec(None::<String>, values)],
+        }
+    }
+
+    fn pkgbuild(name: &str, provides: &[&str], depends: &[&str]) -> Pkgbuild {
+        let mut package = Package::default();
+        package.pkgname = name.to_string();
+        package.provides = vecs(provides);
+
+        let mut pkgbuild = Pkgbuild::default();
+        pkgbuild.pkgbase = name.to_string();
+        pkgbuild.depends = vecs(depends);
+        pkgbuild.packages.push(package);
+        pkgbuild
+    }
+
+    fn makepkg() -> Makepkg {
+        let config = Config::builder()
+            .arch("x86_64")
+            .build_env(["check"])
+            .build()
+            .unwrap();
+        Makepkg::from_config(config)
+    }
+
+    #[test]
+    fn build_order() {
+        let makepkg = makepkg();
+        let pkgbuilds = [
+            pkgbuild("app", &[], &["lib>=2", "tool"]),
+            pkgbuild("lib", &[], &[]),
+            pkgbuild("tool", &[], &["lib"]),
+            pkgbuild("other", &[], &["glibc"]),
+        ];
+        let order = makepkg
+            .build_order(&Options::default(), &pkgbuilds)
+            .unwrap();
+        assert_eq!(order, [1, 2, 0, 3]);
+    }
+
+    #[test]
+    fn build_order_provides() {
+        let makepkg = makepkg();
+        let pkgbuilds = [
+            pkgbuild("app", &[], &["libfoo.so=1-64"]),
+            pkgbuild("foo", &["libfoo.so=1-64"], &[]),
+        ];
+        let order = makepkg
+            .build_order(&Options::default(), &pkgbuilds)
+            .unwrap();
+        assert_eq!(order, [1, 0]);
+    }
+
+    #[test]
+    fn build_order_cycle() {
+        let makepkg = makepkg();
+        let pkgbuilds = [
+            pkgbuild("ok", &[], &[]),
+            pkgbuild("a", &[], &["b"]),
+            pkgbuild("b", &["c"], &["a"]),
+        ];
+        let err = makepkg
+            .build_order(&Options::default(), &pkgbuilds)
+            .unwrap_err();
+        match err {
+            Error::DependencyCycle(e) => assert_eq!(e.pkgbases, ["a", "b"]),
+            e => panic!("unexpected error: {}", e),
+        }
+    }
+
+    #[test]
+    fn batch_deps_checkdepends() {
+        let makepkg = makepkg();
+        let mut app = pkgbuild("app", &[], &[]);
+        app.checkdepends = vecs(&["test-runner"]);
+        let pkgbuilds = [app, pkgbuild("test-runner", &[], &[])];
+
+        let deps = makepkg.batch_deps(&Options::default(), &pkgbuilds);
+        assert_eq!(deps[0], BTreeSet::from([1]));
+
+        let options = Options {
+            no_check: true,
+            ..Default::default()
+        };
+        let deps = makepkg.batch_deps(&options, &pkgbuilds);
+        assert!(deps[0].is_empty());
+    }
+}
@@ -0,0 +1,205 @@
+use std::{
+    collections::BTreeMap,
+    os::unix::fs::MetadataExt,
+    path::{Path, PathBuf},
+};
+
+use nix::{
+    sys::{
+        statfs::{statfs, TMPFS_MAGIC},
+        statvfs::statvfs,
+    },
+    unistd::{Gid, Uid},
+};
+
+use crate::{
+    callback::{Event, LogLevel, LogMessage},
+    config::PkgbuildDirs,
+    error::{CommandErrorExt, Context, IOContext, IOErrorExt, InsufficientSpaceError, Result},
+    fs::{mkdir, resolve_path_relative},
+    options::Options,
+    pkgbuild::Pkgbuild,
+    plan::DISK_USAGE_FACTOR,
+    Makepkg,
+};
+
+impl Makepkg {
+    /// Picks the first of [`Config::builddir_candidates`](crate::config::Config::builddir_candidates)
+    /// with enough free space for the build.
+    ///
+    /// If none have enough space the candidate with the most free space is used.
+    pub(crate) fn select_builddir(&self, pkgbuild: &Pkgbuild) -> Result<()> {
+        let config = &self.config;
+        *self.builddir.borrow_mut() = None;
+
+        if config.builddir_candidates.is_empty() && config.tmpfs_builddir.is_none() {
+            return Ok(());
+        }
+
+        let dirs = self.pkgbuild_dirs(pkgbuild)?;
+        let needed =
+            self.estimate_disk_usage(&dirs, pkgbuild).unwrap_or(0) + config.builddir_min_free;
+
+        if let Some(tmpfs) = &config.tmpfs_builddir {
+            let dir = resolve_path_relative(tmpfs, &dirs.startdir);
+            if self.use_tmpfs(&dir, needed)? {
+                self.event(Event::UsingBuilddir(&dir))?;
+                *self.builddir.borrow_mut() = Some(dir);
+                return Ok(());
+            }
+        }
+        let mut best: Option<(PathBuf, u64)> = None;
+
+        for candidate in &config.builddir_candidates {
+            let dir = resolve_path_relative(candidate, &dirs.startdir);
+            let free = free_space(&dir)?;
+
+            if free >= needed {
+                self.event(Event::UsingBuilddir(&dir))?;
+                *self.builddir.borrow_mut() = Some(dir);
+                return Ok(());
+            }
+            if best.as_ref().is_none_or(|b| free > b.1) {
+                best = Some((dir, free));
+            }
+        }
+
+        if let Some((dir, _)) = best {
+            self.log(LogLevel::Warning, LogMessage::NoBuilddirFits(&dir))?;
+            self.event(Event::UsingBuilddir(&dir))?;
+            *self.builddir.borrow_mut() = Some(dir);
+        }
+
+        Ok(())
+    }
+
+    /// Fails early if builddir or pkgdest do not have enough free space for the build
+    /// instead of running out of space halfway through.
+    ///
+    /// Space is estimated from the size of the sources already downloaded, nothing is checked
+    /// if none are. Servers are not asked for sizes as that would block the build on a request
+    /// per source. Directories on the same filesystem have their needs added together.
+    pub(crate) fn check_free_space(
+        &self,
+        options: &Options,
+        dirs: &PkgbuildDirs,
+        pkgbuild: &Pkgbuild,
+    ) -> Result<()> {
+        let total = pkgbuild
+            .source
+            .enabled(&self.config.arch)
+            .filter_map(|s| self.local_source_size(dirs, s))
+            .sum::<u64>();
+        if total == 0 {
+            return Ok(());
+        }
+
+        let mut needs = Vec::new();
+        if !options.no_build || !options.no_extract {
+            needs.push((&dirs.srcdir, total * (DISK_USAGE_FACTOR - 2)));
+        }
+        if !options.no_package {
+            needs.push((&dirs.pkgdest, total));
+        }
+
+        let mut filesystems: BTreeMap<u64, (&Path, u64)> = BTreeMap::new();
+        for (dir, needed) in needs {
+            let existing = dir.ancestors().find(|d| d.exists()).unwrap_or(dir);
+            let dev = existing
+                .metadata()
+                .context(Context::BuildPackage, IOContext::Stat(existing.into()))?
+                .dev();
+            filesystems.entry(dev).or_insert((dir, 0)).1 += needed;
+        }
+
+        for (dir, needed) in filesystems.into_values() {
+            let available = free_space(dir)?;
+            if available < needed {
+                return Err(InsufficientSpaceError {
+                    path: dir.to_path_buf(),
+                    needed,
+                    available,
+                }
+                .into());
+            }
+        }
+
+        Ok(())
+    }
+
+    // uses an existing tmpfs or mounts one if there is enough memory, returns false and warns
+    // if the build would not fit
+    fn use_tmpfs(&self, dir: &Path, needed: u64) -> Result<bool> {
+        let available = if is_tmpfs(dir) {
+            free_space(dir)?
+        } else {
+            mem_available()
+        };
+        if available < needed {
+            self.log(
+                LogLevel::Warning,
+                LogMessage::TmpfsTooSmall(dir, needed, available),
+            )?;
+            return Ok(false);
+        }
+        if is_tmpfs(dir) {
+            return Ok(true);
+        }
+
+        self.event(Event::MountingTmpfs(dir))?;
+        mkdir(dir, Context::MountTmpfs)?;
+        let uid = Uid::current();
+        let gid = Gid::current();
+        let mut command = self.as_root(vec![
+            "mount".into(),
+            "-t".into(),
+            "tmpfs".into(),
+            "-o".into(),
+            format!("size={},mode=0755,uid={},gid={}", needed, uid, gid),
+            "tmpfs".into(),
+            dir.to_string_lossy().into_owned(),
+        ]);
+        command
+            .status()
+            .cmd_context(&command, Context::MountTmpfs)?;
+        *self.tmpfs.borrow_mut() = Some(dir.to_path_buf());
+        Ok(true)
+    }
+
+    /// Unmounts the tmpfs mounted by [`Makepkg::select_builddir`] if there is one.
+    pub(crate) fn unmount_tmpfs(&self) -> Result<()> {
+        let Some(dir) = self.tmpfs.borrow_mut().take() else {
+            return Ok(());
+        };
+        let mut command = self.as_root(vec!["umount".into(), dir.to_string_lossy().into_owned()]);
+        command
+            .status()
+            .cmd_context(&command, Context::MountTmpfs)?;
+        Ok(())
+    }
+}
+
+fn is_tmpfs(dir: &Path) -> bool {
+    statfs(dir).is_ok_and(|s| s.filesystem_type() == TMPFS_MAGIC)
+}
+
+// memory that can be used without swapping according to the kernel
+fn mem_available() -> u64 {
+    let Ok(meminfo) = std::fs::read_to_string("/proc/meminfo") else {
+        return 0;
+    };
+    meminfo
+        .lines()
+        .find_map(|l| l.strip_prefix("MemAvailable:"))
+        .and_then(|l| l.trim().trim_end_matches("kB").trim().parse::<u64>().ok())
+        .map(|kb| kb * 1024)
+        .unwrap_or(0)
+}
+
+/// Free space available to unprivileged users on the filesystem that `dir` is or will be
+/// created on.
+fn free_space(dir: &Path) -> Result<u64> {
+    let existing = dir.ancestors().find(|d| d.exists()).unwrap_or(dir);
+    let stat = statvfs(existing).context(Context::BuildPackage, IOContext::Stat(dir.into()))?;
+    Ok(stat.blocks_available() as u64 * stat.fragment_size() as u64)
+}
@@ -0,0 +1,114 @@
+use crate::{
+    callback::Event,
+    error::{Context, Result},
+    fs::write,
+    options::Options,
+    pkgbuild::Pkgbuild,
+    Makepkg,
+};
+
+/// What [`Makepkg::bump_version`] changed.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize))]
+pub struct BumpReport {
+    pub old_version: String,
+    pub new_version: String,
+    /// The checksum arrays that were rewritten such as `sha256sums_x86_64`.
+    pub checksums: Vec<String>,
+    /// Whether .SRCINFO was written.
+    pub srcinfo: bool,
+}
+
+impl Makepkg {
+    /// Sets pkgver in the PKGBUILD, resetting pkgrel to 1, then updates the checksums of
+    /// every source whose url changed with the new version and optionally writes .SRCINFO.
+    ///
+    /// Checksums of sources that did not change are kept and `SKIP` entries are left alone.
+    /// The changed sources are downloaded if they are not already in SRCDEST. `pkgbuild` is
+    /// reloaded from disk afterwards.
+    pub fn bump_version(
+        &self,
+        options: &Options,
+        pkgbuild: &mut Pkgbuild,
+        pkgver: &str,
+        srcinfo: bool,
+    ) -> Result<BumpReport> {
+        let old = pkgbuild.clone();
+        let mut report = BumpReport {
+            old_version: old.version(),
+            ..Default::default()
+        };
+        let dirs = self.pkgbuild_dirs(pkgbuild)?;
+
+        self.event(Event::BumpingVersion(&old.pkgbase, pkgver))?;
+        pkgbuild.set_pkgver(&dirs.pkgbuild, pkgver)?;
+        *pkgbuild = self.load_pkgbuild(&old.dir)?;
+
+        let changed = pkgbuild.source.values.iter().any(|arch| {
+            old.source
+                .get(arch.arch.as_deref())
+                .is_none_or(|old| old.values != arch.values)
+        });
+
+        if changed {
+            self.download_sources(options, pkgbuild, true)?;
+            self.event(Event::GeneratingChecksums)?;
+
+            let mut arrays = Vec::new();
+            for (kind, sums) in pkgbuild.get_all_checksums() {
+                if sums.is_empty() {
+                    continue;
+                }
+
+                for arch in &pkgbuild.source.values {
+                    let old_sources = old
+                        .source
+                        .get(arch.arch.as_deref())
+                        .map(|v| v.values.as_slice())
+                        .unwrap_or_default();
+                    let sums = sums
+                        .get(arch.arch.as_deref())
+                        .map(|v| v.values.as_slice())
+                        .unwrap_or_default();
+
+                    let mut new = Vec::new();
+                    for (n, source) in arch.values.iter().enumerate() {
+                        match sums.get(n) {
+                            Some(sum) if sum == "SKIP" => new.push(sum.clone()),
+                            Some(sum) if old_sources.get(n) == Some(source) => {
+                                new.push(sum.clone())
+                            }
+                            _ => new.push(self.checksum_source(&dirs, pkgbuild, kind, source)?),
+                        }
+                    }
+
+                    if new != sums {
+                        arrays.push((kind, arch.arch.clone(), new));
+                    }
+                }
+            }
+
+            if !arrays.is_empty() {
+                let mut editor = pkgbuild.editor()?;
+                for (kind, arch, sums) in &arrays {
+                    editor.set_array(kind.name(), arch.as_deref(), sums);
+                    report.checksums.push(match arch {
+                        Some(arch) => format!("{}_{}", kind, arch),
+                        None => kind.to_string(),
+                    });
+                }
+                editor.save()?;
+                *pkgbuild = self.load_pkgbuild(&old.dir)?;
+            }
+        }
+
+        if srcinfo {
+            let path = pkgbuild.dir.join(".SRCINFO");
+            write(&path, pkgbuild.srcinfo(), Context::BumpVersion)?;
+            report.srcinfo = true;
+        }
+
+        report.new_version = pkgbuild.version();
+        Ok(report)
+    }
+}
@@ -2,16 +2,53 @@ use std::{
     fmt::Display,
     fs::File,
     io::{self, stdout, Write},
+    path::{Path, PathBuf},
+    process::{Command, ExitStatus},
+    time::Duration,
 };
 
 use crate::{
+    dedup::DuplicateFile,
     error::{Context, IOContext, IOErrorExt, Result},
+    package_lint::PackageWarning,
     pkgbuild::{Pkgbuild, Source},
+    report::{BuildPhase, BuildReport},
     sources::VCSKind,
     Makepkg,
 };
 
-pub trait Callbacks: std::fmt::Debug + 'static {
+/// Returned from a [`Callbacks`] method to abort the current operation.
+///
+/// Pending downloads are stopped, any running command is killed and the operation fails with
+/// [`Error::Cancelled`](crate::error::Error::Cancelled). Return it as `Err(Cancelled.into())`.
+#[derive(Debug, Copy, Clone, PartialEq, Eq, PartialOrd, Ord, Hash, Default)]
+pub struct Cancelled;
+
+impl Display for Cancelled {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str("cancelled")
+    }
+}
+
+impl std::error::Error for Cancelled {}
+
+impl From<Cancelled> for io::Error {
+    fn from(value: Cancelled) -> Self {
+        io::Error::new(io::ErrorKind::Interrupted, value)
+    }
+}
+
+impl Cancelled {
+    /// Whether an error returned by a callback is a [`Cancelled`].
+    pub fn is(err: &io::Error) -> bool {
+        err.get_ref().is_some_and(|e| e.is::<Cancelled>())
+    }
+}
+
+/// Receives progress from [`Makepkg`].
+///
+/// Callbacks must be [`Send`] so that a [`Makepkg`] can be moved to another thread to build.
+pub trait Callbacks: std::fmt::Debug + Send + 'static {
     fn event(&mut self, _event: Event) -> io::Result<()> {
         Ok(())
     }
@@ -19,22 +56,101 @@ pub trait Callbacks: std::fmt::Debug + 'static {
         Ok(())
     }
 
-    fn command_new(&mut self, _id: usize, _kind: CommandKind) -> io::Result<CommandOutput> {
+    fn command_new(
+        &mut self,
+        _id: usize,
+        _kind: CommandKind,
+        _command: &CommandInfo,
+    ) -> io::Result<CommandOutput> {
         Ok(Default::default())
     }
-    fn command_exit(&mut self, _id: usize, _kind: CommandKind) -> io::Result<()> {
+    /// Called once the command has been waited on. The status is `None` if makepkg killed it
+    /// for running past [`Options::timeout`](crate::options::Options::timeout).
+    fn command_exit(
+        &mut self,
+        _id: usize,
+        _kind: CommandKind,
+        _status: Option<ExitStatus>,
+    ) -> io::Result<()> {
         Ok(())
     }
     fn command_output(&mut self, _id: usize, _kind: CommandKind, _output: &[u8]) -> io::Result<()> {
         Ok(())
     }
+    /// Whether a command run with [`Options::interactive`](crate::options::Options::interactive)
+    /// may read from makepkg's stdin. The default allows it.
+    fn command_stdin(&mut self, _id: usize, _kind: CommandKind) -> io::Result<bool> {
+        Ok(true)
+    }
 
     fn download(&mut self, _pkgbuild: &Pkgbuild, _event: DownloadEvent) -> io::Result<()> {
         Ok(())
     }
+
+    fn package(&mut self, _event: PackageEvent) -> io::Result<()> {
+        Ok(())
+    }
+
+    /// Periodic progress while checksumming a large source. Gives the bytes hashed so far and
+    /// the size of the file. The last call for a file has both equal.
+    fn hashing(&mut self, _file_name: &str, _hashed: u64, _total: u64) -> io::Result<()> {
+        Ok(())
+    }
+
+    /// Asks the user to make a decision. The default implementation always answers no.
+    fn question(&mut self, _question: Question) -> io::Result<Answer> {
+        Ok(Answer::No)
+    }
+}
+
+/// A decision makepkg can not make on its own. See [`Callbacks::question`].
+#[derive(Debug, Copy, Clone, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub enum Question<'a> {
+    /// The key that signed a source is listed in `validpgpkeys` but not in the keyring.
+    /// Answering yes receives it from the keyserver and verifies the source again.
+    ImportKey {
+        file_name: &'a str,
+        fingerprint: &'a str,
+    },
+    /// The key that signed a source is not trusted. Answering yes accepts the signature for
+    /// this build.
+    TrustKey {
+        file_name: &'a str,
+        fingerprint: &'a str,
+    },
+}
+
+impl<'a> Display for Question<'a> {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Question::ImportKey {
+                file_name,
+                fingerprint,
+            } => write!(
+                f,
+                "{} is signed by unknown key {}, import it?",
+                file_name, fingerprint
+            ),
+            Question::TrustKey {
+                file_name,
+                fingerprint,
+            } => write!(
+                f,
+                "{} is signed by untrusted key {}, trust it for this build?",
+                file_name, fingerprint
+            ),
+        }
+    }
 }
 
 #[derive(Debug, Copy, Clone, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub enum Answer {
+    Yes,
+    No,
+}
+
+#[derive(Debug, Copy, Clone, PartialEq, Eq, PartialOrd, Ord, Hash)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize))]
 pub struct Download<'a> {
     pub n: usize,
     pub total: usize,
@@ -42,15 +158,34 @@ pub struct Download<'a> {
 }
 
 #[derive(Debug, Copy, Clone, PartialEq, PartialOrd)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize))]
 pub enum DownloadEvent<'a> {
     DownloadStart(usize),
     Init(Download<'a>),
     Progress(Download<'a>, f64, f64),
     Completed(Download<'a>),
+    NotModified(Download<'a>),
     Failed(Download<'a>, u32),
     DownloadEnd,
 }
 
+/// Progress while assembling a package.
+///
+/// Large packages can take a long time to list and archive so these are emitted periodically
+/// to show that work is still being done.
+#[derive(Debug, Copy, Clone, PartialEq, PartialOrd)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize))]
+pub enum PackageEvent<'a> {
+    /// Number of files found so far in the package directory.
+    CollectingFiles(&'a Path, usize),
+    /// All files in the package directory have been found.
+    CollectedFiles(&'a Path, usize),
+    /// Bytes written to the named file so far and how long it has been writing.
+    Archiving(&'a str, u64, Duration),
+    /// The named file has been fully written.
+    Archived(&'a str, u64, Duration),
+}
+
 #[derive(Debug, Default)]
 pub enum CommandOutput {
     #[default]
@@ -60,6 +195,49 @@ pub enum CommandOutput {
     File(File),
 }
 
+/// The process behind a command. See [`Callbacks::command_new`].
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize))]
+pub struct CommandInfo {
+    /// The program followed by its arguments.
+    pub argv: Vec<String>,
+    /// The directory the command runs in or `None` for makepkg's current directory.
+    pub cwd: Option<PathBuf>,
+}
+
+impl CommandInfo {
+    pub(crate) fn new(command: &Command) -> Self {
+        let argv = std::iter::once(command.get_program())
+            .chain(command.get_args())
+            .map(|arg| arg.to_string_lossy().into_owned())
+            .collect();
+        CommandInfo {
+            argv,
+            cwd: command.get_current_dir().map(|d| d.to_path_buf()),
+        }
+    }
+}
+
+impl Display for CommandInfo {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        for (i, arg) in self.argv.iter().enumerate() {
+            if i != 0 {
+                f.write_str(" ")?;
+            }
+            if !arg.is_empty()
+                && arg
+                    .bytes()
+                    .all(|b| b.is_ascii_alphanumeric() || b"-_./=:,+@%".contains(&b))
+            {
+                f.write_str(arg)?;
+            } else {
+                write!(f, "'{}'", arg.replace('\'', "'\\''"))?;
+            }
+        }
+        Ok(())
+    }
+}
+
 #[derive(Debug, Copy, Clone, PartialEq, Eq, PartialOrd, Ord, Hash)]
 pub enum CommandKind<'a> {
     PkgbuildFunction(&'a Pkgbuild),
@@ -67,6 +245,9 @@ pub enum CommandKind<'a> {
     DownloadSources(&'a Pkgbuild, &'a Source),
     ExtractSources(&'a Pkgbuild, &'a Source),
     Integ(&'a Pkgbuild, &'a Source),
+    ManageKeys(&'a Pkgbuild),
+    RemoteBuild(&'a Pkgbuild),
+    Chroot(&'a Pkgbuild),
 }
 
 impl<'a> CommandKind<'a> {
@@ -77,6 +258,9 @@ impl<'a> CommandKind<'a> {
             CommandKind::DownloadSources(p, _) => p,
             CommandKind::ExtractSources(p, _) => p,
             CommandKind::Integ(p, _) => p,
+            CommandKind::ManageKeys(p) => p,
+            CommandKind::RemoteBuild(p) => p,
+            CommandKind::Chroot(p) => p,
         }
     }
 }
@@ -92,12 +276,19 @@ impl Callbacks for CallBackPrinter {
             | Event::DownloadingCurl(_)
             | Event::NoExtact(_)
             | Event::Extacting(_)
+            | Event::UsingCachedExtraction(_)
             | Event::RemovingSrcdir
             | Event::RemovingPkgdir
+            | Event::RemovedDir(..)
+            | Event::PossiblyUnusedDepend(..)
+            | Event::NormalizedFileName(_)
             | Event::AddingFileToPackage(_)
             | Event::GeneratingPackageFile(_)
             | Event::DownloadingVCS(_, _)
-            | Event::UpdatingVCS(_, _) => writeln!(stdout(), "    {}", event),
+            | Event::UpdatingVCS(_, _)
+            | Event::RetryingVCS(..)
+            | Event::ReceivingKey(_)
+            | Event::ReceivedKey(_) => writeln!(stdout(), "    {}", event),
             Event::VerifyingChecksum(_) | Event::VerifyingSignature(_) => {
                 write!(stdout(), "    {} ...", event)?;
                 stdout().flush()
@@ -117,6 +308,7 @@ impl Callbacks for CallBackPrinter {
 }
 
 #[derive(Debug, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize))]
 pub enum SigFailedKind<'a> {
     NotSigned,
     UnknownPublicKey,
@@ -142,6 +334,7 @@ impl<'a> Display for SigFailedKind<'a> {
 }
 
 #[derive(Debug, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize))]
 pub struct SigFailed<'a> {
     pub file_name: &'a str,
     pub fingerprint: &'a str,
@@ -165,10 +358,21 @@ impl<'a> SigFailed<'a> {
 }
 
 #[derive(Debug, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize))]
 pub enum Event<'a> {
     BuildingPackage(&'a str, &'a str),
+    /// The pkgbase is not built because the second pkgbase it depends on was not built.
+    BuildSkipped(&'a str, &'a str),
     BuildingSourcePackage(&'a str, &'a str),
+    /// The pkgbase is being built on the remote host.
+    BuildingRemote(&'a str, &'a str),
+    /// The pkgbase is being built in the chroot.
+    BuildingInChroot(&'a str, &'a Path),
+    CreatingChroot(&'a Path),
+    UpdatingChroot(&'a Path),
     BuiltPackage(&'a str, &'a str),
+    /// The build finished and this is how long it took.
+    BuildSummary(&'a BuildReport),
     BuiltSourcePackage(&'a str, &'a str),
     CreatingArchive(&'a str),
     RetrievingSources,
@@ -177,6 +381,7 @@ pub enum Event<'a> {
     DownloadingCurl(&'a str),
     VerifyingSignatures,
     VerifyingChecksums,
+    VerifyingManifest(&'a str),
     VerifyingSignature(&'a str),
     VerifyingChecksum(&'a str),
     ChecksumSkipped(&'a str),
@@ -184,25 +389,58 @@ pub enum Event<'a> {
     ChecksumPass(&'a str),
     SignatureCheckFailed(SigFailed<'a>),
     SignatureCheckPass(&'a str),
+    ReceivingKey(&'a str),
+    ReceivedKey(&'a str),
     ExtractingSources,
     GeneratingChecksums,
+    /// The generated checksums were written to the PKGBUILD.
+    ChecksumsUpdated(&'a Path),
+    /// The pkgbase's pkgver is being set to the version.
+    BumpingVersion(&'a str, &'a str),
     SourcesAreReady,
     NoExtact(&'a str),
     Extacting(&'a str),
+    UsingCachedExtraction(&'a str),
+    UsingSrcdirSnapshot,
+    /// A package file was copied from the artifact cache instead of being built.
+    UsingCachedPackage(&'a str),
+    /// The first build of [`Makepkg::check_reproducible`] is done and the packages are being
+    /// built again.
+    CheckingReproducibility,
+    /// A previous build with [`Options::resume`](crate::options::Options::resume) got as far
+    /// as completing this phase.
+    ResumingBuild(BuildPhase),
     RunningFunction(&'a str),
     RemovingSrcdir,
     RemovingPkgdir,
+    /// A build directory was removed freeing this many bytes.
+    RemovedDir(&'a Path, u64),
     UsingExistingSrcdir,
+    UsingBuilddir(&'a Path),
+    MountingTmpfs(&'a Path),
     StartingFakeroot,
     CreatingPackage(&'a str),
     CreatingDebugPackage(&'a str),
     CreatingSourcePackage(&'a str),
+    SigningPackage(&'a str),
+    PackageSigned(&'a Path),
+    SigningRepoDb(&'a str),
+    VerifyingPackage(&'a str),
+    RepoDbSigned(&'a Path),
     AddingPackageFiles,
     AddingFileToPackage(&'a str),
     GeneratingPackageFile(&'a str),
     DownloadingVCS(VCSKind, &'a Source),
     UpdatingVCS(VCSKind, &'a Source),
+    /// A VCS download failed and will be tried again after the delay. The number is the
+    /// retry attempt starting at 1.
+    RetryingVCS(VCSKind, &'a Source, u32, Duration),
     ExtractingVCS(VCSKind, &'a Source),
+    /// A makedepend or checkdepend that does not look to have been used by the build. The
+    /// first field is the variable the dependency is from.
+    PossiblyUnusedDepend(&'a str, &'a str),
+    /// A packaged file was renamed to its NFC normalized name.
+    NormalizedFileName(&'a Path),
 }
 
 impl<'a> From<SigFailed<'a>> for Event<'a> {
@@ -216,7 +454,17 @@ impl<'a> Display for Event<'a> {
         match self {
             Event::BuildingPackage(name, ver) => write!(f, "Package {}-{}", name, ver),
             Event::BuildingSourcePackage(name, ver) => write!(f, "Source package {}-{}", name, ver),
+            Event::BuildSkipped(name, dep) => {
+                write!(f, "Skipping {} because {} was not built", name, dep)
+            }
+            Event::BuildingRemote(name, host) => write!(f, "Building {} on {}", name, host),
+            Event::BuildingInChroot(name, dir) => {
+                write!(f, "Building {} in chroot {}", name, dir.display())
+            }
+            Event::CreatingChroot(dir) => write!(f, "Creating chroot {}", dir.display()),
+            Event::UpdatingChroot(dir) => write!(f, "Updating chroot {}", dir.display()),
             Event::BuiltPackage(name, ver) => write!(f, "Built package {}-{}", name, ver),
+            Event::BuildSummary(report) => report.fmt(f),
             Event::BuiltSourcePackage(name, ver) => {
                 write!(f, "Built source package {}-{}", name, ver)
             }
@@ -225,6 +473,9 @@ impl<'a> Display for Event<'a> {
             Event::RetrievingSources => write!(f, "Retrieving sources..."),
             Event::VerifyingSignatures => write!(f, "Verifying source signatures..."),
             Event::VerifyingChecksums => write!(f, "Verifying source checksums..."),
+            Event::VerifyingManifest(file) => {
+                write!(f, "Verifying sources against checksum manifest {}...", file)
+            }
             Event::FoundSource(file) => write!(f, "found {}", file),
             Event::Downloading(file) => write!(f, "downloading {}...", file),
             Event::DownloadingCurl(file) => write!(f, "downloading {}...", file),
@@ -235,23 +486,60 @@ impl<'a> Display for Event<'a> {
             Event::ChecksumPass(_) => write!(f, "Passsed"),
             Event::SignatureCheckFailed(e) => write!(f, "Failed ({})", e),
             Event::SignatureCheckPass(_) => write!(f, "Passsed"),
+            Event::ReceivingKey(key) => write!(f, "receiving key {} from keyserver...", key),
+            Event::ReceivedKey(key) => write!(f, "imported key {}", key),
             Event::GeneratingChecksums => write!(f, "Generating checksums for source files..."),
+            Event::ChecksumsUpdated(path) => write!(f, "Updated checksums in {}", path.display()),
+            Event::BumpingVersion(name, ver) => write!(f, "Bumping {} to {}", name, ver),
             Event::ExtractingSources => write!(f, "ExtractingSources..."),
             Event::SourcesAreReady => write!(f, "Sources are ready"),
             Event::NoExtact(file) => write!(f, "skipping {} (no extract)", file),
             Event::Extacting(file) => write!(f, "extracting {} ...", file),
+            Event::UsingCachedExtraction(file) => {
+                write!(f, "using cached extraction of {} ...", file)
+            }
             Event::RunningFunction(func) => write!(f, "Starting {}()...", func),
             Event::RemovingSrcdir => write!(f, "removing existing $srcdir/ directory"),
             Event::RemovingPkgdir => write!(f, "removing existing $pkgdir/ directory"),
+            Event::RemovedDir(dir, size) => {
+                write!(f, "removed {} ({})", dir.display(), HumanSize(*size))
+            }
+            Event::NormalizedFileName(path) => {
+                write!(f, "normalized file name {}", path.display())
+            }
+            Event::PossiblyUnusedDepend(var, dep) => {
+                write!(f, "{} in {} may not be needed", dep, var)
+            }
+            Event::UsingSrcdirSnapshot => write!(f, "Restoring sources from snapshot..."),
+            Event::UsingCachedPackage(file) => write!(f, "Using cached package {}...", file),
+            Event::CheckingReproducibility => {
+                write!(f, "Building again to check reproducibility...")
+            }
+            Event::ResumingBuild(phase) => write!(f, "Resuming build after {} phase...", phase),
             Event::UsingExistingSrcdir => write!(f, "using existing $srcdir/ directory"),
+            Event::UsingBuilddir(dir) => write!(f, "Using build directory {}", dir.display()),
+            Event::MountingTmpfs(dir) => write!(f, "Mounting tmpfs on {}", dir.display()),
             Event::StartingFakeroot => write!(f, "Starting fakeroot daemon..."),
             Event::CreatingPackage(file) => write!(f, "Creating package {}...", file),
             Event::CreatingDebugPackage(file) => write!(f, "Creating debug package {}...", file),
             Event::CreatingSourcePackage(file) => write!(f, "Creating source package {}...", file),
+            Event::SigningPackage(file) => write!(f, "Signing package {}...", file),
+            Event::PackageSigned(sig) => write!(f, "Created signature file {}", sig.display()),
+            Event::SigningRepoDb(file) => write!(f, "Signing database {}...", file),
+            Event::VerifyingPackage(file) => write!(f, "Verifying package {}...", file),
+            Event::RepoDbSigned(sig) => write!(f, "Created signature file {}", sig.display()),
             Event::AddingFileToPackage(file) => write!(f, "adding {} ...", file),
             Event::GeneratingPackageFile(file) => write!(f, "generating {} ...", file),
             Event::DownloadingVCS(k, s) => write!(f, "cloning {} repo {} ...", k, s.file_name()),
             Event::UpdatingVCS(k, s) => write!(f, "updading {} repo {} ...", k, s.file_name()),
+            Event::RetryingVCS(k, s, attempt, delay) => write!(
+                f,
+                "failed to download {} repo {}, retrying in {}s (attempt {})...",
+                k,
+                s.file_name(),
+                delay.as_secs(),
+                attempt
+            ),
             Event::ExtractingVCS(k, s) => write!(
                 f,
                 "creating working copy of {} {} repo...",
@@ -263,6 +551,7 @@ impl<'a> Display for Event<'a> {
 }
 
 #[derive(Debug, Copy, Clone, PartialEq, Eq, PartialOrd, Ord, Hash)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize))]
 pub enum LogLevel {
     Debug,
     Warning,
@@ -280,11 +569,57 @@ impl Display for LogLevel {
 }
 
 #[derive(Debug, Clone, PartialEq, Eq, PartialOrd, Ord, Hash)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize))]
 pub enum LogMessage<'a> {
     SkippingAllIntegrityChecks,
     SkippingPGPIntegrityChecks,
     SkippingChecksumIntegrityChecks,
     KeyNotDoundInKeys(&'a str),
+    FailedToReceiveKey(&'a str),
+    NoBuilddirFits(&'a Path),
+    /// The tmpfs builddir is too small for the build. The values are the space needed and
+    /// the space available.
+    TmpfsTooSmall(&'a Path, u64, u64),
+    RemovingStaleLock(&'a Path),
+    /// A remote source is not listed in any of the upstream checksum manifests.
+    NotInManifest(&'a str),
+    PkgbuildWarning(&'a str),
+    /// A package links against libraries from a package it does not depend on.
+    UndeclaredLibDepend(&'a str, &'a str, &'a [String]),
+    /// Files in a package contain the path of the build directory.
+    PackageContainsReference(&'a str, &'a Path, &'a [PathBuf]),
+    /// Files in a package have questionable permissions or locations.
+    PackageFileIssue(&'a str, FileIssue, &'a [PathBuf]),
+    /// A package contains no files besides its metadata.
+    EmptyPackage(&'a str),
+    /// A [`PackageCheck`](crate::config::PackageCheck) found a problem in a package.
+    PackageWarning(&'a str, &'a PackageWarning),
+    /// The same file is packaged by multiple packages of a split package.
+    DuplicateFile(&'a DuplicateFile),
+    /// The total bytes wasted by [`LogMessage::DuplicateFile`] for the pkgbase.
+    DuplicateFilesWasted(&'a str, u64),
+}
+
+/// A problem with files found in a package.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize))]
+pub enum FileIssue {
+    Setuid,
+    Setgid,
+    WorldWritable,
+    /// Dotfiles in the root of the package.
+    Dotfile,
+}
+
+impl Display for FileIssue {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            FileIssue::Setuid => f.write_str("setuid files"),
+            FileIssue::Setgid => f.write_str("setgid files"),
+            FileIssue::WorldWritable => f.write_str("world writable files"),
+            FileIssue::Dotfile => f.write_str("dotfiles in the package root"),
+        }
+    }
 }
 
 impl<'a> Display for LogMessage<'a> {
@@ -298,12 +633,75 @@ impl<'a> Display for LogMessage<'a> {
                 f.write_str("skipping checksum integrity checks")
             }
             LogMessage::KeyNotDoundInKeys(k) => write!(f, "key {} not found in keys/pgp", k),
+            LogMessage::FailedToReceiveKey(k) => write!(f, "failed to receive key {}", k),
+            LogMessage::NotInManifest(s) => {
+                write!(f, "{} is not listed in any checksum manifest", s)
+            }
+            LogMessage::PkgbuildWarning(w) => f.write_str(w),
+            LogMessage::PackageContainsReference(pkg, dir, files) => {
+                write!(f, "{} contains references to {}:", pkg, dir.display())?;
+                for file in *files {
+                    write!(f, "\n    {}", file.display())?;
+                }
+                Ok(())
+            }
+            LogMessage::PackageFileIssue(pkg, issue, files) => {
+                write!(f, "{} contains {}:", pkg, issue)?;
+                for file in *files {
+                    write!(f, "\n    {}", file.display())?;
+                }
+                Ok(())
+            }
+            LogMessage::PackageWarning(pkg, warning) => write!(f, "{}: {}", pkg, warning),
+            LogMessage::DuplicateFile(dup) => {
+                write!(f, "identical file ({}) in", HumanSize(dup.size))?;
+                for (i, (pkg, path)) in dup.files.iter().enumerate() {
+                    let sep = if i == 0 { "" } else { "," };
+                    write!(f, "{} {}:/{}", sep, pkg, path.display())?;
+                }
+                Ok(())
+            }
+            LogMessage::DuplicateFilesWasted(pkgbase, wasted) => write!(
+                f,
+                "{} duplicated files waste {}, consider moving them to a common package",
+                pkgbase,
+                HumanSize(*wasted)
+            ),
+            LogMessage::EmptyPackage(pkg) => {
+                write!(f, "{} contains no files besides metadata", pkg)
+            }
+            LogMessage::UndeclaredLibDepend(pkg, owner, libs) => write!(
+                f,
+                "{} links against {} from {} which is not in depends",
+                pkg,
+                libs.join(", "),
+                owner
+            ),
+            LogMessage::RemovingStaleLock(lock) => {
+                write!(f, "removing stale lock {}", lock.display())
+            }
+            LogMessage::TmpfsTooSmall(dir, needed, available) => write!(
+                f,
+                "tmpfs {} is too small ({} needed, {} available), building on disk",
+                dir.display(),
+                HumanSize(*needed),
+                HumanSize(*available)
+            ),
+            LogMessage::NoBuilddirFits(dir) => write!(
+                f,
+                "no build directory has enough free space, using {}",
+                dir.display()
+            ),
         }
     }
 }
 
 impl Makepkg {
     pub fn event(&self, event: Event) -> Result<()> {
+        #[cfg(feature = "tracing")]
+        tracing::info!("{}", event);
+        self.build_log_line(&event.to_string())
+            .context(Context::Callback, IOContext::WriteBuffer)?;
         if let Some(cb) = &mut *self.callbacks.borrow_mut() {
             cb.event(event)
                 .context(Context::Callback, IOContext::WriteBuffer)?;
@@ -312,6 +710,14 @@ impl Makepkg {
     }
 
     pub fn log(&self, level: LogLevel, msg: LogMessage) -> Result<()> {
+        #[cfg(feature = "tracing")]
+        match level {
+            LogLevel::Debug => tracing::debug!("{}", msg),
+            LogLevel::Warning => tracing::warn!("{}", msg),
+            LogLevel::Error => tracing::error!("{}", msg),
+        }
+        self.build_log_line(&format!("{}: {}", level, msg))
+            .context(Context::Callback, IOContext::WriteBuffer)?;
         if let Some(cb) = &mut *self.callbacks.borrow_mut() {
             cb.log(level, msg)
                 .context(Context::Callback, IOContext::WriteBuffer)?;
@@ -320,10 +726,79 @@ impl Makepkg {
     }
 
     pub fn download(&self, pkgbuild: &Pkgbuild, event: DownloadEvent) -> Result<()> {
+        #[cfg(feature = "tracing")]
+        match event {
+            DownloadEvent::DownloadStart(n) => tracing::debug!(count = n, "starting downloads"),
+            DownloadEvent::Init(d) => tracing::debug!(file = d.source.file_name(), "downloading"),
+            DownloadEvent::Progress(d, done, total) => {
+                tracing::trace!(
+                    file = d.source.file_name(),
+                    done,
+                    total,
+                    "download progress"
+                )
+            }
+            DownloadEvent::Completed(d) => {
+                tracing::debug!(file = d.source.file_name(), "download completed")
+            }
+            DownloadEvent::NotModified(d) => {
+                tracing::debug!(file = d.source.file_name(), "download not modified")
+            }
+            DownloadEvent::Failed(d, code) => {
+                tracing::warn!(file = d.source.file_name(), code, "download failed")
+            }
+            DownloadEvent::DownloadEnd => tracing::debug!("downloads finished"),
+        }
         if let Some(cb) = &mut *self.callbacks.borrow_mut() {
             cb.download(pkgbuild, event)
                 .context(Context::Callback, IOContext::WriteBuffer)?;
         }
         Ok(())
     }
+
+    pub fn package(&self, event: PackageEvent) -> Result<()> {
+        if let Some(cb) = &mut *self.callbacks.borrow_mut() {
+            cb.package(event)
+                .context(Context::Callback, IOContext::WriteBuffer)?;
+        }
+        Ok(())
+    }
+
+    pub fn hashing(&self, file_name: &str, hashed: u64, total: u64) -> Result<()> {
+        if let Some(cb) = &mut *self.callbacks.borrow_mut() {
+            cb.hashing(file_name, hashed, total)
+                .context(Context::Callback, IOContext::WriteBuffer)?;
+        }
+        Ok(())
+    }
+
+    pub fn question(&self, question: Question) -> Result<Answer> {
+        if let Some(cb) = &mut *self.callbacks.borrow_mut() {
+            let answer = cb
+                .question(question)
+                .context(Context::Callback, IOContext::WriteBuffer)?;
+            return Ok(answer);
+        }
+        Ok(Answer::No)
+    }
+}
+
+/// Formats a byte count using binary units.
+pub(crate) struct HumanSize(pub u64);
+
+impl Display for HumanSize {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        const UNITS: &[&str] = &["B", "KiB", "MiB", "GiB", "TiB"];
+        let mut size = self.0 as f64;
+        let mut unit = 0;
+        while size >= 1024.0 && unit < UNITS.len() - 1 {
+            size /= 1024.0;
+            unit += 1;
+        }
+        if unit == 0 {
+            write!(f, "{} {}", self.0, UNITS[0])
+        } else {
+            write!(f, "{:.1} {}", size, UNITS[unit])
+        }
+    }
 }
@@ -0,0 +1,128 @@
+use std::path::PathBuf;
+
+use sha2::Sha256;
+
+use crate::{
+    callback::Event,
+    config::PkgbuildDirs,
+    error::{Context, Result},
+    fs::{mkdir, read_to_string, rm_file, write},
+    integ::hash_file,
+    options::Options,
+    report::BuildPhase,
+    Makepkg,
+};
+
+/// The phases of a build that have completed, kept on disk so a failed build can be resumed
+/// with [`Options::resume`].
+///
+/// The file is tied to the PKGBUILD it was written for and is ignored once it changes.
+#[derive(Debug)]
+pub(crate) struct Checkpoint {
+    path: PathBuf,
+    pkgbuild: PathBuf,
+    key: String,
+    done: Vec<BuildPhase>,
+}
+
+impl Checkpoint {
+    /// Whether the phase completed during a previous build.
+    pub(crate) fn done(&self, phase: BuildPhase) -> bool {
+        self.done.contains(&phase)
+    }
+
+    /// Records the phase as completed.
+    pub(crate) fn complete(&mut self, phase: BuildPhase) -> Result<()> {
+        if !self.done(phase) {
+            self.done.push(phase);
+        }
+        self.save()
+    }
+
+    /// Keys the checkpoint to the PKGBUILD again after pkgver() rewrote it.
+    pub(crate) fn update_pkgbuild(&mut self) -> Result<()> {
+        let key = hash_file::<Sha256>(&self.pkgbuild)?;
+        if key != self.key {
+            self.key = key;
+            if !self.done.is_empty() {
+                self.save()?;
+            }
+        }
+        Ok(())
+    }
+
+    fn save(&self) -> Result<()> {
+        let mut contents = format!("{}\n", self.key);
+        for phase in &self.done {
+            contents.push_str(phase.name());
+            contents.push('\n');
+        }
+        // the builddir is not created until extraction
+        if let Some(dir) = self.path.parent().filter(|dir| !dir.exists()) {
+            mkdir(dir, Context::BuildPackage)?;
+        }
+        write(&self.path, contents, Context::BuildPackage)
+    }
+
+    /// Removes the file after the build succeeded.
+    pub(crate) fn finish(self) -> Result<()> {
+        if self.path.exists() {
+            rm_file(&self.path, Context::BuildPackage)?;
+        }
+        Ok(())
+    }
+}
+
+impl Makepkg {
+    /// Loads the checkpoint of the last build.
+    ///
+    /// Unless [`Options::resume`] is set the previous checkpoint is discarded and the build
+    /// starts from the beginning.
+    pub(crate) fn load_checkpoint(
+        &self,
+        options: &Options,
+        dirs: &PkgbuildDirs,
+    ) -> Result<Checkpoint> {
+        let path = dirs.srcdir.with_file_name(".makepkg-checkpoint");
+        let key = hash_file::<Sha256>(&dirs.pkgbuild)?;
+        let mut checkpoint = Checkpoint {
+            path,
+            pkgbuild: dirs.pkgbuild.clone(),
+            key,
+            done: Vec::new(),
+        };
+
+        if !checkpoint.path.exists() {
+            return Ok(checkpoint);
+        }
+        if !options.resume {
+            rm_file(&checkpoint.path, Context::BuildPackage)?;
+            return Ok(checkpoint);
+        }
+
+        let contents = read_to_string(&checkpoint.path, Context::BuildPackage)?;
+        let mut lines = contents.lines();
+        if lines.next() != Some(checkpoint.key.as_str()) {
+            return Ok(checkpoint);
+        }
+        checkpoint.done = lines
+            .filter_map(|line| ALL_PHASES.iter().find(|p| p.name() == line).copied())
+            .collect();
+
+        if let Some(&last) = checkpoint.done.last() {
+            self.event(Event::ResumingBuild(last))?;
+        }
+        Ok(checkpoint)
+    }
+}
+
+const ALL_PHASES: &[BuildPhase] = &[
+    BuildPhase::Download,
+    BuildPhase::Verify,
+    BuildPhase::Extract,
+    BuildPhase::Prepare,
+    BuildPhase::Build,
+    BuildPhase::Check,
+    BuildPhase::Package,
+    BuildPhase::Archive,
+];
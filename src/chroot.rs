@@ -0,0 +1,362 @@
+use std::{
+    fs::{self, read_dir},
+    path::{Component, Path, PathBuf},
+    process::Command,
+};
+
+use crate::{
+    callback::{CommandKind, Event},
+    error::{
+        CommandErrorExt, Context, IOContext, IOErrorExt, Result, UnsafeDeleteError,
+        UnsafeDeleteReason,
+    },
+    fs::mkdir,
+    options::Options,
+    pacman::shell_quote,
+    pkgbuild::Pkgbuild,
+    run::{CommandOutput, RunOptions},
+    Makepkg,
+};
+
+// the unprivileged user builds run as inside the chroot
+const BUILD_USER: &str = "builduser";
+// written to the root once it has been fully set up
+const CHROOT_MARKER: &str = ".arch-chroot";
+
+/// A clean chroot to build packages in.
+///
+/// Like devtools, the chroot directory holds a pristine `root` that is only ever updated and a
+/// working copy that is recreated from `root` before every build so that builds can not affect
+/// each other. Commands that need root are run the same way as pacman is, see
+/// [`Makepkg::pacman`].
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub struct Chroot {
+    /// The directory holding the root and working copy.
+    pub dir: PathBuf,
+    /// The packages installed when creating the root.
+    pub packages: Vec<String>,
+    /// A tarball of a previously created root to extract instead of running pacstrap.
+    pub tarball: Option<PathBuf>,
+    /// The pacman.conf used to create the root and installed into it.
+    pub pacman_conf: Option<PathBuf>,
+    /// The makepkg.conf installed into the root.
+    pub makepkg_conf: Option<PathBuf>,
+    /// The name of the working copy. This is deleted before each build so must be a plain
+    /// directory name other than `root`, anything else is refused.
+    pub copy: String,
+}
+
+impl Chroot {
+    pub fn new<P: Into<PathBuf>>(dir: P) -> Self {
+        Chroot {
+            dir: dir.into(),
+            packages: vec!["base-devel".to_string()],
+            tarball: None,
+            pacman_conf: None,
+            makepkg_conf: None,
+            copy: "build".to_string(),
+        }
+    }
+
+    /// The pristine root.
+    pub fn root(&self) -> PathBuf {
+        self.dir.join("root")
+    }
+
+    /// The working copy builds run in.
+    pub fn working_copy(&self) -> PathBuf {
+        self.dir.join(&self.copy)
+    }
+
+    // the working copy is removed as root so it has to be a directory directly inside the chroot
+    fn check_copy(&self) -> Result<()> {
+        let mut components = Path::new(&self.copy).components();
+        match (components.next(), components.next()) {
+            (Some(Component::Normal(name)), None) if name != "root" => Ok(()),
+            _ => Err(UnsafeDeleteError {
+                path: self.working_copy(),
+                reason: UnsafeDeleteReason::InvalidChrootCopy,
+            }
+            .into()),
+        }
+    }
+
+    /// Whether the root has been created.
+    pub fn exists(&self) -> bool {
+        self.root().join(CHROOT_MARKER).exists()
+    }
+}
+
+impl Makepkg {
+    /// Creates the root of a chroot with pacstrap or by extracting
+    /// [`Chroot::tarball`].
+    ///
+    /// Does nothing if the root already exists. The commands run are reported as
+    /// [`CommandKind::Chroot`] for `pkgbuild`.
+    pub fn create_chroot(&self, pkgbuild: &Pkgbuild, chroot: &Chroot) -> Result<()> {
+        if chroot.exists() {
+            return Ok(());
+        }
+
+        let root = path_arg(&chroot.root());
+        self.event(Event::CreatingChroot(&chroot.dir))?;
+        self.chroot_cmd(pkgbuild, vec!["install".into(), "-d".into(), root.clone()])?;
+
+        if let Some(tarball) = &chroot.tarball {
+            let cmd = vec![
+                "tar".into(),
+                "-xpf".into(),
+                path_arg(tarball),
+                "-C".into(),
+                root.clone(),
+            ];
+            self.chroot_cmd(pkgbuild, cmd)?;
+        } else {
+            let mut cmd = vec!["pacstrap".into(), "-GMc".into()];
+            if let Some(conf) = &chroot.pacman_conf {
+                cmd.extend(["-C".into(), path_arg(conf)]);
+            }
+            cmd.push(root.clone());
+            cmd.extend(chroot.packages.iter().cloned());
+            self.chroot_cmd(pkgbuild, cmd)?;
+        }
+
+        for (conf, dest) in [
+            (&chroot.pacman_conf, "etc/pacman.conf"),
+            (&chroot.makepkg_conf, "etc/makepkg.conf"),
+        ] {
+            if let Some(conf) = conf {
+                let dest = path_arg(&chroot.root().join(dest));
+                self.chroot_cmd(
+                    pkgbuild,
+                    vec!["install".into(), "-m644".into(), path_arg(conf), dest],
+                )?;
+            }
+        }
+
+        self.chroot_cmd(
+            pkgbuild,
+            arch_chroot(&root, ["useradd", "-m", "-U", BUILD_USER]),
+        )?;
+        let marker = path_arg(&chroot.root().join(CHROOT_MARKER));
+        self.chroot_cmd(pkgbuild, vec!["touch".into(), marker])?;
+        Ok(())
+    }
+
+    /// Updates the packages in the root of a chroot.
+    pub fn update_chroot(&self, pkgbuild: &Pkgbuild, chroot: &Chroot) -> Result<()> {
+        self.event(Event::UpdatingChroot(&chroot.dir))?;
+        let root = path_arg(&chroot.root());
+        self.chroot_cmd(
+            pkgbuild,
+            arch_chroot(&root, ["pacman", "-Syu", "--noconfirm"]),
+        )
+    }
+
+    /// Builds a PKGBUILD inside of a clean chroot.
+    ///
+    /// The chroot is created if it does not exist. The working copy is recreated from the root,
+    /// the dependencies of the PKGBUILD are installed into it and makepkg is run as an
+    /// unprivileged user. The built packages are copied to PKGDEST and logs to LOGDEST.
+    ///
+    /// Returns the paths of the built packages.
+    pub fn build_in_chroot(
+        &self,
+        options: &Options,
+        pkgbuild: &Pkgbuild,
+        chroot: &Chroot,
+    ) -> Result<Vec<PathBuf>> {
+        let dirs = self.pkgbuild_dirs(pkgbuild)?;
+        let arch = &self.config.arch;
+
+        chroot.check_copy()?;
+        self.create_chroot(pkgbuild, chroot)?;
+        self.event(Event::BuildingInChroot(&pkgbuild.pkgbase, &chroot.dir))?;
+
+        let root = path_arg(&chroot.root());
+        let copy = path_arg(&chroot.working_copy());
+        self.chroot_cmd(
+            pkgbuild,
+            vec!["rm".into(), "-rf".into(), "--".into(), copy.clone()],
+        )?;
+        self.chroot_cmd(
+            pkgbuild,
+            vec![
+                "cp".into(),
+                "-a".into(),
+                "--reflink=auto".into(),
+                root,
+                copy.clone(),
+            ],
+        )?;
+
+        let owner = format!("{0}:{0}", BUILD_USER);
+        self.chroot_cmd(
+            pkgbuild,
+            arch_chroot(
+                &copy,
+                ["install", "-d", "-o", BUILD_USER, "/build", "/build/out"],
+            ),
+        )?;
+
+        let mut tar = Command::new("tar");
+        tar.arg("-C")
+            .arg(&dirs.startdir)
+            .arg("--exclude=./src")
+            .arg("--exclude=./pkg")
+            .arg("-cf")
+            .arg("-")
+            .arg(".");
+        self.chroot_upload(pkgbuild, tar, &copy)?;
+
+        let sources = pkgbuild
+            .source
+            .enabled(arch)
+            .map(|s| s.file_name())
+            .filter(|f| dirs.srcdest != dirs.startdir && dirs.srcdest.join(f).exists())
+            .collect::<Vec<_>>();
+        if !sources.is_empty() {
+            let mut tar = Command::new("tar");
+            tar.arg("-C")
+                .arg(&dirs.srcdest)
+                .arg("-cf")
+                .arg("-")
+                .arg("--")
+                .args(&sources);
+            self.chroot_upload(pkgbuild, tar, &copy)?;
+        }
+        self.chroot_cmd(
+            pkgbuild,
+            arch_chroot(&copy, ["chown", "-R", &owner, "/build"]),
+        )?;
+
+        let mut deps = pkgbuild
+            .packages()
+            .flat_map(|p| p.depends.enabled(arch))
+            .chain(pkgbuild.makedepends.enabled(arch))
+            .collect::<Vec<_>>();
+        if !options.no_check {
+            deps.extend(pkgbuild.checkdepends.enabled(arch));
+        }
+        if !deps.is_empty() {
+            let mut cmd = arch_chroot(
+                &copy,
+                ["pacman", "-S", "--needed", "--noconfirm", "--asdeps", "--"],
+            );
+            cmd.extend(deps.into_iter().cloned());
+            self.chroot_cmd(pkgbuild, cmd)?;
+        }
+
+        let mut args = vec!["makepkg", "--noconfirm", "--nodeps"];
+        for (set, arg) in [
+            (options.no_check, "--nocheck"),
+            (options.ignore_arch, "--ignorearch"),
+            (options.hold_ver, "--holdver"),
+            (options.no_checksums, "--skipchecksums"),
+            (options.no_signatures, "--skippgpcheck"),
+            (options.log, "--log"),
+        ] {
+            if set {
+                args.push(arg);
+            }
+        }
+        let makepkg = args
+            .iter()
+            .map(|a| shell_quote(a))
+            .collect::<Vec<_>>()
+            .join(" ");
+        let script = format!(
+            "cd /build && PKGDEST=/build/out LOGDEST=/build/out {}",
+            makepkg
+        );
+        let mut command = self.as_root(arch_chroot(
+            &copy,
+            ["runuser", "-u", BUILD_USER, "--", "sh", "-c", &script],
+        ));
+        command
+            .process_spawn(self, CommandKind::Chroot(pkgbuild))
+            .cmd_context(&command, Context::BuildInChroot)?;
+
+        let out = chroot.working_copy().join("build/out");
+        let mut pkgs = Vec::new();
+        mkdir(&dirs.pkgdest, Context::BuildInChroot)?;
+        mkdir(&dirs.logdest, Context::BuildInChroot)?;
+
+        for file in
+            read_dir(&out).context(Context::BuildInChroot, IOContext::ReadDir(out.clone()))?
+        {
+            let file = file.context(Context::BuildInChroot, IOContext::ReadDir(out.clone()))?;
+            let name = file.file_name();
+            let dest = if name.to_string_lossy().ends_with(".log") {
+                dirs.logdest.join(&name)
+            } else {
+                let dest = self
+                    .config
+                    .package_file_dest(pkgbuild, &name.to_string_lossy())?;
+                if let Some(dir) = dest.parent().filter(|d| !d.exists()) {
+                    mkdir(dir, Context::BuildInChroot)?;
+                }
+                pkgs.push(dest.clone());
+                dest
+            };
+            fs::copy(file.path(), &dest)
+                .context(Context::BuildInChroot, IOContext::Copy(file.path(), dest))?;
+        }
+
+        Ok(pkgs)
+    }
+
+    fn chroot_upload(&self, pkgbuild: &Pkgbuild, mut tar: Command, copy: &str) -> Result<()> {
+        let dest = format!("{}/build", copy);
+        let mut extract = self.as_root(vec![
+            "tar".into(),
+            "-C".into(),
+            dest,
+            "-xf".into(),
+            "-".into(),
+        ]);
+        tar.process_pipe(self, CommandKind::Chroot(pkgbuild), &[], &mut extract, None)
+            .cmd_context(&tar, Context::BuildInChroot)?;
+        Ok(())
+    }
+
+    fn chroot_cmd(&self, pkgbuild: &Pkgbuild, cmd: Vec<String>) -> Result<()> {
+        // like pacman these may need to prompt for a password so they get stdin
+        let mut command = self.as_root(cmd);
+        let run = RunOptions {
+            stdin: true,
+            ..Default::default()
+        };
+        command
+            .process_function(self, CommandKind::Chroot(pkgbuild), &[], None, None, run)
+            .cmd_context(&command, Context::BuildInChroot)?;
+        Ok(())
+    }
+}
+
+fn arch_chroot<'a, I: IntoIterator<Item = &'a str>>(dir: &'a str, args: I) -> Vec<String> {
+    ["arch-chroot", dir]
+        .into_iter()
+        .chain(args)
+        .map(|a| a.to_string())
+        .collect()
+}
+
+fn path_arg(path: &Path) -> String {
+    path.to_string_lossy().into_owned()
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn check_copy() {
+        let mut chroot = Chroot::new("/var/lib/chroot");
+        assert!(chroot.check_copy().is_ok());
+        for copy in ["", "root", ".", "..", "../build", "a/b", "/build"] {
+            chroot.copy = copy.to_string();
+            assert!(chroot.check_copy().is_err(), "{}", copy);
+        }
+    }
+}
@@ -1,4 +1,5 @@
 use std::{
+    collections::BTreeMap,
     ffi::OsStr,
     fmt::Display,
     fs::read_dir,
@@ -10,9 +11,10 @@ use std::{
 
 pub use crate::lint_config::*;
 use crate::{
-    error::{Context, DownloadAgentError, LintError, LintKind, Result, VCSClientError},
+    error::{Context, DownloadAgentError, LintError, LintKind, LintLevels, Result, VCSClientError},
     fs::{resolve_path, resolve_path_relative, Check},
     installation_variables::{MAKEPKG_CONFIG_PATH, PREFIX},
+    package::PackageKind,
     pkgbuild::{ChecksumKind, OptionState, Options, Package, Pkgbuild, Source},
     raw::RawConfig,
     sources::VCSKind,
@@ -151,10 +153,29 @@ impl FromStr for VCSClient {
             package: package.to_string(),
         };
 
+        agent.validate()?;
         Ok(agent)
     }
 }
 
+impl Display for VCSClient {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}::{}", self.protocol, self.package)
+    }
+}
+
+impl VCSClient {
+    /// Checks that the client names a package to install for its protocol.
+    pub fn validate(&self) -> StdResult<(), VCSClientError> {
+        if self.package.is_empty() || self.package.contains(char::is_whitespace) {
+            return Err(VCSClientError {
+                input: self.to_string(),
+            });
+        }
+        Ok(())
+    }
+}
+
 #[derive(Debug, Clone, PartialOrd, Ord, PartialEq, Eq)]
 pub struct DownloadAgent {
     pub protocol: String,
@@ -180,10 +201,315 @@ impl FromStr for DownloadAgent {
             args: words.map(|s| s.to_string()).collect(),
         };
 
+        agent.validate()?;
         Ok(agent)
     }
 }
 
+impl Display for DownloadAgent {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}::{}", self.protocol, self.command)?;
+        for arg in &self.args {
+            write!(f, " {}", arg)?;
+        }
+        Ok(())
+    }
+}
+
+impl DownloadAgent {
+    /// Checks that the agent has a protocol and a command to run.
+    pub fn validate(&self) -> StdResult<(), DownloadAgentError> {
+        let valid = !self.protocol.is_empty()
+            && !self.command.is_empty()
+            && !self
+                .protocol
+                .contains(|c: char| c.is_whitespace() || c == ':')
+            && !self.command.contains(char::is_whitespace);
+
+        if !valid {
+            return Err(DownloadAgentError {
+                input: self.to_string(),
+            });
+        }
+        Ok(())
+    }
+
+    /// Whether the agent runs curl, in which case the download is done with libcurl instead.
+    pub fn is_curl(&self) -> bool {
+        self.command.rsplit('/').next() == Some("curl")
+    }
+
+    /// Whether the agent is curl run with only the options from the stock makepkg.conf.
+    ///
+    /// libcurl can stand in for such an agent without losing any of the user's arguments.
+    pub fn is_stock_curl(&self) -> bool {
+        if !self.is_curl() {
+            return false;
+        }
+
+        let mut args = self.args.iter().map(|a| a.as_str());
+        while let Some(arg) = args.next() {
+            let ok = match arg {
+                "%u" | "--globoff" | "--fail" | "--location" | "--ftp-pasv" => true,
+                "--retry" | "--retry-delay" => args.next().is_some_and(is_number),
+                "--continue-at" | "--output" | "--cookie" => {
+                    args.next().is_some_and(|v| stock_curl_value(arg, v))
+                }
+                _ => match arg.strip_prefix('-') {
+                    Some(flags) if !flags.is_empty() && !flags.starts_with('-') => {
+                        stock_curl_flags(flags, &mut args)
+                    }
+                    _ => false,
+                },
+            };
+            if !ok {
+                return false;
+            }
+        }
+        true
+    }
+}
+
+// a cluster of short curl options such as `-qgb`, taking the value of the last from `args`
+// when it is not attached
+fn stock_curl_flags<'a>(flags: &str, args: &mut impl Iterator<Item = &'a str>) -> bool {
+    for (i, flag) in flags.char_indices() {
+        let long = match flag {
+            'q' | 'g' | 'f' | 'L' => continue,
+            'C' => "--continue-at",
+            'o' => "--output",
+            'b' => "--cookie",
+            _ => return false,
+        };
+        let value = match &flags[i + 1..] {
+            "" => args.next(),
+            value => Some(value),
+        };
+        return value.is_some_and(|v| stock_curl_value(long, v));
+    }
+    true
+}
+
+// the only values makepkg's own curl options are given
+fn stock_curl_value(option: &str, value: &str) -> bool {
+    match option {
+        "--continue-at" => value == "-",
+        "--output" => value == "%o",
+        // an empty cookie only enables the cookie engine, written as `""` in makepkg.conf
+        "--cookie" => value == "\"\"",
+        _ => false,
+    }
+}
+
+fn is_number(s: &str) -> bool {
+    !s.is_empty() && s.chars().all(|c| c.is_ascii_digit())
+}
+
+/// How packages end up with files owned by root without building as root.
+#[derive(Debug, Default, Copy, Clone, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub enum PackagingBackend {
+    /// Run package() and bsdtar under a fakeroot daemon.
+    ///
+    /// Files are recorded as owned by root unless package() changes their owner, in which
+    /// case the faked owner is recorded. Needs faked and libfakeroot, which often break in
+    /// containers.
+    #[default]
+    Fakeroot,
+    /// Run package() as the current user and have bsdtar record every file as owned by root.
+    ///
+    /// Ownership changes made in package() are lost.
+    TarOverride,
+    /// Run package() and bsdtar in a user namespace where the current user is root.
+    ///
+    /// Files are recorded as owned by root. Changing a file's owner to anyone else fails as
+    /// only the current user is mapped into the namespace.
+    UserNamespace,
+}
+
+impl Display for PackagingBackend {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            PackagingBackend::Fakeroot => f.write_str("fakeroot"),
+            PackagingBackend::TarOverride => f.write_str("bsdtar ownership override"),
+            PackagingBackend::UserNamespace => f.write_str("user namespace"),
+        }
+    }
+}
+
+/// The order sources downloaded with curl are started in.
+#[derive(Debug, Default, Copy, Clone, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub enum DownloadOrder {
+    /// Download in the order the sources are listed in the PKGBUILD.
+    #[default]
+    Listed,
+    /// Query the size of each source first and start the largest downloads first.
+    LargestFirst,
+    /// Alternate between hosts so no one host is hit with every download at once.
+    InterleaveHosts,
+}
+
+/// Controls how concurrent curl downloads are scheduled.
+#[derive(Debug, Clone, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct DownloadPolicy {
+    /// Maximum number of downloads running at once.
+    pub max_downloads: usize,
+    /// Maximum number of downloads running at once from a single host.
+    pub max_downloads_per_host: Option<usize>,
+    /// The order downloads are started in.
+    pub order: DownloadOrder,
+}
+
+impl Default for DownloadPolicy {
+    fn default() -> Self {
+        DownloadPolicy {
+            max_downloads: 8,
+            max_downloads_per_host: None,
+            order: DownloadOrder::default(),
+        }
+    }
+}
+
+/// A check run over built packages.
+///
+/// Every check is enabled unless disabled with `!name` in `PACKAGE_CHECKS`.
+#[derive(Debug, Copy, Clone, PartialEq, Eq, PartialOrd, Ord, Hash)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize))]
+pub enum PackageCheck {
+    /// Files containing the path of srcdir or pkgdir.
+    BuildReferences,
+    /// Setuid, setgid and world writable files, dotfiles and empty packages.
+    Hygiene,
+    /// Backup entries that are not in the package.
+    Backup,
+    /// Libraries linked against from packages not in depends.
+    LibDepends,
+    /// Files outside of the directories packages are expected to install to.
+    Fhs,
+    /// Custom licenses that are not installed to /usr/share/licenses.
+    License,
+    /// Malformed .desktop files.
+    Desktop,
+    /// Python bytecode outside of __pycache__.
+    PythonBytecode,
+    /// Makedepends and checkdepends that do not look to have been used by the build.
+    UnusedDepends,
+    /// Identical files packaged by more than one package of a split package.
+    DuplicateFiles,
+    /// File names that are not valid UTF-8 or not NFC normalized.
+    FileNames,
+}
+
+impl PackageCheck {
+    pub const ALL: &'static [PackageCheck] = &[
+        PackageCheck::BuildReferences,
+        PackageCheck::Hygiene,
+        PackageCheck::Backup,
+        PackageCheck::LibDepends,
+        PackageCheck::Fhs,
+        PackageCheck::License,
+        PackageCheck::Desktop,
+        PackageCheck::PythonBytecode,
+        PackageCheck::UnusedDepends,
+        PackageCheck::DuplicateFiles,
+        PackageCheck::FileNames,
+    ];
+
+    pub fn name(&self) -> &'static str {
+        match self {
+            PackageCheck::BuildReferences => "references",
+            PackageCheck::Hygiene => "hygiene",
+            PackageCheck::Backup => "backup",
+            PackageCheck::LibDepends => "libdepends",
+            PackageCheck::Fhs => "fhs",
+            PackageCheck::License => "license",
+            PackageCheck::Desktop => "desktop",
+            PackageCheck::PythonBytecode => "pycache",
+            PackageCheck::UnusedDepends => "unuseddepends",
+            PackageCheck::DuplicateFiles => "duplicates",
+            PackageCheck::FileNames => "filenames",
+        }
+    }
+}
+
+impl Display for PackageCheck {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str(self.name())
+    }
+}
+
+impl FromStr for PackageCheck {
+    type Err = LintKind;
+
+    fn from_str(s: &str) -> StdResult<Self, Self::Err> {
+        PackageCheck::ALL
+            .iter()
+            .find(|c| c.name() == s)
+            .copied()
+            .ok_or_else(|| LintKind::UnknownPackageCheck(s.to_string()))
+    }
+}
+
+/// Which downloads a [`DownloadHeader`] applies to.
+#[derive(Debug, Clone, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub enum DownloadTarget {
+    /// Sources from this host or any of its subdomains.
+    Host(String),
+    /// Sources whose url starts with this.
+    Url(String),
+}
+
+/// An extra HTTP header sent when downloading matching sources with curl.
+///
+/// Parsed from `DOWNLOAD_HEADERS` entries in the form `target::Header: value` where target
+/// is either a host or a url prefix. A `User-Agent` header replaces the default user agent.
+#[derive(Debug, Clone, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct DownloadHeader {
+    pub target: DownloadTarget,
+    pub header: String,
+}
+
+impl FromStr for DownloadHeader {
+    type Err = LintKind;
+
+    fn from_str(s: &str) -> StdResult<Self, Self::Err> {
+        let err = || LintKind::InvalidDownloadHeader(s.to_string());
+        let (target, header) = s.split_once("::").ok_or_else(err)?;
+        if target.is_empty() || !header.contains(':') {
+            return Err(err());
+        }
+
+        let target = if target.contains("://") {
+            DownloadTarget::Url(target.to_string())
+        } else {
+            DownloadTarget::Host(target.to_string())
+        };
+
+        Ok(DownloadHeader {
+            target,
+            header: header.to_string(),
+        })
+    }
+}
+
+impl DownloadHeader {
+    pub fn matches(&self, source: &Source) -> bool {
+        match &self.target {
+            DownloadTarget::Url(url) => source.url.starts_with(url.as_str()),
+            DownloadTarget::Host(host) => {
+                let url = source
+                    .url
+                    .split_once("://")
+                    .map(|u| u.1)
+                    .unwrap_or(&source.url);
+                let authority = url.split(['/', '?', '#']).next().unwrap_or(url);
+                let source_host = authority.rsplit('@').next().unwrap_or(authority);
+                let source_host = source_host.split(':').next().unwrap_or(source_host);
+                source_host == host || source_host.ends_with(&format!(".{}", host))
+            }
+        }
+    }
+}
+
 /// These are the paths that makepkg will use to run the build process and output package files.
 ///
 /// By default makepkg will run the build and generate package files inside the PKGBUILD directory
@@ -213,6 +539,11 @@ pub struct PkgbuildDirs {
     pub srcdest: PathBuf,
     /// The directory the build package is created in.
     pub pkgdest: PathBuf,
+    /// The directory the debug package is created in. This is the same as
+    /// [`pkgdest`](`PkgbuildDirs::pkgdest`) unless configured.
+    pub debug_pkgdest: PathBuf,
+    /// Directories specific packages are created in instead, keyed by pkgname.
+    pub pkgdest_overrides: BTreeMap<String, PathBuf>,
     /// The directory built source packages are created in.
     pub srcpkgdest: PathBuf,
     /// The directory to write logfiles to. This is the same as [`startdir`](`PkgbuildDirs::startdir`) unless configured.
@@ -232,6 +563,31 @@ impl PkgbuildDirs {
         }
     }
 
+    /// Gets the path the HTTP validators of a remote [`Source`] are stored at.
+    ///
+    /// This expands to [`srcdest`](`PkgbuildDirs::srcdest`)/.[`filename`](`Source::file_name`).validators
+    /// and is only used when [`conditional_download`](`crate::Options::conditional_download`) is set.
+    pub fn validators_path(&self, source: &Source) -> PathBuf {
+        self.srcdest
+            .join(format!(".{}.validators", source.file_name()))
+    }
+
+    /// Gets the directory a package file is created in.
+    ///
+    /// A [`pkgdest_overrides`](`PkgbuildDirs::pkgdest_overrides`) entry for the pkgname wins,
+    /// then debug packages go to [`debug_pkgdest`](`PkgbuildDirs::debug_pkgdest`),
+    /// everything else to [`pkgdest`](`PkgbuildDirs::pkgdest`).
+    pub fn package_dest(&self, pkgname: &str, kind: PackageKind) -> &Path {
+        if let Some(dir) = self.pkgdest_overrides.get(pkgname) {
+            return dir;
+        }
+        match kind {
+            PackageKind::Debug => &self.debug_pkgdest,
+            PackageKind::Source => &self.srcpkgdest,
+            PackageKind::Package => &self.pkgdest,
+        }
+    }
+
     /// Gets the pkgdir for the specific [`Package`].
     ///
     /// This expands to [`pkgdir`](`PkgbuildDirs::pkgdir`)/[`pkgname`](`Package::pkgname`).
@@ -244,6 +600,9 @@ impl PkgbuildDirs {
 pub struct Config {
     pub dl_agents: Vec<DownloadAgent>,
     pub vcs_agents: Vec<VCSClient>,
+    pub download_policy: DownloadPolicy,
+    /// Extra headers sent when downloading sources with curl.
+    pub download_headers: Vec<DownloadHeader>,
     pub arch: String,
     pub chost: String,
 
@@ -263,6 +622,22 @@ pub struct Config {
     pub options: Options,
 
     pub gpgkey: Option<String>,
+    /// The GnuPG home directory used for verifying and signing. Defaults to `$GNUPGHOME`.
+    pub gnupghome: Option<PathBuf>,
+    /// Use only this keyring instead of the default keyring in the home directory.
+    pub gpg_keyring: Option<PathBuf>,
+    /// Extra options passed to every gpg invocation.
+    pub gpg_options: Vec<String>,
+    pub keyserver: Option<String>,
+    pub ssh_allowed_signers: Option<PathBuf>,
+    /// Extract source archives here once and copy the files into each srcdir.
+    ///
+    /// Files are reflinked if the filesystem supports it, so only the extraction is saved
+    /// on filesystems without reflinks.
+    pub extract_cache: Option<PathBuf>,
+    /// Store built packages here keyed by their PKGBUILD, local files and toolchain. Later
+    /// builds with the same key copy the packages from the cache instead of building.
+    pub artifact_cache: Option<PathBuf>,
     pub integrity_check: Vec<ChecksumKind>,
     pub strip_binaries: String,
     pub strip_shared: String,
@@ -286,18 +661,53 @@ pub struct Config {
     pub pkgext: Pkgext,
     pub srcext: Srcext,
     pub pacman_auth: Vec<String>,
+    pub packaging_backend: PackagingBackend,
 
     pub builddir: Option<PathBuf>,
+    /// Locations to build in, tried in order. The first with enough free space for the build
+    /// is used instead of [`builddir`](`Config::builddir`).
+    pub builddir_candidates: Vec<PathBuf>,
+    /// Free space in bytes a builddir candidate must have left over after the build.
+    pub builddir_min_free: u64,
+    /// A tmpfs to build in when it is big enough for the build. If the directory is not
+    /// already a tmpfs one is mounted there for the duration of the build.
+    pub tmpfs_builddir: Option<PathBuf>,
+    /// How many times to retry a VCS download that failed with what looks like a network
+    /// error.
+    pub vcs_retries: u32,
+    /// Package checks that should not be run.
+    pub disabled_package_checks: Vec<PackageCheck>,
+    /// Overrides for how PKGBUILD lints are reported.
+    pub lint_levels: LintLevels,
+    /// The shell PKGBUILD functions are run with instead of bash.
+    ///
+    /// It must be compatible with bash 5.1. PKGBUILDs and makepkg.conf are always read
+    /// with bash.
+    pub shell: Option<PathBuf>,
     pub srcdir: Option<PathBuf>,
     pub pkgdir: Option<PathBuf>,
 
     pub pkgdest: Option<PathBuf>,
+    /// Where debug packages are created instead of [`Config::pkgdest`].
+    pub debug_pkgdest: Option<PathBuf>,
+    /// Where specific packages are created instead of [`Config::pkgdest`], keyed by pkgname.
+    ///
+    /// Set with `PKGDEST_OVERRIDES=(pkgname=dir ...)`.
+    pub pkgdest_overrides: BTreeMap<String, PathBuf>,
     pub srcdest: Option<PathBuf>,
     pub srcpkgdest: Option<PathBuf>,
 
     pub source_date_epoch: u64,
     pub reproducible: bool,
+    /// Appended to pkgver of built packages, e.g. `+ci.20240610`.
+    ///
+    /// The suffix only applies to the generated package metadata and file names, the
+    /// PKGBUILD is not modified. It is recorded in .BUILDINFO as `version_suffix`.
+    pub version_suffix: Option<String>,
     pub pacman: String,
+    /// The pacman database directory read for the packages recorded in .BUILDINFO. Defaults
+    /// to the DBPath set in /etc/pacman.conf.
+    pub pacman_dbpath: Option<PathBuf>,
 
     pub buildtool: String,
     pub buildtoolver: String,
@@ -316,6 +726,142 @@ impl Config {
         Config::load(Some(path.into()))
     }
 
+    /// Builds a config in code without reading any config files or the environment.
+    ///
+    /// DLAGENTS, VCSCLIENTS, BUILDENV, OPTIONS and INTEGRITY_CHECK start out with the values
+    /// from the makepkg.conf shipped with pacman. Other values makepkg.conf would set, such
+    /// as CFLAGS, are left empty. CARCH defaults to the architecture makepkg was compiled for
+    /// and SOURCE_DATE_EPOCH to 0.
+    pub fn builder() -> ConfigBuilder {
+        let mut config = Config::defaults(0);
+        config.arch = std::env::consts::ARCH.to_string();
+        config.dl_agents = [
+            "file::/usr/bin/curl -qgC - -o %o %u",
+            "ftp::/usr/bin/curl -qgfC - --ftp-pasv --retry 3 --retry-delay 3 -o %o %u",
+            "http::/usr/bin/curl -qgb \"\" -fLC - --retry 3 --retry-delay 3 -o %o %u",
+            "https::/usr/bin/curl -qgb \"\" -fLC - --retry 3 --retry-delay 3 -o %o %u",
+            "rsync::/usr/bin/rsync --no-motd -z %u %o",
+            "scp::/usr/bin/scp -C %u %o",
+        ]
+        .iter()
+        .map(|a| a.parse().expect("stock DLAGENTS are valid"))
+        .collect();
+        config.vcs_agents = [
+            "bzr::breezy",
+            "fossil::fossil",
+            "git::git",
+            "hg::mercurial",
+            "svn::subversion",
+        ]
+        .iter()
+        .map(|c| c.parse().expect("stock VCSCLIENTS are valid"))
+        .collect();
+        config.build_env = ["!distcc", "color", "!ccache", "check", "!sign"]
+            .into_iter()
+            .collect();
+        config.options = [
+            "strip",
+            "docs",
+            "!libtool",
+            "!staticlibs",
+            "emptydirs",
+            "zipman",
+            "purge",
+            "debug",
+            "lto",
+        ]
+        .into_iter()
+        .collect();
+        config.integrity_check = vec![ChecksumKind::Sha256];
+        ConfigBuilder { config }
+    }
+
+    /// The configured download agents in order of preference.
+    pub fn dl_agents(&self) -> &[DownloadAgent] {
+        &self.dl_agents
+    }
+
+    /// Gets the download agent that will be used for `protocol`.
+    pub fn dl_agent(&self, protocol: &str) -> Option<&DownloadAgent> {
+        self.dl_agents.iter().find(|a| a.protocol == protocol)
+    }
+
+    /// Adds a download agent, replacing any existing agent for the same protocol.
+    ///
+    /// New protocols are added with the lowest preference.
+    pub fn set_dl_agent(&mut self, agent: DownloadAgent) -> StdResult<(), DownloadAgentError> {
+        agent.validate()?;
+        match self
+            .dl_agents
+            .iter_mut()
+            .find(|a| a.protocol == agent.protocol)
+        {
+            Some(existing) => *existing = agent,
+            None => self.dl_agents.push(agent),
+        }
+        Ok(())
+    }
+
+    /// Replaces all download agents. Earlier agents take preference over later ones.
+    pub fn set_dl_agents(
+        &mut self,
+        agents: Vec<DownloadAgent>,
+    ) -> StdResult<(), DownloadAgentError> {
+        agents.iter().try_for_each(|a| a.validate())?;
+        self.dl_agents = agents;
+        Ok(())
+    }
+
+    /// Removes the download agents for `protocol`, returning the one that was in use.
+    pub fn remove_dl_agent(&mut self, protocol: &str) -> Option<DownloadAgent> {
+        let pos = self.dl_agents.iter().position(|a| a.protocol == protocol)?;
+        let agent = self.dl_agents.remove(pos);
+        self.dl_agents.retain(|a| a.protocol != protocol);
+        Some(agent)
+    }
+
+    /// The configured VCS clients.
+    pub fn vcs_agents(&self) -> &[VCSClient] {
+        &self.vcs_agents
+    }
+
+    /// Gets the VCS client that will be used for `protocol`.
+    pub fn vcs_agent(&self, protocol: VCSKind) -> Option<&VCSClient> {
+        self.vcs_agents.iter().find(|a| a.protocol == protocol)
+    }
+
+    /// Adds a VCS client, replacing any existing client for the same protocol.
+    pub fn set_vcs_agent(&mut self, client: VCSClient) -> StdResult<(), VCSClientError> {
+        client.validate()?;
+        match self
+            .vcs_agents
+            .iter_mut()
+            .find(|a| a.protocol == client.protocol)
+        {
+            Some(existing) => *existing = client,
+            None => self.vcs_agents.push(client),
+        }
+        Ok(())
+    }
+
+    /// Replaces all VCS clients.
+    pub fn set_vcs_agents(&mut self, clients: Vec<VCSClient>) -> StdResult<(), VCSClientError> {
+        clients.iter().try_for_each(|a| a.validate())?;
+        self.vcs_agents = clients;
+        Ok(())
+    }
+
+    /// Removes the VCS clients for `protocol`, returning the one that was in use.
+    pub fn remove_vcs_agent(&mut self, protocol: VCSKind) -> Option<VCSClient> {
+        let pos = self
+            .vcs_agents
+            .iter()
+            .position(|a| a.protocol == protocol)?;
+        let client = self.vcs_agents.remove(pos);
+        self.vcs_agents.retain(|a| a.protocol != protocol);
+        Some(client)
+    }
+
     pub fn compress_args(&self, compress: Compress) -> &[String] {
         match compress {
             Compress::Cat => self.compress_none.as_slice(),
@@ -345,6 +891,36 @@ impl Config {
         }
     }
 
+    /// The configured OPTIONS with the PKGBUILD's options, then the package's own options,
+    /// applied on top. Options the PKGBUILD sets that are not BUILDENV options and are not
+    /// configured are added to the end.
+    pub fn effective_options(&self, pkgbuild: &Pkgbuild, pkg: &Package) -> Options {
+        let mut options = self.options.clone();
+        let overrides = pkgbuild.options.values.iter().chain(&pkg.options.values);
+        for value in overrides {
+            if let Some(option) = options.values.iter_mut().find(|o| o.name == value.name) {
+                option.enabled = value.enabled;
+            } else if self.build_env.get(&value.name).unset() {
+                options.values.push(value.clone());
+            }
+        }
+        options
+    }
+
+    /// The configured BUILDENV with the PKGBUILD's options applied on top, as
+    /// [`Config::build_option`] resolves them.
+    pub fn effective_build_env(&self, pkgbuild: &Pkgbuild) -> Options {
+        let mut build_env = self.build_env.clone();
+        for option in &mut build_env.values {
+            match pkgbuild.options.get(&option.name) {
+                OptionState::Enabled => option.enabled = true,
+                OptionState::Disabled => option.enabled = false,
+                OptionState::Unset => (),
+            }
+        }
+        build_env
+    }
+
     pub fn build_env(&self, name: &str) -> OptionState {
         self.build_env.get(name)
     }
@@ -361,7 +937,7 @@ impl Config {
             load_local = false;
             PathBuf::from(&config)
         } else {
-            Self::config_file().to_path_buf()
+            toml_fallback(Self::config_file().to_path_buf())
         };
 
         Check::new(Context::ReadConfig).file().check(&main_config)?;
@@ -382,7 +958,7 @@ impl Config {
 
         if load_local {
             let path = dirs::config_dir()
-                .map(|d| d.join("pacman/makepkg.conf"))
+                .map(|d| toml_fallback(d.join("pacman/makepkg.conf")))
                 .filter(|d| d.exists());
 
             if let Some(path) = path {
@@ -402,49 +978,9 @@ impl Config {
                 1
             }
         };
-        let packager = "Unknown packager".to_string();
-        let pacman = "pacman".to_string();
-        let buildtool = env!("CARGO_PKG_NAME").to_string();
-        let buildtoolver = env!("CARGO_PKG_VERSION").to_string();
-        let compress_none = to_string(&["cat"]);
-        let compress_gz = to_string(&["gzip", "-c", "-f2", "-n"]);
-        let compress_bz2 = to_string(&["bzip2", "-c", "-f"]);
-        let compress_xz = to_string(&["xz", "-c", "-z", "-"]);
-        let compress_zst = to_string(&["zstd", "-c", "-z", "-"]);
-        let compress_lzo = to_string(&["lzop", "-q"]);
-        let compress_lrz = to_string(&["lrzip", "-q"]);
-        let compress_lz4 = to_string(&["lz4", "-q"]);
-        let compress_z = to_string(&["compress", "-c", "-f"]);
-        let compress_lz = to_string(&["lzip", "-c", "-f"]);
-        let strip_shared = "-S".to_string();
-        let strip_static = "-S".to_string();
-        let ltoflags = "--flto".to_string();
-        let dbg_srcdir = Path::new(PREFIX).join("src/debug");
-
-        let mut config = Config {
-            source_date_epoch,
-            packager,
-            pacman,
-            buildtool,
-            buildtoolver,
-            dbg_srcdir,
-            compress_none,
-            compress_gz,
-            compress_bz2,
-            compress_xz,
-            compress_zst,
-            compress_lzo,
-            compress_lrz,
-            compress_lz4,
-            compress_z,
-            compress_lz,
-            strip_shared,
-            strip_static,
-            ltoflags,
-            ..Default::default()
-        };
+        let mut config = Config::defaults(source_date_epoch);
 
-        let raw_config = RawConfig::from_paths(&conf_files)?;
+        let raw_config = RawConfig::from_config_files(&conf_files, &mut lints)?;
         raw_config.lint(&mut lints);
         config.parse_raw(raw_config, &mut lints);
 
@@ -454,6 +990,9 @@ impl Config {
         if let Ok(pkgdest) = std::env::var("PKGDEST") {
             config.pkgdest = Some(PathBuf::from(pkgdest));
         }
+        if let Ok(pkgdest) = std::env::var("DEBUG_PKGDEST") {
+            config.debug_pkgdest = Some(PathBuf::from(pkgdest));
+        }
         if let Ok(srcdest) = std::env::var("SRCDEST") {
             config.srcdest = Some(PathBuf::from(srcdest));
         }
@@ -468,6 +1007,8 @@ impl Config {
         }
         if let Ok(builddir) = std::env::var("BUILDDIR") {
             config.builddir = Some(PathBuf::from(builddir));
+            config.builddir_candidates.clear();
+            config.tmpfs_builddir = None;
         }
         if let Ok(carch) = std::env::var("CARCH") {
             config.arch = carch;
@@ -487,6 +1028,9 @@ impl Config {
         if let Ok(key) = std::env::var("GPGKET") {
             config.gpgkey = Some(key);
         }
+        if let Ok(gnupghome) = std::env::var("GNUPGHOME") {
+            config.gnupghome = Some(gnupghome.into());
+        }
         if let Ok(epoch) = std::env::var("SOURCE_DATE_EPOCH") {
             config.source_date_epoch = epoch
                 .parse()
@@ -494,6 +1038,10 @@ impl Config {
             config.reproducible = true;
         }
 
+        if let Ok(suffix) = std::env::var("VERSION_SUFFIX") {
+            config.version_suffix = Some(suffix);
+        }
+
         if let Ok(buildtool) = std::env::var("BUILDTOOL") {
             config.buildtool = buildtool;
         }
@@ -510,14 +1058,38 @@ impl Config {
         Ok(config)
     }
 
+    /// Whether a [`PackageCheck`] should be run.
+    pub fn package_check_enabled(&self, check: PackageCheck) -> bool {
+        !self.disabled_package_checks.contains(&check)
+    }
+
+    /// The full version of the packages built from a PKGBUILD including the
+    /// [`version_suffix`](`Config::version_suffix`).
+    pub fn package_version(&self, pkgbuild: &Pkgbuild) -> String {
+        let suffix = self.version_suffix.as_deref().unwrap_or_default();
+        if let Some(epoch) = &pkgbuild.epoch {
+            format!(
+                "{}:{}{}-{}",
+                epoch, pkgbuild.pkgver, suffix, pkgbuild.pkgrel
+            )
+        } else {
+            format!("{}{}-{}", pkgbuild.pkgver, suffix, pkgbuild.pkgrel)
+        }
+    }
+
     pub fn pkgbuild_dirs(&self, pkgbuild: &Pkgbuild) -> Result<PkgbuildDirs> {
+        self.pkgbuild_dirs_in(pkgbuild, self.builddir.as_deref())
+    }
+
+    pub(crate) fn pkgbuild_dirs_in(
+        &self,
+        pkgbuild: &Pkgbuild,
+        builddir: Option<&Path>,
+    ) -> Result<PkgbuildDirs> {
         let startdir = pkgbuild.dir.clone();
 
         let pkgbuild_file = startdir.join(Pkgbuild::file_name());
-        let builddir = self
-            .builddir
-            .as_ref()
-            .map(|dir| resolve_path_relative(dir, &startdir));
+        let builddir = builddir.map(|dir| resolve_path_relative(dir, &startdir));
 
         let builddir = match builddir {
             Some(dir) if dir != startdir => dir.join(&pkgbuild.pkgbase),
@@ -533,16 +1105,22 @@ impl Config {
         let pkgdir = builddir.join("pkg");
         let srcpkgdir = builddir.join("srcpkg").join(&pkgbuild.pkgbase);
 
-        let pkgdest = self.pkgdest.as_ref().map_or_else(|| &startdir, |dir| dir);
-        let srcdest = self.srcdest.as_ref().map_or_else(|| &startdir, |dir| dir);
-        let srcpkgdest = self
-            .srcpkgdest
-            .as_ref()
-            .map_or_else(|| &startdir, |dir| dir);
+        let pkgdest = self.pkgdest.as_ref().unwrap_or(&startdir);
+        let srcdest = self.srcdest.as_ref().unwrap_or(&startdir);
+        let srcpkgdest = self.srcpkgdest.as_ref().unwrap_or(&startdir);
 
         let pkgdest = resolve_path_relative(pkgdest, &startdir);
         let srcdest = resolve_path_relative(srcdest, &startdir);
         let srcpkgdest = resolve_path_relative(srcpkgdest, &startdir);
+        let debug_pkgdest = match &self.debug_pkgdest {
+            Some(dir) => resolve_path_relative(dir, &startdir),
+            None => pkgdest.clone(),
+        };
+        let pkgdest_overrides = self
+            .pkgdest_overrides
+            .iter()
+            .map(|(name, dir)| (name.clone(), resolve_path_relative(dir, &startdir)))
+            .collect();
 
         let dirs = PkgbuildDirs {
             startdir: startdir.to_path_buf(),
@@ -552,6 +1130,8 @@ impl Config {
             pkgdir,
             srcpkgdir,
             pkgdest,
+            debug_pkgdest,
+            pkgdest_overrides,
             srcdest,
             srcpkgdest,
             logdest,
@@ -560,9 +1140,67 @@ impl Config {
         Ok(dirs)
     }
 
+    // the built in values used for anything the config files don't set
+    fn defaults(source_date_epoch: u64) -> Self {
+        let packager = "Unknown packager".to_string();
+        let pacman = "pacman".to_string();
+        let buildtool = env!("CARGO_PKG_NAME").to_string();
+        let buildtoolver = env!("CARGO_PKG_VERSION").to_string();
+        let compress_none = to_string(&["cat"]);
+        let compress_gz = to_string(&["gzip", "-c", "-f2", "-n"]);
+        let compress_bz2 = to_string(&["bzip2", "-c", "-f"]);
+        let compress_xz = to_string(&["xz", "-c", "-z", "-"]);
+        let compress_zst = to_string(&["zstd", "-c", "-z", "-"]);
+        let compress_lzo = to_string(&["lzop", "-q"]);
+        let compress_lrz = to_string(&["lrzip", "-q"]);
+        let compress_lz4 = to_string(&["lz4", "-q"]);
+        let compress_z = to_string(&["compress", "-c", "-f"]);
+        let compress_lz = to_string(&["lzip", "-c", "-f"]);
+        let strip_shared = "-S".to_string();
+        let strip_static = "-S".to_string();
+        let ltoflags = "--flto".to_string();
+        let dbg_srcdir = Path::new(PREFIX).join("src/debug");
+
+        Config {
+            source_date_epoch,
+            packager,
+            pacman,
+            buildtool,
+            buildtoolver,
+            dbg_srcdir,
+            compress_none,
+            compress_gz,
+            compress_bz2,
+            compress_xz,
+            compress_zst,
+            compress_lzo,
+            compress_lrz,
+            compress_lz4,
+            compress_z,
+            compress_lz,
+            strip_shared,
+            strip_static,
+            ltoflags,
+            ..Default::default()
+        }
+    }
+
     fn parse_raw(&mut self, raw: RawConfig, lints: &mut Vec<LintKind>) {
         for var in raw.variables {
             match var.name.as_str() {
+                "DOWNLOAD_HEADERS" => {
+                    self.download_headers = var
+                        .lint_array(lints)
+                        .into_iter()
+                        .filter_map(|s| match s.parse() {
+                            Ok(v) => Some(v),
+                            Err(e) => {
+                                lints.push(e);
+                                None
+                            }
+                        })
+                        .collect()
+                }
                 "DLAGENTS" => {
                     self.dl_agents = var
                         .lint_array(lints)
@@ -606,7 +1244,70 @@ impl Config {
                 }
                 "DISTCC_HOSTS" => self.distcc_hosts = var.lint_string(lints),
                 "BUILDDIR" => self.builddir = Some(PathBuf::from(var.lint_string(lints))),
+                "BUILDDIR_CANDIDATES" => {
+                    self.builddir_candidates = var
+                        .lint_array(lints)
+                        .into_iter()
+                        .map(PathBuf::from)
+                        .collect()
+                }
+                "TMPFS_BUILDDIR" => {
+                    self.tmpfs_builddir = Some(PathBuf::from(var.lint_string(lints)))
+                }
+                "BUILDDIR_MIN_FREE" => {
+                    let val = var.lint_string(lints);
+                    match val.parse() {
+                        Ok(n) => self.builddir_min_free = n,
+                        Err(_) => {
+                            lints.push(LintKind::InvalidNumber("BUILDDIR_MIN_FREE".into(), val))
+                        }
+                    }
+                }
+                "VCS_RETRIES" => {
+                    let val = var.lint_string(lints);
+                    match val.parse() {
+                        Ok(n) => self.vcs_retries = n,
+                        Err(_) => lints.push(LintKind::InvalidNumber("VCS_RETRIES".into(), val)),
+                    }
+                }
+                "LINT_LEVELS" => {
+                    for entry in var.lint_array(lints) {
+                        let level = entry
+                            .split_once('=')
+                            .and_then(|(name, level)| Some((name, level.parse().ok()?)));
+                        match level {
+                            Some((name, level)) => self.lint_levels.set(name, level),
+                            None => lints.push(LintKind::InvalidLintLevel(entry)),
+                        }
+                    }
+                }
+                "PACKAGE_CHECKS" => {
+                    for check in var.lint_array(lints) {
+                        let (disable, name) = match check.strip_prefix('!') {
+                            Some(name) => (true, name),
+                            None => (false, check.as_str()),
+                        };
+                        match name.parse() {
+                            Ok(check) => {
+                                self.disabled_package_checks.retain(|&c| c != check);
+                                if disable {
+                                    self.disabled_package_checks.push(check);
+                                }
+                            }
+                            Err(e) => lints.push(e),
+                        }
+                    }
+                }
+                "BUILD_SHELL" => self.shell = Some(PathBuf::from(var.lint_string(lints))),
                 "GPGKEY" => self.gpgkey = Some(var.lint_string(lints)),
+                "KEYSERVER" => self.keyserver = Some(var.lint_string(lints)),
+                "EXTRACT_CACHE" => self.extract_cache = Some(PathBuf::from(var.lint_string(lints))),
+                "ARTIFACT_CACHE" => {
+                    self.artifact_cache = Some(PathBuf::from(var.lint_string(lints)))
+                }
+                "SSH_ALLOWED_SIGNERS" => {
+                    self.ssh_allowed_signers = Some(PathBuf::from(var.lint_string(lints)))
+                }
                 "OPTIONS" => {
                     self.options = var.lint_array(lints).iter().map(|s| s.as_str()).collect()
                 }
@@ -626,6 +1327,17 @@ impl Config {
                 "PURGE_TARGETS" => self.purge_targets = var.lint_path_array(lints),
                 "DBGSRCDIR" => self.dbg_srcdir = PathBuf::from(var.lint_string(lints)),
                 "PKGDEST" => self.pkgdest = Some(PathBuf::from(var.lint_string(lints))),
+                "DEBUG_PKGDEST" => self.debug_pkgdest = Some(PathBuf::from(var.lint_string(lints))),
+                "PKGDEST_OVERRIDES" => {
+                    for entry in var.lint_array(lints) {
+                        match entry.split_once('=') {
+                            Some((name, dir)) if !name.is_empty() && !dir.is_empty() => {
+                                self.pkgdest_overrides.insert(name.into(), dir.into());
+                            }
+                            _ => lints.push(LintKind::InvalidPkgdestOverride(entry)),
+                        }
+                    }
+                }
                 "SRCDEST" => self.srcdest = Some(PathBuf::from(var.lint_string(lints))),
                 "SRCPKGDEST" => self.srcpkgdest = Some(PathBuf::from(var.lint_string(lints))),
                 "LOGDEST" => self.logdest = Some(var.lint_string(lints).into()),
@@ -654,6 +1366,172 @@ impl Config {
     }
 }
 
+/// Builds a [`Config`] without reading files. See [`Config::builder`].
+#[derive(Debug)]
+pub struct ConfigBuilder {
+    config: Config,
+}
+
+impl ConfigBuilder {
+    pub fn arch<S: Into<String>>(mut self, arch: S) -> Self {
+        self.config.arch = arch.into();
+        self
+    }
+
+    pub fn chost<S: Into<String>>(mut self, chost: S) -> Self {
+        self.config.chost = chost.into();
+        self
+    }
+
+    pub fn packager<S: Into<String>>(mut self, packager: S) -> Self {
+        self.config.packager = packager.into();
+        self
+    }
+
+    pub fn cflags<S: Into<String>>(mut self, cflags: S) -> Self {
+        self.config.cflags = cflags.into();
+        self
+    }
+
+    pub fn cxxflags<S: Into<String>>(mut self, cxxflags: S) -> Self {
+        self.config.cxxflags = cxxflags.into();
+        self
+    }
+
+    pub fn ldflags<S: Into<String>>(mut self, ldflags: S) -> Self {
+        self.config.ldflags = ldflags.into();
+        self
+    }
+
+    pub fn makeflags<S: Into<String>>(mut self, makeflags: S) -> Self {
+        self.config.makeflags = makeflags.into();
+        self
+    }
+
+    /// Sets OPTIONS, e.g. `["strip", "!debug"]`.
+    pub fn options<I, S>(mut self, options: I) -> Self
+    where
+        I: IntoIterator<Item = S>,
+        S: AsRef<str>,
+    {
+        let options = options.into_iter().collect::<Vec<_>>();
+        self.config.options = options.iter().map(|o| o.as_ref()).collect();
+        self
+    }
+
+    /// Sets BUILDENV, e.g. `["!distcc", "color"]`.
+    pub fn build_env<I, S>(mut self, build_env: I) -> Self
+    where
+        I: IntoIterator<Item = S>,
+        S: AsRef<str>,
+    {
+        let build_env = build_env.into_iter().collect::<Vec<_>>();
+        self.config.build_env = build_env.iter().map(|o| o.as_ref()).collect();
+        self
+    }
+
+    pub fn integrity_check<I: IntoIterator<Item = ChecksumKind>>(mut self, kinds: I) -> Self {
+        self.config.integrity_check = kinds.into_iter().collect();
+        self
+    }
+
+    /// Sets DLAGENTS, replacing the stock agents.
+    pub fn dl_agents<I: IntoIterator<Item = DownloadAgent>>(mut self, agents: I) -> Self {
+        self.config.dl_agents = agents.into_iter().collect();
+        self
+    }
+
+    /// Sets VCSCLIENTS, replacing the stock clients.
+    pub fn vcs_clients<I: IntoIterator<Item = VCSClient>>(mut self, clients: I) -> Self {
+        self.config.vcs_agents = clients.into_iter().collect();
+        self
+    }
+
+    pub fn pkgext(mut self, pkgext: Pkgext) -> Self {
+        self.config.pkgext = pkgext;
+        self
+    }
+
+    pub fn srcext(mut self, srcext: Srcext) -> Self {
+        self.config.srcext = srcext;
+        self
+    }
+
+    pub fn builddir<P: Into<PathBuf>>(mut self, builddir: P) -> Self {
+        self.config.builddir = Some(builddir.into());
+        self
+    }
+
+    pub fn pkgdest<P: Into<PathBuf>>(mut self, pkgdest: P) -> Self {
+        self.config.pkgdest = Some(pkgdest.into());
+        self
+    }
+
+    pub fn srcdest<P: Into<PathBuf>>(mut self, srcdest: P) -> Self {
+        self.config.srcdest = Some(srcdest.into());
+        self
+    }
+
+    pub fn srcpkgdest<P: Into<PathBuf>>(mut self, srcpkgdest: P) -> Self {
+        self.config.srcpkgdest = Some(srcpkgdest.into());
+        self
+    }
+
+    pub fn logdest<P: Into<PathBuf>>(mut self, logdest: P) -> Self {
+        self.config.logdest = Some(logdest.into());
+        self
+    }
+
+    pub fn gnupghome<P: Into<PathBuf>>(mut self, gnupghome: P) -> Self {
+        self.config.gnupghome = Some(gnupghome.into());
+        self
+    }
+
+    pub fn pacman<S: Into<String>>(mut self, pacman: S) -> Self {
+        self.config.pacman = pacman.into();
+        self
+    }
+
+    pub fn pacman_dbpath<P: Into<PathBuf>>(mut self, dbpath: P) -> Self {
+        self.config.pacman_dbpath = Some(dbpath.into());
+        self
+    }
+
+    /// Sets SOURCE_DATE_EPOCH and marks the build as reproducible as if it came from the
+    /// environment.
+    pub fn source_date_epoch(mut self, epoch: u64) -> Self {
+        self.config.source_date_epoch = epoch;
+        self.config.reproducible = true;
+        self
+    }
+
+    /// Sets any other value directly.
+    pub fn with<F: FnOnce(&mut Config)>(mut self, f: F) -> Self {
+        f(&mut self.config);
+        self
+    }
+
+    /// Checks the config the same way as one read from makepkg.conf.
+    pub fn build(self) -> Result<Config> {
+        let mut lints = Vec::new();
+        self.config.lint(&mut lints);
+        if !lints.is_empty() {
+            return Err(LintError::config(lints).into());
+        }
+        Ok(self.config)
+    }
+}
+
+// makepkg.conf is preferred but makepkg.toml next to it is used if it does not exist
+fn toml_fallback(conf: PathBuf) -> PathBuf {
+    let toml = conf.with_extension("toml");
+    if !conf.exists() && toml.exists() {
+        toml
+    } else {
+        conf
+    }
+}
+
 fn to_string(s: &[&str]) -> Vec<String> {
     s.iter().map(|s| s.to_string()).collect()
 }
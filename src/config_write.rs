@@ -0,0 +1,152 @@
+use std::{fmt::Display, io::Write, path::Path};
+
+use crate::{
+    config::{Config, DownloadTarget},
+    error::{Context, IOContext, IOErrorExt, Result},
+    pkgbuild_edit::{format_string, format_words},
+};
+
+impl Config {
+    /// Writes the effective configuration, including values overridden by the environment,
+    /// in makepkg.conf syntax.
+    ///
+    /// Unset values are left out. Values that can only come from the environment are
+    /// written after the makepkg.conf variables.
+    pub fn write_makepkg_conf<W: Write>(&self, w: &mut W) -> Result<()> {
+        let mut out = Vec::new();
+        let mut string = |name: &str, value: &str| out.push(format_string(name, value));
+
+        string("CARCH", &self.arch);
+        string("CHOST", &self.chost);
+        string("CPPFLAGS", &self.cppflags);
+        string("CFLAGS", &self.cflags);
+        string("CXXFLAGS", &self.cxxflags);
+        string("RUSTFLAGS", &self.rustflags);
+        string("LDFLAGS", &self.ldflags);
+        string("LTOFLAGS", &self.ltoflags);
+        string("MAKEFLAGS", &self.makeflags);
+        string("DEBUG_CFLAGS", &self.debug_cflags);
+        string("DEBUG_CXXFLAGS", &self.debug_cxxflags);
+        string("DEBUG_RUSTFLAGS", &self.debug_rustflags);
+        string("DISTCC_HOSTS", &self.distcc_hosts);
+        string("BUILDDIR_MIN_FREE", &self.builddir_min_free.to_string());
+        string("VCS_RETRIES", &self.vcs_retries.to_string());
+        string("STRIP_BINARIES", &self.strip_binaries);
+        string("STRIP_SHARED", &self.strip_shared);
+        string("STRIP_STATIC", &self.strip_static);
+        string("DBGSRCDIR", &path(&self.dbg_srcdir));
+        string("PACKAGER", &self.packager);
+        string("PKGEXT", &self.pkgext.to_string());
+        string("SRCEXT", &self.srcext.to_string());
+
+        let mut opt_path = |name: &str, value: &Option<std::path::PathBuf>| {
+            if let Some(value) = value {
+                out.push(format_string(name, &path(value)));
+            }
+        };
+        opt_path("BUILDDIR", &self.builddir);
+        opt_path("TMPFS_BUILDDIR", &self.tmpfs_builddir);
+        opt_path("BUILD_SHELL", &self.shell);
+        opt_path("EXTRACT_CACHE", &self.extract_cache);
+        opt_path("ARTIFACT_CACHE", &self.artifact_cache);
+        opt_path("SSH_ALLOWED_SIGNERS", &self.ssh_allowed_signers);
+        opt_path("PKGDEST", &self.pkgdest);
+        opt_path("DEBUG_PKGDEST", &self.debug_pkgdest);
+        opt_path("SRCDEST", &self.srcdest);
+        opt_path("SRCPKGDEST", &self.srcpkgdest);
+        opt_path("LOGDEST", &self.logdest);
+        if let Some(key) = &self.gpgkey {
+            out.push(format_string("GPGKEY", key));
+        }
+        if let Some(keyserver) = &self.keyserver {
+            out.push(format_string("KEYSERVER", keyserver));
+        }
+
+        let mut array = |name: &str, values: Vec<String>| out.push(format_words(name, &values));
+
+        array("DLAGENTS", display(&self.dl_agents));
+        array("VCSCLIENTS", display(&self.vcs_agents));
+        array(
+            "DOWNLOAD_HEADERS",
+            self.download_headers
+                .iter()
+                .map(|h| match &h.target {
+                    DownloadTarget::Host(t) | DownloadTarget::Url(t) => {
+                        format!("{}::{}", t, h.header)
+                    }
+                })
+                .collect(),
+        );
+        array("BUILDENV", display(&self.build_env.values));
+        array("OPTIONS", display(&self.options.values));
+        array("INTEGRITY_CHECK", display(&self.integrity_check));
+        array("BUILDDIR_CANDIDATES", paths(&self.builddir_candidates));
+        array(
+            "LINT_LEVELS",
+            self.lint_levels
+                .levels
+                .iter()
+                .map(|(name, level)| format!("{}={}", name, level))
+                .collect(),
+        );
+        array(
+            "PACKAGE_CHECKS",
+            self.disabled_package_checks
+                .iter()
+                .map(|c| format!("!{}", c))
+                .collect(),
+        );
+        array("MAN_DIRS", paths(&self.man_dirs));
+        array("DOC_DIRS", paths(&self.doc_dirs));
+        array("PURGE_TARGETS", paths(&self.purge_targets));
+        array(
+            "PKGDEST_OVERRIDES",
+            self.pkgdest_overrides
+                .iter()
+                .map(|(name, dir)| format!("{}={}", name, path(dir)))
+                .collect(),
+        );
+        array("COMPRESSGZ", self.compress_gz.clone());
+        array("COMPRESSBZ2", self.compress_bz2.clone());
+        array("COMPRESSXZ", self.compress_xz.clone());
+        array("COMPRESSZST", self.compress_zst.clone());
+        array("COMPRESSLZO", self.compress_lzo.clone());
+        array("COMPRESSLRZ", self.compress_lrz.clone());
+        array("COMPRESSZ", self.compress_z.clone());
+        array("COMPRESSLZ4", self.compress_lz4.clone());
+        array("COMPRESSLZ", self.compress_lz.clone());
+        array("PACMAN_AUTH", self.pacman_auth.clone());
+
+        out.push("\n# from the environment".to_string());
+        out.push(format_string("PACMAN", &self.pacman));
+        out.push(format_string("BUILDTOOL", &self.buildtool));
+        out.push(format_string("BUILDTOOLVER", &self.buildtoolver));
+        if let Some(gnupghome) = &self.gnupghome {
+            out.push(format_string("GNUPGHOME", &path(gnupghome)));
+        }
+        if let Some(suffix) = &self.version_suffix {
+            out.push(format_string("VERSION_SUFFIX", suffix));
+        }
+        if self.reproducible {
+            let epoch = self.source_date_epoch.to_string();
+            out.push(format_string("SOURCE_DATE_EPOCH", &epoch));
+        }
+
+        for line in out {
+            writeln!(w, "{}", line).context(Context::WriteConfig, IOContext::WriteBuffer)?;
+        }
+        Ok(())
+    }
+}
+
+fn display<T: Display>(values: &[T]) -> Vec<String> {
+    values.iter().map(|v| v.to_string()).collect()
+}
+
+fn path(path: &Path) -> String {
+    path.to_string_lossy().into_owned()
+}
+
+fn paths<P: AsRef<Path>>(paths: &[P]) -> Vec<String> {
+    paths.iter().map(|p| path(p.as_ref())).collect()
+}
@@ -0,0 +1,147 @@
+use std::{
+    collections::BTreeMap,
+    fs::File,
+    io,
+    path::{Path, PathBuf},
+};
+
+use sha2::{Digest, Sha256};
+use walkdir::WalkDir;
+
+use crate::{
+    callback::{LogLevel, LogMessage},
+    config::PkgbuildDirs,
+    error::{Context, IOContext, IOErrorExt, Result},
+    package_lint::package_files,
+    pkgbuild::Pkgbuild,
+    Makepkg,
+};
+
+/// An identical file installed by more than one package of a split package.
+#[derive(Debug, Clone, PartialEq, Eq, PartialOrd, Ord, Hash)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize))]
+pub struct DuplicateFile {
+    /// Size of the file in bytes.
+    pub size: u64,
+    /// The packages that contain the file and its path relative to their pkgdir.
+    pub files: Vec<(String, PathBuf)>,
+}
+
+impl DuplicateFile {
+    /// The bytes that would be saved if only one copy of the file were packaged.
+    pub fn wasted(&self) -> u64 {
+        self.size * (self.files.len() as u64 - 1)
+    }
+}
+
+impl Makepkg {
+    /// Finds identical files that are packaged by more than one package of a split package.
+    ///
+    /// Files are first grouped by size so only files that could be identical are hashed.
+    pub fn duplicate_files(&self, pkgbuild: &Pkgbuild) -> Result<Vec<DuplicateFile>> {
+        let dirs = self.pkgbuild_dirs(pkgbuild)?;
+        find_duplicates(&dirs, pkgbuild)
+    }
+
+    pub(crate) fn report_duplicate_files(
+        &self,
+        dirs: &PkgbuildDirs,
+        pkgbuild: &Pkgbuild,
+    ) -> Result<()> {
+        if pkgbuild.packages.len() < 2 {
+            return Ok(());
+        }
+
+        let duplicates = find_duplicates(dirs, pkgbuild)?;
+        if duplicates.is_empty() {
+            return Ok(());
+        }
+
+        for dup in &duplicates {
+            self.log(LogLevel::Warning, LogMessage::DuplicateFile(dup))?;
+        }
+        let wasted = duplicates.iter().map(|d| d.wasted()).sum();
+        self.log(
+            LogLevel::Warning,
+            LogMessage::DuplicateFilesWasted(&pkgbuild.pkgbase, wasted),
+        )?;
+        Ok(())
+    }
+}
+
+fn find_duplicates(dirs: &PkgbuildDirs, pkgbuild: &Pkgbuild) -> Result<Vec<DuplicateFile>> {
+    let mut by_size = BTreeMap::<u64, Vec<(&str, PathBuf)>>::new();
+
+    for pkg in pkgbuild.packages() {
+        let pkgdir = dirs.pkgdir(pkg);
+        for file in package_files(WalkDir::new(&pkgdir)) {
+            let file =
+                file.context(Context::GetPackageFiles, IOContext::ReadDir(pkgdir.clone()))?;
+            if !file.file_type().is_file() {
+                continue;
+            }
+            let size = file
+                .metadata()
+                .context(
+                    Context::GetPackageFiles,
+                    IOContext::Stat(file.path().into()),
+                )?
+                .len();
+            if size == 0 {
+                continue;
+            }
+            by_size
+                .entry(size)
+                .or_default()
+                .push((pkg.pkgname.as_str(), file.path().to_path_buf()));
+        }
+    }
+
+    let mut duplicates = Vec::new();
+
+    for (size, files) in by_size {
+        if !spans_packages(files.iter().map(|f| f.0)) {
+            continue;
+        }
+
+        let mut by_hash = BTreeMap::<_, Vec<_>>::new();
+        for (pkgname, path) in files {
+            by_hash
+                .entry(hash(&path)?)
+                .or_default()
+                .push((pkgname, path));
+        }
+
+        for files in by_hash.into_values() {
+            if !spans_packages(files.iter().map(|f| f.0)) {
+                continue;
+            }
+            let files = files
+                .into_iter()
+                .map(|(pkgname, path)| {
+                    let pkgdir = dirs.pkgdir.join(pkgname);
+                    let rel = path.strip_prefix(&pkgdir).unwrap_or(&path).to_path_buf();
+                    (pkgname.to_string(), rel)
+                })
+                .collect();
+            duplicates.push(DuplicateFile { size, files });
+        }
+    }
+
+    duplicates.sort_by_key(|d| std::cmp::Reverse(d.wasted()));
+    Ok(duplicates)
+}
+
+fn spans_packages<'a>(mut pkgs: impl Iterator<Item = &'a str>) -> bool {
+    let first = pkgs.next();
+    pkgs.any(|p| Some(p) != first)
+}
+
+fn hash(path: &Path) -> Result<Vec<u8>> {
+    let mut file =
+        File::open(path).context(Context::GetPackageFiles, IOContext::Open(path.into()))?;
+    let mut hasher = Sha256::new();
+    io::copy(&mut file, &mut hasher)
+        .context(Context::GetPackageFiles, IOContext::Read(path.into()))?;
+    Ok(hasher.finalize().to_vec())
+}
@@ -0,0 +1,137 @@
+use std::fmt::Display;
+
+use crate::{
+    error::Result,
+    pacman::deptest,
+    pkgbuild::{Depend, Pkgbuild},
+    Makepkg,
+};
+
+/// Which dependency array a dependency comes from.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub enum DepKind {
+    Depend,
+    MakeDepend,
+    CheckDepend,
+}
+
+impl Display for DepKind {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            DepKind::Depend => f.write_str("depends"),
+            DepKind::MakeDepend => f.write_str("makedepends"),
+            DepKind::CheckDepend => f.write_str("checkdepends"),
+        }
+    }
+}
+
+/// Whether a dependency is satisfied by the installed packages.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub enum DepStatus {
+    Satisfied,
+    /// Nothing installed provides the dependency.
+    Missing,
+    /// The dependency is installed but not at a version that satisfies the constraint.
+    VersionMismatch,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub struct DepCheck {
+    pub dep: String,
+    pub kind: DepKind,
+    pub status: DepStatus,
+}
+
+/// The state of a PKGBUILD's dependencies on the running system.
+///
+/// Returned by [`Makepkg::check_deps`].
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct DepReport {
+    pub deps: Vec<DepCheck>,
+}
+
+impl DepReport {
+    pub fn satisfied(&self) -> impl Iterator<Item = &DepCheck> {
+        self.with_status(DepStatus::Satisfied)
+    }
+
+    pub fn missing(&self) -> impl Iterator<Item = &DepCheck> {
+        self.with_status(DepStatus::Missing)
+    }
+
+    pub fn version_mismatched(&self) -> impl Iterator<Item = &DepCheck> {
+        self.with_status(DepStatus::VersionMismatch)
+    }
+
+    /// Dependencies that are either missing or installed at the wrong version.
+    pub fn unsatisfied(&self) -> impl Iterator<Item = &DepCheck> {
+        self.deps
+            .iter()
+            .filter(|d| d.status != DepStatus::Satisfied)
+    }
+
+    pub fn is_satisfied(&self) -> bool {
+        self.unsatisfied().next().is_none()
+    }
+
+    fn with_status(&self, status: DepStatus) -> impl Iterator<Item = &DepCheck> {
+        self.deps.iter().filter(move |d| d.status == status)
+    }
+}
+
+impl Makepkg {
+    /// Checks which of a PKGBUILD's dependencies are installed.
+    ///
+    /// checkdepends are only included when the check function will be run.
+    pub fn check_deps(&self, pkgbuild: &Pkgbuild) -> Result<DepReport> {
+        let config = &self.config;
+        let arch = config.arch.as_str();
+        let mut deps = Vec::new();
+
+        deps.extend(pkgbuild.depends.enabled(arch).map(|d| (d, DepKind::Depend)));
+        deps.extend(
+            pkgbuild
+                .makedepends
+                .enabled(arch)
+                .map(|d| (d, DepKind::MakeDepend)),
+        );
+        if config.build_option(pkgbuild, "check").enabled() {
+            deps.extend(
+                pkgbuild
+                    .checkdepends
+                    .enabled(arch)
+                    .map(|d| (d, DepKind::CheckDepend)),
+            );
+        }
+
+        let missing = deptest(self, deps.iter().map(|(d, _)| d.as_str()))?;
+        // a versioned dep that is missing may still be installed at another version
+        let versioned = missing.iter().filter(|d| Depend::name_of(d) != d.as_str());
+        let unversioned_missing = deptest(self, versioned.map(|d| Depend::name_of(d)))?;
+
+        let deps = deps
+            .into_iter()
+            .map(|(dep, kind)| {
+                let status = if !missing.contains(dep) {
+                    DepStatus::Satisfied
+                } else if Depend::name_of(dep) != dep.as_str()
+                    && !unversioned_missing
+                        .iter()
+                        .any(|d| d == Depend::name_of(dep))
+                {
+                    DepStatus::VersionMismatch
+                } else {
+                    DepStatus::Missing
+                };
+
+                DepCheck {
+                    dep: dep.clone(),
+                    kind,
+                    status,
+                }
+            })
+            .collect();
+
+        Ok(DepReport { deps })
+    }
+}
@@ -1,24 +1,36 @@
 use std::io::ErrorKind;
 use std::process::{Child, ExitStatus, Output};
 use std::{
+    collections::BTreeMap,
     fmt::Display,
     io, iter,
     path::{PathBuf, StripPrefixError},
     process::Command,
     result::Result as StdResult,
+    str::FromStr,
     string::FromUtf8Error,
-    time::SystemTimeError,
+    time::{Duration, SystemTimeError},
 };
 
 use crate::{
+    callback::{Cancelled, HumanSize},
     package::PackageKind,
-    pkgbuild::{Fragment, Source},
+    pkgbuild::{Fragment, Pkgbuild, Source},
     sources::VCSKind,
     FileKind,
 };
 
 pub type Result<T> = std::result::Result<T, Error>;
 
+// errors from other crates are serialized as their message
+#[cfg(feature = "serde")]
+fn serialize_display<T: Display, S: serde::Serializer>(
+    value: &T,
+    serializer: S,
+) -> StdResult<S::Ok, S::Error> {
+    serializer.collect_str(value)
+}
+
 pub(crate) trait CommandOutputExt: Sized {
     fn read(self, command: &Command, context: Context) -> StdResult<String, CommandError>;
     fn download_read(
@@ -135,6 +147,7 @@ impl<T> IOErrorExt<T> for walkdir::Result<T> {
 }
 
 #[derive(Debug, Clone, Eq, PartialEq, Ord, PartialOrd, Hash)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize))]
 pub struct DownloadAgentError {
     pub input: String,
 }
@@ -152,6 +165,7 @@ impl Display for DownloadAgentError {
 impl std::error::Error for DownloadAgentError {}
 
 #[derive(Debug, Clone, Eq, PartialEq, Ord, PartialOrd, Hash)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize))]
 pub struct VCSClientError {
     pub input: String,
 }
@@ -165,6 +179,7 @@ impl Display for VCSClientError {
 impl std::error::Error for VCSClientError {}
 
 #[derive(Debug, Copy, Clone, PartialEq, Eq, PartialOrd, Ord, Hash)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize))]
 pub enum Expected {
     String,
     Array,
@@ -180,12 +195,14 @@ impl Display for Expected {
 }
 
 #[derive(Debug, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize))]
 pub enum ParseErrorKind {
     UnknownEscapeSequence(char),
     UnterminatedString(String),
     UnescapedQuoteInString(String),
     UnexpectedWord(String),
     UnexpectedEndOfInput,
+    Toml(String),
 }
 
 impl Display for ParseErrorKind {
@@ -200,20 +217,28 @@ impl Display for ParseErrorKind {
             }
             ParseErrorKind::UnexpectedWord(word) => write!(f, "unexpected word {}", word),
             ParseErrorKind::UnexpectedEndOfInput => f.write_str("unexpected end of input"),
+            ParseErrorKind::Toml(e) => f.write_str(e),
         }
     }
 }
 
 #[derive(Debug, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize))]
 pub struct ParseError {
     pub line: String,
     pub kind: ParseErrorKind,
     pub file_kind: FileKind,
+    /// Where in the PKGBUILD the value that failed to parse is, if known.
+    pub location: Option<SourceLocation>,
 }
 
 impl Display for ParseError {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
-        write!(f, "failed to parse {}: {}", self.file_kind, self.kind)
+        write!(f, "failed to parse {}: {}", self.file_kind, self.kind)?;
+        if let Some(location) = &self.location {
+            write!(f, " ({})", location)?;
+        }
+        Ok(())
     }
 }
 
@@ -223,33 +248,50 @@ impl ParseError {
             line: line.into(),
             file_kind,
             kind,
+            location: None,
         }
     }
 }
 
 #[derive(Debug, Clone, PartialEq, Eq, PartialOrd, Ord)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize))]
 pub enum Context {
     Callback,
     IntegrityCheck,
     RetrieveSources,
     ExtractSources,
     GenerateSrcinfo,
+    WriteSrcinfo,
+    UpdateSrcinfo,
+    BumpVersion,
     SetPkgbuildVar(String),
     UnifySourceTime,
     CreatePackage,
     BuildPackage,
+    CleanBuildDirs,
     GetPackageSize,
     GetPackageFiles,
     GeneratePackageFile(String),
     RunFunction(String),
+    CheckShell,
     ReadPkgbuild,
     SourcePkgbuild,
     ParsePkgbuild,
     ReadConfig,
+    WriteConfig,
     QueryPacman,
     RunPacman,
     StartFakeroot,
+    SignPackage,
+    SignRepoDb,
+    PruneExtractCache,
     SetMakepkgOutput,
+    RemoteBuild(String),
+    BuildInChroot,
+    MountTmpfs,
+    CheckReproducible,
+    DiffPackages,
+    VerifyPackage,
     None,
 }
 
@@ -261,28 +303,44 @@ impl Display for Context {
             Context::RetrieveSources => f.write_str("failed to download sources"),
             Context::ExtractSources => f.write_str("failed to extract sources"),
             Context::GenerateSrcinfo => f.write_str("failed to generate .SRCINFO"),
+            Context::WriteSrcinfo => f.write_str("failed to write .SRCINFO"),
+            Context::UpdateSrcinfo => f.write_str("failed to update .SRCINFO files"),
+            Context::BumpVersion => f.write_str("failed to bump version"),
             Context::SetPkgbuildVar(v) => write!(f, "failed to set {}", v),
             Context::UnifySourceTime => write!(f, "failed to unify file timestamps"),
             Context::CreatePackage => write!(f, "failed to create package tarball"),
+            Context::CleanBuildDirs => f.write_str("failed to clean build directories"),
             Context::BuildPackage => write!(f, "failed to build package"),
             Context::GetPackageSize => write!(f, "failed to get packge size"),
             Context::GetPackageFiles => write!(f, "failed to get packge files"),
             Context::GeneratePackageFile(name) => write!(f, "failed to generate {}", name),
             Context::RunFunction(func) => write!(f, "failed to run {}()", func),
+            Context::CheckShell => f.write_str("failed to check shell"),
             Context::ReadPkgbuild => write!(f, "failed to read PKGBUILD"),
             Context::SourcePkgbuild => write!(f, "failed to source PKGBUILD"),
             Context::ParsePkgbuild => write!(f, "failed to parse PKGBUILD"),
             Context::ReadConfig => write!(f, "failed to read config file"),
+            Context::WriteConfig => write!(f, "failed to write config"),
             Context::QueryPacman => write!(f, "failed to query pacman"),
             Context::RunPacman => write!(f, "failed to run pacman"),
             Context::StartFakeroot => write!(f, "failed to start fakeroot"),
+            Context::SignPackage => write!(f, "failed to sign package"),
+            Context::SignRepoDb => write!(f, "failed to sign repository database"),
+            Context::PruneExtractCache => write!(f, "failed to prune extraction cache"),
             Context::SetMakepkgOutput => write!(f, "failed to configure output location"),
+            Context::RemoteBuild(host) => write!(f, "failed to build on {}", host),
+            Context::BuildInChroot => f.write_str("failed to build in chroot"),
+            Context::MountTmpfs => f.write_str("failed to mount tmpfs"),
+            Context::CheckReproducible => f.write_str("failed to check reproducibility"),
+            Context::DiffPackages => f.write_str("failed to compare packages"),
+            Context::VerifyPackage => f.write_str("failed to verify package"),
             Context::None => f.write_str("no context"),
         }
     }
 }
 
 #[derive(Debug, Clone, PartialEq, Eq, PartialOrd, Ord)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize))]
 pub enum IOContext {
     HashFile(PathBuf),
     WriteDownload(String),
@@ -311,6 +369,7 @@ pub enum IOContext {
     NotFound(PathBuf),
     FindLibfakeroot(Vec<PathBuf>),
     Chmod(PathBuf),
+    Sandbox(String),
 }
 
 impl Display for IOContext {
@@ -341,6 +400,7 @@ impl Display for IOContext {
             IOContext::Copy(src, dst) => write!(f, "copy {} -> {}", src.display(), dst.display()),
             IOContext::WriteProcess(name) => write!(f, "couldn't write to {}", name),
             IOContext::Stat(p) => write!(f, "stat {}", p.display()),
+            IOContext::Sandbox(name) => write!(f, "unable to sandbox {}", name),
             IOContext::Socket => write!(f, "failed to create socket pair"),
             IOContext::Dup => write!(f, "unable to duplicate file description"),
             IOContext::InvalidPath(p) => write!(f, "invalid path \"{}\"", p.display()),
@@ -360,9 +420,11 @@ impl Display for IOContext {
 }
 
 #[derive(Debug)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize))]
 pub struct IOError {
     pub context: Context,
     pub iocontext: IOContext,
+    #[cfg_attr(feature = "serde", serde(serialize_with = "serialize_display"))]
     pub err: std::io::Error,
 }
 
@@ -397,9 +459,10 @@ impl IOError {
 }
 
 #[derive(Debug)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize))]
 pub enum CommandErrorKind {
-    Command(io::Error),
-    UTF8(FromUtf8Error),
+    Command(#[cfg_attr(feature = "serde", serde(serialize_with = "serialize_display"))] io::Error),
+    UTF8(#[cfg_attr(feature = "serde", serde(serialize_with = "serialize_display"))] FromUtf8Error),
     ExitCode(Option<i32>),
 }
 
@@ -415,6 +478,7 @@ impl Display for CommandErrorKind {
 }
 
 #[derive(Debug, Clone)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize))]
 pub enum LintKind {
     UnknownFragment(String),
     WrongValueType(String, String, String),
@@ -441,8 +505,21 @@ pub enum LintKind {
     InvalidEpoch(String),
     InvalidVCSClient(VCSClientError),
     InvalidDownloadAgent(DownloadAgentError),
-    InvalidSystemTime(SystemTimeError),
+    InvalidSystemTime(
+        #[cfg_attr(feature = "serde", serde(serialize_with = "serialize_display"))] SystemTimeError,
+    ),
     InvalidIntegrityCheck(String),
+    InvalidNumber(String, String),
+    EmptyFunction(String),
+    PackageInstallsNothing(String),
+    BackupFileMissing(String, String),
+    InvalidDownloadHeader(String),
+    UnknownPackageCheck(String),
+    InvalidLintLevel(String),
+    InvalidPkgdestOverride(String),
+    ConflictsWithSibling(String, String),
+    ProvidesSiblingWithoutVersion(String, String),
+    InconsistentSiblingProvides(String, Vec<String>),
 }
 
 impl Display for LintKind {
@@ -486,11 +563,131 @@ impl Display for LintKind {
             LintKind::InvalidDownloadAgent(e) => e.fmt(f),
             LintKind::InvalidSystemTime(_) => f.write_str("invalid system time"),
             LintKind::InvalidIntegrityCheck(kind) => write!(f, "invalid integrity check {}", kind),
+            LintKind::InvalidNumber(k, v) => write!(f, "{} must be a number not '{}'", k, v),
+            LintKind::UnknownPackageCheck(c) => write!(f, "unknown package check '{}'", c),
+            LintKind::ConflictsWithSibling(pkg, sibling) => write!(
+                f,
+                "{} conflicts with {} which is built from the same PKGBUILD",
+                pkg, sibling
+            ),
+            LintKind::ProvidesSiblingWithoutVersion(pkg, sibling) => write!(
+                f,
+                "{} provides {} without a version but {} is built from the same PKGBUILD",
+                pkg, sibling, sibling
+            ),
+            LintKind::InconsistentSiblingProvides(name, pkgs) => write!(
+                f,
+                "{} is provided by {} with inconsistent versions",
+                name,
+                pkgs.join(", ")
+            ),
+            LintKind::InvalidLintLevel(l) => {
+                write!(f, "invalid lint level '{}': expected name=error|warning|allow", l)
+            }
+            LintKind::InvalidPkgdestOverride(o) => {
+                write!(f, "invalid PKGDEST_OVERRIDES entry '{}': expected pkgname=dir", o)
+            }
+            LintKind::InvalidDownloadHeader(h) => {
+                write!(f, "invalid download header '{}': expected target::Header: value", h)
+            }
+            LintKind::BackupFileMissing(pkg, file) => {
+                write!(f, "{} has backup entry '{}' which is not in the package", pkg, file)
+            }
+            LintKind::EmptyFunction(func) => write!(f, "{}() does nothing", func),
+            LintKind::PackageInstallsNothing(func) => {
+                write!(f, "{}() does not seem to install anything into $pkgdir", func)
+            }
         }
     }
 }
 
 impl LintKind {
+    // the variable and value in the PKGBUILD that caused the lint
+    pub(crate) fn variable(&self) -> Option<(&str, Option<&str>)> {
+        let var = match self {
+            LintKind::UnknownFragment(f) => ("source", Some(f.as_str())),
+            LintKind::WrongValueType(name, ..)
+            | LintKind::CantBeArchitectureSpecific(_, name)
+            | LintKind::VariableCantBeInPackageFunction(name)
+            | LintKind::VariabeContainsNewlines(name)
+            | LintKind::VariabeContainsEmptyString(name)
+            | LintKind::IntegrityChecksMissing(name)
+            | LintKind::StartsWithInvalid(name, _)
+            | LintKind::AsciiOnly(name, _)
+            | LintKind::IntegrityChecksDifferentSize(_, name) => (name.as_str(), None),
+            LintKind::MissingFile(name, file) => (name.as_str(), Some(file.as_str())),
+            LintKind::InvalidChars(name, chars) => (name.as_str(), Some(chars.as_str())),
+            LintKind::AnyArchWithOthers => ("arch", None),
+            LintKind::BackupHasLeadingSlash(file) | LintKind::BackupFileMissing(_, file) => {
+                ("backup", Some(file.as_str()))
+            }
+            LintKind::ConflictsWithSibling(_, sibling) => ("conflicts", Some(sibling.as_str())),
+            LintKind::ProvidesSiblingWithoutVersion(_, name)
+            | LintKind::InconsistentSiblingProvides(name, _) => ("provides", Some(name.as_str())),
+            LintKind::InvalidPkgver(_) => ("pkgver", None),
+            LintKind::InvalidPkgrel(_) => ("pkgrel", None),
+            _ => return None,
+        };
+        Some(var)
+    }
+
+    /// A stable name for this kind of lint used to configure its [`LintLevel`].
+    pub fn name(&self) -> &'static str {
+        match self {
+            LintKind::UnknownFragment(_) => "unknown-fragment",
+            LintKind::WrongValueType(..) => "wrong-value-type",
+            LintKind::CantBeArchitectureSpecific(..) => "cant-be-architecture-specific",
+            LintKind::CantBeArchitectureSpecificAny => "cant-be-architecture-specific-any",
+            LintKind::VariableCantBeInPackageFunction(_) => "variable-cant-be-in-package-function",
+            LintKind::VariabeContainsNewlines(_) => "variable-contains-newlines",
+            LintKind::VariabeContainsEmptyString(_) => "variable-contains-empty-string",
+            LintKind::ConflictingPackageFunctions => "conflicting-package-functions",
+            LintKind::WrongPackgeFunctionFormat => "wrong-package-function-format",
+            LintKind::MissingPackageFunction(_) => "missing-package-function",
+            LintKind::MissingFile(..) => "missing-file",
+            LintKind::AnyArchWithOthers => "any-arch-with-others",
+            LintKind::BackupHasLeadingSlash(_) => "backup-has-leading-slash",
+            LintKind::IntegrityChecksMissing(_) => "integrity-checks-missing",
+            LintKind::StartsWithInvalid(..) => "starts-with-invalid",
+            LintKind::InvalidChars(..) => "invalid-chars",
+            LintKind::InvalidPkgver(_) => "invalid-pkgver",
+            LintKind::InvalidPkgrel(_) => "invalid-pkgrel",
+            LintKind::AsciiOnly(..) => "ascii-only",
+            LintKind::IntegrityChecksDifferentSize(..) => "integrity-checks-different-size",
+            LintKind::InvalidPkgExt(_) => "invalid-pkgext",
+            LintKind::InvalidSrcExt(_) => "invalid-srcext",
+            LintKind::InvalidEpoch(_) => "invalid-epoch",
+            LintKind::InvalidVCSClient(_) => "invalid-vcs-client",
+            LintKind::InvalidDownloadAgent(_) => "invalid-download-agent",
+            LintKind::InvalidSystemTime(_) => "invalid-system-time",
+            LintKind::InvalidIntegrityCheck(_) => "invalid-integrity-check",
+            LintKind::InvalidNumber(..) => "invalid-number",
+            LintKind::EmptyFunction(_) => "empty-function",
+            LintKind::PackageInstallsNothing(_) => "package-installs-nothing",
+            LintKind::BackupFileMissing(..) => "backup-file-missing",
+            LintKind::InvalidDownloadHeader(_) => "invalid-download-header",
+            LintKind::UnknownPackageCheck(_) => "unknown-package-check",
+            LintKind::InvalidLintLevel(_) => "invalid-lint-level",
+            LintKind::InvalidPkgdestOverride(_) => "invalid-pkgdest-override",
+            LintKind::ConflictsWithSibling(..) => "conflicts-with-sibling",
+            LintKind::ProvidesSiblingWithoutVersion(..) => "provides-sibling-without-version",
+            LintKind::InconsistentSiblingProvides(..) => "inconsistent-sibling-provides",
+        }
+    }
+
+    /// The level used when no [`LintLevel`] is configured for this lint.
+    pub fn default_level(&self) -> LintLevel {
+        match self {
+            LintKind::EmptyFunction(_)
+            | LintKind::PackageInstallsNothing(_)
+            | LintKind::BackupFileMissing(..)
+            | LintKind::ConflictsWithSibling(..)
+            | LintKind::ProvidesSiblingWithoutVersion(..)
+            | LintKind::InconsistentSiblingProvides(..) => LintLevel::Warning,
+            _ => LintLevel::Error,
+        }
+    }
+
     pub(crate) fn pkgbuild(self) -> LintError {
         LintError::pkgbuild(vec![self])
     }
@@ -500,10 +697,95 @@ impl LintKind {
     }
 }
 
+/// How a lint is reported.
+#[derive(Debug, Copy, Clone, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub enum LintLevel {
+    /// Fail with a [`LintError`].
+    Error,
+    /// Log a warning and continue.
+    Warning,
+    /// Ignore the lint.
+    Allow,
+}
+
+impl Display for LintLevel {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            LintLevel::Error => f.write_str("error"),
+            LintLevel::Warning => f.write_str("warning"),
+            LintLevel::Allow => f.write_str("allow"),
+        }
+    }
+}
+
+impl FromStr for LintLevel {
+    type Err = ();
+
+    fn from_str(s: &str) -> StdResult<Self, Self::Err> {
+        match s {
+            "error" | "deny" => Ok(LintLevel::Error),
+            "warning" | "warn" => Ok(LintLevel::Warning),
+            "allow" | "ignore" => Ok(LintLevel::Allow),
+            _ => Err(()),
+        }
+    }
+}
+
+/// Overrides the [`LintLevel`] of lints by their [`name`](LintKind::name).
+#[derive(Debug, Default, Clone, PartialEq, Eq)]
+pub struct LintLevels {
+    pub levels: BTreeMap<String, LintLevel>,
+}
+
+impl LintLevels {
+    pub fn set<S: Into<String>>(&mut self, name: S, level: LintLevel) {
+        self.levels.insert(name.into(), level);
+    }
+
+    pub fn get(&self, lint: &LintKind) -> LintLevel {
+        self.levels
+            .get(lint.name())
+            .copied()
+            .unwrap_or_else(|| lint.default_level())
+    }
+
+    /// Splits lints into errors and warnings dropping allowed lints.
+    pub fn split(&self, lints: Vec<LintKind>) -> (Vec<LintKind>, Vec<LintKind>) {
+        let mut errors = Vec::new();
+        let mut warnings = Vec::new();
+        for lint in lints {
+            match self.get(&lint) {
+                LintLevel::Error => errors.push(lint),
+                LintLevel::Warning => warnings.push(lint),
+                LintLevel::Allow => (),
+            }
+        }
+        (errors, warnings)
+    }
+}
+
+/// A line in a PKGBUILD.
+#[derive(Debug, Clone, PartialEq, Eq, PartialOrd, Ord, Hash)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize))]
+pub struct SourceLocation {
+    pub path: PathBuf,
+    /// The line number starting from 1.
+    pub line: usize,
+}
+
+impl Display for SourceLocation {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}:{}", self.path.display(), self.line)
+    }
+}
+
 #[derive(Debug, Clone)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize))]
 pub struct LintError {
     pub file_kind: FileKind,
     pub issues: Vec<LintKind>,
+    /// Where each of [`issues`](LintError::issues) was found, if known.
+    pub locations: Vec<Option<SourceLocation>>,
 }
 
 impl Display for LintError {
@@ -512,11 +794,13 @@ impl Display for LintError {
             FileKind::Pkgbuild => f.write_str("invalid PKGBUILD: ")?,
             FileKind::Config => f.write_str("invalid config")?,
         }
-        if let Some(issue) = self.issues.get(0) {
-            issue.fmt(f)?;
-        }
-        for issue in self.issues.iter().skip(1) {
-            f.write_str("\n    ")?;
+        for (i, issue) in self.issues.iter().enumerate() {
+            if i != 0 {
+                f.write_str("\n    ")?;
+            }
+            if let Some(Some(location)) = self.locations.get(i) {
+                write!(f, "{}: ", location)?;
+            }
             issue.fmt(f)?;
         }
         Ok(())
@@ -528,23 +812,38 @@ impl LintError {
         LintError {
             file_kind: FileKind::Pkgbuild,
             issues: v,
+            locations: Vec::new(),
         }
     }
     pub(crate) fn config(v: Vec<LintKind>) -> Self {
         LintError {
             file_kind: FileKind::Config,
             issues: v,
+            locations: Vec::new(),
         }
     }
+
+    pub(crate) fn with_locations(mut self, pkgbuild: &Pkgbuild) -> Self {
+        self.locations = self
+            .issues
+            .iter()
+            .map(|i| pkgbuild.lint_location(i))
+            .collect();
+        self
+    }
 }
 
 #[derive(Debug)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize))]
 pub enum DownloadError {
     SourceMissing(Source),
     UnknownProtocol(Source),
     UnknownVCSClient(Source),
-    Curl(curl::Error),
-    CurlMulti(curl::MultiError),
+    Curl(#[cfg_attr(feature = "serde", serde(serialize_with = "serialize_display"))] curl::Error),
+    CurlMulti(
+        #[cfg_attr(feature = "serde", serde(serialize_with = "serialize_display"))]
+        curl::MultiError,
+    ),
     Status(Source, u32),
     Command(Source, CommandError),
     UnsupportedFragment(Source, VCSKind, Fragment),
@@ -594,6 +893,7 @@ impl Display for DownloadError {
 }
 
 #[derive(Debug)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize))]
 pub enum IntegError {
     ValidityCheck,
     VerifyFunction,
@@ -602,7 +902,13 @@ pub enum IntegError {
     MissingFileForSig(String),
     SignatureNotFound(Source),
     ReadFingerprint(String),
-    Gpgme(gpgme::Error),
+    #[cfg(feature = "gpgme")]
+    Gpgme(#[cfg_attr(feature = "serde", serde(serialize_with = "serialize_display"))] gpgme::Error),
+    #[cfg(feature = "sequoia")]
+    Sequoia(
+        #[cfg_attr(feature = "serde", serde(serialize_with = "serialize_display"))] anyhow::Error,
+    ),
+    NoPgpBackend,
 }
 
 impl Display for IntegError {
@@ -629,14 +935,23 @@ impl Display for IntegError {
             IntegError::ReadFingerprint(s) => {
                 write!(f, "failed to get fingerprint for {}", s)
             }
+            #[cfg(feature = "gpgme")]
             IntegError::Gpgme(e) => {
                 write!(f, "gpgme: {}", e)
             }
+            #[cfg(feature = "sequoia")]
+            IntegError::Sequoia(e) => {
+                write!(f, "sequoia: {}", e)
+            }
+            IntegError::NoPgpBackend => {
+                f.write_str("signature verification is not supported by this build")
+            }
         }
     }
 }
 
 #[derive(Debug)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize))]
 pub struct CommandError {
     pub kind: CommandErrorKind,
     pub command: Vec<String>,
@@ -681,6 +996,10 @@ impl CommandError {
         }
     }
 
+    fn is_cancelled(&self) -> bool {
+        matches!(&self.kind, CommandErrorKind::Command(e) if Cancelled::is(e))
+    }
+
     fn command_to_string(command: &Command) -> Vec<String> {
         iter::once(command.get_program())
             .chain(command.get_args())
@@ -690,6 +1009,7 @@ impl CommandError {
 }
 
 #[derive(Debug, Clone)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize))]
 pub struct ArchitectureError {
     pub pkgbase: String,
     pub arch: String,
@@ -706,6 +1026,7 @@ impl Display for ArchitectureError {
 }
 
 #[derive(Debug)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize))]
 pub struct AlreadyBuiltError {
     pub kind: PackageKind,
     pub pkgbase: String,
@@ -717,7 +1038,157 @@ impl Display for AlreadyBuiltError {
     }
 }
 
+/// The packages were already built from identical inputs. See [`Options::skip_unchanged`].
+///
+/// [`Options::skip_unchanged`]: crate::options::Options::skip_unchanged
+#[derive(Debug)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize))]
+pub struct UnchangedError {
+    pub pkgbase: String,
+}
+
+impl Display for UnchangedError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{} is unchanged since it was last built", self.pkgbase)
+    }
+}
+
+/// The signing key is missing or can't be used to sign.
+#[derive(Debug)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize))]
+pub struct SigningKeyError {
+    /// The configured key, `None` when gpg's default key was wanted.
+    pub key: Option<String>,
+}
+
+impl Display for SigningKeyError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match &self.key {
+            Some(key) => write!(f, "the key {} can not be used for signing", key),
+            None => write!(f, "no secret key is available for signing"),
+        }
+    }
+}
+
+/// PKGBUILDs passed to [`Makepkg::build_many`](crate::Makepkg::build_many) depend on each
+/// other in a cycle.
+#[derive(Debug)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize))]
+pub struct DependencyCycleError {
+    /// The pkgbases that could not be ordered.
+    pub pkgbases: Vec<String>,
+}
+
+impl Display for DependencyCycleError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(
+            f,
+            "dependency cycle between PKGBUILDs: {}",
+            self.pkgbases.join(" ")
+        )
+    }
+}
+
+/// The configured shell lacks a feature needed to run PKGBUILD functions.
+#[derive(Debug)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize))]
+pub struct UnsupportedShellError {
+    pub shell: PathBuf,
+    pub feature: &'static str,
+}
+
+impl Display for UnsupportedShellError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(
+            f,
+            "shell {} is not supported: it does not support {} (a bash 5.1 compatible shell is required)",
+            self.shell.display(),
+            self.feature
+        )
+    }
+}
+
+/// A filesystem does not have enough free space for the build.
+#[derive(Debug)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize))]
+pub struct InsufficientSpaceError {
+    pub path: PathBuf,
+    /// The estimated space the build needs on this filesystem in bytes.
+    pub needed: u64,
+    pub available: u64,
+}
+
+impl Display for InsufficientSpaceError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(
+            f,
+            "not enough free space in {}: the build needs about {} but only {} is available",
+            self.path.display(),
+            HumanSize(self.needed),
+            HumanSize(self.available)
+        )
+    }
+}
+
+/// A PKGBUILD function ran for longer than [`Options::timeout`](crate::options::Options::timeout)
+/// and was killed.
+#[derive(Debug)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize))]
+pub struct TimeoutError {
+    pub function: String,
+    pub timeout: Duration,
+}
+
+impl Display for TimeoutError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(
+            f,
+            "{}() timed out after {} seconds",
+            self.function,
+            self.timeout.as_secs()
+        )
+    }
+}
+
+/// Why makepkg refused to delete a build directory.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize))]
+pub enum UnsafeDeleteReason {
+    /// The directory is not inside the builddir.
+    OutsideBuilddir,
+    /// The directory is a symlink.
+    Symlink,
+    /// The directory was not created by makepkg.
+    MissingMarker,
+    /// The chroot working copy is not a single directory inside the chroot other than `root`.
+    InvalidChrootCopy,
+}
+
+#[derive(Debug)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize))]
+pub struct UnsafeDeleteError {
+    pub path: PathBuf,
+    pub reason: UnsafeDeleteReason,
+}
+
+impl Display for UnsafeDeleteError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "refusing to remove {}: ", self.path.display())?;
+        match self.reason {
+            UnsafeDeleteReason::OutsideBuilddir => f.write_str("it is not inside the builddir"),
+            UnsafeDeleteReason::Symlink => f.write_str("it is a symlink"),
+            UnsafeDeleteReason::MissingMarker => f.write_str(
+                "it was not created by makepkg (remove it manually if this is intended)",
+            ),
+            UnsafeDeleteReason::InvalidChrootCopy => {
+                f.write_str("it is not a working copy directly inside the chroot")
+            }
+        }
+    }
+}
+
 #[derive(Debug)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize))]
 pub enum Error {
     Parse(ParseError),
     Lint(LintError),
@@ -726,7 +1197,16 @@ pub enum Error {
     Integ(IntegError),
     Architecture(ArchitectureError),
     AlreadyBuilt(AlreadyBuiltError),
+    Unchanged(UnchangedError),
+    SigningKey(SigningKeyError),
+    DependencyCycle(DependencyCycleError),
     Command(CommandError),
+    UnsafeDelete(UnsafeDeleteError),
+    UnsupportedShell(UnsupportedShellError),
+    InsufficientSpace(InsufficientSpaceError),
+    Timeout(TimeoutError),
+    /// A callback returned [`Cancelled`].
+    Cancelled,
 }
 
 impl std::error::Error for Error {}
@@ -741,7 +1221,15 @@ impl Display for Error {
             Error::Integ(e) => e.fmt(f),
             Error::Architecture(e) => e.fmt(f),
             Error::AlreadyBuilt(e) => e.fmt(f),
+            Error::Unchanged(e) => e.fmt(f),
+            Error::SigningKey(e) => e.fmt(f),
+            Error::DependencyCycle(e) => e.fmt(f),
             Error::Command(e) => e.fmt(f),
+            Error::UnsafeDelete(e) => e.fmt(f),
+            Error::UnsupportedShell(e) => e.fmt(f),
+            Error::InsufficientSpace(e) => e.fmt(f),
+            Error::Timeout(e) => e.fmt(f),
+            Error::Cancelled => f.write_str("cancelled by callback"),
         }
     }
 }
@@ -756,11 +1244,31 @@ impl Display for Error {
             Error::Integ(_) => todo!(),
             Error::Architecture(_) => todo!(),
             Error::AlreadyBuilt(_) => todo!(),
+            Error::Unchanged(_) => todo!(),
+            Error::SigningKey(_) => todo!(),
+            Error::DependencyCycle(_) => todo!(),
             Error::Command(_) => todo!(),
+            Error::UnsafeDelete(_) => todo!(),
+            Error::UnsupportedShell(_) => todo!(),
+            Error::InsufficientSpace(_) => todo!(),
+            Error::Timeout(_) => todo!(),
+            Error::Cancelled => todo!(),
         }
     }
 }*/
 
+impl From<TimeoutError> for Error {
+    fn from(value: TimeoutError) -> Self {
+        Self::Timeout(value)
+    }
+}
+
+impl From<InsufficientSpaceError> for Error {
+    fn from(value: InsufficientSpaceError) -> Self {
+        Self::InsufficientSpace(value)
+    }
+}
+
 impl From<ParseError> for Error {
     fn from(value: ParseError) -> Self {
         Self::Parse(value)
@@ -769,6 +1277,9 @@ impl From<ParseError> for Error {
 
 impl From<IOError> for Error {
     fn from(value: IOError) -> Self {
+        if Cancelled::is(&value.err) {
+            return Self::Cancelled;
+        }
         Self::IO(value)
     }
 }
@@ -781,6 +1292,11 @@ impl From<LintError> for Error {
 
 impl From<DownloadError> for Error {
     fn from(value: DownloadError) -> Self {
+        if let DownloadError::Command(_, e) = &value {
+            if e.is_cancelled() {
+                return Self::Cancelled;
+            }
+        }
         Self::Download(value)
     }
 }
@@ -805,6 +1321,9 @@ impl From<IntegError> for Error {
 
 impl From<CommandError> for Error {
     fn from(value: CommandError) -> Self {
+        if value.is_cancelled() {
+            return Self::Cancelled;
+        }
         Error::Command(value)
     }
 }
@@ -815,8 +1334,38 @@ impl From<ArchitectureError> for Error {
     }
 }
 
+impl From<UnsupportedShellError> for Error {
+    fn from(value: UnsupportedShellError) -> Self {
+        Error::UnsupportedShell(value)
+    }
+}
+
+impl From<UnsafeDeleteError> for Error {
+    fn from(value: UnsafeDeleteError) -> Self {
+        Error::UnsafeDelete(value)
+    }
+}
+
 impl From<AlreadyBuiltError> for Error {
     fn from(value: AlreadyBuiltError) -> Self {
         Error::AlreadyBuilt(value)
     }
 }
+
+impl From<UnchangedError> for Error {
+    fn from(value: UnchangedError) -> Self {
+        Error::Unchanged(value)
+    }
+}
+
+impl From<SigningKeyError> for Error {
+    fn from(value: SigningKeyError) -> Self {
+        Error::SigningKey(value)
+    }
+}
+
+impl From<DependencyCycleError> for Error {
+    fn from(value: DependencyCycleError) -> Self {
+        Error::DependencyCycle(value)
+    }
+}
@@ -0,0 +1,206 @@
+use std::{
+    fs::read_dir,
+    os::unix::ffi::OsStrExt,
+    path::{Path, PathBuf},
+    process::Command,
+};
+
+use sha2::Sha256;
+
+use crate::{
+    callback::{CommandKind, Event},
+    config::PkgbuildDirs,
+    error::{CommandErrorExt, Context, IOContext, IOErrorExt, Result},
+    fs::{mkdir, read_to_string, rename, rm_all, rm_file, write},
+    integ::{hash, hash_file},
+    options::Options,
+    pkgbuild::{Pkgbuild, Source},
+    run::CommandOutput,
+    Makepkg,
+};
+
+/// A source archive that has been extracted into
+/// [`Config::extract_cache`](crate::config::Config::extract_cache).
+#[derive(Debug, Clone, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct ExtractCacheEntry {
+    /// The sha256sum of the archive.
+    pub key: String,
+    /// The directory the archive was extracted into.
+    pub path: PathBuf,
+    /// The srcdirs the extracted files have been copied into.
+    pub refs: Vec<PathBuf>,
+}
+
+impl Makepkg {
+    /// Lists the entries of the extraction cache.
+    pub fn extract_cache_entries(&self) -> Result<Vec<ExtractCacheEntry>> {
+        let Some(cache) = &self.config.extract_cache else {
+            return Ok(Vec::new());
+        };
+        if !cache.exists() {
+            return Ok(Vec::new());
+        }
+
+        let mut entries = Vec::new();
+
+        for entry in read_dir(cache).context(
+            Context::PruneExtractCache,
+            IOContext::ReadDir(cache.to_path_buf()),
+        )? {
+            let entry = entry.context(
+                Context::PruneExtractCache,
+                IOContext::ReadDir(cache.to_path_buf()),
+            )?;
+            let path = entry.path();
+            let refs_dir = path.join("refs");
+            let mut refs = Vec::new();
+
+            if refs_dir.exists() {
+                for r in read_dir(&refs_dir).context(
+                    Context::PruneExtractCache,
+                    IOContext::ReadDir(refs_dir.clone()),
+                )? {
+                    let r = r.context(
+                        Context::PruneExtractCache,
+                        IOContext::ReadDir(refs_dir.clone()),
+                    )?;
+                    let srcdir = read_to_string(r.path(), Context::PruneExtractCache)?;
+                    refs.push(PathBuf::from(srcdir));
+                }
+            }
+
+            refs.sort();
+            entries.push(ExtractCacheEntry {
+                key: entry.file_name().to_string_lossy().into_owned(),
+                path,
+                refs,
+            });
+        }
+
+        entries.sort();
+        Ok(entries)
+    }
+
+    /// Removes entries of the extraction cache that are no longer used by any srcdir.
+    ///
+    /// References to srcdirs that no longer exist are dropped first. Returns the removed
+    /// entries.
+    pub fn prune_extract_cache(&self) -> Result<Vec<ExtractCacheEntry>> {
+        let mut removed = Vec::new();
+
+        for entry in self.extract_cache_entries()? {
+            for srcdir in &entry.refs {
+                if !srcdir.exists() {
+                    rm_file(ref_path(&entry.path, srcdir), Context::PruneExtractCache)?;
+                }
+            }
+
+            if entry.refs.iter().all(|srcdir| !srcdir.exists()) {
+                rm_all(&entry.path, Context::PruneExtractCache)?;
+                removed.push(entry);
+            }
+        }
+
+        Ok(removed)
+    }
+
+    /// Extracts an archive into the extraction cache if it is not already there and links
+    /// the extracted files into srcdir.
+    pub(crate) fn extract_cached(
+        &self,
+        dirs: &PkgbuildDirs,
+        options: &Options,
+        pkgbuild: &Pkgbuild,
+        source: &Source,
+        cache: &Path,
+    ) -> Result<()> {
+        let srcfile = dirs.download_path(source);
+        let key = hash_file::<Sha256>(&srcfile)?;
+        let entry = cache.join(&key);
+        let tree = entry.join("tree");
+
+        if !tree.exists() {
+            self.event(Event::Extacting(source.file_name()))?;
+            let tmp = entry.join(format!("tree.{}", std::process::id()));
+            if tmp.exists() {
+                rm_all(&tmp, Context::ExtractSources)?;
+            }
+            mkdir(&tmp, Context::ExtractSources)?;
+
+            let mut command = Command::new("bsdtar");
+            self.sandbox(options, &mut command, &[&tmp], Context::ExtractSources)?;
+            let res = command
+                .arg("-xf")
+                .arg(&srcfile)
+                .current_dir(&tmp)
+                .process_spawn(self, CommandKind::ExtractSources(pkgbuild, source))
+                .cmd_context(&command, Context::ExtractSources);
+
+            if let Err(e) = res {
+                rm_all(&tmp, Context::ExtractSources)?;
+                return Err(e.into());
+            }
+
+            // another build may have extracted the same archive in the meantime
+            if tree.exists() {
+                rm_all(&tmp, Context::ExtractSources)?;
+            } else {
+                rename(&tmp, &tree, Context::ExtractSources)?;
+            }
+        } else {
+            self.event(Event::UsingCachedExtraction(source.file_name()))?;
+        }
+
+        self.link_tree(pkgbuild, source, &tree, &dirs.srcdir)?;
+
+        mkdir(entry.join("refs"), Context::ExtractSources)?;
+        write(
+            ref_path(&entry, &dirs.srcdir),
+            dirs.srcdir.as_os_str().as_bytes(),
+            Context::ExtractSources,
+        )?;
+
+        Ok(())
+    }
+
+    // reflinks are preferred as they are cheap and can be safely modified in place by
+    // prepare(). hardlinks would share those modifications with the cache so fall back to a
+    // full copy
+    fn link_tree(
+        &self,
+        pkgbuild: &Pkgbuild,
+        source: &Source,
+        tree: &Path,
+        dest: &Path,
+    ) -> Result<()> {
+        let kind = CommandKind::ExtractSources(pkgbuild, source);
+
+        let mut command = Command::new("cp");
+        command
+            .arg("-a")
+            .arg("--reflink=always")
+            .arg("--")
+            .arg(tree.join("."))
+            .arg(dest);
+        if command.process_output().is_ok_and(|o| o.status.success()) {
+            return Ok(());
+        }
+
+        let mut command = Command::new("cp");
+        command
+            .arg("-a")
+            .arg("--")
+            .arg(tree.join("."))
+            .arg(dest)
+            .process_spawn(self, kind)
+            .cmd_context(&command, Context::ExtractSources)?;
+        Ok(())
+    }
+}
+
+fn ref_path(entry: &Path, srcdir: &Path) -> PathBuf {
+    let mut bytes = srcdir.as_os_str().as_bytes();
+    // hashing a slice can't fail
+    let name = hash::<Sha256, _>(srcdir, &mut bytes).unwrap_or_default();
+    entry.join("refs").join(name)
+}
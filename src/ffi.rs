@@ -0,0 +1,340 @@
+//! A C ABI for embedding makepkg in other languages.
+//!
+//! All objects are opaque handles that must be freed with their matching `_free` function.
+//! Functions that can fail return a negative number or null and the error message can then
+//! be retrieved with [`makepkg_last_error`]. A panic is reported the same way instead of
+//! unwinding into the caller. See `include/makepkg.h` for the C declarations.
+
+use std::{
+    cell::RefCell,
+    ffi::{c_char, c_int, c_void, CStr, CString},
+    io,
+    panic::{catch_unwind, AssertUnwindSafe},
+    path::PathBuf,
+    ptr::null_mut,
+};
+
+use crate::{
+    callback::{Callbacks, CommandInfo, CommandKind, CommandOutput, Event, LogLevel, LogMessage},
+    config::Config,
+    options::Options,
+    pkgbuild::Pkgbuild,
+    Makepkg,
+};
+
+pub const MAKEPKG_CLEAN_BUILD: u32 = 1 << 0;
+pub const MAKEPKG_NO_CHECK: u32 = 1 << 1;
+pub const MAKEPKG_IGNORE_ARCH: u32 = 1 << 2;
+pub const MAKEPKG_SKIP_INTEG: u32 = 1 << 3;
+pub const MAKEPKG_FORCE: u32 = 1 << 4;
+pub const MAKEPKG_NO_ARCHIVE: u32 = 1 << 5;
+pub const MAKEPKG_LOG: u32 = 1 << 6;
+
+pub const MAKEPKG_LOG_DEBUG: c_int = 0;
+pub const MAKEPKG_LOG_WARNING: c_int = 1;
+pub const MAKEPKG_LOG_ERROR: c_int = 2;
+
+pub type MakepkgEventFn = Option<unsafe extern "C" fn(data: *mut c_void, msg: *const c_char)>;
+pub type MakepkgLogFn =
+    Option<unsafe extern "C" fn(data: *mut c_void, level: c_int, msg: *const c_char)>;
+pub type MakepkgOutputFn =
+    Option<unsafe extern "C" fn(data: *mut c_void, output: *const u8, len: usize)>;
+
+thread_local! {
+    static LAST_ERROR: RefCell<Option<CString>> = const { RefCell::new(None) };
+}
+
+fn set_error<E: std::fmt::Display>(err: E) {
+    let msg = CString::new(err.to_string().replace('\0', "")).unwrap_or_default();
+    LAST_ERROR.with(|e| *e.borrow_mut() = Some(msg));
+}
+
+fn result<T, E: std::fmt::Display>(res: Result<T, E>) -> c_int {
+    match res {
+        Ok(_) => 0,
+        Err(e) => {
+            set_error(e);
+            -1
+        }
+    }
+}
+
+/// Runs `f`, turning a panic into an error and `on_panic` so it never unwinds into C.
+fn guard<T, F: FnOnce() -> T>(on_panic: T, f: F) -> T {
+    match catch_unwind(AssertUnwindSafe(f)) {
+        Ok(ret) => ret,
+        Err(e) => {
+            let msg = e
+                .downcast_ref::<&str>()
+                .copied()
+                .or_else(|| e.downcast_ref::<String>().map(|s| s.as_str()))
+                .unwrap_or("unknown error");
+            set_error(format!("panicked: {}", msg));
+            on_panic
+        }
+    }
+}
+
+unsafe fn path(path: *const c_char) -> Option<PathBuf> {
+    if path.is_null() {
+        return None;
+    }
+    let path = CStr::from_ptr(path).to_string_lossy().into_owned();
+    Some(path.into())
+}
+
+fn c_string<S: ToString>(s: S) -> CString {
+    CString::new(s.to_string().replace('\0', "")).unwrap_or_default()
+}
+
+#[derive(Debug)]
+struct FfiCallbacks {
+    event: MakepkgEventFn,
+    log: MakepkgLogFn,
+    output: MakepkgOutputFn,
+    data: *mut c_void,
+}
+
+// the caller is responsible for making data usable from whichever thread the makepkg handle is
+// used on
+unsafe impl Send for FfiCallbacks {}
+
+impl Callbacks for FfiCallbacks {
+    fn event(&mut self, event: Event) -> io::Result<()> {
+        if let Some(cb) = self.event {
+            let msg = c_string(event);
+            unsafe { cb(self.data, msg.as_ptr()) };
+        }
+        Ok(())
+    }
+
+    fn log(&mut self, level: LogLevel, msg: LogMessage) -> io::Result<()> {
+        if let Some(cb) = self.log {
+            let level = match level {
+                LogLevel::Debug => MAKEPKG_LOG_DEBUG,
+                LogLevel::Warning => MAKEPKG_LOG_WARNING,
+                LogLevel::Error => MAKEPKG_LOG_ERROR,
+            };
+            let msg = c_string(msg);
+            unsafe { cb(self.data, level, msg.as_ptr()) };
+        }
+        Ok(())
+    }
+
+    fn command_new(
+        &mut self,
+        _id: usize,
+        _kind: CommandKind,
+        _command: &CommandInfo,
+    ) -> io::Result<CommandOutput> {
+        if self.output.is_some() {
+            Ok(CommandOutput::Callback)
+        } else {
+            Ok(CommandOutput::Null)
+        }
+    }
+
+    fn command_output(&mut self, _id: usize, _kind: CommandKind, output: &[u8]) -> io::Result<()> {
+        if let Some(cb) = self.output {
+            unsafe { cb(self.data, output.as_ptr(), output.len()) };
+        }
+        Ok(())
+    }
+}
+
+/// Returns the message of the last error on this thread or null if there was none.
+///
+/// The string is valid until the next call that fails on this thread.
+#[no_mangle]
+pub extern "C" fn makepkg_last_error() -> *const c_char {
+    guard(std::ptr::null(), || {
+        LAST_ERROR.with(|e| {
+            e.borrow()
+                .as_ref()
+                .map(|e| e.as_ptr())
+                .unwrap_or(std::ptr::null())
+        })
+    })
+}
+
+/// Creates a makepkg handle reading the config from `config`, or the default
+/// makepkg.conf if null.
+///
+/// # Safety
+///
+/// `config` must be null or a valid nul terminated string.
+#[no_mangle]
+pub unsafe extern "C" fn makepkg_new(config: *const c_char) -> *mut Makepkg {
+    guard(null_mut(), || {
+        let config = match path(config) {
+            Some(path) => Config::from_path(path),
+            None => Config::new(),
+        };
+        match config {
+            Ok(config) => Box::into_raw(Box::new(Makepkg::from_config(config))),
+            Err(e) => {
+                set_error(e);
+                null_mut()
+            }
+        }
+    })
+}
+
+/// Frees a handle created by [`makepkg_new`].
+///
+/// # Safety
+///
+/// `makepkg` must be null or a handle returned by [`makepkg_new`] that has not been freed.
+#[no_mangle]
+pub unsafe extern "C" fn makepkg_free(makepkg: *mut Makepkg) {
+    guard((), || {
+        if !makepkg.is_null() {
+            drop(Box::from_raw(makepkg));
+        }
+    })
+}
+
+/// Sets the functions called with events, log messages and the output of commands. Any of
+/// them may be null. `data` is passed to every call.
+///
+/// # Safety
+///
+/// `makepkg` must be a valid handle and the callbacks must be safe to call with `data` for
+/// as long as the handle is used.
+#[no_mangle]
+pub unsafe extern "C" fn makepkg_set_callbacks(
+    makepkg: *mut Makepkg,
+    event: MakepkgEventFn,
+    log: MakepkgLogFn,
+    output: MakepkgOutputFn,
+    data: *mut c_void,
+) {
+    guard((), || {
+        let makepkg = &mut *makepkg;
+        let callbacks = FfiCallbacks {
+            event,
+            log,
+            output,
+            data,
+        };
+        *makepkg.callbacks.get_mut() = Some(Box::new(callbacks));
+    })
+}
+
+/// Reads the PKGBUILD in `dir`. Returns null on error.
+///
+/// # Safety
+///
+/// `makepkg` must be a valid handle and `dir` a valid nul terminated string.
+#[no_mangle]
+pub unsafe extern "C" fn makepkg_pkgbuild_load(
+    makepkg: *const Makepkg,
+    dir: *const c_char,
+) -> *mut Pkgbuild {
+    guard(null_mut(), || {
+        let makepkg = &*makepkg;
+        let dir = path(dir).unwrap_or_default();
+        match makepkg.load_pkgbuild(dir) {
+            Ok(pkgbuild) => Box::into_raw(Box::new(pkgbuild)),
+            Err(e) => {
+                set_error(e);
+                null_mut()
+            }
+        }
+    })
+}
+
+/// Frees a PKGBUILD returned by [`makepkg_pkgbuild_load`].
+///
+/// # Safety
+///
+/// `pkgbuild` must be null or a PKGBUILD that has not been freed.
+#[no_mangle]
+pub unsafe extern "C" fn makepkg_pkgbuild_free(pkgbuild: *mut Pkgbuild) {
+    guard((), || {
+        if !pkgbuild.is_null() {
+            drop(Box::from_raw(pkgbuild));
+        }
+    })
+}
+
+/// Generates the .SRCINFO of a PKGBUILD. The string must be freed with
+/// [`makepkg_string_free`].
+///
+/// # Safety
+///
+/// `pkgbuild` must be a valid PKGBUILD.
+#[no_mangle]
+pub unsafe extern "C" fn makepkg_srcinfo(pkgbuild: *const Pkgbuild) -> *mut c_char {
+    guard(null_mut(), || c_string((*pkgbuild).srcinfo()).into_raw())
+}
+
+/// Frees a string returned by this library.
+///
+/// # Safety
+///
+/// `s` must be null or a string returned by this library that has not been freed.
+#[no_mangle]
+pub unsafe extern "C" fn makepkg_string_free(s: *mut c_char) {
+    guard((), || {
+        if !s.is_null() {
+            drop(CString::from_raw(s));
+        }
+    })
+}
+
+/// Downloads and verifies the sources of a PKGBUILD. Returns 0 on success.
+///
+/// # Safety
+///
+/// `makepkg` must be a valid handle and `pkgbuild` a valid PKGBUILD.
+#[no_mangle]
+pub unsafe extern "C" fn makepkg_verify_sources(
+    makepkg: *const Makepkg,
+    pkgbuild: *const Pkgbuild,
+) -> c_int {
+    guard(-1, || {
+        let makepkg = &*makepkg;
+        let pkgbuild = &*pkgbuild;
+        let options = Options::new();
+        result(
+            makepkg
+                .download_sources(&options, pkgbuild, false)
+                .and_then(|_| makepkg.check_integ(&options, pkgbuild, false)),
+        )
+    })
+}
+
+/// Builds a PKGBUILD. `flags` is a combination of the `MAKEPKG_*` build flags. Returns 0 on
+/// success.
+///
+/// # Safety
+///
+/// `makepkg` must be a valid handle and `pkgbuild` a valid PKGBUILD.
+#[no_mangle]
+pub unsafe extern "C" fn makepkg_build(
+    makepkg: *const Makepkg,
+    pkgbuild: *mut Pkgbuild,
+    flags: u32,
+) -> c_int {
+    guard(-1, || {
+        let makepkg = &*makepkg;
+        let pkgbuild = &mut *pkgbuild;
+        let flag = |f| flags & f != 0;
+
+        let mut options = Options::new();
+        options.clean_build = flag(MAKEPKG_CLEAN_BUILD);
+        options.no_check = flag(MAKEPKG_NO_CHECK);
+        options.ignore_arch = flag(MAKEPKG_IGNORE_ARCH);
+        options.rebuild = flag(MAKEPKG_FORCE);
+        options.log = flag(MAKEPKG_LOG);
+        if flag(MAKEPKG_SKIP_INTEG) {
+            options.no_integ();
+        }
+        if flag(MAKEPKG_NO_ARCHIVE) {
+            options.no_archive = true;
+        }
+
+        result(makepkg.build(&options, pkgbuild))
+    })
+}
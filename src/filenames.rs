@@ -0,0 +1,103 @@
+use std::{
+    collections::BTreeMap,
+    fs::rename,
+    path::{Path, PathBuf},
+};
+
+use unicode_normalization::{is_nfc, UnicodeNormalization};
+use walkdir::WalkDir;
+
+use crate::{
+    callback::Event,
+    config::{PackageCheck, PkgbuildDirs},
+    error::{Context, IOContext, IOErrorExt, Result},
+    package_lint::{package_files, warn, PackageWarning},
+    pkgbuild::Package,
+    Makepkg,
+};
+
+impl Makepkg {
+    /// Renames files in a package whose names are not NFC normalized.
+    ///
+    /// Files are left alone when the normalized name already exists, these are reported by
+    /// [`PackageCheck::FileNames`] instead.
+    pub(crate) fn normalize_file_names(&self, dirs: &PkgbuildDirs, pkg: &Package) -> Result<()> {
+        let pkgdir = dirs.pkgdir(pkg);
+
+        // children first so renaming a directory does not invalidate the paths below it
+        for file in package_files(WalkDir::new(&pkgdir).contents_first(true)) {
+            let file = file.context(Context::CreatePackage, IOContext::ReadDir(pkgdir.clone()))?;
+            let Some(name) = file.file_name().to_str() else {
+                continue;
+            };
+            if is_nfc(name) {
+                continue;
+            }
+
+            let dest = file.path().with_file_name(name.nfc().collect::<String>());
+            if dest.symlink_metadata().is_ok() {
+                continue;
+            }
+
+            let rel = dest.strip_prefix(&pkgdir).unwrap_or(&dest);
+            self.event(Event::NormalizedFileName(rel))?;
+            rename(file.path(), &dest).context(
+                Context::CreatePackage,
+                IOContext::Rename(file.path().to_path_buf(), dest.clone()),
+            )?;
+        }
+
+        Ok(())
+    }
+}
+
+/// Warns about file names that are not valid UTF-8, not NFC normalized or that only differ
+/// from another file in the same directory by normalization.
+pub(crate) fn lint_file_names(pkgdir: &Path, warnings: &mut Vec<PackageWarning>) -> Result<()> {
+    let mut normalized = BTreeMap::<(PathBuf, String), Vec<PathBuf>>::new();
+
+    for file in package_files(WalkDir::new(pkgdir)) {
+        let file = file.context(
+            Context::GetPackageFiles,
+            IOContext::ReadDir(pkgdir.to_path_buf()),
+        )?;
+        let rel = file.path().strip_prefix(pkgdir).unwrap_or(file.path());
+
+        let Some(name) = file.file_name().to_str() else {
+            warnings.push(warn(
+                PackageCheck::FileNames,
+                Some(rel),
+                "file name is not valid UTF-8",
+            ));
+            continue;
+        };
+        if !is_nfc(name) {
+            warnings.push(warn(
+                PackageCheck::FileNames,
+                Some(rel),
+                "file name is not NFC normalized",
+            ));
+        }
+
+        let parent = rel.parent().unwrap_or(Path::new("")).to_path_buf();
+        normalized
+            .entry((parent, name.nfc().collect()))
+            .or_default()
+            .push(rel.to_path_buf());
+    }
+
+    for files in normalized.into_values().filter(|f| f.len() > 1) {
+        let others = files[1..]
+            .iter()
+            .map(|f| f.display().to_string())
+            .collect::<Vec<_>>()
+            .join(", ");
+        warnings.push(warn(
+            PackageCheck::FileNames,
+            Some(&files[0]),
+            format!("file name is the same as {} once normalized", others),
+        ));
+    }
+
+    Ok(())
+}
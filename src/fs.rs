@@ -7,7 +7,7 @@ use std::{fs::metadata, path::Path};
 
 use nix::sys::stat::{utimensat, UtimensatFlags};
 use nix::sys::time::TimeSpec;
-use nix::NixPath;
+use tempfile::TempDir;
 use walkdir::WalkDir;
 
 use crate::error::{Context, IOContext, IOError, IOErrorExt, Result};
@@ -101,6 +101,39 @@ pub fn rm_all<P: AsRef<Path>>(path: P, context: Context) -> Result<()> {
     Ok(())
 }
 
+/// Like [`rm_all`] but first makes every directory writable by the current user, so trees
+/// extracted from archives with read only directories can be removed without root.
+pub fn rm_all_writable<P: AsRef<Path>>(path: P, context: Context) -> Result<()> {
+    let path = path.as_ref();
+    make_dirs_writable(path).context(context.clone(), IOContext::Chmod(path.into()))?;
+    rm_all(path, context)
+}
+
+fn make_dirs_writable(path: &Path) -> io::Result<()> {
+    let meta = std::fs::symlink_metadata(path)?;
+    if !meta.is_dir() {
+        return Ok(());
+    }
+    let mode = meta.permissions().mode();
+    if mode & 0o700 != 0o700 {
+        std::fs::set_permissions(path, PermissionsExt::from_mode(mode | 0o700))?;
+    }
+    for entry in std::fs::read_dir(path)? {
+        make_dirs_writable(&entry?.path())?;
+    }
+    Ok(())
+}
+
+/// Creates a private directory with a unique name in the system temporary directory. It is
+/// removed when the returned [`TempDir`] is dropped.
+pub fn temp_dir(prefix: &str, context: Context) -> Result<TempDir> {
+    let dir = tempfile::Builder::new()
+        .prefix(prefix)
+        .tempdir()
+        .context(context, IOContext::Mkdir(std::env::temp_dir()))?;
+    Ok(dir)
+}
+
 pub fn rm_file<P: AsRef<Path>>(path: P, context: Context) -> Result<()> {
     let path = path.as_ref();
     remove_file(path).context(context, IOContext::Remove(path.into()))?;
@@ -133,7 +166,7 @@ pub fn copy_dir<P1: AsRef<Path>, P2: AsRef<Path>>(
             .strip_prefix(src)
             .context(context.clone(), IOContext::ReadDir(src.to_path_buf()))?;
         let mut dest = dest.to_path_buf();
-        if !rel_path.is_empty() {
+        if !rel_path.as_os_str().is_empty() {
             dest = dest.join(rel_path);
         }
 
@@ -155,6 +188,12 @@ pub fn copy_dir<P1: AsRef<Path>, P2: AsRef<Path>>(
     Ok(())
 }
 
+pub fn read_to_string<P: AsRef<Path>>(path: P, context: Context) -> Result<String> {
+    let path = path.as_ref();
+    let contents = std::fs::read_to_string(path).context(context, IOContext::Read(path.into()))?;
+    Ok(contents)
+}
+
 pub fn write<P: AsRef<Path>, C: AsRef<[u8]>>(path: P, contents: C, context: Context) -> Result<()> {
     let path = path.as_ref();
     std::fs::write(path, contents).context(context, IOContext::Write(path.into()))?;
@@ -0,0 +1,141 @@
+use std::{env, fs::read_to_string, thread::available_parallelism};
+
+use crate::Makepkg;
+
+// Variables that can change the outcome of a build. Anything else is left out of the report
+// so secrets in the environment don't end up in packages.
+static REPORTED_VARS: &[&str] = &[
+    "LANG",
+    "LANGUAGE",
+    "TZ",
+    "PATH",
+    "CARCH",
+    "CHOST",
+    "CFLAGS",
+    "CXXFLAGS",
+    "CPPFLAGS",
+    "LDFLAGS",
+    "LTOFLAGS",
+    "RUSTFLAGS",
+    "MAKEFLAGS",
+    "DEBUG_CFLAGS",
+    "DEBUG_CXXFLAGS",
+    "DEBUG_RUSTFLAGS",
+    "SOURCE_DATE_EPOCH",
+];
+
+/// A snapshot of the machine a build ran on.
+///
+/// Taken at the start of each build and available from [`Makepkg::host_environment`].
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct HostEnvironment {
+    /// The kernel name and release, e.g. `Linux 6.9.3-arch1-1`.
+    pub kernel: Option<String>,
+    /// The architecture makepkg was compiled for.
+    pub machine: String,
+    /// The number of CPUs the build can use.
+    pub nproc: usize,
+    /// Total memory in bytes.
+    pub memory: Option<u64>,
+    /// The memory limit of the cgroup makepkg is running in, in bytes.
+    pub cgroup_memory_limit: Option<u64>,
+    /// The CPU quota of the cgroup makepkg is running in as `quota/period` in microseconds.
+    pub cgroup_cpu_limit: Option<String>,
+    /// The locale used for text handling, taken from `LC_ALL`, `LC_CTYPE` or `LANG`.
+    pub locale: Option<String>,
+    /// Environment variables that affect builds.
+    pub env: Vec<(String, String)>,
+}
+
+impl HostEnvironment {
+    /// Collects information about the running system.
+    pub fn collect() -> HostEnvironment {
+        let kernel = read_proc("/proc/sys/kernel/ostype")
+            .zip(read_proc("/proc/sys/kernel/osrelease"))
+            .map(|(os, release)| format!("{} {}", os, release));
+
+        let memory = read_proc("/proc/meminfo").and_then(|meminfo| {
+            meminfo
+                .lines()
+                .find_map(|l| l.strip_prefix("MemTotal:"))
+                .and_then(|kb| kb.trim().trim_end_matches("kB").trim().parse::<u64>().ok())
+                .map(|kb| kb * 1024)
+        });
+
+        let cgroup = cgroup_dir();
+        let cgroup_memory_limit = cgroup
+            .as_ref()
+            .and_then(|dir| read_proc(&format!("{}/memory.max", dir)))
+            .and_then(|max| max.parse().ok());
+        let cgroup_cpu_limit = cgroup
+            .as_ref()
+            .and_then(|dir| read_proc(&format!("{}/cpu.max", dir)))
+            .filter(|max| !max.starts_with("max"))
+            .map(|max| max.replace(' ', "/"));
+
+        let locale = ["LC_ALL", "LC_CTYPE", "LANG"]
+            .iter()
+            .find_map(|var| env::var(var).ok().filter(|v| !v.is_empty()));
+
+        let env = env::vars()
+            .filter(|(k, _)| REPORTED_VARS.contains(&k.as_str()) || k.starts_with("LC_"))
+            .collect();
+
+        HostEnvironment {
+            kernel,
+            machine: env::consts::ARCH.to_string(),
+            nproc: available_parallelism().map(|n| n.get()).unwrap_or(1),
+            memory,
+            cgroup_memory_limit,
+            cgroup_cpu_limit,
+            locale,
+            env,
+        }
+    }
+
+    /// The report as `key=value` pairs as written to .BUILDINFO.
+    pub fn entries(&self) -> Vec<String> {
+        let mut entries = Vec::new();
+
+        if let Some(kernel) = &self.kernel {
+            entries.push(format!("kernel={}", kernel));
+        }
+        entries.push(format!("machine={}", self.machine));
+        entries.push(format!("nproc={}", self.nproc));
+        if let Some(memory) = self.memory {
+            entries.push(format!("memory={}", memory));
+        }
+        if let Some(limit) = self.cgroup_memory_limit {
+            entries.push(format!("cgroup_memory_limit={}", limit));
+        }
+        if let Some(limit) = &self.cgroup_cpu_limit {
+            entries.push(format!("cgroup_cpu_limit={}", limit));
+        }
+        if let Some(locale) = &self.locale {
+            entries.push(format!("locale={}", locale));
+        }
+        for (k, v) in &self.env {
+            entries.push(format!("env:{}={}", k, v));
+        }
+
+        entries
+    }
+}
+
+impl Makepkg {
+    /// The host environment recorded at the start of the last build.
+    pub fn host_environment(&self) -> Option<HostEnvironment> {
+        self.host.borrow().clone()
+    }
+}
+
+fn read_proc(path: &str) -> Option<String> {
+    read_to_string(path).ok().map(|s| s.trim().to_string())
+}
+
+// only the unified cgroup v2 hierarchy is supported
+fn cgroup_dir() -> Option<String> {
+    let cgroup = read_proc("/proc/self/cgroup")?;
+    let path = cgroup.lines().find_map(|l| l.strip_prefix("0::"))?;
+    Some(format!("/sys/fs/cgroup{}", path))
+}
@@ -7,8 +7,10 @@ use digest::Digest;
 
 use crate::{
     config::PkgbuildDirs,
-    error::{CommandErrorExt, CommandOutputExt, Context, DownloadError, IntegError, Result},
-    integ::finalize,
+    error::{CommandErrorExt, CommandOutputExt, Context, DownloadError, Result},
+    fs::{read_to_string, temp_dir, write},
+    integ::{finalize, pgp::Verifier},
+    options::Options,
     pkgbuild::{Fragment, Pkgbuild, Source},
     run::CommandOutput,
     sources::VCSKind,
@@ -57,9 +59,10 @@ impl Makepkg {
     pub(crate) fn verify_git_sig(
         &self,
         dirs: &PkgbuildDirs,
+        options: &Options,
         pkgbuild: &Pkgbuild,
         source: &Source,
-        gpg: &mut gpgme::Context,
+        gpg: &mut Verifier,
     ) -> Result<bool> {
         let path = dirs.download_path(source);
         let fragval = match &source.fragment {
@@ -76,6 +79,10 @@ impl Makepkg {
             .process_output()
             .read(&command, Context::IntegrityCheck)?;
 
+        if object.contains("-----BEGIN SSH SIGNATURE-----") {
+            return self.verify_git_ssh_sig(dirs, pkgbuild, source, fragval);
+        }
+
         if !object.contains("-----BEGIN PGP SIGNATURE-----") {
             self.event(Event::SignatureCheckFailed(SigFailed::new(
                 source.file_name(),
@@ -105,9 +112,93 @@ impl Makepkg {
             .join("\n");
         object.push('\n');
 
-        let res = gpg
-            .verify_detached(sig, object)
-            .map_err(IntegError::Gpgme)?;
-        self.process_sig(source, pkgbuild, &res)
+        self.verify_detached(options, gpg, pkgbuild, source, || {
+            Ok((sig.clone().into(), object.clone().into()))
+        })
     }
+
+    fn verify_git_ssh_sig(
+        &self,
+        dirs: &PkgbuildDirs,
+        pkgbuild: &Pkgbuild,
+        source: &Source,
+        fragval: &str,
+    ) -> Result<bool> {
+        let file = source.file_name();
+        let path = dirs.download_path(source);
+        self.event(Event::VerifyingSignature(file))?;
+
+        let signers = self.ssh_allowed_signers(pkgbuild)?;
+        if signers.is_empty() {
+            self.event(SigFailed::new(file, "none", SigFailedKind::UnknownPublicKey).into())?;
+            return Ok(false);
+        }
+
+        let tmp = temp_dir("makepkg-ssh-", Context::IntegrityCheck)?;
+        let signers_path = tmp.path().join("allowed_signers");
+        write(&signers_path, signers, Context::IntegrityCheck)?;
+
+        let verify = if matches!(source.fragment, Some(Fragment::Tag(_))) {
+            "verify-tag"
+        } else {
+            "verify-commit"
+        };
+
+        let mut command = Command::new("git");
+        command
+            .arg("-c")
+            .arg(format!(
+                "gpg.ssh.allowedSignersFile={}",
+                signers_path.display()
+            ))
+            .arg(verify)
+            .arg(fragval)
+            .current_dir(&path);
+        let output = command
+            .process_output()
+            .cmd_context(&command, Context::IntegrityCheck)?;
+
+        // git reports the result of ssh-keygen -Y verify on stderr
+        let stderr = String::from_utf8_lossy(&output.stderr);
+        let fingerprint = stderr
+            .split_whitespace()
+            .find(|w| w.starts_with("SHA256:"))
+            .unwrap_or("none");
+
+        if output.status.success() {
+            self.event(Event::SignatureCheckPass(file))?;
+            return Ok(true);
+        }
+
+        let kind = if stderr.contains("No principal matched") {
+            SigFailedKind::NotInValidPgpKeys
+        } else {
+            SigFailedKind::Other(stderr.lines().last().unwrap_or("bad signature"))
+        };
+        self.event(SigFailed::new(file, fingerprint, kind).into())?;
+        Ok(false)
+    }
+
+    fn ssh_allowed_signers(&self, pkgbuild: &Pkgbuild) -> Result<String> {
+        let mut signers = String::new();
+
+        if let Some(path) = &self.config.ssh_allowed_signers {
+            signers.push_str(&read_to_string(path, Context::IntegrityCheck)?);
+            if !signers.is_empty() && !signers.ends_with('\n') {
+                signers.push('\n');
+            }
+        }
+
+        for key in pkgbuild.validpgpkeys.iter().filter(|k| is_ssh_key(k)) {
+            signers.push_str(&format!("* namespaces=\"git\" {}\n", key));
+        }
+
+        Ok(signers)
+    }
+}
+
+fn is_ssh_key(key: &str) -> bool {
+    ["ssh-", "ecdsa-sha2-", "sk-ssh-", "sk-ecdsa-sha2-"]
+        .iter()
+        .any(|prefix| key.starts_with(prefix))
 }
@@ -0,0 +1,87 @@
+use crate::{
+    error::{CommandError, Context, Result},
+    pkgbuild::Pkgbuild,
+    CommandKind, Makepkg,
+};
+
+/// A key listed in a PKGBUILD's validpgpkeys.
+#[derive(Debug, Clone, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct PgpKey {
+    pub fingerprint: String,
+    /// Whether the key is in the local keyring.
+    pub present: bool,
+}
+
+/// The outcome of [`Makepkg::import_key`].
+#[derive(Debug, Copy, Clone, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub enum KeyImport {
+    /// The key was received from the keyserver.
+    Imported,
+    /// The key was already in the local keyring so nothing was done.
+    AlreadyPresent,
+    /// The keyserver did not return the key.
+    Failed,
+}
+
+impl Makepkg {
+    /// Lists the keys a PKGBUILD requires to verify its sources and whether each is
+    /// present in the local keyring.
+    ///
+    /// SSH keys in validpgpkeys are not looked up in the keyring and are not returned.
+    pub fn required_keys(&self, pkgbuild: &Pkgbuild) -> Result<Vec<PgpKey>> {
+        let mut keys = Vec::new();
+
+        for key in &pkgbuild.validpgpkeys {
+            if key.contains(char::is_whitespace) {
+                continue;
+            }
+
+            keys.push(PgpKey {
+                fingerprint: key.clone(),
+                present: self.has_key(key)?,
+            });
+        }
+
+        Ok(keys)
+    }
+
+    /// Lists the keys from validpgpkeys that are not in the local keyring.
+    pub fn missing_keys(&self, pkgbuild: &Pkgbuild) -> Result<Vec<String>> {
+        let keys = self.required_keys(pkgbuild)?;
+        Ok(keys
+            .into_iter()
+            .filter(|k| !k.present)
+            .map(|k| k.fingerprint)
+            .collect())
+    }
+
+    /// Checks whether a key is in the local keyring.
+    pub fn has_key(&self, key: &str) -> Result<bool> {
+        let mut command = self.gpg_command();
+        command
+            .arg("--batch")
+            .arg("--list-keys")
+            .arg("--with-colons")
+            .arg("--")
+            .arg(key);
+
+        // gpg exits non zero when the key is not found
+        match command.output() {
+            Ok(output) => Ok(output.status.success()),
+            Err(e) => Err(CommandError::exec(e, &command, Context::IntegrityCheck).into()),
+        }
+    }
+
+    /// Receives a key from the keyserver unless it is already in the local keyring.
+    pub fn import_key(&self, pkgbuild: &Pkgbuild, key: &str) -> Result<KeyImport> {
+        if self.has_key(key)? {
+            return Ok(KeyImport::AlreadyPresent);
+        }
+
+        if self.recv_key(CommandKind::ManageKeys(pkgbuild), key)? {
+            Ok(KeyImport::Imported)
+        } else {
+            Ok(KeyImport::Failed)
+        }
+    }
+}
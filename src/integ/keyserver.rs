@@ -0,0 +1,124 @@
+use crate::{
+    callback::{Answer, Event, LogLevel, LogMessage, Question},
+    error::{CommandError, CommandErrorExt, CommandErrorKind, Context, Result},
+    integ::{
+        key_matches,
+        pgp::{SigStatus, Signature, Signed, Verifier},
+    },
+    options::Options,
+    pkgbuild::{Pkgbuild, Source},
+    run::CommandOutput,
+    CommandKind, Makepkg,
+};
+
+impl Makepkg {
+    pub(crate) fn verify_detached<F>(
+        &self,
+        options: &Options,
+        gpg: &mut Verifier,
+        pkgbuild: &Pkgbuild,
+        source: &Source,
+        mut open: F,
+    ) -> Result<bool>
+    where
+        F: FnMut() -> Result<(Signed, Signed)>,
+    {
+        let file = source.file_name();
+        let (sig, data) = open()?;
+        let mut sigs = gpg.verify_detached(file, sig, data)?;
+
+        if self.recv_missing_keys(options, pkgbuild, source, &sigs)? {
+            let (sig, data) = open()?;
+            sigs = gpg.verify_detached(file, sig, data)?;
+        }
+
+        // with no validpgpkeys the user has the final say on untrusted keys
+        if pkgbuild.validpgpkeys.is_empty() {
+            for sig in &mut sigs {
+                if sig.status != SigStatus::Good(false) {
+                    continue;
+                }
+                let question = Question::TrustKey {
+                    file_name: file,
+                    fingerprint: &sig.fingerprint,
+                };
+                if self.question(question)? == Answer::Yes {
+                    sig.status = SigStatus::Good(true);
+                }
+            }
+        }
+
+        self.process_sig(source, pkgbuild, &sigs)
+    }
+
+    fn recv_missing_keys(
+        &self,
+        options: &Options,
+        pkgbuild: &Pkgbuild,
+        source: &Source,
+        sigs: &[Signature],
+    ) -> Result<bool> {
+        let mut received = false;
+
+        for sig in sigs {
+            if sig.status != SigStatus::KeyMissing {
+                continue;
+            }
+
+            // only ever fetch keys the PKGBUILD would accept, without validpgpkeys
+            // the unknown key is reported instead
+            let key = match pkgbuild
+                .validpgpkeys
+                .iter()
+                .find(|k| key_matches(k, &sig.fingerprint))
+            {
+                Some(key) => key.as_str(),
+                None => continue,
+            };
+
+            if !options.recv_keys {
+                let question = Question::ImportKey {
+                    file_name: source.file_name(),
+                    fingerprint: key,
+                };
+                if self.question(question)? != Answer::Yes {
+                    continue;
+                }
+            }
+
+            received |= self.recv_key(CommandKind::Integ(pkgbuild, source), key)?;
+        }
+
+        Ok(received)
+    }
+
+    pub(crate) fn recv_key(&self, kind: CommandKind, key: &str) -> Result<bool> {
+        self.event(Event::ReceivingKey(key))?;
+
+        let mut command = self.gpg_command();
+        command.arg("--batch");
+        if let Some(keyserver) = &self.config.keyserver {
+            command.arg("--keyserver").arg(keyserver);
+        }
+        command.arg("--recv-keys").arg(key);
+
+        let status = command
+            .process_spawn(self, kind)
+            .cmd_context(&command, Context::IntegrityCheck);
+
+        match status {
+            Ok(_) => {
+                self.event(Event::ReceivedKey(key))?;
+                Ok(true)
+            }
+            Err(CommandError {
+                kind: CommandErrorKind::ExitCode(_),
+                ..
+            }) => {
+                self.log(LogLevel::Warning, LogMessage::FailedToReceiveKey(key))?;
+                Ok(false)
+            }
+            Err(e) => Err(e.into()),
+        }
+    }
+}
@@ -0,0 +1,147 @@
+use std::collections::{HashMap, HashSet};
+
+use crate::{
+    callback::{Event, LogLevel, LogMessage},
+    config::PkgbuildDirs,
+    error::{Context, IntegError, Result},
+    fs::read_to_string,
+    pkgbuild::{ChecksumKind, Pkgbuild, Source},
+    Makepkg,
+};
+
+/// Gets the kind of checksums in an upstream checksum manifest such as `SHA256SUMS` from its
+/// file name.
+pub(crate) fn manifest_kind(file_name: &str) -> Option<ChecksumKind> {
+    let name = file_name.strip_suffix(".txt").unwrap_or(file_name);
+    let kind = match name.to_ascii_uppercase().as_str() {
+        "MD5SUMS" => ChecksumKind::Md5,
+        "SHA1SUMS" => ChecksumKind::Sha1,
+        "SHA224SUMS" => ChecksumKind::Sha224,
+        "SHA256SUMS" => ChecksumKind::Sha256,
+        "SHA384SUMS" => ChecksumKind::Sha384,
+        "SHA512SUMS" => ChecksumKind::Sha512,
+        "B2SUMS" => ChecksumKind::Blake2,
+        _ => return None,
+    };
+    Some(kind)
+}
+
+/// Parses both the GNU (`<sum>  <file>`) and BSD (`SHA256 (<file>) = <sum>`) manifest formats.
+///
+/// The returned map is keyed by file name with any leading directories removed.
+fn parse_manifest(manifest: &str) -> HashMap<&str, String> {
+    let mut sums = HashMap::new();
+
+    for line in manifest.lines().map(|l| l.trim()) {
+        let entry = if let Some((file, sum)) = line
+            .split_once(" (")
+            .and_then(|(_, rest)| rest.rsplit_once(") = "))
+        {
+            Some((file, sum))
+        } else {
+            line.split_once(char::is_whitespace)
+                .map(|(sum, file)| (file.trim_start().trim_start_matches('*'), sum))
+        };
+
+        if let Some((file, sum)) = entry {
+            let file = file.rsplit('/').next().unwrap_or(file);
+            sums.insert(file, sum.to_ascii_lowercase());
+        }
+    }
+
+    sums
+}
+
+impl Makepkg {
+    /// Verifies sources against any upstream checksum manifests listed in the source array.
+    ///
+    /// This should be run after signatures are checked so a signed manifest is only trusted
+    /// once its own signature has been verified.
+    pub(crate) fn check_manifests(
+        &self,
+        dirs: &PkgbuildDirs,
+        pkgbuild: &Pkgbuild,
+        all: bool,
+    ) -> Result<()> {
+        let sources = if all {
+            pkgbuild.source.all().collect::<Vec<_>>()
+        } else {
+            pkgbuild
+                .source
+                .enabled(&self.config.arch)
+                .collect::<Vec<_>>()
+        };
+        let mut ok = true;
+        let mut covered = HashSet::new();
+        let mut manifests = false;
+
+        for manifest in &sources {
+            if let Some(kind) = manifest_kind(manifest.file_name()) {
+                manifests = true;
+                ok &=
+                    self.check_manifest(dirs, pkgbuild, &sources, manifest, kind, &mut covered)?;
+            }
+        }
+
+        // local files can't be in an upstream manifest so only remote files are reported
+        if manifests {
+            for source in &sources {
+                let name = source.file_name();
+                let sig = name.ends_with(".sig") || name.ends_with(".asc");
+                if source.vcs_kind().is_none()
+                    && source.is_remote()
+                    && manifest_kind(name).is_none()
+                    && !sig
+                    && !covered.contains(name)
+                {
+                    self.log(LogLevel::Warning, LogMessage::NotInManifest(name))?;
+                }
+            }
+        }
+
+        if !ok {
+            return Err(IntegError::ValidityCheck.into());
+        }
+
+        Ok(())
+    }
+
+    fn check_manifest<'a>(
+        &self,
+        dirs: &PkgbuildDirs,
+        pkgbuild: &Pkgbuild,
+        sources: &[&'a Source],
+        manifest: &Source,
+        kind: ChecksumKind,
+        covered: &mut HashSet<&'a str>,
+    ) -> Result<bool> {
+        let mut ok = true;
+        self.event(Event::VerifyingManifest(manifest.file_name()))?;
+
+        let contents = read_to_string(dirs.download_path(manifest), Context::IntegrityCheck)?;
+        let sums = parse_manifest(&contents);
+
+        for source in sources {
+            if source.vcs_kind().is_some() || source == &manifest {
+                continue;
+            }
+            let Some(sum) = sums.get(source.file_name()) else {
+                continue;
+            };
+            covered.insert(source.file_name());
+
+            let mut failed = Vec::new();
+            self.event(Event::VerifyingChecksum(source.file_name()))?;
+            kind.verity_file_checksum(self, dirs, source, pkgbuild, sum, &mut failed)?;
+
+            if failed.is_empty() {
+                self.event(Event::ChecksumPass(source.file_name()))?;
+            } else {
+                self.event(Event::ChecksumFailed(source.file_name(), &failed))?;
+                ok = false;
+            }
+        }
+
+        Ok(ok)
+    }
+}
@@ -1,10 +1,11 @@
+use std::fmt::Display;
 use std::fs::File;
 use std::io::{ErrorKind, Read, Write};
 use std::path::Path;
+use std::time::{Duration, Instant};
 
 use blake2::Blake2b512;
 use digest::Digest;
-use gpgme::{Protocol, SignatureSummary, Validity};
 use md5::Md5;
 use sha1::Sha1;
 use sha2::{Sha224, Sha256, Sha384, Sha512};
@@ -12,18 +13,27 @@ use sha2::{Sha224, Sha256, Sha384, Sha512};
 use crate::callback::{Event, LogLevel, LogMessage, SigFailed, SigFailedKind};
 use crate::config::PkgbuildDirs;
 use crate::error::{
-    CommandError, CommandErrorKind, Context, Error, IOContext, IOErrorExt, IntegError, Result,
+    CommandError, CommandErrorKind, Context, DownloadError, Error, IOContext, IOErrorExt,
+    IntegError, Result,
 };
 use crate::fs::open;
 use crate::options::Options;
 use crate::pkgbuild::{ArchVec, ArchVecs, ChecksumKind, Function, Pkgbuild, Source};
+use crate::pkgbuild_edit::format_array;
 use crate::Makepkg;
 
 mod bzr;
 mod git;
+mod keys;
+mod keyserver;
+mod manifest;
 mod mercurial;
+mod pgp;
 mod vcs;
 
+pub use self::keys::*;
+pub(crate) use self::pgp::{SigStatus, Signature, Verifier};
+
 impl Makepkg {
     pub fn check_integ(&self, options: &Options, pkgbuild: &Pkgbuild, all: bool) -> Result<()> {
         if options.no_signatures && options.no_checksums {
@@ -38,13 +48,15 @@ impl Makepkg {
                 LogLevel::Warning,
                 LogMessage::SkippingChecksumIntegrityChecks,
             )?;
-            self.check_signatures(pkgbuild, all)?
+            self.check_signatures(options, pkgbuild, all)?
         } else if options.no_signatures {
             self.log(LogLevel::Warning, LogMessage::SkippingPGPIntegrityChecks)?;
             self.check_checksums(&dirs, pkgbuild, all)?;
+            self.check_manifests(&dirs, pkgbuild, all)?;
         } else {
             self.check_checksums(&dirs, pkgbuild, all)?;
-            self.check_signatures(pkgbuild, all)?;
+            self.check_signatures(options, pkgbuild, all)?;
+            self.check_manifests(&dirs, pkgbuild, all)?;
         }
 
         if pkgbuild.has_function(Function::Verify) {
@@ -61,10 +73,14 @@ impl Makepkg {
         Ok(())
     }
 
-    pub fn check_signatures(&self, pkgbuild: &Pkgbuild, all: bool) -> Result<()> {
+    pub fn check_signatures(
+        &self,
+        options: &Options,
+        pkgbuild: &Pkgbuild,
+        all: bool,
+    ) -> Result<()> {
         self.event(Event::VerifyingSignatures)?;
-        let mut gpg =
-            gpgme::Context::from_protocol(Protocol::OpenPgp).map_err(IntegError::Gpgme)?;
+        let mut gpg = Verifier::new(&self.config)?;
         let mut ok = true;
         let dirs = self.pkgbuild_dirs(pkgbuild)?;
 
@@ -73,7 +89,7 @@ impl Makepkg {
                 continue;
             }
 
-            ok &= self.check_sigs_one_arch(&dirs, &mut gpg, pkgbuild, source)?;
+            ok &= self.check_sigs_one_arch(&dirs, options, &mut gpg, pkgbuild, source)?;
         }
 
         if !ok {
@@ -86,7 +102,8 @@ impl Makepkg {
     fn check_sigs_one_arch(
         &self,
         dirs: &PkgbuildDirs,
-        gpg: &mut gpgme::Context,
+        options: &Options,
+        gpg: &mut Verifier,
         pkgbuild: &Pkgbuild,
         sources: &ArchVec<Source>,
     ) -> Result<bool> {
@@ -94,7 +111,7 @@ impl Makepkg {
 
         for source in &sources.values {
             if let Some(proto) = source.vcs_kind() {
-                ok &= self.verify_vcs_sig(dirs, proto, pkgbuild, source, gpg)?;
+                ok &= self.verify_vcs_sig(dirs, options, proto, pkgbuild, source, gpg)?;
                 continue;
             }
 
@@ -115,67 +132,52 @@ impl Makepkg {
 
             let sig = dirs.download_path(source);
             let data = dirs.download_path(source_file);
-            let sig = open(File::options().read(true), sig, Context::IntegrityCheck)?;
-            let data = open(File::options().read(true), data, Context::IntegrityCheck)?;
 
-            let res = gpg.verify_detached(sig, data).map_err(IntegError::Gpgme)?;
-            ok &= self.process_sig(source_file, pkgbuild, &res)?;
+            ok &= self.verify_detached(options, gpg, pkgbuild, source_file, || {
+                let sig = open(File::options().read(true), &sig, Context::IntegrityCheck)?;
+                let data = open(File::options().read(true), &data, Context::IntegrityCheck)?;
+                Ok((sig.into(), data.into()))
+            })?;
         }
 
         Ok(ok)
     }
 
-    fn process_sig(
+    pub(crate) fn process_sig(
         &self,
         source: &Source,
         pkgbuild: &Pkgbuild,
-        res: &gpgme::VerificationResult,
+        sigs: &[Signature],
     ) -> Result<bool> {
         let mut ok = true;
 
         let file = source.file_name();
         self.event(Event::VerifyingSignature(file))?;
 
-        for sig in res.signatures() {
-            let fingerprint = sig
-                .fingerprint()
-                .map_err(|_| IntegError::ReadFingerprint(file.to_string()))?;
-            if let Err(err) = sig.status() {
-                ok = false;
-
-                if sig.summary().contains(SignatureSummary::KEY_MISSING) {
-                    self.event(
-                        SigFailed::new(file, fingerprint, SigFailedKind::UnknownPublicKey).into(),
-                    )?;
-                } else if sig.summary().contains(SignatureSummary::KEY_REVOKED) {
-                    self.event(SigFailed::new(file, fingerprint, SigFailedKind::Revoked).into())?;
-                } else if sig.summary().contains(SignatureSummary::KEY_REVOKED) {
-                    self.event(SigFailed::new(file, fingerprint, SigFailedKind::Expired).into())?;
-                } else {
-                    let d = err.to_string();
-                    self.event(SigFailed::new(file, fingerprint, SigFailedKind::Other(&d)).into())?;
+        for sig in sigs {
+            let fingerprint = sig.fingerprint.as_str();
+            let kind = match &sig.status {
+                SigStatus::KeyMissing => SigFailedKind::UnknownPublicKey,
+                SigStatus::Revoked => SigFailedKind::Revoked,
+                SigStatus::Expired => SigFailedKind::Expired,
+                SigStatus::Bad(e) => SigFailedKind::Other(e),
+                SigStatus::Good(_) if !pkgbuild.validpgpkeys.is_empty() => {
+                    if pkgbuild
+                        .validpgpkeys
+                        .iter()
+                        .any(|k| key_matches(k, fingerprint))
+                    {
+                        self.event(Event::SignatureCheckPass(file))?;
+                        continue;
+                    }
+                    SigFailedKind::NotInValidPgpKeys
                 }
-                continue;
-            }
+                SigStatus::Good(false) => SigFailedKind::NotTrusted,
+                SigStatus::Good(true) => continue,
+            };
 
-            if pkgbuild.validpgpkeys.is_empty() {
-                if !matches!(
-                    sig.validity(),
-                    Validity::Full | Validity::Marginal | Validity::Ultimate
-                ) {
-                    self.event(
-                        SigFailed::new(file, fingerprint, SigFailedKind::NotTrusted).into(),
-                    )?;
-                    ok = false;
-                }
-            } else if !pkgbuild.validpgpkeys.iter().any(|p| p == fingerprint) {
-                self.event(
-                    SigFailed::new(file, fingerprint, SigFailedKind::NotInValidPgpKeys).into(),
-                )?;
-                ok = false;
-            } else {
-                self.event(Event::SignatureCheckPass(file))?
-            }
+            self.event(SigFailed::new(file, fingerprint, kind).into())?;
+            ok = false;
         }
 
         Ok(ok)
@@ -246,120 +248,125 @@ impl Makepkg {
         }
     }
 
+    /// Generates the checksum arrays for the PKGBUILD and formats them ready to paste into
+    /// it. See [`Makepkg::generate_checksums`].
     pub fn geninteg(&self, options: &Options, p: &Pkgbuild) -> Result<String> {
-        use std::fmt::Write;
+        let sums = self.generate_checksums(options, p, &[])?;
+        let sums = sums.iter().map(|s| s.to_string()).collect::<Vec<_>>();
+        Ok(sums.join("\n"))
+    }
 
-        let mut arrays = Vec::new();
-        let mut output = String::new();
+    /// Generates a checksum array of each kind for every source array in the PKGBUILD.
+    ///
+    /// With no `kinds` the kinds the PKGBUILD already uses are generated, falling back to the
+    /// configured INTEGRITY_CHECK and then sha512. Sources are only downloaded if some are
+    /// missing from SRCDEST and never with [`Options::no_download`]. Entries the PKGBUILD
+    /// sets to `SKIP` stay skipped.
+    pub fn generate_checksums(
+        &self,
+        options: &Options,
+        p: &Pkgbuild,
+        kinds: &[ChecksumKind],
+    ) -> Result<Vec<GeneratedChecksums>> {
         let dirs = self.pkgbuild_dirs(p)?;
-
-        let mut enabled = p
-            .get_all_checksums()
-            .into_iter()
-            .filter(|(_, v)| !v.is_empty())
-            .map(|(k, _)| k)
-            .collect::<Vec<_>>();
-
-        if enabled.is_empty() {
-            enabled.extend(&self.config.integrity_check);
+        let mut kinds = kinds.to_vec();
+
+        if kinds.is_empty() {
+            kinds.extend(
+                p.get_all_checksums()
+                    .into_iter()
+                    .filter(|(_, v)| !v.is_empty())
+                    .map(|(k, _)| k),
+            );
         }
-        if enabled.is_empty() {
-            enabled.push(ChecksumKind::Sha512);
+        if kinds.is_empty() {
+            kinds.extend(&self.config.integrity_check);
+        }
+        if kinds.is_empty() {
+            kinds.push(ChecksumKind::Sha512);
         }
 
-        self.download_sources(options, p, true)?;
-        self.event(Event::GeneratingChecksums)?;
-
-        for sum in enabled {
-            let sums = p.get_checksums(sum);
-            match sum {
-                ChecksumKind::Md5 => self.gen_integ::<Md5>(&dirs, p, &mut arrays, sums, sum)?,
-                ChecksumKind::Sha1 => self.gen_integ::<Sha1>(&dirs, p, &mut arrays, sums, sum)?,
-                ChecksumKind::Sha224 => {
-                    self.gen_integ::<Sha224>(&dirs, p, &mut arrays, sums, sum)?
-                }
-                ChecksumKind::Sha256 => {
-                    self.gen_integ::<Sha256>(&dirs, p, &mut arrays, sums, sum)?
-                }
-                ChecksumKind::Sha384 => {
-                    self.gen_integ::<Sha384>(&dirs, p, &mut arrays, sums, sum)?
-                }
-                ChecksumKind::Sha512 => {
-                    self.gen_integ::<Sha512>(&dirs, p, &mut arrays, sums, sum)?
-                }
-                ChecksumKind::Blake2 => {
-                    self.gen_integ::<Blake2b512>(&dirs, p, &mut arrays, sums, sum)?
-                }
+        let missing = p.source.all().find(|s| !dirs.download_path(s).exists());
+        match missing {
+            Some(source) if options.no_download => {
+                return Err(DownloadError::SourceMissing(source.clone()).into())
             }
+            Some(_) => self.download_sources(options, p, true)?,
+            None => (),
         }
+        self.event(Event::GeneratingChecksums)?;
 
-        for (name, mut arr) in arrays {
-            let pad = name.len() + 2;
-            write!(output, "{}=(", name).unwrap();
-            if !arr.is_empty() {
-                write!(output, "'{}'", arr.remove(0)).unwrap();
-            }
-            for val in arr {
-                write!(output, "\n{:pad$}'{}'", "", val, pad = pad).unwrap();
+        let mut out = Vec::new();
+        for kind in kinds {
+            let existing = p.get_checksums(kind);
+            for arch in &p.source.values {
+                let skip = get_sum_array(existing, &arch.arch);
+                let mut sums = Vec::new();
+                for (n, source) in arch.values.iter().enumerate() {
+                    match skip.get(n) {
+                        Some(sum) if sum == "SKIP" => sums.push(sum.clone()),
+                        _ => sums.push(self.checksum_source(&dirs, p, kind, source)?),
+                    }
+                }
+                out.push(GeneratedChecksums {
+                    kind,
+                    arch: arch.arch.clone(),
+                    sums,
+                });
             }
-            writeln!(output, ")").unwrap();
         }
 
-        let _ = output.pop();
+        Ok(out)
+    }
 
-        Ok(output)
+    /// Generates checksums like [`Makepkg::generate_checksums`] and writes them to the
+    /// PKGBUILD in place of its checksum arrays. `pkgbuild` is reloaded afterwards.
+    pub fn update_checksums(
+        &self,
+        options: &Options,
+        pkgbuild: &mut Pkgbuild,
+        kinds: &[ChecksumKind],
+    ) -> Result<Vec<GeneratedChecksums>> {
+        let sums = self.generate_checksums(options, pkgbuild, kinds)?;
+        let mut editor = pkgbuild.editor()?;
+        for sum in &sums {
+            editor.set_array(sum.kind.name(), sum.arch.as_deref(), &sum.sums);
+        }
+        editor.save()?;
+        self.event(Event::ChecksumsUpdated(editor.path()))?;
+        *pkgbuild = self.load_pkgbuild(&pkgbuild.dir)?;
+        Ok(sums)
     }
 
-    fn gen_integ<D: Digest + Write>(
+    /// Hashes a downloaded source with the given kind of checksum.
+    pub(crate) fn checksum_source(
         &self,
         dirs: &PkgbuildDirs,
         pkgbuild: &Pkgbuild,
-        out: &mut Vec<(String, Vec<String>)>,
-        sums: &ArchVecs<String>,
         kind: ChecksumKind,
-    ) -> Result<()> {
-        for arch in &pkgbuild.source.values {
-            let default = ArchVec::default();
-
-            let sums = sums.get(arch.arch.as_deref()).unwrap_or(&default);
-            let array = self.gen_integ_arr::<D>(dirs, pkgbuild, &arch.values, &sums.values)?;
-            let name = match &arch.arch {
-                Some(a) => format!("{}_{}", kind, a),
-                None => format!("{}", kind),
-            };
-
-            out.push((name, array));
+        source: &Source,
+    ) -> Result<String> {
+        match kind {
+            ChecksumKind::Md5 => self.checksum_source_with::<Md5>(dirs, pkgbuild, source),
+            ChecksumKind::Sha1 => self.checksum_source_with::<Sha1>(dirs, pkgbuild, source),
+            ChecksumKind::Sha224 => self.checksum_source_with::<Sha224>(dirs, pkgbuild, source),
+            ChecksumKind::Sha256 => self.checksum_source_with::<Sha256>(dirs, pkgbuild, source),
+            ChecksumKind::Sha384 => self.checksum_source_with::<Sha384>(dirs, pkgbuild, source),
+            ChecksumKind::Sha512 => self.checksum_source_with::<Sha512>(dirs, pkgbuild, source),
+            ChecksumKind::Blake2 => self.checksum_source_with::<Blake2b512>(dirs, pkgbuild, source),
         }
-
-        Ok(())
     }
 
-    fn gen_integ_arr<D: Digest + Write>(
+    fn checksum_source_with<D: Digest + Write>(
         &self,
         dirs: &PkgbuildDirs,
         pkgbuild: &Pkgbuild,
-        sources: &[Source],
-        sums: &[String],
-    ) -> Result<Vec<String>> {
-        let mut out = Vec::new();
-
-        for (n, source) in sources.iter().enumerate() {
-            if let Some(v) = sums.get(n) {
-                if v == "SKIP" {
-                    out.push("SKIP".to_string());
-                    continue;
-                }
-            }
-            let path = dirs.download_path(source);
-
-            let hash = match source.vcs_kind() {
-                Some(vcs) => self.checksum_vcs::<D>(dirs, pkgbuild, vcs, source)?,
-                _ => hash_file::<D>(&path)?,
-            };
-            out.push(hash);
+        source: &Source,
+    ) -> Result<String> {
+        match source.vcs_kind() {
+            Some(vcs) => self.checksum_vcs::<D>(dirs, pkgbuild, vcs, source),
+            _ => self.hash_source::<D>(&dirs.download_path(source)),
         }
-
-        Ok(out)
     }
 
     pub(crate) fn verify_file_checksum<D: Digest + Write>(
@@ -379,7 +386,7 @@ impl Makepkg {
 
         let output = match source.vcs_kind() {
             Some(vcs) => self.checksum_vcs::<D>(dirs, p, vcs, source)?,
-            _ => hash_file::<D>(&path)?,
+            _ => self.hash_source::<D>(&path)?,
         };
 
         if output != *sum {
@@ -389,20 +396,89 @@ impl Makepkg {
     }
 }
 
-fn get_sum_array<'a>(sums: &'a ArchVecs<String>, arch: &Option<String>) -> &'a [String] {
+/// A checksum array generated by [`Makepkg::generate_checksums`].
+///
+/// Displays as the array ready to paste into a PKGBUILD.
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize))]
+pub struct GeneratedChecksums {
+    pub kind: ChecksumKind,
+    /// The architecture of the source array the sums are for.
+    pub arch: Option<String>,
+    /// The checksum of each source in order.
+    pub sums: Vec<String>,
+}
+
+impl GeneratedChecksums {
+    /// The name of the array such as `sha256sums_x86_64`.
+    pub fn name(&self) -> String {
+        match &self.arch {
+            Some(arch) => format!("{}_{}", self.kind, arch),
+            None => self.kind.to_string(),
+        }
+    }
+}
+
+impl Display for GeneratedChecksums {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str(&format_array(&self.name(), &self.sums))
+    }
+}
+
+pub(crate) fn get_sum_array<'a>(sums: &'a ArchVecs<String>, arch: &Option<String>) -> &'a [String] {
     sums.get(arch.as_deref())
         .map(|v| v.values.as_slice())
         .unwrap_or_default()
 }
 
+/// Sources at least this big report their progress while being hashed.
+const HASH_PROGRESS_THRESHOLD: u64 = 64 * 1024 * 1024;
+const HASH_PROGRESS_INTERVAL: Duration = Duration::from_millis(250);
+
+impl Makepkg {
+    // hash_file but with progress for large files
+    fn hash_source<D: Digest + Write>(&self, path: &Path) -> Result<String> {
+        let mut file = open(File::options().read(true), path, Context::IntegrityCheck)?;
+        let total = file.metadata().map(|m| m.len()).unwrap_or(0);
+        if total < HASH_PROGRESS_THRESHOLD {
+            return hash::<D, _>(path, &mut file);
+        }
+
+        let name = path
+            .file_name()
+            .unwrap_or(path.as_os_str())
+            .to_string_lossy();
+        let mut last = Instant::now();
+        let hash = hash_progress::<D, _, _>(path, &mut file, |hashed| {
+            if last.elapsed() >= HASH_PROGRESS_INTERVAL {
+                last = Instant::now();
+                self.hashing(&name, hashed, total)?;
+            }
+            Ok(())
+        })?;
+        self.hashing(&name, total, total)?;
+        Ok(hash)
+    }
+}
+
 pub(crate) fn hash_file<D: Digest + Write>(path: &Path) -> Result<String> {
     let mut file = open(File::options().read(true), path, Context::IntegrityCheck)?;
     hash::<D, _>(path, &mut file)
 }
 
 pub(crate) fn hash<D: Digest + Write, R: Read>(path: &Path, r: &mut R) -> Result<String> {
+    hash_progress::<D, _, _>(path, r, |_| Ok(()))
+}
+
+fn hash_progress<D, R, F>(path: &Path, r: &mut R, mut progress: F) -> Result<String>
+where
+    D: Digest + Write,
+    R: Read,
+    F: FnMut(u64) -> Result<()>,
+{
     let mut buffer = vec![0; 1024];
     let mut digest = D::new();
+    let mut hashed = 0;
 
     loop {
         let n = match r.read(&mut buffer) {
@@ -417,11 +493,45 @@ pub(crate) fn hash<D: Digest + Write, R: Read>(path: &Path, r: &mut R) -> Result
         };
 
         digest.update(&buffer[0..n]);
+        hashed += n as u64;
+        progress(hashed)?;
     }
 
     Ok(finalize(digest))
 }
 
 pub(crate) fn finalize<D: Digest>(digest: D) -> String {
-    hex::encode(&digest.finalize())
+    hex::encode(digest.finalize())
+}
+
+/// Checks if the key ID reported for a signature refers to a key from validpgpkeys.
+///
+/// The ID has to be the full fingerprint or a 16 digit long key ID, shorter IDs are too easy
+/// to collide with.
+pub(crate) fn key_matches(valid: &str, id: &str) -> bool {
+    if id.len() == valid.len() {
+        return !id.is_empty() && id.eq_ignore_ascii_case(valid);
+    }
+    id.len() == 16
+        && id.bytes().all(|b| b.is_ascii_hexdigit())
+        && valid.len() > 16
+        && valid.as_bytes()[valid.len() - 16..].eq_ignore_ascii_case(id.as_bytes())
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn key_ids() {
+        let valid = "F3691687D867B81B51CE07D9BBE43771487328A9";
+        assert!(key_matches(valid, valid));
+        assert!(key_matches(valid, &valid.to_ascii_lowercase()));
+        assert!(key_matches(valid, "BBE43771487328A9"));
+        assert!(!key_matches(valid, ""));
+        assert!(!key_matches(valid, "487328A9"));
+        assert!(!key_matches(valid, "A9"));
+        assert!(!key_matches(valid, "CBE43771487328A9"));
+        assert!(!key_matches("", ""));
+    }
 }
@@ -0,0 +1,489 @@
+use std::{
+    fs::File,
+    io,
+    path::{Path, PathBuf},
+    process::{Command, Stdio},
+};
+
+use crate::{
+    config::Config,
+    error::{CommandError, Context, IOContext, IOErrorExt, IntegError, Result},
+    fs::{open, temp_dir, write},
+    Makepkg,
+};
+
+/// Input to signature verification. Either the signature or the data it signs.
+#[derive(Debug)]
+pub(crate) enum Signed {
+    File(File),
+    Bytes(Vec<u8>),
+}
+
+impl From<File> for Signed {
+    fn from(value: File) -> Self {
+        Signed::File(value)
+    }
+}
+
+impl From<String> for Signed {
+    fn from(value: String) -> Self {
+        Signed::Bytes(value.into_bytes())
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub(crate) enum SigStatus {
+    /// The signature is good. Holds whether the key is trusted by the keyring.
+    Good(bool),
+    KeyMissing,
+    Revoked,
+    Expired,
+    Bad(String),
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub(crate) struct Signature {
+    pub fingerprint: String,
+    pub status: SigStatus,
+}
+
+impl Makepkg {
+    /// Creates a gpg command using the configured home directory, keyring and options.
+    pub(crate) fn gpg_command(&self) -> Command {
+        Gpg::new(&self.config).command()
+    }
+}
+
+/// The gpg settings from the [`Config`].
+#[derive(Debug, Clone)]
+struct Gpg {
+    home: Option<PathBuf>,
+    keyring: Option<PathBuf>,
+    options: Vec<String>,
+}
+
+impl Gpg {
+    fn new(config: &Config) -> Self {
+        Gpg {
+            home: config.gnupghome.clone(),
+            keyring: config.gpg_keyring.clone(),
+            options: config.gpg_options.clone(),
+        }
+    }
+
+    /// Whether the settings need the gpg binary, gpgme only supports setting the home directory.
+    fn needs_cli(&self) -> bool {
+        self.keyring.is_some() || !self.options.is_empty()
+    }
+
+    fn command(&self) -> Command {
+        let mut command = Command::new("gpg");
+        if let Some(home) = &self.home {
+            command.arg("--homedir").arg(home);
+        }
+        if let Some(keyring) = &self.keyring {
+            command
+                .arg("--no-default-keyring")
+                .arg("--keyring")
+                .arg(keyring);
+        }
+        command.args(&self.options);
+        command
+    }
+
+    fn verify_detached(&self, file: &str, sig: Signed, data: Signed) -> Result<Vec<Signature>> {
+        let tmp = temp_dir("makepkg-gpg-", Context::IntegrityCheck)?;
+        self.verify_in(tmp.path(), file, sig, data)
+    }
+
+    fn verify_in(
+        &self,
+        tmp: &Path,
+        file: &str,
+        sig: Signed,
+        data: Signed,
+    ) -> Result<Vec<Signature>> {
+        let sig_path = tmp.join("sig");
+        match sig {
+            Signed::File(mut sig) => {
+                let mut out = open(
+                    File::options().write(true).create(true).truncate(true),
+                    &sig_path,
+                    Context::IntegrityCheck,
+                )?;
+                io::copy(&mut sig, &mut out)
+                    .context(Context::IntegrityCheck, IOContext::Write(sig_path.clone()))?;
+            }
+            Signed::Bytes(bytes) => write(&sig_path, bytes, Context::IntegrityCheck)?,
+        }
+
+        let mut command = self.command();
+        command
+            .arg("--batch")
+            .arg("--status-fd")
+            .arg("1")
+            .arg("--verify")
+            .arg(&sig_path)
+            .stdout(Stdio::piped())
+            .stderr(Stdio::null());
+
+        match data {
+            Signed::File(file) => {
+                command.arg("-").stdin(file);
+            }
+            Signed::Bytes(bytes) => {
+                let data_path = tmp.join("data");
+                write(&data_path, bytes, Context::IntegrityCheck)?;
+                command.arg(&data_path).stdin(Stdio::null());
+            }
+        }
+
+        // gpg exits non zero for bad signatures, the status output says why
+        let output = command
+            .output()
+            .map_err(|e| CommandError::exec(e, &command, Context::IntegrityCheck))?;
+        let sigs = parse_status(&String::from_utf8_lossy(&output.stdout));
+        if sigs.is_empty() {
+            let err = CommandError::exit(&command, output.status.code(), Context::IntegrityCheck);
+            return Err(err.into());
+        }
+        if sigs.iter().any(|s| s.fingerprint.is_empty()) {
+            return Err(IntegError::ReadFingerprint(file.to_string()).into());
+        }
+        Ok(sigs)
+    }
+}
+
+/// Parses the output of `gpg --status-fd`.
+fn parse_status(status: &str) -> Vec<Signature> {
+    let mut sigs: Vec<Signature> = Vec::new();
+
+    for line in status.lines() {
+        let Some(line) = line.strip_prefix("[GNUPG:] ") else {
+            continue;
+        };
+        let mut words = line.split(' ');
+        let keyword = words.next().unwrap_or_default();
+        let args = words.collect::<Vec<_>>();
+
+        if keyword == "NEWSIG" {
+            sigs.push(Signature {
+                fingerprint: String::new(),
+                status: SigStatus::Bad("no signature status".to_string()),
+            });
+            continue;
+        }
+        let Some(sig) = sigs.last_mut() else {
+            continue;
+        };
+        let keyid = args.first().copied().unwrap_or_default();
+
+        match keyword {
+            "GOODSIG" => sig.status = SigStatus::Good(false),
+            "EXPKEYSIG" => sig.status = SigStatus::Expired,
+            "REVKEYSIG" => sig.status = SigStatus::Revoked,
+            "EXPSIG" => sig.status = SigStatus::Bad("signature has expired".to_string()),
+            "BADSIG" => sig.status = SigStatus::Bad("bad signature".to_string()),
+            "ERRSIG" => {
+                // ERRSIG <keyid> <pkalgo> <hashalgo> <class> <time> <rc> <fpr>
+                sig.status = match args.get(5) {
+                    Some(&"9") => SigStatus::KeyMissing,
+                    rc => SigStatus::Bad(format!("gpg error {}", rc.unwrap_or(&"?"))),
+                };
+                if let Some(fpr) = args.get(6).filter(|f| **f != "-") {
+                    sig.fingerprint = fpr.to_string();
+                }
+            }
+            // VALIDSIG <fpr> <date> <time> <expire> <ver> <reserved> <pkalgo> <hashalgo> <class> <primary fpr>
+            "VALIDSIG" => {
+                sig.fingerprint = args.get(9).unwrap_or(&keyid).to_string();
+                continue;
+            }
+            "TRUST_MARGINAL" | "TRUST_FULLY" | "TRUST_ULTIMATE" => {
+                if sig.status == SigStatus::Good(false) {
+                    sig.status = SigStatus::Good(true);
+                }
+                continue;
+            }
+            _ => continue,
+        }
+
+        if sig.fingerprint.is_empty() {
+            sig.fingerprint = keyid.to_string();
+        }
+    }
+
+    sigs
+}
+
+/// The OpenPGP implementation used to verify signatures.
+///
+/// gpgme is used by default, or sequoia when built with the `sequoia` feature and without the
+/// `gpgme` feature. Both only honour [`Config::gnupghome`] so when a keyring or extra options are
+/// configured signatures are verified by running gpg instead. When built with neither feature
+/// only the latter is available and other signature verification fails with
+/// [`IntegError::NoPgpBackend`].
+#[derive(Debug)]
+pub(crate) struct Verifier {
+    backend: Backend,
+}
+
+#[derive(Debug)]
+enum Backend {
+    Gpg(Gpg),
+    #[cfg(feature = "gpgme")]
+    Gpgme(gpgme::Context),
+    #[cfg(all(feature = "sequoia", not(feature = "gpgme")))]
+    Sequoia(Vec<sequoia_openpgp::Cert>),
+}
+
+impl Verifier {
+    pub(crate) fn new(config: &Config) -> Result<Self> {
+        let gpg = Gpg::new(config);
+        let backend = if gpg.needs_cli() {
+            Backend::Gpg(gpg)
+        } else {
+            Backend::new(config, gpg)?
+        };
+        Ok(Verifier { backend })
+    }
+
+    pub(crate) fn verify_detached(
+        &mut self,
+        file: &str,
+        sig: Signed,
+        data: Signed,
+    ) -> Result<Vec<Signature>> {
+        match &mut self.backend {
+            Backend::Gpg(gpg) => gpg.verify_detached(file, sig, data),
+            #[cfg(feature = "gpgme")]
+            Backend::Gpgme(ctx) => gpgme_verify_detached(ctx, file, sig, data),
+            #[cfg(all(feature = "sequoia", not(feature = "gpgme")))]
+            Backend::Sequoia(certs) => sequoia_verify_detached(certs, sig, data),
+        }
+    }
+}
+
+#[cfg(feature = "gpgme")]
+impl Backend {
+    fn new(config: &Config, _gpg: Gpg) -> Result<Self> {
+        let mut ctx =
+            gpgme::Context::from_protocol(gpgme::Protocol::OpenPgp).map_err(IntegError::Gpgme)?;
+        if let Some(home) = &config.gnupghome {
+            ctx.set_engine_home_dir(home.to_string_lossy().as_bytes())
+                .map_err(IntegError::Gpgme)?;
+        }
+        Ok(Backend::Gpgme(ctx))
+    }
+}
+
+#[cfg(all(feature = "sequoia", not(feature = "gpgme")))]
+impl Backend {
+    fn new(_config: &Config, gpg: Gpg) -> Result<Self> {
+        Ok(Backend::Sequoia(gpg.export_certs()?))
+    }
+}
+
+#[cfg(not(any(feature = "gpgme", feature = "sequoia")))]
+impl Backend {
+    fn new(_config: &Config, _gpg: Gpg) -> Result<Self> {
+        Err(IntegError::NoPgpBackend.into())
+    }
+}
+
+#[cfg(feature = "gpgme")]
+impl Signed {
+    fn into_data(self) -> gpgme::Result<gpgme::Data<'static>> {
+        use gpgme::IntoData;
+
+        match self {
+            Signed::File(file) => file.into_data(),
+            Signed::Bytes(bytes) => bytes.into_data(),
+        }
+    }
+}
+
+#[cfg(feature = "gpgme")]
+fn gpgme_verify_detached(
+    ctx: &mut gpgme::Context,
+    file: &str,
+    sig: Signed,
+    data: Signed,
+) -> Result<Vec<Signature>> {
+    use gpgme::{SignatureSummary, Validity};
+
+    let sig = sig.into_data().map_err(IntegError::Gpgme)?;
+    let data = data.into_data().map_err(IntegError::Gpgme)?;
+    let res = ctx.verify_detached(sig, data).map_err(IntegError::Gpgme)?;
+
+    let mut sigs = Vec::new();
+    for sig in res.signatures() {
+        let fingerprint = sig
+            .fingerprint()
+            .map_err(|_| IntegError::ReadFingerprint(file.to_string()))?
+            .to_string();
+        let summary = sig.summary();
+
+        let status = match sig.status() {
+            Ok(()) => SigStatus::Good(matches!(
+                sig.validity(),
+                Validity::Full | Validity::Marginal | Validity::Ultimate
+            )),
+            Err(_) if summary.contains(SignatureSummary::KEY_MISSING) => SigStatus::KeyMissing,
+            Err(_) if summary.contains(SignatureSummary::KEY_REVOKED) => SigStatus::Revoked,
+            Err(_) if summary.contains(SignatureSummary::KEY_EXPIRED) => SigStatus::Expired,
+            Err(e) => SigStatus::Bad(e.to_string()),
+        };
+
+        sigs.push(Signature {
+            fingerprint,
+            status,
+        });
+    }
+
+    Ok(sigs)
+}
+
+#[cfg(all(feature = "sequoia", not(feature = "gpgme")))]
+impl Gpg {
+    /// Reads the public keys from the keyring using `gpg --export`.
+    fn export_certs(&self) -> Result<Vec<sequoia_openpgp::Cert>> {
+        use crate::error::CommandErrorExt;
+        use sequoia_openpgp::{cert::CertParser, parse::Parse};
+
+        let mut command = self.command();
+        command.arg("--batch").arg("--export");
+        let output = command
+            .output()
+            .cmd_context(&command, Context::IntegrityCheck)?;
+
+        if output.stdout.is_empty() {
+            return Ok(Vec::new());
+        }
+        let certs = CertParser::from_bytes(&output.stdout)
+            .and_then(|certs| certs.collect())
+            .map_err(IntegError::Sequoia)?;
+        Ok(certs)
+    }
+}
+
+/// Verifies a signature using the certificates from the keyring.
+///
+/// sequoia does not implement gpg's web of trust, so good signatures are never considered
+/// trusted and must be allowed by `validpgpkeys` or by the user.
+#[cfg(all(feature = "sequoia", not(feature = "gpgme")))]
+fn sequoia_verify_detached(
+    certs: &[sequoia_openpgp::Cert],
+    sig: Signed,
+    data: Signed,
+) -> Result<Vec<Signature>> {
+    use sequoia_openpgp::{
+        parse::{stream::DetachedVerifierBuilder, Parse},
+        policy::StandardPolicy,
+    };
+
+    let policy = StandardPolicy::new();
+    let helper = SequoiaHelper {
+        certs,
+        sigs: Vec::new(),
+    };
+
+    let builder = match &sig {
+        Signed::File(file) => DetachedVerifierBuilder::from_reader(file),
+        Signed::Bytes(bytes) => DetachedVerifierBuilder::from_bytes(bytes),
+    };
+    let mut verifier = builder
+        .and_then(|b| b.with_policy(&policy, None, helper))
+        .map_err(IntegError::Sequoia)?;
+
+    match data {
+        Signed::File(file) => verifier.verify_reader(file),
+        Signed::Bytes(bytes) => verifier.verify_bytes(bytes),
+    }
+    .map_err(IntegError::Sequoia)?;
+
+    Ok(verifier.into_helper().sigs)
+}
+
+#[cfg(all(feature = "sequoia", not(feature = "gpgme")))]
+struct SequoiaHelper<'a> {
+    certs: &'a [sequoia_openpgp::Cert],
+    sigs: Vec<Signature>,
+}
+
+#[cfg(all(feature = "sequoia", not(feature = "gpgme")))]
+impl sequoia_openpgp::parse::stream::VerificationHelper for SequoiaHelper<'_> {
+    fn get_certs(
+        &mut self,
+        _ids: &[sequoia_openpgp::KeyHandle],
+    ) -> sequoia_openpgp::Result<Vec<sequoia_openpgp::Cert>> {
+        Ok(self.certs.to_vec())
+    }
+
+    fn check(
+        &mut self,
+        structure: sequoia_openpgp::parse::stream::MessageStructure,
+    ) -> sequoia_openpgp::Result<()> {
+        use sequoia_openpgp::{
+            cert::amalgamation::ValidAmalgamation,
+            parse::stream::{MessageLayer, VerificationError},
+            types::RevocationStatus,
+        };
+
+        for layer in structure {
+            let MessageLayer::SignatureGroup { results } = layer else {
+                continue;
+            };
+
+            for res in results {
+                let (fingerprint, status) = match res {
+                    Ok(good) => (good.ka.cert().fingerprint(), SigStatus::Good(false)),
+                    Err(VerificationError::MissingKey { sig }) => {
+                        let issuer = sig.get_issuers().into_iter().next();
+                        let fingerprint = issuer.map(|i| i.to_hex()).unwrap_or_default();
+                        self.sigs.push(Signature {
+                            fingerprint,
+                            status: SigStatus::KeyMissing,
+                        });
+                        continue;
+                    }
+                    Err(VerificationError::BadKey { ka, error, .. }) => {
+                        let revoked = |s| matches!(s, RevocationStatus::Revoked(_));
+                        let status = if revoked(ka.revocation_status())
+                            || revoked(ka.cert().revocation_status())
+                        {
+                            SigStatus::Revoked
+                        } else if ka.alive().is_err() || ka.cert().alive().is_err() {
+                            SigStatus::Expired
+                        } else {
+                            SigStatus::Bad(error.to_string())
+                        };
+                        (ka.cert().fingerprint(), status)
+                    }
+                    Err(VerificationError::BadSignature { ka, error, .. }) => {
+                        (ka.cert().fingerprint(), SigStatus::Bad(error.to_string()))
+                    }
+                    Err(VerificationError::UnboundKey { cert, error, .. }) => {
+                        (cert.fingerprint(), SigStatus::Bad(error.to_string()))
+                    }
+                    Err(VerificationError::MalformedSignature { sig, error }) => {
+                        let issuer = sig.get_issuers().into_iter().next();
+                        let fingerprint = issuer.map(|i| i.to_hex()).unwrap_or_default();
+                        self.sigs.push(Signature {
+                            fingerprint,
+                            status: SigStatus::Bad(error.to_string()),
+                        });
+                        continue;
+                    }
+                };
+
+                self.sigs.push(Signature {
+                    fingerprint: fingerprint.to_hex(),
+                    status,
+                });
+            }
+        }
+
+        Ok(())
+    }
+}
@@ -5,6 +5,8 @@ use digest::Digest;
 use crate::{
     config::PkgbuildDirs,
     error::{IntegError, Result},
+    integ::pgp::Verifier,
+    options::Options,
     pkgbuild::{Pkgbuild, Source},
     sources::VCSKind,
     Makepkg,
@@ -14,17 +16,18 @@ impl Makepkg {
     pub(crate) fn verify_vcs_sig(
         &self,
         dirs: &PkgbuildDirs,
+        options: &Options,
         vcs: VCSKind,
         pkgbuild: &Pkgbuild,
         source: &Source,
-        gpg: &mut gpgme::Context,
+        gpg: &mut Verifier,
     ) -> Result<bool> {
         if source.query.as_deref() != Some("signed") {
             return Ok(true);
         }
 
         match vcs {
-            VCSKind::Git => self.verify_git_sig(dirs, pkgbuild, source, gpg),
+            VCSKind::Git => self.verify_git_sig(dirs, options, pkgbuild, source, gpg),
             _ => Err(IntegError::DoesNotSupportSignatures(source.clone()).into()),
         }
     }
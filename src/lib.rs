@@ -2,36 +2,101 @@
 
 use std::fmt::Display;
 
+#[cfg(feature = "tokio")]
+pub use asynchronous::{AsyncCallbacks, AsyncMakepkg};
+pub use build_many::{BatchBuild, BatchOutcome, BatchReport};
+pub use bump::BumpReport;
 pub use callback::*;
+pub use chroot::Chroot;
+pub use dedup::DuplicateFile;
+pub use deps::*;
+pub use extract_cache::*;
+pub use host::*;
+pub use integ::{GeneratedChecksums, KeyImport, PgpKey};
 pub use makepkg::*;
 pub use options::*;
+pub use package::{BuiltPackage, PackageKind};
+pub use package_contents::{Member, MemberKind};
+pub use package_diff::{diff_packages, FileChange, MetadataChange, PackageDiff};
+pub use package_lint::PackageWarning;
 use pkgbuild::Pkgbuild;
+pub use pkgbuild_edit::PkgbuildEditor;
+pub use plan::*;
+pub use remote::RemoteBuilder;
+pub use report::{BuildPhase, BuildReport};
+pub use repro::{PackageReproducibility, ReproducibilityReport};
+pub use srcinfo_update::SrcinfoReport;
+pub use verify_package::{PackageProblem, PackageVerification};
 
+mod artifact_cache;
+#[cfg(feature = "tokio")]
+mod asynchronous;
 mod build;
+mod build_dirs;
 mod build_env;
+mod build_key;
+mod build_log;
+mod build_many;
+mod builddir;
+mod bump;
 mod callback;
+mod checkpoint;
+mod chroot;
+mod config_write;
+mod dedup;
+mod deps;
+mod extract_cache;
+mod filenames;
 mod fs;
+mod host;
 mod integ;
+mod libdepends;
 mod lint_config;
 mod lint_pkgbuild;
+#[cfg(feature = "localdb")]
+mod localdb;
+mod location;
 mod makepkg;
+mod netns;
 mod options;
 mod package;
+mod package_contents;
+mod package_diff;
+mod package_lint;
 mod pacman;
-mod raw;
+mod pkgbuild_edit;
+mod plan;
+mod raw_toml;
+mod remote;
+mod report;
+mod repro;
 mod run;
+mod sandbox;
+mod shell;
+mod snapshot;
+mod soname;
 mod sources;
 mod srcinfo;
+mod srcinfo_update;
+mod unused_depends;
 mod util;
+mod verify_package;
 
 pub mod config;
 pub mod error;
+#[cfg(feature = "ffi")]
+pub mod ffi;
 mod installation_variables;
+pub mod license;
 pub mod pkgbuild;
+#[cfg(feature = "python")]
+mod python;
+pub mod raw;
 
 pub(crate) static TOOL_NAME: &str = env!("CARGO_PKG_NAME");
 
 #[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize))]
 pub enum FileKind {
     Pkgbuild,
     Config,
@@ -41,7 +106,7 @@ impl Display for FileKind {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
         match self {
             FileKind::Pkgbuild => f.write_str(Pkgbuild::file_name()),
-            FileKind::Config => todo!("config"),
+            FileKind::Config => f.write_str("config"),
         }
     }
 }
@@ -0,0 +1,98 @@
+use std::{collections::BTreeMap, path::Path};
+
+use crate::{
+    callback::{LogLevel, LogMessage},
+    config::PkgbuildDirs,
+    error::{Context, IOContext, IOErrorExt, Result},
+    pkgbuild::{Depend, Package, Pkgbuild},
+    soname::{dynamic_entries, elf_class, ET_DYN, ET_EXEC},
+    Makepkg,
+};
+
+static LIB_DIRS: &[&str] = &["/usr/lib", "/usr/lib32"];
+
+impl Makepkg {
+    /// Warns about libraries packaged binaries link against that are not provided by any
+    /// declared dependency.
+    pub(crate) fn check_lib_depends(
+        &self,
+        dirs: &PkgbuildDirs,
+        pkgbuild: &Pkgbuild,
+        pkg: &Package,
+    ) -> Result<()> {
+        let arch = &self.config.arch;
+        let pkgdir = dirs.pkgdir(pkg);
+        let depends = pkg
+            .depends
+            .enabled(arch)
+            .map(|d| {
+                let Ok(dep) = d.parse::<Depend>();
+                dep.name
+            })
+            .collect::<Vec<_>>();
+
+        let mut needed = Vec::new();
+        let mut packaged = Vec::new();
+
+        for file in walkdir::WalkDir::new(&pkgdir) {
+            let file =
+                file.context(Context::GetPackageFiles, IOContext::ReadDir(pkgdir.clone()))?;
+            if file.file_type().is_dir() {
+                continue;
+            }
+            packaged.push(file.file_name().to_os_string());
+
+            if !file.file_type().is_file() {
+                continue;
+            }
+            if elf_class(file.path(), ET_DYN)
+                .or_else(|| elf_class(file.path(), ET_EXEC))
+                .is_none()
+            {
+                continue;
+            }
+
+            let dynamic = self.elf_dynamic(pkgbuild, file.path())?;
+            needed.extend(dynamic_entries(&dynamic, "Shared library"));
+        }
+
+        needed.sort();
+        needed.dedup();
+
+        let mut owners = BTreeMap::new();
+
+        for lib in needed {
+            if packaged.iter().any(|p| *p == *lib) {
+                continue;
+            }
+            let soname = lib.split_once(".so").map(|(n, _)| format!("{}.so", n));
+            if soname.is_some_and(|s| depends.contains(&s)) {
+                continue;
+            }
+
+            let Some(path) = LIB_DIRS
+                .iter()
+                .map(|d| Path::new(d).join(&lib))
+                .find(|p| p.exists())
+            else {
+                continue;
+            };
+            let Some(owner) = self.file_owner(&path) else {
+                continue;
+            };
+
+            if !depends.contains(&owner) && owner != pkg.pkgname {
+                owners.entry(owner).or_insert_with(Vec::new).push(lib);
+            }
+        }
+
+        for (owner, libs) in owners {
+            self.log(
+                LogLevel::Warning,
+                LogMessage::UndeclaredLibDepend(&pkg.pkgname, &owner, &libs),
+            )?;
+        }
+
+        Ok(())
+    }
+}
@@ -0,0 +1,132 @@
+//! Helpers for migrating the license array from Arch's legacy license names to SPDX
+//! identifiers.
+
+use std::{fmt::Display, str::FromStr};
+
+use crate::pkgbuild::Pkgbuild;
+
+/// Legacy Arch license names that have a single SPDX equivalent.
+pub static SPDX_MAPPING: &[(&str, &str)] = &[
+    ("AGPL", "AGPL-3.0-or-later"),
+    ("AGPL3", "AGPL-3.0-only"),
+    ("APACHE", "Apache-2.0"),
+    ("Apache", "Apache-2.0"),
+    ("Artistic2.0", "Artistic-2.0"),
+    ("Boost", "BSL-1.0"),
+    ("CDDL", "CDDL-1.0"),
+    ("EPL", "EPL-1.0"),
+    ("FDL", "GFDL-1.3-or-later"),
+    ("FDL1.2", "GFDL-1.2-only"),
+    ("FDL1.3", "GFDL-1.3-only"),
+    ("GPL", "GPL-2.0-or-later"),
+    ("GPL2", "GPL-2.0-only"),
+    ("GPL3", "GPL-3.0-only"),
+    ("LGPL", "LGPL-2.1-or-later"),
+    ("LGPL2.1", "LGPL-2.1-only"),
+    ("LGPL3", "LGPL-3.0-only"),
+    ("LPPL", "LPPL-1.3c"),
+    ("MPL", "MPL-1.1"),
+    ("MPL2", "MPL-2.0"),
+    ("PerlArtistic", "Artistic-1.0-Perl"),
+    ("PHP", "PHP-3.01"),
+    ("PSF", "PSF-2.0"),
+    ("Python", "PSF-2.0"),
+    ("RUBY", "Ruby"),
+    ("ZPL", "ZPL-2.1"),
+    ("zlib", "Zlib"),
+];
+
+/// Legacy Arch license names that cover several SPDX licenses and have to be migrated by hand.
+pub static AMBIGUOUS: &[&str] = &["BSD", "CCPL", "custom"];
+
+/// Maps a legacy Arch license name to its SPDX identifier.
+pub fn to_spdx(name: &str) -> Option<&'static str> {
+    SPDX_MAPPING
+        .iter()
+        .find(|(legacy, _)| *legacy == name)
+        .map(|(_, spdx)| *spdx)
+}
+
+/// An entry of the license array.
+#[derive(Debug, Clone, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub enum License {
+    /// A license expression that is not a known legacy name.
+    Spdx(String),
+    /// `custom:name`, a license that is not in the licenses package.
+    Custom(String),
+    /// A legacy Arch license name along with its SPDX equivalent if there is one.
+    Legacy(String, Option<&'static str>),
+}
+
+impl FromStr for License {
+    type Err = std::convert::Infallible;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        if let Some(name) = s.strip_prefix("custom:") {
+            return Ok(License::Custom(name.to_string()));
+        }
+        if let Some(spdx) = to_spdx(s) {
+            return Ok(License::Legacy(s.to_string(), Some(spdx)));
+        }
+        if AMBIGUOUS.contains(&s) {
+            return Ok(License::Legacy(s.to_string(), None));
+        }
+        Ok(License::Spdx(s.to_string()))
+    }
+}
+
+impl Display for License {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            License::Spdx(s) => f.write_str(s),
+            License::Custom(s) => write!(f, "custom:{}", s),
+            License::Legacy(s, _) => f.write_str(s),
+        }
+    }
+}
+
+impl License {
+    pub fn is_legacy(&self) -> bool {
+        matches!(self, License::Legacy(..) | License::Custom(_))
+    }
+
+    /// The SPDX form of this license if it has one.
+    ///
+    /// Custom licenses are mapped to a `LicenseRef-` identifier.
+    pub fn spdx(&self) -> Option<String> {
+        match self {
+            License::Spdx(s) => Some(s.clone()),
+            License::Custom(s) => Some(format!("LicenseRef-{}", s)),
+            License::Legacy(_, spdx) => spdx.map(|s| s.to_string()),
+        }
+    }
+}
+
+/// A license entry of a package that still uses a legacy name.
+#[derive(Debug, Clone, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct LegacyLicense {
+    pub pkgname: String,
+    pub license: License,
+    /// The identifier the license should be replaced with if it can be done automatically.
+    pub replacement: Option<String>,
+}
+
+/// Reports every package in a PKGBUILD that still uses legacy license names.
+pub fn legacy_licenses(pkgbuild: &Pkgbuild) -> Vec<LegacyLicense> {
+    let mut legacy = Vec::new();
+
+    for pkg in pkgbuild.packages() {
+        for license in &pkg.license {
+            let Ok(license) = license.parse::<License>();
+            if license.is_legacy() {
+                legacy.push(LegacyLicense {
+                    pkgname: pkg.pkgname.clone(),
+                    replacement: license.spdx(),
+                    license,
+                })
+            }
+        }
+    }
+
+    legacy
+}
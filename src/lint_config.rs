@@ -3,6 +3,7 @@ use std::fmt::Display;
 use crate::{
     config::Config,
     error::LintKind,
+    lint_pkgbuild::check_pkgver,
     raw::{RawConfig, Value, Variable},
 };
 
@@ -37,8 +38,10 @@ impl Config {
         warnings
     }
 
-    pub(crate) fn lint(&self, _lints: &mut [LintKind]) {
-        //
+    pub(crate) fn lint(&self, lints: &mut Vec<LintKind>) {
+        if let Some(suffix) = &self.version_suffix {
+            check_pkgver(suffix, "VERSION_SUFFIX", lints);
+        }
     }
 }
 
@@ -59,15 +62,11 @@ fn warn_packager(config: &Config, warnings: &mut Vec<Warning>) {
 fn lint_newline<'a, I: Iterator<Item = &'a Variable>>(iter: I, lints: &mut Vec<LintKind>) {
     for var in iter {
         match &var.value {
-            Value::Array(a) => {
-                if a.iter().any(|v| v.contains('\n')) {
-                    lints.push(LintKind::VariabeContainsNewlines(var.name.clone()))
-                }
+            Value::Array(a) if a.iter().any(|v| v.contains('\n')) => {
+                lints.push(LintKind::VariabeContainsNewlines(var.name.clone()))
             }
-            Value::String(s) => {
-                if s.contains('\n') {
-                    lints.push(LintKind::VariabeContainsNewlines(var.name.clone()))
-                }
+            Value::String(s) if s.contains('\n') => {
+                lints.push(LintKind::VariabeContainsNewlines(var.name.clone()))
             }
             _ => (),
         }
@@ -1,13 +1,15 @@
 use std::{
-    collections::HashSet,
+    collections::{BTreeMap, HashSet},
     iter,
     path::{Path, PathBuf},
 };
 
 use crate::{
-    error::LintKind,
-    pkgbuild::{ArchVec, ArchVecs, Function, Pkgbuild, Source},
+    callback::{LogLevel, LogMessage},
+    error::{LintError, LintKind, LintLevel, Result},
+    pkgbuild::{ArchVec, ArchVecs, Depend, Function, FunctionSource, Pkgbuild, Source},
     raw::{RawPkgbuild, Value, Variable},
+    Makepkg,
 };
 
 static PKGBUILD_PACKAGE_OVERRIDES: &[&str] = &[
@@ -146,18 +148,39 @@ impl RawPkgbuild {
     }
 }
 
+static NOOP_STATEMENTS: &[&str] = &[":", "true", "return", "return 0"];
+
+static INSTALL_COMMANDS: &[&str] = &[
+    "install", "cp", "mv", "ln", "mkdir", "make", "ninja", "meson", "cmake", "cargo", "python",
+    "pip", "bsdtar", "tar", "rsync",
+];
+
+// the statements of a function as printed by declare -f
+fn function_statements(body: &str) -> Vec<&str> {
+    let mut lines = body.lines().map(|l| l.trim()).collect::<Vec<_>>();
+    // strip "name ()", "{" and "}"
+    if let Some(open) = lines.iter().position(|l| *l == "{") {
+        lines.drain(..=open);
+    }
+    if lines.last() == Some(&"}") {
+        lines.pop();
+    }
+
+    lines
+        .into_iter()
+        .map(|l| l.trim_end_matches(';').trim())
+        .filter(|l| !l.is_empty())
+        .collect()
+}
+
 fn lint_newline<'a, I: Iterator<Item = &'a Variable>>(iter: I, lints: &mut Vec<LintKind>) {
     for var in iter {
         match &var.value {
-            Value::Array(a) => {
-                if a.iter().any(|v| v.contains('\n')) {
-                    lints.push(LintKind::VariabeContainsNewlines(var.name.clone()))
-                }
+            Value::Array(a) if a.iter().any(|v| v.contains('\n')) => {
+                lints.push(LintKind::VariabeContainsNewlines(var.name.clone()))
             }
-            Value::String(s) => {
-                if s.contains('\n') {
-                    lints.push(LintKind::VariabeContainsNewlines(var.name.clone()))
-                }
+            Value::String(s) if s.contains('\n') => {
+                lints.push(LintKind::VariabeContainsNewlines(var.name.clone()))
             }
             _ => (),
         }
@@ -174,6 +197,37 @@ fn lint_arrays<'a, I: Iterator<Item = &'a Variable>>(iter: I, lints: &mut Vec<Li
     }
 }
 
+impl Makepkg {
+    /// Parses the PKGBUILD in `dir` using the configured
+    /// [`lint_levels`](crate::config::Config::lint_levels), logging lints at the warning level.
+    pub fn load_pkgbuild<P: Into<PathBuf>>(&self, dir: P) -> Result<Pkgbuild> {
+        let (pkgbuild, warnings) = Pkgbuild::with_lint_levels(dir, &self.config.lint_levels)?;
+        self.report_lints(&pkgbuild, warnings)?;
+        Ok(pkgbuild)
+    }
+
+    /// Sets the [`LintLevel`] of the lint with this [`name`](LintKind::name).
+    pub fn set_lint_level<S: Into<String>>(&mut self, name: S, level: LintLevel) {
+        self.config.lint_levels.set(name, level);
+    }
+
+    /// Logs lints at the warning level and fails if any are errors.
+    pub(crate) fn report_lints(&self, pkgbuild: &Pkgbuild, lints: Vec<LintKind>) -> Result<()> {
+        let (errors, warnings) = self.config.lint_levels.split(lints);
+        for lint in warnings {
+            let lint = match pkgbuild.lint_location(&lint) {
+                Some(location) => format!("{}: {}", location, lint),
+                None => lint.to_string(),
+            };
+            self.log(LogLevel::Warning, LogMessage::PkgbuildWarning(&lint))?;
+        }
+        if !errors.is_empty() {
+            return Err(LintError::pkgbuild(errors).with_locations(pkgbuild).into());
+        }
+        Ok(())
+    }
+}
+
 impl Pkgbuild {
     pub(crate) fn lint(&self, lints: &mut Vec<LintKind>) {
         self.lint_pkgbase(lints);
@@ -191,6 +245,7 @@ impl Pkgbuild {
         self.lint_conflicts(lints);
         self.lint_provides(lints);
         self.lint_replaces(lints);
+        self.lint_split_relations(lints);
         self.lint_package_function(lints);
 
         self.lint_backup(lints);
@@ -199,6 +254,33 @@ impl Pkgbuild {
         self.lint_sources(lints);
     }
 
+    /// Heuristic checks for functions that look unfinished.
+    ///
+    /// Unlike the other lints these can have false positives so they default to the warning
+    /// [`LintLevel`] and are not checked when the PKGBUILD is loaded.
+    pub fn lint_functions(&self) -> Vec<LintKind> {
+        let mut lints = Vec::new();
+
+        for FunctionSource { name, body, .. } in self.function_sources() {
+            let statements = function_statements(body);
+
+            if statements.iter().all(|s| NOOP_STATEMENTS.contains(s)) {
+                lints.push(LintKind::EmptyFunction(name.clone()));
+            } else if name.starts_with("package")
+                && !body.contains("pkgdir")
+                && !statements.iter().any(|s| {
+                    s.split_whitespace()
+                        .next()
+                        .is_some_and(|cmd| INSTALL_COMMANDS.contains(&cmd))
+                })
+            {
+                lints.push(LintKind::PackageInstallsNothing(name.clone()));
+            }
+        }
+
+        lints
+    }
+
     fn lint_pkgbase(&self, lints: &mut Vec<LintKind>) {
         check_pkgname(&self.pkgbase, "pkgbase", lints)
     }
@@ -257,7 +339,6 @@ impl Pkgbuild {
             .all()
             .chain(self.packages().flat_map(|p| p.optdepends.all()))
         {
-            let fulldep = fulldep.split(": ").next().unwrap();
             check_depend(fulldep, "optdepends", lints)
         }
     }
@@ -272,6 +353,60 @@ impl Pkgbuild {
         }
     }
 
+    // conflicts and provides between packages built from the same PKGBUILD
+    fn lint_split_relations(&self, lints: &mut Vec<LintKind>) {
+        if self.packages.len() < 2 {
+            return;
+        }
+
+        // provided name to the packages providing it and the version they provide
+        let mut provided = BTreeMap::<String, Vec<(&str, Option<String>)>>::new();
+
+        for pkg in self.packages() {
+            let siblings = self.pkgnames().filter(|&n| n != pkg.pkgname);
+
+            for sibling in siblings {
+                let conflicts = pkg.conflicts.all().map(|c| c.parse::<Depend>());
+                if conflicts.into_iter().any(|Ok(c)| c.name == sibling) {
+                    lints.push(LintKind::ConflictsWithSibling(
+                        pkg.pkgname.clone(),
+                        sibling.to_string(),
+                    ));
+                }
+            }
+
+            let mut seen = HashSet::new();
+            for provide in pkg.provides.all() {
+                let Ok(provide) = provide.parse::<Depend>();
+                if !seen.insert(provide.name.clone()) {
+                    continue;
+                }
+
+                if provide.version.is_none()
+                    && provide.name != pkg.pkgname
+                    && self.pkgnames().any(|n| n == provide.name)
+                {
+                    lints.push(LintKind::ProvidesSiblingWithoutVersion(
+                        pkg.pkgname.clone(),
+                        provide.name.clone(),
+                    ));
+                }
+
+                provided
+                    .entry(provide.name)
+                    .or_default()
+                    .push((&pkg.pkgname, provide.version));
+            }
+        }
+
+        for (name, pkgs) in provided {
+            if pkgs.iter().any(|p| p.1 != pkgs[0].1) {
+                let pkgs = pkgs.iter().map(|p| p.0.to_string()).collect();
+                lints.push(LintKind::InconsistentSiblingProvides(name, pkgs));
+            }
+        }
+    }
+
     fn lint_replaces(&self, lints: &mut Vec<LintKind>) {
         for fulldep in self
             .replaces
@@ -407,15 +542,15 @@ fn check_invalid_chars<F: Fn(char) -> bool>(tp: &str, s: &str, f: F, lints: &mut
 }
 
 fn check_depend(fulldep: &str, tp: &str, lints: &mut Vec<LintKind>) {
-    if let Some((dep, rest)) = fulldep.split_once(['<', '>', '=']) {
-        check_pkgname(dep, tp, lints);
-        let rest = rest.trim_start_matches(['<', '>', '=']);
-        if !rest.is_empty() {
-            check_fullpkgver(rest, tp, lints);
-        }
-    } else {
-        check_pkgname(fulldep, tp, lints);
-    };
+    let Ok(dep) = fulldep.parse::<Depend>();
+    check_pkgname(&dep.name, tp, lints);
+    if let Some(version) = dep.version.filter(|v| !v.is_empty()) {
+        check_fullpkgver(&version, tp, lints);
+    }
+    if tp != "optdepends" && dep.description.is_some() {
+        // only optdepends may have a description
+        lints.push(LintKind::InvalidChars(tp.to_string(), ": ".to_string()));
+    }
 }
 
 fn check_pkgname(name: &str, tp: &str, lints: &mut Vec<LintKind>) {
@@ -476,7 +611,7 @@ pub(crate) fn check_pkgver(val: &str, tp: &str, lints: &mut Vec<LintKind>) {
         lints.push(LintKind::InvalidPkgver(tp.to_string()));
     }
 
-    if !val.chars().all(|c| c.is_ascii()) {
+    if !val.is_ascii() {
         lints.push(LintKind::AsciiOnly("pkgver".to_string(), tp.to_string()));
     }
 }
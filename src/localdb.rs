@@ -0,0 +1,67 @@
+use std::{
+    fs::read_dir,
+    path::{Path, PathBuf},
+};
+
+use crate::{
+    config::Config,
+    error::{Context, IOContext, IOErrorExt, Result},
+    fs::read_to_string,
+};
+
+const PACMAN_CONF: &str = "/etc/pacman.conf";
+const DEFAULT_DBPATH: &str = "/var/lib/pacman/";
+
+/// The pacman database directory from the config, pacman.conf or pacman's default.
+pub(crate) fn dbpath(config: &Config) -> PathBuf {
+    if let Some(dbpath) = &config.pacman_dbpath {
+        return dbpath.clone();
+    }
+
+    let conf = std::fs::read_to_string(PACMAN_CONF).unwrap_or_default();
+    let mut options = false;
+    for line in conf.lines() {
+        let line = line.trim();
+        if line.starts_with('[') {
+            options = line == "[options]";
+        } else if let Some((key, value)) = line.split_once('=') {
+            if options && key.trim() == "DBPath" {
+                return PathBuf::from(value.trim());
+            }
+        }
+    }
+
+    PathBuf::from(DEFAULT_DBPATH)
+}
+
+pub(crate) fn installed(db: &Path) -> Result<Vec<String>> {
+    let mut installed = Vec::new();
+
+    for entry in read_dir(db).context(Context::QueryPacman, IOContext::ReadDir(db.into()))? {
+        let entry = entry.context(Context::QueryPacman, IOContext::ReadDir(db.into()))?;
+        let desc = entry.path().join("desc");
+        if !desc.exists() {
+            continue;
+        }
+
+        let desc = read_to_string(&desc, Context::QueryPacman)?;
+        let mut lines = desc.lines();
+        let (mut name, mut version, mut arch) = (None, None, None);
+
+        while let Some(line) = lines.next() {
+            match line {
+                "%NAME%" => name = lines.next(),
+                "%VERSION%" => version = lines.next(),
+                "%ARCH%" => arch = lines.next(),
+                _ => (),
+            }
+        }
+
+        if let (Some(name), Some(version), Some(arch)) = (name, version, arch) {
+            installed.push(format!("{}-{}-{}", name, version, arch));
+        }
+    }
+
+    installed.sort();
+    Ok(installed)
+}
@@ -0,0 +1,202 @@
+use std::{ops::Range, path::Path};
+
+use crate::{
+    error::{LintKind, SourceLocation},
+    pkgbuild::{FunctionSource, Pkgbuild},
+    raw::{RawPkgbuild, Span},
+};
+
+impl Pkgbuild {
+    /// Makes a best effort attempt at finding the line in the PKGBUILD that caused a lint.
+    pub fn lint_location(&self, lint: &LintKind) -> Option<SourceLocation> {
+        let path = self.dir.join(Pkgbuild::file_name());
+
+        let line = match lint {
+            LintKind::EmptyFunction(name) | LintKind::PackageInstallsNothing(name) => {
+                self.function_source(name)?.line
+            }
+            _ => {
+                let (name, value) = lint.variable()?;
+                let contents = std::fs::read_to_string(&path).ok()?;
+                find_variable(&contents, name, value)?
+            }
+        };
+
+        Some(SourceLocation { path, line })
+    }
+}
+
+/// Finds the line of the dump output's variable in the PKGBUILD.
+pub(crate) fn locate_dump_line(path: &Path, dump_line: &str) -> Option<SourceLocation> {
+    let words = dump_line.split_whitespace().collect::<Vec<_>>();
+    let line = match words.as_slice() {
+        ["FUNCTION", _, line, ..] => line.parse().ok()?,
+        ["VAR", "GLOBAL", _, name, ..] | ["VAR", "FUNCTION", _, _, name, ..] => {
+            let contents = std::fs::read_to_string(path).ok()?;
+            find_variable(&contents, name, None)?
+        }
+        _ => return None,
+    };
+
+    Some(SourceLocation {
+        path: path.to_path_buf(),
+        line,
+    })
+}
+
+// returns the 1 based line a variable is assigned on
+//
+// if value is given and the assignment spans multiple lines the line containing the value
+// is returned instead
+fn find_variable(contents: &str, name: &str, value: Option<&str>) -> Option<usize> {
+    let lines = contents.lines().collect::<Vec<_>>();
+    let mut first = None;
+
+    for span in assignments(&lines, name) {
+        first.get_or_insert(span.line);
+        let Some(value) = value else {
+            break;
+        };
+
+        let found = lines[span.line - 1..span.end_line]
+            .iter()
+            .position(|l| l.contains(value));
+        if let Some(offset) = found {
+            return Some(span.line + offset);
+        }
+    }
+
+    first
+}
+
+/// Fills in the [`Span`] of each variable by searching the PKGBUILD's source.
+///
+/// Global variables match assignments outside of functions and function variables match
+/// assignments inside of their function.
+pub(crate) fn add_spans(contents: &str, raw: &mut RawPkgbuild) {
+    let lines = contents.lines().collect::<Vec<_>>();
+    let ranges = function_ranges(&lines, &raw.functions);
+    let in_function = |line: usize| ranges.iter().find(|r| r.1.contains(&line)).map(|r| r.0);
+
+    for var in &mut raw.variables {
+        var.span = assignments(&lines, &var.name_arch()).find(|s| in_function(s.line).is_none());
+    }
+
+    for func in &mut raw.function_variables {
+        for var in &mut func.variables {
+            var.span = assignments(&lines, &var.name_arch())
+                .find(|s| in_function(s.line) == Some(func.function_name.as_str()));
+        }
+    }
+}
+
+// the lines each function covers
+//
+// functions are assumed to end at the first line starting with } or at the next function
+fn function_ranges<'a>(
+    lines: &[&str],
+    functions: &'a [FunctionSource],
+) -> Vec<(&'a str, Range<usize>)> {
+    let mut starts = functions
+        .iter()
+        .map(|f| (f.name.as_str(), f.line))
+        .collect::<Vec<_>>();
+    starts.sort_by_key(|f| f.1);
+
+    let mut ranges = Vec::new();
+    for (i, &(name, start)) in starts.iter().enumerate() {
+        let next = starts.get(i + 1).map_or(lines.len() + 1, |f| f.1);
+        let end = (start..next)
+            .skip(1)
+            .find(|&n| lines.get(n - 1).is_some_and(|l| l.starts_with('}')))
+            .map_or(next, |n| n + 1);
+        ranges.push((name, start..end));
+    }
+    ranges
+}
+
+// every assignment to a variable in the source
+fn assignments<'a>(lines: &'a [&'a str], name: &'a str) -> impl Iterator<Item = Span> + 'a {
+    lines.iter().enumerate().filter_map(move |(n, line)| {
+        let line = line.trim_start();
+        let line = line.strip_prefix("local ").unwrap_or(line).trim_start();
+        let rest = line.strip_prefix(name)?;
+        let rest = rest.strip_prefix('=').or_else(|| rest.strip_prefix("+="))?;
+
+        let mut raw = String::new();
+        let mut end = n;
+        let mut scanner = Scanner::default();
+        let mut text = rest;
+
+        loop {
+            let (len, done) = scanner.scan(text);
+            raw.push_str(&text[..len]);
+            if done || end + 1 >= lines.len() {
+                break;
+            }
+            raw.push('\n');
+            end += 1;
+            text = lines[end];
+        }
+
+        Some(Span {
+            line: n + 1,
+            end_line: end + 1,
+            raw: raw.trim_end().to_string(),
+        })
+    })
+}
+
+// tracks enough of bash's quoting to find the end of an assignment
+#[derive(Default)]
+struct Scanner {
+    single: bool,
+    double: bool,
+    depth: usize,
+}
+
+impl Scanner {
+    // returns how much of the line belongs to the assignment and if the assignment ended
+    //
+    // comments inside of arrays are kept so the original text can be reproduced
+    fn scan(&mut self, line: &str) -> (usize, bool) {
+        let mut chars = line.char_indices().peekable();
+        let mut prev_space = true;
+
+        while let Some((i, c)) = chars.next() {
+            if self.single {
+                self.single = c != '\'';
+            } else if self.double {
+                match c {
+                    '\\' => {
+                        chars.next();
+                    }
+                    '"' => self.double = false,
+                    _ => (),
+                }
+            } else {
+                match c {
+                    '\\' => {
+                        chars.next();
+                    }
+                    '\'' => self.single = true,
+                    '"' => self.double = true,
+                    '(' => self.depth += 1,
+                    ')' => {
+                        self.depth = self.depth.saturating_sub(1);
+                        if self.depth == 0 {
+                            return (i + 1, true);
+                        }
+                    }
+                    '#' if prev_space && self.depth > 0 => return (line.len(), false),
+                    c if c.is_whitespace() && self.depth == 0 => return (i, true),
+                    ';' if self.depth == 0 => return (i, true),
+                    _ => (),
+                }
+            }
+            prev_space = c.is_whitespace();
+        }
+
+        (line.len(), !self.single && !self.double && self.depth == 0)
+    }
+}
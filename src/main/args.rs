@@ -1,81 +1,197 @@
 use std::path::PathBuf;
 
-use clap::Parser;
+use clap::{ArgAction, Parser, Subcommand, ValueEnum};
+
+#[derive(ValueEnum, Debug, Default, Copy, Clone, PartialEq, Eq)]
+pub enum ColorChoice {
+    /// Color output to a terminal unless NO_COLOR is set or color is disabled in BUILDENV
+    #[default]
+    Auto,
+    Always,
+    Never,
+}
 
 #[derive(Parser, Debug, Default)]
 #[command(author, version, about)]
 pub struct Args {
-    #[arg(long, short = 'D')]
-    pub chdir: Option<PathBuf>,
-    #[arg(long, short = 'm')]
+    #[command(subcommand)]
+    pub command: Option<Command>,
+    #[arg(value_name = "DIR")]
+    pub dirs: Vec<PathBuf>,
+    #[arg(long, short = 'D', global = true)]
+    pub chdir: Vec<PathBuf>,
+    #[arg(long, value_enum, default_value_t = ColorChoice::Auto, global = true)]
+    pub color: ColorChoice,
+    #[arg(long, short = 'm', conflicts_with = "color", global = true)]
     pub nocolor: bool,
-    #[arg(long, short = 'L')]
+    #[arg(long, short = 'L', global = true)]
     pub log: bool,
-    #[arg(long, short)]
+    #[arg(long, short, conflicts_with = "verbose", global = true)]
+    pub quiet: bool,
+    #[arg(long, short, action = ArgAction::Count, global = true)]
+    pub verbose: u8,
+    #[arg(long, global = true)]
+    pub buildlog: bool,
+    #[arg(long, global = true)]
+    pub json: bool,
+    #[arg(long, global = true)]
+    pub nospacecheck: bool,
+    #[arg(long, global = true)]
+    pub nonetwork: bool,
+    #[arg(long, global = true)]
+    pub timeout: Option<u64>,
+    #[arg(long, global = true)]
+    pub pty: bool,
+    #[arg(long, global = true)]
+    pub interactive: bool,
+    #[arg(long, global = true)]
+    pub resume: bool,
+    #[arg(long, global = true)]
+    pub skip_unchanged: bool,
+    #[arg(long, global = true)]
+    pub checkrepro: bool,
+    #[arg(long, global = true)]
+    pub sandbox: bool,
+    #[arg(long, global = true)]
+    pub confinepackage: bool,
+    #[arg(long, global = true)]
+    pub normalizenames: bool,
+    #[arg(long, global = true)]
+    pub snapshot: bool,
+    #[arg(long, global = true)]
+    pub remote: Option<String>,
+    #[arg(long, global = true)]
+    pub chroot: Option<PathBuf>,
+    #[arg(long, short, global = true)]
     pub force: bool,
     #[arg(long)]
     pub packagelist: bool,
+    #[arg(long = "null", short = '0', global = true)]
+    pub null: bool,
+    #[arg(long, global = true)]
+    pub nodebug: bool,
     #[arg(long)]
     pub printsrcinfo: bool,
+    #[arg(long, requires = "printsrcinfo")]
+    pub write: bool,
+    #[arg(long, conflicts_with = "printsrcinfo")]
+    pub checksrcinfo: bool,
+    #[arg(long)]
+    pub printbuildenv: bool,
+    #[arg(long)]
+    pub dump_config: bool,
     #[arg(long, short = 'g')]
     pub geninteg: bool,
-    #[arg(long, short = 'd')]
+    #[arg(skip)]
+    pub update: bool,
+    #[arg(long, short = 'd', global = true)]
     pub nodeps: bool,
-    #[arg(long)]
+    #[arg(long, global = true)]
     pub skipinteg: bool,
-    #[arg(long)]
+    #[arg(long, global = true)]
     pub skipchecksums: bool,
-    #[arg(long)]
+    #[arg(long, global = true)]
     pub skippgpcheck: bool,
-    #[clap(long, overrides_with = "check")]
+    #[arg(long, global = true)]
+    pub recvkeys: bool,
+    #[clap(long, overrides_with = "check", global = true)]
     pub nocheck: bool,
-    #[clap(long)]
+    #[clap(long, global = true)]
     pub noverify: bool,
-    #[clap(long, overrides_with = "nocheck")]
+    #[clap(long, overrides_with = "nocheck", global = true)]
     pub check: bool,
-    #[arg(long)]
+    #[arg(long, global = true)]
     pub config: Option<String>,
-    #[arg(long, short = 'A')]
+    #[arg(long, short = 'A', global = true)]
     pub ignorearch: bool,
-    #[arg(long, short = 'e')]
+    #[arg(long, short = 'e', global = true)]
     pub noextract: bool,
     #[arg(long)]
     pub verifysource: bool,
-    #[arg(long, short = 'C')]
+    #[arg(skip)]
+    pub allarch: bool,
+    #[arg(long, short = 'C', global = true)]
     pub cleanbuild: bool,
-    #[arg(long, short)]
+    #[arg(long, short, global = true)]
     pub clean: bool,
-    #[arg(long)]
+    #[arg(long, global = true)]
     pub noprepare: bool,
-    #[arg(long, short = 'o')]
+    #[arg(long, short = 'o', global = true)]
     pub nobuild: bool,
-    #[arg(long, short = 'R')]
+    #[arg(long, short = 'R', global = true)]
     pub repackage: bool,
-    #[arg(long)]
+    #[arg(long, global = true)]
     pub noarchive: bool,
-    #[clap(long, overrides_with = "nosign")]
+    #[clap(long, overrides_with = "nosign", global = true)]
     pub sign: bool,
-    #[clap(long, overrides_with = "sign")]
+    #[clap(long, overrides_with = "sign", global = true)]
     pub nosign: bool,
     #[arg(long, short = 'S')]
     pub source: bool,
-    #[arg(long)]
+    #[arg(skip)]
     pub allsource: bool,
-    #[arg(long)]
+    #[arg(long, global = true)]
     pub holdver: bool,
+    #[arg(long, global = true)]
+    pub conditional_download: bool,
+    #[arg(long, global = true)]
+    pub cleanvcslocks: bool,
 
-    #[arg(long, short)]
+    #[arg(long, short, global = true)]
     pub rmdeps: bool,
-    #[arg(long, short)]
+    #[arg(long, short, global = true)]
     pub syncdeps: bool,
-    #[arg(long, short)]
+    #[arg(long, short, global = true)]
     pub install: bool,
-    #[arg(long)]
+    #[arg(long, global = true)]
     pub asdeps: bool,
-    #[arg(long)]
+    #[arg(long, global = true)]
+    pub install_intermediates: bool,
+    #[arg(long, global = true)]
     pub needed: bool,
-    #[arg(long)]
+    #[arg(long, global = true)]
     pub noconfirm: bool,
-    #[arg(long)]
+    #[arg(long, global = true)]
     pub noprogressbar: bool,
+    #[arg(long)]
+    pub generate_man: bool,
+}
+
+#[derive(Subcommand, Debug)]
+pub enum Command {
+    /// Build the package, the same as giving no subcommand
+    Build,
+    /// Create a source package
+    Source {
+        /// Include the downloaded sources in the source package
+        #[arg(long, alias = "allsource")]
+        all: bool,
+    },
+    /// Print the .SRCINFO
+    Srcinfo {
+        /// Write .SRCINFO instead of printing it
+        #[arg(long)]
+        write: bool,
+        /// Fail if .SRCINFO is not up to date
+        #[arg(long, conflicts_with = "write")]
+        check: bool,
+    },
+    /// Generate checksums for the sources
+    Geninteg {
+        /// Update the checksums in the PKGBUILD
+        #[arg(long)]
+        update: bool,
+    },
+    /// Download and verify the sources without building
+    Verify {
+        /// Verify the sources of every architecture
+        #[arg(long, alias = "allarch")]
+        all: bool,
+    },
+    /// Remove srcdir and pkgdir left by a previous build
+    Clean,
+    /// Print the package files the build would create
+    Packagelist,
+    /// Compare the files and metadata of two built packages
+    Diff { old: PathBuf, new: PathBuf },
 }
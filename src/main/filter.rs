@@ -0,0 +1,64 @@
+use makepkg::{Event, LogLevel};
+
+/// How much the [`Printer`](crate::print::Printer) shows. Set with `-q`, `-v` and `-vv`.
+#[derive(Debug, Default, Copy, Clone, PartialEq, Eq, PartialOrd, Ord)]
+pub enum Verbosity {
+    Quiet,
+    #[default]
+    Normal,
+    Verbose,
+    Debug,
+}
+
+impl Verbosity {
+    pub fn new(quiet: bool, verbose: u8) -> Self {
+        match verbose {
+            _ if quiet => Verbosity::Quiet,
+            0 => Verbosity::Normal,
+            1 => Verbosity::Verbose,
+            _ => Verbosity::Debug,
+        }
+    }
+
+    pub fn show_event(self, event: &Event) -> bool {
+        self >= event_verbosity(event)
+    }
+
+    pub fn show_log(self, level: LogLevel) -> bool {
+        match level {
+            LogLevel::Debug => self >= Verbosity::Debug,
+            LogLevel::Warning | LogLevel::Error => true,
+        }
+    }
+
+    /// Whether to print the argv of commands makepkg runs.
+    pub fn show_commands(self) -> bool {
+        self >= Verbosity::Verbose
+    }
+}
+
+// the least verbosity an event is shown at
+fn event_verbosity(event: &Event) -> Verbosity {
+    match event {
+        Event::FoundSource(_)
+        | Event::Downloading(_)
+        | Event::NoExtact(_)
+        | Event::Extacting(_)
+        | Event::UsingCachedExtraction(_)
+        | Event::RemovingSrcdir
+        | Event::RemovingPkgdir
+        | Event::RemovedDir(..)
+        | Event::NormalizedFileName(_)
+        | Event::AddingFileToPackage(_)
+        | Event::GeneratingPackageFile(_)
+        | Event::DownloadingVCS(_, _)
+        | Event::ExtractingVCS(_, _)
+        | Event::UpdatingVCS(_, _)
+        | Event::RetryingVCS(..)
+        | Event::ReceivingKey(_)
+        | Event::ReceivedKey(_) => Verbosity::Normal,
+        // the download progress bar already names the file
+        Event::DownloadingCurl(_) => Verbosity::Debug,
+        _ => Verbosity::Quiet,
+    }
+}
@@ -0,0 +1,111 @@
+use std::{
+    fmt::Debug,
+    io::{self, stdout, Write},
+    os::unix::process::ExitStatusExt,
+    process::ExitStatus,
+};
+
+use makepkg::{
+    pkgbuild::Pkgbuild, Callbacks, CommandInfo, CommandKind, CommandOutput, DownloadEvent, Event,
+    LogLevel, LogMessage, PackageEvent,
+};
+use serde_json::{json, Value};
+
+/// Prints everything as newline delimited JSON for other programs to consume.
+#[derive(Debug, Default)]
+pub struct JsonPrinter;
+
+pub fn print_json(value: Value) -> io::Result<()> {
+    let mut stdout = stdout().lock();
+    serde_json::to_writer(&mut stdout, &value)?;
+    writeln!(stdout)
+}
+
+// the name of an enum variant without its fields
+fn variant<T: Debug>(value: &T) -> String {
+    let name = format!("{:?}", value);
+    let end = name.find(['(', ' ', '{']).unwrap_or(name.len());
+    name[..end].to_string()
+}
+
+impl Callbacks for JsonPrinter {
+    fn event(&mut self, event: Event) -> io::Result<()> {
+        print_json(json!({
+            "type": "event",
+            "event": event,
+            "message": event.to_string(),
+        }))
+    }
+
+    fn log(&mut self, level: LogLevel, msg: LogMessage) -> io::Result<()> {
+        print_json(json!({
+            "type": "log",
+            "level": level,
+            "log": msg,
+            "message": msg.to_string(),
+        }))
+    }
+
+    fn command_new(
+        &mut self,
+        id: usize,
+        kind: CommandKind,
+        command: &CommandInfo,
+    ) -> io::Result<CommandOutput> {
+        print_json(json!({
+            "type": "command_start",
+            "id": id,
+            "kind": variant(&kind),
+            "argv": command.argv,
+            "cwd": command.cwd,
+        }))?;
+        Ok(CommandOutput::Callback)
+    }
+
+    fn command_exit(
+        &mut self,
+        id: usize,
+        kind: CommandKind,
+        status: Option<ExitStatus>,
+    ) -> io::Result<()> {
+        print_json(json!({
+            "type": "command_exit",
+            "id": id,
+            "kind": variant(&kind),
+            "code": status.and_then(|s| s.code()),
+            "signal": status.and_then(|s| s.signal()),
+        }))
+    }
+
+    fn command_output(&mut self, id: usize, _kind: CommandKind, output: &[u8]) -> io::Result<()> {
+        print_json(json!({
+            "type": "command_output",
+            "id": id,
+            "output": String::from_utf8_lossy(output),
+        }))
+    }
+
+    fn download(&mut self, pkgbuild: &Pkgbuild, event: DownloadEvent) -> io::Result<()> {
+        print_json(json!({
+            "type": "download",
+            "pkgbase": pkgbuild.pkgbase,
+            "event": event,
+        }))
+    }
+
+    fn package(&mut self, event: PackageEvent) -> io::Result<()> {
+        print_json(json!({
+            "type": "package",
+            "event": event,
+        }))
+    }
+
+    fn hashing(&mut self, file_name: &str, hashed: u64, total: u64) -> io::Result<()> {
+        print_json(json!({
+            "type": "hashing",
+            "file": file_name,
+            "hashed": hashed,
+            "total": total,
+        }))
+    }
+}
@@ -1,19 +1,26 @@
 mod args;
+mod filter;
+mod json;
 mod print;
 
+use args::ColorChoice;
+use filter::Verbosity;
+use json::{print_json, JsonPrinter};
 use print::Printer;
 
 use std::{
-    env::set_current_dir,
-    io::{stdout, IsTerminal, Write},
+    env::{self, set_current_dir},
+    io::{stdout, Write},
     os::unix::ffi::OsStrExt,
+    path::PathBuf,
+    time::Duration,
 };
 
 use ansi_term::{Color, Style};
 use anyhow::{bail, Context, Error, Result};
-use clap::Parser;
+use clap::{CommandFactory, Parser};
 use makepkg::{config::Config, Makepkg};
-use makepkg::{pkgbuild::Pkgbuild, Options};
+use makepkg::{pkgbuild::OptionValue, Chroot, Options, PackageKind, RemoteBuilder};
 use nix::unistd::Uid;
 
 pub fn print_error(style: Style, err: Error) {
@@ -29,9 +36,37 @@ pub fn print_error(style: Style, err: Error) {
     eprintln!();
 }
 
+fn is_unchanged(err: &Error) -> bool {
+    matches!(
+        err.downcast_ref::<makepkg::error::Error>(),
+        Some(makepkg::error::Error::Unchanged(_))
+    )
+}
+
 pub fn main() {
-    match run() {
+    let cli = args::Args::parse();
+    let json = cli.json;
+
+    match run(cli) {
         Ok(_) => (),
+        // nothing to do is not a failure for callers that build repeatedly
+        Err(e) if !json && is_unchanged(&e) => println!("{}", e),
+        Err(e) if json => {
+            let message = e
+                .chain()
+                .map(|e| e.to_string())
+                .collect::<Vec<_>>()
+                .join(": ");
+            let error = e.downcast_ref::<makepkg::error::Error>();
+            let _ = print_json(serde_json::json!({
+                "type": "error",
+                "error": error,
+                "message": message,
+            }));
+            if !is_unchanged(&e) {
+                std::process::exit(1);
+            }
+        }
         Err(e) => {
             print_error(Style::new().fg(Color::Red).bold(), e);
             std::process::exit(1);
@@ -39,39 +74,128 @@ pub fn main() {
     }
 }
 
-fn run() -> Result<()> {
-    let cli = args::Args::parse();
+fn run(mut cli: args::Args) -> Result<()> {
+    // packaging runs as fakeroot so allow this as root
+    if cli.generate_man {
+        let man = clap_mangen::Man::new(args::Args::command()).title("makepkg-rs");
+        man.render(&mut stdout().lock())?;
+        return Ok(());
+    }
 
     if Uid::current().is_root() {
         bail!("running {} as root is not allowed", env!("CARGO_PKG_NAME"))
     }
 
-    if let Some(path) = &cli.chdir {
+    let dirs = cli
+        .chdir
+        .iter()
+        .chain(&cli.dirs)
+        .cloned()
+        .collect::<Vec<_>>();
+    if let [path] = dirs.as_slice() {
         set_current_dir(path).with_context(|| format!("failed to cd into {}", path.display()))?;
     }
 
-    let config = if let Some(config) = cli.config {
+    if let Some(args::Command::Diff { old, new }) = &cli.command {
+        let diff = makepkg::diff_packages(old, new)?;
+        if cli.json {
+            print_json(serde_json::json!({
+                "type": "package_diff",
+                "diff": diff,
+            }))?;
+        } else {
+            print!("{}", diff);
+        }
+        return Ok(());
+    }
+
+    // subcommands are shorthands for the top level flags
+    match &cli.command {
+        Some(args::Command::Source { all }) => {
+            cli.source = !all;
+            cli.allsource = *all;
+        }
+        Some(args::Command::Srcinfo { write, check }) => {
+            cli.printsrcinfo = !check;
+            cli.checksrcinfo |= check;
+            cli.write |= write;
+        }
+        Some(args::Command::Geninteg { update }) => {
+            cli.geninteg = true;
+            cli.update |= update;
+        }
+        Some(args::Command::Verify { all }) => {
+            cli.verifysource = true;
+            cli.allarch |= all;
+        }
+        Some(args::Command::Packagelist) => cli.packagelist = true,
+        Some(args::Command::Build | args::Command::Clean | args::Command::Diff { .. }) | None => (),
+    }
+
+    let mut config = if let Some(config) = &cli.config {
         Config::from_path(config)?
     } else {
         Config::new()?
     };
 
-    let color = config.build_env("color").enabled() && !cli.nocolor && stdout().is_terminal();
-    let makepkg = Makepkg::from_config(config).callbacks(Printer::new(color));
-    let mut pkgbuild = Pkgbuild::new(".")?;
+    let mut color = cli.color;
+    let no_color = env::var_os("NO_COLOR").is_some_and(|v| !v.is_empty());
+    if cli.nocolor || (color == ColorChoice::Auto && no_color) {
+        color = ColorChoice::Never;
+    }
+
+    match color {
+        ColorChoice::Auto if !config.build_env("color").enabled() => color = ColorChoice::Never,
+        ColorChoice::Auto => (),
+        ColorChoice::Always | ColorChoice::Never => {
+            let value = if color == ColorChoice::Always {
+                "color"
+            } else {
+                "!color"
+            };
+            config.build_env.values.retain(|v| v.name != "color");
+            config.build_env.values.push(OptionValue::new(value));
+        }
+    }
 
+    if cli.dump_config {
+        config.write_makepkg_conf(&mut stdout().lock())?;
+        return Ok(());
+    }
+
+    let verbosity = Verbosity::new(cli.quiet, cli.verbose);
+    let mut makepkg = if cli.json {
+        Makepkg::from_config(config).callbacks(JsonPrinter)
+    } else {
+        Makepkg::from_config(config).callbacks(Printer::new(color, verbosity, cli.noconfirm))
+    };
     let mut options = Options {
         no_deps: cli.nodeps,
         sync_deps: cli.syncdeps,
+        rm_deps: cli.rmdeps,
         install: cli.install,
+        as_deps: cli.asdeps,
+        install_intermediates: cli.install_intermediates,
+        needed: cli.needed,
+        no_confirm: cli.noconfirm,
         log: cli.log,
+        build_log: cli.buildlog,
         clean: false,
         clean_build: cli.cleanbuild,
         ignore_arch: cli.ignorearch,
         hold_ver: cli.holdver,
         no_download: false,
+        all_arch: cli.allarch,
+        no_space_check: cli.nospacecheck,
+        conditional_download: cli.conditional_download,
+        clean_vcs_locks: cli.cleanvcslocks,
+        host_info: false,
+        size_count_hardlinks: false,
+        size_allocated: false,
+        size_exclude_metadata: false,
         no_checksums: cli.skipchecksums || cli.skipinteg,
         no_signatures: cli.skippgpcheck || cli.skipinteg,
+        recv_keys: cli.recvkeys,
         no_verify: cli.noverify,
         no_extract: cli.noextract,
         no_prepare: cli.noprepare,
@@ -81,6 +205,19 @@ fn run() -> Result<()> {
         no_package: false,
         no_archive: cli.noarchive,
         rebuild: cli.force,
+        sandbox: cli.sandbox,
+        confine_package: cli.confinepackage,
+        normalize_file_names: cli.normalizenames,
+        srcdir_snapshot: cli.snapshot,
+        no_network: cli.nonetwork,
+        timeout: cli.timeout.map(Duration::from_secs),
+        pty: cli.pty,
+        interactive: cli.interactive,
+        resume: cli.resume,
+        skip_unchanged: cli.skip_unchanged,
+        sign: cli.sign,
+        no_sign: cli.nosign,
+        packaging_backend: None,
     };
 
     if cli.repackage {
@@ -91,19 +228,56 @@ fn run() -> Result<()> {
         options.no_build();
     }
 
+    if dirs.len() > 1 {
+        return build_dirs(&cli, &makepkg, &options, &dirs);
+    }
+
+    let mut pkgbuild = makepkg.load_pkgbuild(".")?;
+
+    if let Some(args::Command::Clean) = cli.command {
+        makepkg.clean_build_dirs(&pkgbuild)?;
+        return Ok(());
+    }
+
+    if cli.geninteg && cli.update {
+        makepkg.update_checksums(&options, &mut pkgbuild, &[])?;
+        return Ok(());
+    }
     if cli.geninteg {
         let integ = makepkg.geninteg(&options, &pkgbuild)?;
         println!("{}", integ);
         return Ok(());
     }
+    if cli.checksrcinfo {
+        if !pkgbuild.srcinfo_is_current() {
+            bail!(".SRCINFO is out of date");
+        }
+        return Ok(());
+    }
+    if cli.printsrcinfo && cli.write {
+        pkgbuild.update_srcinfo_file()?;
+        return Ok(());
+    }
     if cli.printsrcinfo {
         pkgbuild.write_srcinfo(&mut stdout().lock())?;
         return Ok(());
     }
+    if cli.printbuildenv {
+        let mut stdout = stdout().lock();
+        for (key, value) in makepkg.build_environment(&pkgbuild)? {
+            writeln!(stdout, "{}={}", key, value.to_string_lossy())?;
+        }
+        return Ok(());
+    }
     if cli.packagelist {
         let mut stdout = stdout().lock();
-        for path in makepkg.config().package_list(&pkgbuild)? {
-            stdout.write_all(path.as_os_str().as_bytes())?;
+        let sep = if cli.null { b'\0' } else { b'\n' };
+        for package in makepkg.config().package_list(&pkgbuild)? {
+            if cli.nodebug && package.kind == PackageKind::Debug {
+                continue;
+            }
+            stdout.write_all(package.path.as_os_str().as_bytes())?;
+            stdout.write_all(&[sep])?;
         }
         return Ok(());
     }
@@ -112,6 +286,87 @@ fn run() -> Result<()> {
         return Ok(());
     }
 
+    if let Some(dir) = cli.chroot {
+        makepkg.build_in_chroot(&options, &pkgbuild, &Chroot::new(dir))?;
+        return Ok(());
+    }
+    if let Some(host) = cli.remote {
+        makepkg.build_remote(&pkgbuild, &RemoteBuilder::new(host))?;
+        return Ok(());
+    }
+
+    if cli.checkrepro {
+        let report = makepkg.check_reproducible(&options, &mut pkgbuild)?;
+        if cli.json {
+            print_json(serde_json::json!({
+                "type": "reproducibility",
+                "report": report,
+            }))?;
+        } else {
+            print!("{}", report);
+        }
+        if !report.is_reproducible() {
+            bail!("{} is not reproducible", pkgbuild.pkgbase);
+        }
+        return Ok(());
+    }
+
     makepkg.build(&options, &mut pkgbuild)?;
     Ok(())
 }
+
+fn build_dirs(
+    cli: &args::Args,
+    makepkg: &Makepkg,
+    options: &Options,
+    dirs: &[PathBuf],
+) -> Result<()> {
+    if cli.geninteg
+        || cli.printsrcinfo
+        || cli.checksrcinfo
+        || cli.printbuildenv
+        || cli.packagelist
+        || cli.source
+        || cli.allsource
+        || cli.chroot.is_some()
+        || cli.remote.is_some()
+        || cli.checkrepro
+    {
+        bail!("only building and cleaning support multiple directories");
+    }
+
+    let mut pkgbuilds = dirs
+        .iter()
+        .map(|dir| {
+            makepkg
+                .load_pkgbuild(dir)
+                .with_context(|| format!("failed to load {}", dir.display()))
+        })
+        .collect::<Result<Vec<_>>>()?;
+
+    if let Some(args::Command::Clean) = cli.command {
+        for pkgbuild in &pkgbuilds {
+            makepkg.clean_build_dirs(pkgbuild)?;
+        }
+        return Ok(());
+    }
+
+    let report = makepkg.build_many(options, &mut pkgbuilds)?;
+    if cli.json {
+        print_json(serde_json::json!({
+            "type": "batch",
+            "report": report,
+        }))?;
+    } else {
+        print!("{}", report);
+    }
+    if !report.is_success() {
+        let unbuilt = report.unbuilt().count();
+        bail!(
+            "{} of {} PKGBUILDs were not built",
+            unbuilt,
+            report.builds.len()
+        );
+    }
+    Ok(())
+}
@@ -1,19 +1,20 @@
 use std::{
     collections::HashMap,
-    io::{self, stdout, Write},
+    io::{self, stdin, stdout, IsTerminal, Write},
     mem::replace,
 };
 
+use crate::{args::ColorChoice, filter::Verbosity};
+
 use ansi_term::{Color::*, Style};
-use indicatif::{MultiProgress, ProgressBar, ProgressFinish, ProgressStyle};
+use indicatif::{HumanBytes, MultiProgress, ProgressBar, ProgressFinish, ProgressStyle};
 use makepkg::{
-    pkgbuild::Pkgbuild, Callbacks, CommandKind, CommandOutput, DownloadEvent, Event, LogLevel,
-    LogMessage,
+    pkgbuild::Pkgbuild, Answer, Callbacks, CommandKind, CommandOutput, DownloadEvent, Event,
+    LogLevel, LogMessage, PackageEvent, Question,
 };
 
 #[derive(Debug, Default, Copy, Clone)]
 pub struct Colors {
-    pub enabled: bool,
     pub general: Style,
     pub error: Style,
     pub warning: Style,
@@ -24,7 +25,6 @@ pub struct Colors {
 impl Colors {
     pub fn new() -> Colors {
         Colors {
-            enabled: true,
             general: Style::new(),
             error: Style::new().fg(Red).bold(),
             warning: Style::new().fg(Yellow).bold(),
@@ -37,9 +37,14 @@ impl Colors {
 #[derive(Debug)]
 pub struct Printer {
     colors: Colors,
+    verbosity: Verbosity,
+    no_confirm: bool,
     start_line: bool,
     progress: indicatif::MultiProgress,
     bars: HashMap<usize, indicatif::ProgressBar>,
+    package_bar: Option<ProgressBar>,
+    // width of the hashing percentage written after "..."
+    hash_width: usize,
     //term_width: Option<u16>,
     msg_width: u16,
 }
@@ -47,19 +52,30 @@ pub struct Printer {
 impl Callbacks for Printer {
     fn event(&mut self, event: Event) -> io::Result<()> {
         let c = self.colors;
+        if !self.verbosity.show_event(&event) {
+            return Ok(());
+        }
 
         match event {
             Event::FoundSource(_)
+            | Event::DownloadingCurl(_)
             | Event::Downloading(_)
             | Event::NoExtact(_)
             | Event::Extacting(_)
+            | Event::UsingCachedExtraction(_)
             | Event::RemovingSrcdir
             | Event::RemovingPkgdir
+            | Event::RemovedDir(..)
+            | Event::PossiblyUnusedDepend(..)
+            | Event::NormalizedFileName(_)
             | Event::AddingFileToPackage(_)
             | Event::GeneratingPackageFile(_)
             | Event::DownloadingVCS(_, _)
             | Event::ExtractingVCS(_, _)
-            | Event::UpdatingVCS(_, _) => {
+            | Event::UpdatingVCS(_, _)
+            | Event::RetryingVCS(..)
+            | Event::ReceivingKey(_)
+            | Event::ReceivedKey(_) => {
                 writeln!(stdout(), "    {}", c.general.paint(event.to_string()))
             }
             Event::VerifyingChecksum(_) | Event::VerifyingSignature(_) => {
@@ -72,7 +88,6 @@ impl Callbacks for Printer {
             Event::ChecksumSkipped(_) | Event::ChecksumPass(_) | Event::SignatureCheckPass(_) => {
                 writeln!(stdout(), " {}", c.general.paint(event.to_string()))
             }
-            Event::DownloadingCurl(_) => Ok(()),
             _ => {
                 writeln!(
                     stdout(),
@@ -86,12 +101,16 @@ impl Callbacks for Printer {
 
     fn log(&mut self, level: LogLevel, msg: LogMessage) -> io::Result<()> {
         let c = self.colors;
+        if !self.verbosity.show_log(level) {
+            return Ok(());
+        }
+
         match level {
             LogLevel::Warning => {
                 writeln!(stdout(), "{}: {}", c.warning.paint(level.to_string()), msg)
             }
             LogLevel::Error => writeln!(stdout(), "{}: {}", c.error.paint(level.to_string()), msg),
-            _ => Ok(()),
+            LogLevel::Debug => writeln!(stdout(), "{}: {}", c.bold.paint(level.to_string()), msg),
         }
     }
 
@@ -99,10 +118,21 @@ impl Callbacks for Printer {
         &mut self,
         _id: usize,
         kind: makepkg::CommandKind,
+        command: &makepkg::CommandInfo,
     ) -> io::Result<makepkg::CommandOutput> {
         self.start_line = true;
+        if self.verbosity.show_commands() {
+            let argv = command.argv.join(" ");
+            writeln!(
+                stdout(),
+                "    {}",
+                self.colors.general.paint(format!("$ {}", argv))
+            )?;
+        }
         match kind {
-            CommandKind::PkgbuildFunction(_) => Ok(CommandOutput::Inherit),
+            CommandKind::PkgbuildFunction(_)
+            | CommandKind::RemoteBuild(_)
+            | CommandKind::Chroot(_) => Ok(CommandOutput::Inherit),
             _ => Ok(CommandOutput::Callback),
         }
     }
@@ -119,7 +149,7 @@ impl Callbacks for Printer {
                     write!(stdout(), "    ")?;
                 }
                 stdout().write_all(line).unwrap();
-                if line.ends_with(&[b'\n']) {
+                if line.ends_with(b"\n") {
                     self.start_line = true;
                 }
             }
@@ -159,10 +189,107 @@ impl Callbacks for Printer {
         }
         Ok(())
     }
+
+    fn package(&mut self, event: PackageEvent) -> io::Result<()> {
+        let msg = match event {
+            PackageEvent::CollectingFiles(_, n) => format!("found {} files", n),
+            PackageEvent::Archiving(name, size, elapsed) => {
+                let rate = size as f64 / elapsed.as_secs_f64().max(0.001);
+                format!(
+                    "writing {} {} ({}/s)",
+                    name,
+                    HumanBytes(size),
+                    HumanBytes(rate as u64)
+                )
+            }
+            PackageEvent::CollectedFiles(..) | PackageEvent::Archived(..) => {
+                if let Some(bar) = self.package_bar.take() {
+                    bar.finish_and_clear();
+                }
+                return Ok(());
+            }
+        };
+
+        let bar = self.package_bar.get_or_insert_with(|| {
+            let style = ProgressStyle::with_template("    {spinner} {msg}").unwrap();
+            ProgressBar::new_spinner().with_style(style)
+        });
+        bar.set_message(msg);
+        bar.tick();
+        Ok(())
+    }
+
+    fn question(&mut self, question: Question) -> io::Result<Answer> {
+        if self.no_confirm || !stdin().is_terminal() {
+            return Ok(Answer::No);
+        }
+
+        let c = self.colors;
+        let (file_name, prompt) = match question {
+            Question::ImportKey {
+                file_name,
+                fingerprint,
+            } => (
+                file_name,
+                format!("Import key {} from keyserver?", fingerprint),
+            ),
+            Question::TrustKey {
+                file_name,
+                fingerprint,
+            } => (
+                file_name,
+                format!("Trust key {} for this build?", fingerprint),
+            ),
+        };
+
+        // questions are asked part way through the "verifying ..." line of the source
+        let mut stdout = stdout().lock();
+        writeln!(stdout)?;
+        write!(
+            stdout,
+            "{} {} [y/N] ",
+            c.action.paint("::"),
+            c.bold.paint(prompt)
+        )?;
+        stdout.flush()?;
+
+        let mut line = String::new();
+        stdin().read_line(&mut line)?;
+        let answer = match line.trim() {
+            "y" | "Y" | "yes" | "Yes" => Answer::Yes,
+            _ => Answer::No,
+        };
+
+        write!(stdout, "    {} ...", c.general.paint(file_name))?;
+        stdout.flush()?;
+        Ok(answer)
+    }
+
+    fn hashing(&mut self, _file_name: &str, hashed: u64, total: u64) -> io::Result<()> {
+        if !stdout().is_terminal() {
+            return Ok(());
+        }
+
+        let mut stdout = stdout().lock();
+        let back = "\x08".repeat(self.hash_width);
+        write!(stdout, "{back}{:width$}{back}", "", width = self.hash_width)?;
+        self.hash_width = 0;
+        if hashed < total {
+            let msg = format!(" {}%", hashed * 100 / total);
+            write!(stdout, "{}", msg)?;
+            self.hash_width = msg.len();
+        }
+        stdout.flush()
+    }
 }
 
 impl Printer {
-    pub fn new(color: bool) -> Self {
+    pub fn new(color: ColorChoice, verbosity: Verbosity, no_confirm: bool) -> Self {
+        let color = match color {
+            ColorChoice::Auto => stdout().is_terminal(),
+            ColorChoice::Always => true,
+            ColorChoice::Never => false,
+        };
         let colors = if color {
             Colors::new()
         } else {
@@ -170,15 +297,19 @@ impl Printer {
         };
 
         let term_width = terminal_size::terminal_size().map(|s| s.0 .0);
-        let msg_width = term_width.unwrap_or(50) * 6 / 10 - 36;
+        let msg_width = (term_width.unwrap_or(50) * 6 / 10).saturating_sub(36);
 
         Printer {
             colors,
+            verbosity,
+            no_confirm,
             start_line: true,
             //term_width,
             msg_width,
             progress: MultiProgress::new(),
             bars: HashMap::new(),
+            package_bar: None,
+            hash_width: 0,
         }
     }
 
@@ -1,10 +1,17 @@
-use std::{cell::RefCell, process::Child};
+use std::{
+    cell::{Cell, RefCell},
+    path::PathBuf,
+    process::Child,
+};
 
 use crate::{
+    build_log::BuildLog,
     callback::Callbacks,
     config::{Config, PkgbuildDirs},
     error::Result,
+    host::HostEnvironment,
     pkgbuild::Pkgbuild,
+    report::BuildReport,
 };
 
 #[derive(Debug)]
@@ -29,14 +36,34 @@ impl FakeRoot {
     }
 }
 
+/// The state for building packages.
+///
+/// A Makepkg is [`Send`] but not [`Sync`], it can be moved to a worker thread but only be used
+/// by one thread at a time.
 #[derive(Debug)]
 pub struct Makepkg {
     pub config: Config,
     pub(crate) callbacks: RefCell<Option<Box<dyn Callbacks>>>,
     pub(crate) fakeroot: RefCell<Option<FakeRoot>>,
     pub(crate) id: RefCell<usize>,
+    pub(crate) builddir: RefCell<Option<PathBuf>>,
+    /// A tmpfs mounted for the current build that needs unmounting.
+    pub(crate) tmpfs: RefCell<Option<PathBuf>>,
+    pub(crate) host: RefCell<Option<HostEnvironment>>,
+    pub(crate) build_log: RefCell<Option<BuildLog>>,
+    /// While set the output of every command run is also collected here.
+    pub(crate) captured_output: RefCell<Option<Vec<u8>>>,
+    /// Phase timings of the build in progress.
+    pub(crate) report: RefCell<Option<BuildReport>>,
+    pub(crate) shell_checked: Cell<bool>,
 }
 
+// a Makepkg is moved between threads by frontends so make sure it stays Send
+const _: fn() = || {
+    fn assert_send<T: Send>() {}
+    assert_send::<Makepkg>();
+};
+
 impl Makepkg {
     pub fn new() -> Result<Makepkg> {
         let config = Config::new()?;
@@ -49,6 +76,13 @@ impl Makepkg {
             callbacks: RefCell::new(None),
             fakeroot: RefCell::new(None),
             id: RefCell::new(0),
+            builddir: RefCell::new(None),
+            tmpfs: RefCell::new(None),
+            host: RefCell::new(None),
+            build_log: RefCell::new(None),
+            captured_output: RefCell::new(None),
+            report: RefCell::new(None),
+            shell_checked: Cell::new(false),
         }
     }
 
@@ -57,7 +91,10 @@ impl Makepkg {
     }
 
     pub fn pkgbuild_dirs(&self, pkgbuild: &Pkgbuild) -> Result<PkgbuildDirs> {
-        self.config.pkgbuild_dirs(pkgbuild)
+        match &*self.builddir.borrow() {
+            Some(builddir) => self.config.pkgbuild_dirs_in(pkgbuild, Some(builddir)),
+            None => self.config.pkgbuild_dirs(pkgbuild),
+        }
     }
 
     pub fn callbacks<CB: Callbacks>(mut self, callbacks: CB) -> Self {
@@ -0,0 +1,81 @@
+use std::{ffi::CString, io, os::unix::process::CommandExt, process::Command};
+
+use nix::{
+    libc,
+    sched::{unshare, CloneFlags},
+    unistd::{Gid, Uid},
+};
+
+use crate::{options::Options, Makepkg};
+
+impl Makepkg {
+    /// Runs a PKGBUILD function in a new network namespace so it can not reach the network.
+    ///
+    /// This is a no-op unless [`Options::no_network`] is set. An unprivileged user namespace is
+    /// created alongside the network namespace with the current user mapped to itself. Only the
+    /// loopback interface is available.
+    pub(crate) fn isolate_network(&self, options: &Options, command: &mut Command) {
+        if !options.no_network {
+            return;
+        }
+
+        let maps = [
+            ("/proc/self/setgroups", "deny".to_string()),
+            ("/proc/self/uid_map", format!("{0} {0} 1", Uid::current())),
+            ("/proc/self/gid_map", format!("{0} {0} 1", Gid::current())),
+        ]
+        .map(|(path, val)| (CString::new(path).unwrap(), val));
+
+        // Everything is allocated up front, the child only makes syscalls.
+        unsafe {
+            command.pre_exec(move || {
+                unshare(CloneFlags::CLONE_NEWUSER | CloneFlags::CLONE_NEWNET)?;
+                for (path, val) in &maps {
+                    write_proc(path, val.as_bytes())?;
+                }
+                loopback_up()
+            });
+        }
+    }
+}
+
+fn write_proc(path: &CString, val: &[u8]) -> io::Result<()> {
+    unsafe {
+        let fd = libc::open(path.as_ptr(), libc::O_WRONLY | libc::O_CLOEXEC);
+        if fd < 0 {
+            return Err(io::Error::last_os_error());
+        }
+        let ret = libc::write(fd, val.as_ptr().cast(), val.len());
+        let err = io::Error::last_os_error();
+        libc::close(fd);
+        if ret < 0 {
+            return Err(err);
+        }
+    }
+    Ok(())
+}
+
+// a new network namespace starts with lo down, many test suites expect it to work
+fn loopback_up() -> io::Result<()> {
+    unsafe {
+        let sock = libc::socket(libc::AF_INET, libc::SOCK_DGRAM | libc::SOCK_CLOEXEC, 0);
+        if sock < 0 {
+            return Err(io::Error::last_os_error());
+        }
+
+        let mut req: libc::ifreq = std::mem::zeroed();
+        req.ifr_name[..2].copy_from_slice(&[b'l' as libc::c_char, b'o' as libc::c_char]);
+
+        let mut ret = libc::ioctl(sock, libc::SIOCGIFFLAGS, &mut req);
+        if ret == 0 {
+            req.ifr_ifru.ifru_flags |= libc::IFF_UP as libc::c_short;
+            ret = libc::ioctl(sock, libc::SIOCSIFFLAGS, &req);
+        }
+        let err = io::Error::last_os_error();
+        libc::close(sock);
+        if ret < 0 {
+            return Err(err);
+        }
+    }
+    Ok(())
+}
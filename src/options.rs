@@ -1,9 +1,26 @@
+use std::time::Duration;
+
+use crate::config::PackagingBackend;
+
 #[derive(Debug, Clone, Default)]
 pub struct Options {
     pub no_deps: bool,
     pub sync_deps: bool,
+    /// Remove dependencies installed by [`Options::sync_deps`] after the build.
+    pub rm_deps: bool,
     pub install: bool,
+    /// Install packages as dependencies.
+    pub as_deps: bool,
+    /// Install packages that later PKGBUILDs depend on in
+    /// [`Makepkg::build_many`](crate::Makepkg::build_many) as dependencies.
+    pub install_intermediates: bool,
+    /// Don't reinstall packages that are already up to date.
+    pub needed: bool,
+    /// Pass --noconfirm to pacman.
+    pub no_confirm: bool,
     pub log: bool,
+    /// Write a single timestamped log of the whole build to LOGDEST.
+    pub build_log: bool,
 
     pub clean: bool,
     pub clean_build: bool,
@@ -11,8 +28,19 @@ pub struct Options {
     pub hold_ver: bool,
 
     pub no_download: bool,
+    /// Download and verify the sources of every architecture instead of only CARCH.
+    pub all_arch: bool,
+    /// Don't check that there is enough free space for the build before starting.
+    pub no_space_check: bool,
+    /// Re-request sources that already exist if the server reports they changed, using the
+    /// ETag and Last-Modified headers stored from the previous download.
+    pub conditional_download: bool,
+    /// Remove lock files left in VCS mirrors by interrupted updates once they are an hour old.
+    pub clean_vcs_locks: bool,
     pub no_checksums: bool,
     pub no_signatures: bool,
+    /// Receive keys from validpgpkeys that are missing from the keyring without asking first.
+    pub recv_keys: bool,
     pub no_verify: bool,
     pub no_extract: bool,
     pub no_prepare: bool,
@@ -22,6 +50,55 @@ pub struct Options {
     pub no_package: bool,
     pub no_archive: bool,
     pub rebuild: bool,
+    /// Restrict download agents, extractors and compressors to writing beneath the directories
+    /// they work in using Landlock and filter dangerous syscalls with seccomp.
+    pub sandbox: bool,
+    /// Only allow package() to write beneath pkgdir and srcdir.
+    pub confine_package: bool,
+    /// Rename packaged files to their NFC normalized names before archiving.
+    pub normalize_file_names: bool,
+    /// Keep a copy of the extracted sources and restore it instead of extracting again when
+    /// srcdir is empty, such as with [`Options::clean_build`].
+    pub srcdir_snapshot: bool,
+    /// Run build(), check() and package() without network access.
+    pub no_network: bool,
+    /// Kill a PKGBUILD function along with everything it started if it runs for longer than
+    /// this. The function is run in its own process group so it will not receive signals
+    /// sent to the terminal.
+    pub timeout: Option<Duration>,
+    /// Run build() and check() with a pseudo-terminal as their output so tools that check for
+    /// a terminal produce colored output and progress.
+    pub pty: bool,
+    /// Connect makepkg's stdin to PKGBUILD functions instead of /dev/null so they can prompt
+    /// the user. Callbacks can refuse this per command with [`Callbacks::command_stdin`].
+    ///
+    /// [`Callbacks::command_stdin`]: crate::Callbacks::command_stdin
+    pub interactive: bool,
+    /// Skip the phases a previous failed build of the same PKGBUILD already completed.
+    /// Downloading and verifying, extracting, build() and check() are each only done once.
+    pub resume: bool,
+    /// Fail with [`Error::Unchanged`](crate::error::Error::Unchanged) instead of building if
+    /// the packages were last built from identical PKGBUILD, sources and configuration.
+    ///
+    /// Builds with VCS sources are never considered unchanged.
+    pub skip_unchanged: bool,
+    /// Sign packages even if the sign BUILDENV option is not set.
+    pub sign: bool,
+    /// Never sign packages.
+    pub no_sign: bool,
+    /// Record the [`HostEnvironment`](crate::HostEnvironment) in .BUILDINFO.
+    pub host_info: bool,
+    /// Count every hard link to a file towards the installed size instead of only the first.
+    pub size_count_hardlinks: bool,
+    /// Count the disk blocks files use towards the installed size instead of their apparent
+    /// size, so sparse files only count the space they occupy.
+    pub size_allocated: bool,
+    /// Leave files in the root of pkgdir starting with a dot, such as .INSTALL, out of the
+    /// installed size.
+    pub size_exclude_metadata: bool,
+    /// Overrides [`Config::packaging_backend`](crate::config::Config::packaging_backend) for
+    /// this build.
+    pub packaging_backend: Option<PackagingBackend>,
 }
 
 impl Options {
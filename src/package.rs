@@ -2,38 +2,95 @@ use std::{
     collections::HashSet,
     fmt::Display,
     fs::File,
-    io::Write,
+    io::{self, Write},
     os::{
         unix::fs::MetadataExt,
         unix::{ffi::OsStrExt, fs::PermissionsExt},
     },
-    path::Path,
+    path::{Path, PathBuf},
     process::{Command, Stdio},
+    time::{Duration, Instant},
 };
 
-use nix::{
-    sys::stat::{umask, Mode},
-    NixPath,
-};
+use nix::sys::stat::{umask, Mode};
 use sha2::Sha256;
 
 use crate::{
-    callback::{CommandKind, Event, LogLevel, LogMessage},
-    config::PkgbuildDirs,
-    error::{CommandErrorExt, CommandOutputExt, Context, IOContext, IOErrorExt, Result},
+    callback::{Callbacks, CommandKind, Event, LogLevel, LogMessage, PackageEvent},
+    config::{PackagingBackend, PkgbuildDirs},
+    error::{
+        CommandError, CommandErrorExt, CommandOutputExt, Context, IOContext, IOErrorExt, Result,
+        SigningKeyError,
+    },
     fs::{copy, copy_dir, mkdir, open, rm_all, set_time, write},
     installation_variables::FAKEROOT_LIBDIRS,
     integ::hash_file,
     options::Options,
+    package_contents::read_pkginfo,
     pacman::buildinfo_installed,
     pkgbuild::{Package, Pkgbuild},
     run::CommandOutput,
     FakeRoot, Makepkg,
 };
 
+// How often to report progress while listing files and writing archives.
+const FILES_PROGRESS_STEP: usize = 1000;
+const ARCHIVE_PROGRESS_INTERVAL: Duration = Duration::from_millis(500);
+
+struct ArchiveProgress {
+    name: String,
+    file: File,
+    start: Instant,
+    last: Instant,
+}
+
+impl ArchiveProgress {
+    fn new(name: &str, file: &File) -> io::Result<Self> {
+        let now = Instant::now();
+        Ok(ArchiveProgress {
+            name: name.to_string(),
+            file: file.try_clone()?,
+            start: now,
+            last: now,
+        })
+    }
+
+    fn tick(&mut self, callbacks: &mut dyn Callbacks) -> io::Result<()> {
+        if self.last.elapsed() < ARCHIVE_PROGRESS_INTERVAL {
+            return Ok(());
+        }
+        self.last = Instant::now();
+        let size = self.file.metadata()?.len();
+        callbacks.package(PackageEvent::Archiving(
+            &self.name,
+            size,
+            self.start.elapsed(),
+        ))
+    }
+
+    fn finish(self, makepkg: &Makepkg) -> Result<()> {
+        let size = self
+            .file
+            .metadata()
+            .context(
+                Context::CreatePackage,
+                IOContext::Stat(self.name.clone().into()),
+            )?
+            .len();
+        makepkg.package(PackageEvent::Archived(
+            &self.name,
+            size,
+            self.start.elapsed(),
+        ))
+    }
+}
+
 #[derive(Debug, Copy, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize))]
 pub enum PackageKind {
     Package,
+    /// The detached debug symbols of a pkgbase.
+    Debug,
     Source,
 }
 
@@ -41,11 +98,64 @@ impl Display for PackageKind {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
         match self {
             PackageKind::Package => f.write_str("package"),
+            PackageKind::Debug => f.write_str("debug package"),
             PackageKind::Source => f.write_str("source package"),
         }
     }
 }
 
+/// A package file produced by a build.
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize))]
+pub struct BuiltPackage {
+    pub path: PathBuf,
+    /// The pkgname, or the pkgbase for source and debug packages.
+    pub pkgname: String,
+    /// The full version including the epoch and pkgrel.
+    pub version: String,
+    /// The architecture, `None` for source packages.
+    pub arch: Option<String>,
+    /// The size of the package file.
+    pub size: u64,
+    /// The installed size recorded in .PKGINFO, `None` for source packages.
+    pub installed_size: Option<u64>,
+    pub sha256: String,
+    pub kind: PackageKind,
+}
+
+impl BuiltPackage {
+    fn new(
+        path: PathBuf,
+        pkgname: &str,
+        version: String,
+        arch: Option<&str>,
+        kind: PackageKind,
+    ) -> Result<Self> {
+        let size = path
+            .metadata()
+            .context(Context::BuildPackage, IOContext::Stat(path.clone()))?
+            .len();
+        let installed_size = if kind == PackageKind::Source {
+            None
+        } else {
+            read_pkginfo(&path, Context::BuildPackage)?
+                .get("size")
+                .and_then(|s| s.first())
+                .and_then(|s| s.parse().ok())
+        };
+        Ok(BuiltPackage {
+            installed_size,
+            sha256: hash_file::<Sha256>(&path)?,
+            path,
+            pkgname: pkgname.to_string(),
+            version,
+            arch: arch.map(|a| a.to_string()),
+            size,
+            kind,
+        })
+    }
+}
+
 impl Makepkg {
     pub(crate) fn create_package(
         &self,
@@ -63,8 +173,14 @@ impl Makepkg {
 
         let pkgdir = dirs.pkgdir(pkg);
 
-        self.generate_pkginfo(dirs, pkgbuild, pkg, debug)?;
-        self.generate_buildinfo(dirs, pkgbuild, pkg)?;
+        if options.normalize_file_names {
+            self.normalize_file_names(dirs, pkg)?;
+        }
+        self.generate_pkginfo(dirs, options, pkgbuild, pkg, debug)?;
+        self.generate_buildinfo(dirs, options, pkgbuild, pkg)?;
+        if !debug {
+            self.lint_package(dirs, pkgbuild, pkg)?;
+        }
 
         if let Some(install) = &pkg.install {
             let dest = pkgdir.join(".INSTALL");
@@ -89,12 +205,15 @@ impl Makepkg {
             set_time(file.path(), self.config.source_date_epoch, false)?;
         }
 
-        self.generate_mtree(dirs, pkgbuild, pkg)?;
+        self.generate_mtree(dirs, options, pkgbuild, pkg)?;
 
         set_time(pkgdir.join(".MTREE"), self.config.source_date_epoch, false)?;
 
         if !options.no_archive {
-            self.make_archive(dirs, pkgbuild, &pkgbuild.packages[0], false)?;
+            let pkgfile = self.make_archive(dirs, options, pkgbuild, pkg, false)?;
+            if self.sign_enabled(options, pkgbuild) {
+                self.sign_package(pkgbuild, &pkgfile)?;
+            }
         }
 
         Ok(())
@@ -103,6 +222,7 @@ impl Makepkg {
     fn generate_mtree(
         &self,
         dirs: &PkgbuildDirs,
+        options: &Options,
         pkgbuild: &Pkgbuild,
         pkg: &Package,
     ) -> Result<()> {
@@ -113,10 +233,15 @@ impl Makepkg {
         let mtree = pkgdir.join(".MTREE");
         let mut file = File::options();
         file.create(true).write(true).truncate(true);
-        let mtree = open(&file, mtree, Context::GeneratePackageFile(".MTREE".into()))?;
+        let mtree = open(&file, &mtree, Context::GeneratePackageFile(".MTREE".into()))?;
+        let mut progress = ArchiveProgress::new(".MTREE", &mtree).context(
+            Context::GeneratePackageFile(".MTREE".into()),
+            IOContext::Open(pkgdir.join(".MTREE")),
+        )?;
 
         let mut tarcmd = Command::new("bsdtar");
-        self.fakeroot_env(&mut tarcmd)?;
+        self.fakeroot_env(options, &mut tarcmd)?;
+        self.tar_ownership(options, &mut tarcmd);
         tarcmd
             .arg("-cnf")
             .arg("-")
@@ -135,36 +260,43 @@ impl Makepkg {
         let mut gzip = Command::new("gzip");
         gzip.arg("-cfn").stdout(mtree);
 
+        let context = Context::GeneratePackageFile(".MTREE".into());
+        self.sandbox(options, &mut tarcmd, &[], context.clone())?;
+        self.sandbox(options, &mut gzip, &[], context)?;
+
         tarcmd
             .process_pipe(
                 self,
                 CommandKind::BuildingPackage(pkgbuild),
                 files.as_slice(),
                 &mut gzip,
+                Some(&mut |cb| progress.tick(cb)),
             )
             .cmd_context(&tarcmd, Context::GeneratePackageFile(".MTREE".into()))?;
 
+        progress.finish(self)?;
         Ok(())
     }
 
     fn make_archive(
         &self,
         dirs: &PkgbuildDirs,
+        options: &Options,
         pkgbuild: &Pkgbuild,
         pkg: &Package,
         srcpkg: bool,
-    ) -> Result<()> {
+    ) -> Result<PathBuf> {
         let pkgdir;
         let pkgname;
         let pkgfilename;
-        let pkgfile;
+        let pkgfile_path;
         let compress;
 
         if srcpkg {
             pkgname = pkgbuild.pkgbase.as_str();
             pkgdir = dirs.srcpkgdir.parent().unwrap().to_path_buf();
             pkgfilename = format!("{}-{}{}", pkgname, pkgbuild.version(), self.config.srcext);
-            pkgfile = dirs.srcpkgdest.join(&pkgfilename);
+            pkgfile_path = dirs.srcpkgdest.join(&pkgfilename);
             compress = self.config.srcext.compress();
         } else {
             pkgname = pkg.pkgname.as_str();
@@ -172,11 +304,15 @@ impl Makepkg {
             pkgfilename = format!(
                 "{}-{}-{}{}",
                 pkgname,
-                pkgbuild.version(),
-                self.config.arch,
+                self.config.package_version(pkgbuild),
+                self.config.package_arch(&pkg.arch),
                 self.config.pkgext
             );
-            pkgfile = dirs.srcpkgdest.join(&pkgfilename);
+            let dest = dirs.package_dest(pkgname, PackageKind::Package);
+            if !dest.exists() {
+                mkdir(dest, Context::CreatePackage)?;
+            }
+            pkgfile_path = dest.join(&pkgfilename);
             compress = self.config.pkgext.compress();
         };
 
@@ -194,10 +330,15 @@ impl Makepkg {
 
         let mut file = File::options();
         file.create(true).write(true).truncate(true);
-        let pkgfile = open(&file, pkgfile, Context::CreatePackage)?;
+        let pkgfile = open(&file, &pkgfile_path, Context::CreatePackage)?;
+        let mut progress = ArchiveProgress::new(&pkgfilename, &pkgfile).context(
+            Context::CreatePackage,
+            IOContext::Open(pkgfile_path.clone()),
+        )?;
 
         let mut tarcmd = Command::new("bsdtar");
-        self.fakeroot_env(&mut tarcmd)?;
+        self.fakeroot_env(options, &mut tarcmd)?;
+        self.tar_ownership(options, &mut tarcmd);
 
         tarcmd
             .arg("--no-fflags")
@@ -225,21 +366,140 @@ impl Makepkg {
         let mut zipcmd = Command::new(compress_prog);
         zipcmd.args(&compress[1..]).stdout(pkgfile);
 
+        self.sandbox(options, &mut tarcmd, &[], Context::CreatePackage)?;
+        self.sandbox(options, &mut zipcmd, &[], Context::CreatePackage)?;
+
         tarcmd
             .process_pipe(
                 self,
                 CommandKind::BuildingPackage(pkgbuild),
                 files.as_slice(),
                 &mut zipcmd,
+                Some(&mut |cb| progress.tick(cb)),
             )
             .cmd_context(&tarcmd, Context::CreatePackage)?;
 
+        progress.finish(self)?;
+        Ok(pkgfile_path)
+    }
+
+    fn sign_enabled(&self, options: &Options, pkgbuild: &Pkgbuild) -> bool {
+        !options.no_sign && (options.sign || self.config.build_option(pkgbuild, "sign").enabled())
+    }
+
+    /// Creates a detached signature next to a package file, returning the signature's path.
+    pub(crate) fn sign_package(&self, pkgbuild: &Pkgbuild, pkgfile: &Path) -> Result<PathBuf> {
+        self.event(Event::SigningPackage(&file_name(pkgfile)))?;
+        let (mut command, sig) = self.sign_command(pkgfile, self.config.gpgkey.as_deref())?;
+        command
+            .process_spawn(self, CommandKind::BuildingPackage(pkgbuild))
+            .cmd_context(&command, Context::SignPackage)?;
+
+        self.event(Event::PackageSigned(&sig))?;
+        Ok(sig)
+    }
+
+    /// Creates a detached `.sig` signature for a file that has already been built, returning
+    /// the signature's path.
+    ///
+    /// This uses the configured gpg settings. If `key` is `None` the configured GPGKEY is
+    /// used, falling back to gpg's default key.
+    pub fn sign_existing(&self, path: &Path, key: Option<&str>) -> Result<PathBuf> {
+        let key = key.or(self.config.gpgkey.as_deref());
+        self.event(Event::SigningPackage(&file_name(path)))?;
+        let (mut command, sig) = self.sign_command(path, key)?;
+        // there's no PKGBUILD to attribute the output to so let gpg talk to the terminal
+        command
+            .status()
+            .cmd_context(&command, Context::SignPackage)?;
+
+        self.event(Event::PackageSigned(&sig))?;
+        Ok(sig)
+    }
+
+    /// Signs a repository database updated by repo-add, along with the matching `.files`
+    /// database if there is one, returning the signatures' paths.
+    ///
+    /// The configured GPGKEY is used, falling back to gpg's default key. The key is checked
+    /// before anything is signed and [`SigningKeyError`] is returned if it can't sign.
+    pub fn sign_repo_db(&self, db: &Path) -> Result<Vec<PathBuf>> {
+        let key = self.config.gpgkey.as_deref();
+        self.check_signing_key(key)?;
+
+        let mut dbs = vec![db.to_path_buf()];
+        if let Some(files) = files_db(db).filter(|f| f.exists()) {
+            dbs.push(files);
+        }
+
+        let mut sigs = Vec::new();
+        for db in dbs {
+            self.event(Event::SigningRepoDb(&file_name(&db)))?;
+            let (mut command, sig) = self.sign_command(&db, key)?;
+            command
+                .process_output()
+                .cmd_context(&command, Context::SignRepoDb)?;
+            self.event(Event::RepoDbSigned(&sig))?;
+            sigs.push(sig);
+        }
+        Ok(sigs)
+    }
+
+    // gpg only notices a missing, expired or revoked key once it tries to sign so look the
+    // key up first
+    fn check_signing_key(&self, key: Option<&str>) -> Result<()> {
+        let mut command = self.gpg_command();
+        command
+            .arg("--batch")
+            .arg("--with-colons")
+            .arg("--list-secret-keys");
+        if let Some(key) = key {
+            command.arg("--").arg(key);
+        }
+        // gpg exits non zero when the key doesn't exist, that's reported below
+        let output = command
+            .output()
+            .map_err(|e| CommandError::exec(e, &command, Context::SignRepoDb))?;
+
+        // field 12 of the primary key holds the capabilities of the whole key, upper case
+        // letters are the usable ones
+        let usable = output.status.success()
+            && String::from_utf8_lossy(&output.stdout)
+                .lines()
+                .filter(|l| l.starts_with("sec:"))
+                .any(|l| l.split(':').nth(11).is_some_and(|c| c.contains('S')));
+        if !usable {
+            return Err(SigningKeyError {
+                key: key.map(|k| k.to_string()),
+            }
+            .into());
+        }
         Ok(())
     }
 
+    fn sign_command(&self, path: &Path, key: Option<&str>) -> Result<(Command, PathBuf)> {
+        let mut sig = path.as_os_str().to_os_string();
+        sig.push(".sig");
+        let sig = PathBuf::from(sig);
+
+        let mut command = self.gpg_command();
+        command
+            .arg("--batch")
+            .arg("--yes")
+            .arg("--detach-sign")
+            .arg("--use-agent")
+            .arg("--no-armor");
+        if let Some(key) = key {
+            command.arg("-u").arg(key);
+        }
+        command.arg("--output").arg(&sig).arg("--").arg(path);
+
+        Ok((command, sig))
+    }
+
     fn generate_buildinfo(
         &self,
         dirs: &PkgbuildDirs,
+        options: &Options,
         pkgbuild: &Pkgbuild,
         pkg: &Package,
     ) -> Result<()> {
@@ -259,8 +519,11 @@ impl Makepkg {
         self.write_kv(p, &mut file, "format", "2")?;
         self.write_kv(p, &mut file, "pkgname", &pkg.pkgname)?;
         self.write_kv(p, &mut file, "pkgbase", &pkgbuild.pkgbase)?;
-        self.write_kv(p, &mut file, "pkgver", &pkgbuild.version())?;
-        self.write_kv(p, &mut file, "pkgarch", &c.arch)?;
+        self.write_kv(p, &mut file, "pkgver", &c.package_version(pkgbuild))?;
+        if let Some(suffix) = &c.version_suffix {
+            self.write_kv(p, &mut file, "version_suffix", suffix)?;
+        }
+        self.write_kv(p, &mut file, "pkgarch", c.package_arch(&pkg.arch))?;
         let hash = hash_file::<Sha256>(&dirs.pkgbuild)?;
         self.write_kv(p, &mut file, "pkgbuild_sha256sum", &hash)?;
         self.write_kv(p, &mut file, "packager", &c.packager)?;
@@ -284,13 +547,19 @@ impl Makepkg {
             p,
             &mut file,
             "buildenv",
-            c.build_env.values.iter().map(|s| s.to_string()),
+            c.effective_build_env(pkgbuild)
+                .values
+                .iter()
+                .map(|s| s.to_string()),
         )?;
         self.write_kvs(
             p,
             &mut file,
             "options",
-            c.options.values.iter().map(|s| s.to_string()),
+            c.effective_options(pkgbuild, pkg)
+                .values
+                .iter()
+                .map(|s| s.to_string()),
         )?;
 
         let installed = buildinfo_installed(self, pkgbuild);
@@ -299,19 +568,26 @@ impl Makepkg {
         if let Ok(installed) = installed {
             self.write_kvs(p, &mut file, "installed", installed)?;
         }
+
+        if options.host_info {
+            if let Some(host) = &*self.host.borrow() {
+                self.write_kvs(p, &mut file, "host", host.entries())?;
+            }
+        }
         Ok(())
     }
 
     fn generate_pkginfo(
         &self,
         dirs: &PkgbuildDirs,
+        options: &Options,
         pkgbuild: &Pkgbuild,
         pkg: &Package,
         debug: bool,
     ) -> Result<()> {
         self.event(Event::GeneratingPackageFile(".PKGINFO"))?;
 
-        let size = self.package_size(dirs, pkg)?;
+        let size = self.package_size(dirs, options, pkg)?;
         let c = self.config();
 
         let pkgdir = dirs.pkgdir(pkg).join(".PKGINFO");
@@ -323,14 +599,19 @@ impl Makepkg {
             Context::GeneratePackageFile(".PKGINFO".into()),
         )?;
 
-        let mut fakerootcmd = Command::new("fakeroot");
-        let fakeroot = fakerootcmd
-            .arg("-v")
-            .process_read(self, CommandKind::BuildingPackage(pkgbuild))
-            .read(
-                &fakerootcmd,
-                Context::GeneratePackageFile(".PKGINFO".into()),
-            )?;
+        let backend = self.packaging_backend(options);
+        let fakeroot = if backend == PackagingBackend::Fakeroot {
+            let mut fakerootcmd = Command::new("fakeroot");
+            fakerootcmd
+                .arg("-v")
+                .process_read(self, CommandKind::BuildingPackage(pkgbuild))
+                .read(
+                    &fakerootcmd,
+                    Context::GeneratePackageFile(".PKGINFO".into()),
+                )?
+        } else {
+            backend.to_string()
+        };
 
         writeln!(
             file,
@@ -351,20 +632,24 @@ impl Makepkg {
         self.write_kv(p, &mut file, "pkgname", &pkg.pkgname)?;
         self.write_kv(p, &mut file, "pkgbase", &pkgbuild.pkgbase)?;
         //self.write_kv(p, &mut file, "xdata", "pkgtype=pkg")?;
-        self.write_kv(p, &mut file, "pkgver", &pkgbuild.version())?;
+        self.write_kv(p, &mut file, "pkgver", &c.package_version(pkgbuild))?;
 
         self.write_kvs(p, &mut file, "pkgdesc", &pkg.pkgdesc)?;
         self.write_kvs(p, &mut file, "url", &pkg.url)?;
         self.write_kv(p, &mut file, "builddate", &c.source_date_epoch.to_string())?;
         self.write_kv(p, &mut file, "packager", &c.packager)?;
         self.write_kv(p, &mut file, "size", &size.to_string())?;
-        self.write_kv(p, &mut file, "arch", &c.arch)?;
+        self.write_kv(p, &mut file, "arch", c.package_arch(&pkg.arch))?;
 
         self.write_kvs(p, &mut file, "license", &pkg.license)?;
         self.write_kvs(p, &mut file, "replaces", pkg.replaces.enabled(&c.arch))?;
         self.write_kvs(p, &mut file, "group", &pkg.groups)?;
         self.write_kvs(p, &mut file, "conflict", pkg.conflicts.enabled(&c.arch))?;
         self.write_kvs(p, &mut file, "provides", pkg.provides.enabled(&c.arch))?;
+        if !debug {
+            let provides = self.soname_provides(dirs, pkgbuild, pkg)?;
+            self.write_kvs(p, &mut file, "provides", provides)?;
+        }
         self.write_kvs(p, &mut file, "backup", &pkg.backup)?;
         self.write_kvs(p, &mut file, "depend", pkg.depends.enabled(&c.arch))?;
         self.write_kvs(p, &mut file, "optdepend", pkg.optdepends.enabled(&c.arch))?;
@@ -408,11 +693,21 @@ impl Makepkg {
         Ok(())
     }
 
-    fn package_size(&self, dirs: &PkgbuildDirs, pkg: &Package) -> Result<u64> {
+    /// The installed size of a package as written to .PKGINFO. See [`Options::size_allocated`],
+    /// [`Options::size_count_hardlinks`] and [`Options::size_exclude_metadata`].
+    fn package_size(&self, dirs: &PkgbuildDirs, options: &Options, pkg: &Package) -> Result<u64> {
         let path = dirs.pkgdir(pkg);
         let mut size = 0;
         let mut seen = HashSet::new();
-        for file in walkdir::WalkDir::new(&path).follow_root_links(false) {
+        let files = walkdir::WalkDir::new(&path)
+            .follow_root_links(false)
+            .into_iter()
+            .filter_entry(|e| {
+                !(options.size_exclude_metadata
+                    && e.depth() == 1
+                    && e.file_name().as_bytes().starts_with(b"."))
+            });
+        for file in files {
             let file = file.context(Context::GetPackageSize, IOContext::ReadDir(path.clone()))?;
 
             let metadata = file
@@ -423,8 +718,12 @@ impl Makepkg {
                 continue;
             }
 
-            if seen.insert(metadata.ino()) {
-                size += metadata.size();
+            if options.size_count_hardlinks || seen.insert((metadata.dev(), metadata.ino())) {
+                size += if options.size_allocated {
+                    metadata.blocks() * 512
+                } else {
+                    metadata.size()
+                };
             }
         }
 
@@ -439,13 +738,18 @@ impl Makepkg {
             let file = file.context(Context::GetPackageFiles, IOContext::ReadDir(pkgdir.into()))?;
 
             let path = file.path().strip_prefix(pkgdir).unwrap();
-            if path.is_empty() {
+            if path.as_os_str().is_empty() {
                 continue;
             }
 
             files.push(path.to_path_buf());
+            if files.len() % FILES_PROGRESS_STEP == 0 {
+                self.package(PackageEvent::CollectingFiles(pkgdir, files.len()))?;
+            }
         }
 
+        self.package(PackageEvent::CollectedFiles(pkgdir, files.len()))?;
+
         files.sort_by(|a, b| a.as_os_str().cmp(b.as_os_str()));
 
         for path in files {
@@ -462,12 +766,13 @@ impl Makepkg {
         Ok(())
     }
 
+    /// Creates a source package, returning the package file.
     pub fn create_source_package(
         &self,
         options: &Options,
         pkgbuild: &Pkgbuild,
         all: bool,
-    ) -> Result<()> {
+    ) -> Result<BuiltPackage> {
         let mut added = HashSet::new();
         umask(Mode::from_bits_truncate(0o022));
 
@@ -555,19 +860,83 @@ impl Makepkg {
                     set_time(file.path(), self.config.source_date_epoch, false)?;
                 }
             }
+        }
+
+        let pkgfile = self.make_archive(&dirs, options, pkgbuild, &pkgbuild.packages[0], true)?;
+        if self.sign_enabled(options, pkgbuild) {
+            self.sign_package(pkgbuild, &pkgfile)?;
+        }
+
+        self.event(Event::BuiltSourcePackage(
+            &pkgbuild.pkgbase,
+            &pkgbuild.version(),
+        ))?;
+
+        BuiltPackage::new(
+            pkgfile,
+            &pkgbuild.pkgbase,
+            pkgbuild.version(),
+            None,
+            PackageKind::Source,
+        )
+    }
+
+    /// The package files of a [`Pkgbuild`] that exist in pkgdest.
+    ///
+    /// These are the same packages [`Makepkg::build`] reports, this can be used to find the
+    /// packages of an earlier build.
+    pub fn built_packages(&self, pkgbuild: &Pkgbuild) -> Result<Vec<BuiltPackage>> {
+        let version = self.config.package_version(pkgbuild);
+        let mut packages = Vec::new();
+        for package in self.config.package_list(pkgbuild)? {
+            if package.path.exists() {
+                packages.push(BuiltPackage::new(
+                    package.path,
+                    &package.pkgname,
+                    version.clone(),
+                    Some(&package.arch),
+                    package.kind,
+                )?);
+            }
+        }
+        Ok(packages)
+    }
 
-            self.make_archive(&dirs, pkgbuild, pkg, true)?;
+    /// The way package files are made to be owned by root for a build.
+    pub fn packaging_backend(&self, options: &Options) -> PackagingBackend {
+        options
+            .packaging_backend
+            .unwrap_or(self.config.packaging_backend)
+    }
 
-            self.event(Event::BuiltSourcePackage(
-                &pkgbuild.pkgbase,
-                &pkgbuild.version(),
-            ))?;
+    /// Sets up a command that should see itself as root according to the
+    /// [`packaging_backend`](Makepkg::packaging_backend).
+    pub(crate) fn fakeroot_env(&self, options: &Options, command: &mut Command) -> Result<()> {
+        match self.packaging_backend(options) {
+            PackagingBackend::Fakeroot => self.fakeroot_daemon_env(command),
+            PackagingBackend::TarOverride => Ok(()),
+            PackagingBackend::UserNamespace => {
+                map_root_user(command);
+                Ok(())
+            }
         }
+    }
 
-        Ok(())
+    fn tar_ownership(&self, options: &Options, tarcmd: &mut Command) {
+        if self.packaging_backend(options) == PackagingBackend::TarOverride {
+            tarcmd
+                .arg("--uid")
+                .arg("0")
+                .arg("--gid")
+                .arg("0")
+                .arg("--uname")
+                .arg("root")
+                .arg("--gname")
+                .arg("root");
+        }
     }
 
-    pub(crate) fn fakeroot_env(&self, command: &mut Command) -> Result<()> {
+    fn fakeroot_daemon_env(&self, command: &mut Command) -> Result<()> {
         let key = self.fakeroot()?;
         #[cfg(not(target_vendor = "apple"))]
         command.env("LD_LIBRARY_PATH", FAKEROOT_LIBDIRS);
@@ -580,3 +949,52 @@ impl Makepkg {
         Ok(())
     }
 }
+
+/// Runs the command in a new user namespace with the current user mapped to root.
+fn map_root_user(command: &mut Command) {
+    use nix::{
+        fcntl::{open, OFlag},
+        sched::{unshare, CloneFlags},
+        sys::stat::Mode,
+        unistd::{close, getgid, getuid, write},
+    };
+    use std::{ffi::CStr, os::unix::process::CommandExt};
+
+    fn write_file(path: &CStr, contents: &[u8]) -> nix::Result<()> {
+        let fd = open(path, OFlag::O_WRONLY, Mode::empty())?;
+        let res = write(fd, contents);
+        close(fd)?;
+        res.map(|_| ())
+    }
+
+    // Formatted up front, the child should not allocate.
+    let uid_map = format!("0 {} 1", getuid());
+    let gid_map = format!("0 {} 1", getgid());
+
+    unsafe {
+        command.pre_exec(move || {
+            unshare(CloneFlags::CLONE_NEWUSER)?;
+            write_file(c"/proc/self/setgroups", b"deny")?;
+            write_file(c"/proc/self/uid_map", uid_map.as_bytes())?;
+            write_file(c"/proc/self/gid_map", gid_map.as_bytes())?;
+            Ok(())
+        });
+    }
+}
+
+fn file_name(path: &Path) -> String {
+    path.file_name()
+        .unwrap_or_default()
+        .to_string_lossy()
+        .into_owned()
+}
+
+// repo-add writes foo.db.tar.gz alongside foo.files.tar.gz
+fn files_db(db: &Path) -> Option<PathBuf> {
+    let name = db.file_name()?.to_str()?;
+    let (repo, ext) = name.rsplit_once(".db")?;
+    if !ext.is_empty() && !ext.starts_with('.') {
+        return None;
+    }
+    Some(db.with_file_name(format!("{}.files{}", repo, ext)))
+}
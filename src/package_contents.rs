@@ -0,0 +1,128 @@
+use std::{
+    collections::BTreeMap,
+    fs::{read_link, symlink_metadata},
+    os::unix::fs::{MetadataExt, PermissionsExt},
+    path::{Path, PathBuf},
+    process::Command,
+};
+
+use sha2::Sha256;
+use walkdir::WalkDir;
+
+use crate::{
+    error::{CommandErrorExt, Context, IOContext, IOErrorExt, Result},
+    fs::{rm_all_writable, temp_dir},
+    integ::hash_file,
+    run::CommandOutput,
+};
+
+/// What a member of a package archive is.
+#[derive(Debug, Clone, PartialEq, Eq, PartialOrd, Ord, Hash)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize))]
+pub enum MemberKind {
+    /// A regular file and the sha256sum of its contents.
+    File(String),
+    Dir,
+    /// A symlink and its target.
+    Symlink(PathBuf),
+}
+
+/// A file in a package archive.
+#[derive(Debug, Clone, PartialEq, Eq, PartialOrd, Ord, Hash)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize))]
+pub struct Member {
+    pub kind: MemberKind,
+    /// The permission bits.
+    pub mode: u32,
+    /// The size of the file or symlink, directories are always 0.
+    pub size: u64,
+    pub mtime: i64,
+}
+
+/// Extracts a package archive into a temporary directory and reads back every member keyed by
+/// its path inside the archive.
+pub(crate) fn read_package(path: &Path, context: Context) -> Result<BTreeMap<PathBuf, Member>> {
+    let tmp = temp_dir("makepkg-package-", context.clone())?;
+    let res = extract_package(path, tmp.path(), context.clone())
+        .and_then(|_| read_members(tmp.path(), context.clone()));
+    rm_all_writable(tmp.path(), context)?;
+    res
+}
+
+/// Extracts a package archive into `tmp`, which must already exist.
+pub(crate) fn extract_package(path: &Path, tmp: &Path, context: Context) -> Result<()> {
+    let mut command = Command::new("bsdtar");
+    command
+        .arg("-xpf")
+        .arg(path)
+        .arg("-C")
+        .arg(tmp)
+        .process_output()
+        .cmd_context(&command, context)?;
+    Ok(())
+}
+
+/// Reads every file under an extracted package keyed by its path inside the archive.
+pub(crate) fn read_members(tmp: &Path, context: Context) -> Result<BTreeMap<PathBuf, Member>> {
+    let mut members = BTreeMap::new();
+    for entry in WalkDir::new(tmp).min_depth(1) {
+        let entry = entry
+            .map_err(std::io::Error::from)
+            .context(context.clone(), IOContext::ReadDir(tmp.to_path_buf()))?;
+        let file = entry.path();
+        let metadata =
+            symlink_metadata(file).context(context.clone(), IOContext::Stat(file.into()))?;
+
+        let kind = if metadata.is_symlink() {
+            let target =
+                read_link(file).context(context.clone(), IOContext::ReadLink(file.into()))?;
+            MemberKind::Symlink(target)
+        } else if metadata.is_dir() {
+            MemberKind::Dir
+        } else {
+            MemberKind::File(hash_file::<Sha256>(file)?)
+        };
+
+        let name = file.strip_prefix(tmp).unwrap_or(file).to_path_buf();
+        members.insert(
+            name,
+            Member {
+                kind,
+                mode: metadata.permissions().mode() & 0o7777,
+                size: if metadata.is_dir() { 0 } else { metadata.len() },
+                mtime: metadata.mtime(),
+            },
+        );
+    }
+
+    Ok(members)
+}
+
+/// Reads the .PKGINFO of a package archive without extracting the rest.
+pub(crate) fn read_pkginfo(path: &Path, context: Context) -> Result<BTreeMap<String, Vec<String>>> {
+    let mut command = Command::new("bsdtar");
+    let output = command
+        .arg("-qxOf")
+        .arg(path)
+        .arg(".PKGINFO")
+        .process_output()
+        .cmd_context(&command, context)?;
+
+    Ok(parse_pkginfo(&String::from_utf8_lossy(&output.stdout)))
+}
+
+/// Parses a .PKGINFO into its keys and every value given for each key.
+pub(crate) fn parse_pkginfo(pkginfo: &str) -> BTreeMap<String, Vec<String>> {
+    let mut info = BTreeMap::<_, Vec<_>>::new();
+    for line in pkginfo.lines() {
+        if line.starts_with('#') {
+            continue;
+        }
+        if let Some((key, value)) = line.split_once(" = ") {
+            info.entry(key.to_string())
+                .or_default()
+                .push(value.to_string());
+        }
+    }
+    info
+}
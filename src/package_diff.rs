@@ -0,0 +1,211 @@
+use std::{
+    collections::{BTreeMap, BTreeSet},
+    fmt::Display,
+    path::{Path, PathBuf},
+};
+
+use crate::{
+    callback::HumanSize,
+    error::{Context, Result},
+    package_contents::{read_package, read_pkginfo, Member, MemberKind},
+};
+
+/// The differences between two package files. See [`diff_packages`].
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize))]
+pub struct PackageDiff {
+    /// Files only in the new package.
+    pub added: Vec<PathBuf>,
+    /// Files only in the old package.
+    pub removed: Vec<PathBuf>,
+    /// Files in both packages whose type, contents or permissions changed.
+    pub changed: Vec<FileChange>,
+    /// .PKGINFO fields that changed.
+    pub metadata: Vec<MetadataChange>,
+    /// The total size of the files in the old package.
+    pub old_size: u64,
+    /// The total size of the files in the new package.
+    pub new_size: u64,
+}
+
+impl PackageDiff {
+    /// How much bigger the new package's files are.
+    pub fn size_delta(&self) -> i64 {
+        self.new_size as i64 - self.old_size as i64
+    }
+
+    /// Whether the packages have the same files and metadata.
+    pub fn is_empty(&self) -> bool {
+        self.added.is_empty()
+            && self.removed.is_empty()
+            && self.changed.is_empty()
+            && self.metadata.is_empty()
+    }
+}
+
+impl Display for PackageDiff {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        for change in &self.metadata {
+            writeln!(f, "{}", change)?;
+        }
+        for path in &self.removed {
+            writeln!(f, "removed {}", path.display())?;
+        }
+        for path in &self.added {
+            writeln!(f, "added {}", path.display())?;
+        }
+        for change in &self.changed {
+            writeln!(f, "{}", change)?;
+        }
+        writeln!(
+            f,
+            "size {} -> {} ({})",
+            HumanSize(self.old_size),
+            HumanSize(self.new_size),
+            SizeDelta(self.size_delta())
+        )
+    }
+}
+
+/// A file that is in both packages but differs.
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize))]
+pub struct FileChange {
+    pub path: PathBuf,
+    pub old: Member,
+    pub new: Member,
+}
+
+impl FileChange {
+    /// How much bigger the new file is.
+    pub fn size_delta(&self) -> i64 {
+        self.new.size as i64 - self.old.size as i64
+    }
+}
+
+impl Display for FileChange {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "changed {}", self.path.display())?;
+        if self.old.mode != self.new.mode {
+            write!(f, " (mode {:o} -> {:o})", self.old.mode, self.new.mode)?;
+        }
+        match (&self.old.kind, &self.new.kind) {
+            (MemberKind::File(_), MemberKind::File(_)) => {
+                write!(f, " ({})", SizeDelta(self.size_delta()))
+            }
+            (MemberKind::Symlink(old), MemberKind::Symlink(new)) => {
+                write!(f, " (-> {} was -> {})", new.display(), old.display())
+            }
+            (old, new) => write!(f, " ({} -> {})", kind_name(old), kind_name(new)),
+        }
+    }
+}
+
+/// A .PKGINFO field with different values in each package.
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize))]
+pub struct MetadataChange {
+    pub key: String,
+    pub old: Vec<String>,
+    pub new: Vec<String>,
+}
+
+impl Display for MetadataChange {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(
+            f,
+            "{}: {} -> {}",
+            self.key,
+            self.old.join(" "),
+            self.new.join(" ")
+        )
+    }
+}
+
+struct SizeDelta(i64);
+
+impl Display for SizeDelta {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let sign = if self.0 < 0 { '-' } else { '+' };
+        write!(f, "{}{}", sign, HumanSize(self.0.unsigned_abs()))
+    }
+}
+
+fn kind_name(kind: &MemberKind) -> &'static str {
+    match kind {
+        MemberKind::File(_) => "file",
+        MemberKind::Dir => "directory",
+        MemberKind::Symlink(_) => "symlink",
+    }
+}
+
+/// Compares the files and metadata of two package files, usually an old and new version of
+/// the same package.
+///
+/// Modification times are not compared as they change with every build.
+pub fn diff_packages(old: &Path, new: &Path) -> Result<PackageDiff> {
+    let old_files = files(read_package(old, Context::DiffPackages)?);
+    let new_files = files(read_package(new, Context::DiffPackages)?);
+
+    let mut diff = PackageDiff {
+        old_size: total_size(&old_files),
+        new_size: total_size(&new_files),
+        ..Default::default()
+    };
+
+    for path in old_files
+        .keys()
+        .chain(new_files.keys())
+        .collect::<BTreeSet<_>>()
+    {
+        match (old_files.get(path), new_files.get(path)) {
+            (Some(_), None) => diff.removed.push(path.clone()),
+            (None, Some(_)) => diff.added.push(path.clone()),
+            (Some(old), Some(new)) if old.kind != new.kind || old.mode != new.mode => {
+                diff.changed.push(FileChange {
+                    path: path.clone(),
+                    old: old.clone(),
+                    new: new.clone(),
+                })
+            }
+            _ => (),
+        }
+    }
+
+    let old_info = read_pkginfo(old, Context::DiffPackages)?;
+    let new_info = read_pkginfo(new, Context::DiffPackages)?;
+    let empty = Vec::new();
+    for key in old_info
+        .keys()
+        .chain(new_info.keys())
+        .collect::<BTreeSet<_>>()
+    {
+        let old = old_info.get(key).unwrap_or(&empty);
+        let new = new_info.get(key).unwrap_or(&empty);
+        if old != new {
+            diff.metadata.push(MetadataChange {
+                key: key.clone(),
+                old: old.clone(),
+                new: new.clone(),
+            });
+        }
+    }
+
+    Ok(diff)
+}
+
+// drops the package metadata files from the members
+fn files(members: BTreeMap<PathBuf, Member>) -> BTreeMap<PathBuf, Member> {
+    members
+        .into_iter()
+        .filter(|(path, _)| !path.to_string_lossy().starts_with('.'))
+        .collect()
+}
+
+fn total_size(files: &BTreeMap<PathBuf, Member>) -> u64 {
+    files
+        .values()
+        .filter(|m| matches!(m.kind, MemberKind::File(_)))
+        .map(|m| m.size)
+        .sum()
+}
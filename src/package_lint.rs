@@ -0,0 +1,456 @@
+use std::{
+    fmt::Display,
+    fs::File,
+    io::{BufReader, Read},
+    os::unix::{ffi::OsStrExt, fs::PermissionsExt},
+    path::{Path, PathBuf},
+};
+
+use walkdir::WalkDir;
+
+use crate::{
+    callback::{FileIssue, LogLevel, LogMessage},
+    config::{PackageCheck, PkgbuildDirs},
+    error::{Context, IOContext, IOErrorExt, LintKind, Result},
+    filenames::lint_file_names,
+    fs::read_to_string,
+    pkgbuild::{Package, Pkgbuild},
+    Makepkg,
+};
+
+// files makepkg adds to the package root
+static METADATA_FILES: &[&str] = &[".PKGINFO", ".BUILDINFO", ".MTREE", ".INSTALL", ".CHANGELOG"];
+
+static ROOT_DIRS: &[&str] = &["boot", "etc", "opt", "srv", "usr", "var"];
+static USR_DIRS: &[&str] = &["bin", "include", "lib", "lib32", "share", "src"];
+
+/// A problem found in a built package by a [`PackageCheck`].
+#[derive(Debug, Clone, PartialEq, Eq, PartialOrd, Ord, Hash)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize))]
+pub struct PackageWarning {
+    pub check: PackageCheck,
+    /// The file the warning is about relative to pkgdir.
+    pub path: Option<PathBuf>,
+    pub message: String,
+}
+
+impl Display for PackageWarning {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match &self.path {
+            Some(path) => write!(f, "{}: {}", path.display(), self.message)?,
+            None => f.write_str(&self.message)?,
+        }
+        write!(f, " [{}]", self.check)
+    }
+}
+
+impl Makepkg {
+    /// Runs the enabled [`PackageCheck`]s over a package after its metadata has been
+    /// generated.
+    pub(crate) fn lint_package(
+        &self,
+        dirs: &PkgbuildDirs,
+        pkgbuild: &Pkgbuild,
+        pkg: &Package,
+    ) -> Result<()> {
+        let config = &self.config;
+
+        if config.package_check_enabled(PackageCheck::LibDepends) {
+            self.check_lib_depends(dirs, pkgbuild, pkg)?;
+        }
+        if config.package_check_enabled(PackageCheck::BuildReferences) {
+            self.lint_build_references(dirs, pkg)?;
+        }
+        if config.package_check_enabled(PackageCheck::Hygiene) {
+            self.lint_package_hygiene(dirs, pkg)?;
+        }
+        if config.package_check_enabled(PackageCheck::Backup) {
+            self.lint_backup_files(dirs, pkgbuild, pkg)?;
+        }
+
+        let pkgdir = dirs.pkgdir(pkg);
+        let mut warnings = Vec::new();
+        if config.package_check_enabled(PackageCheck::Fhs) {
+            lint_fhs(&pkgdir, &mut warnings)?;
+        }
+        if config.package_check_enabled(PackageCheck::License) {
+            lint_license(&pkgdir, pkg, &mut warnings)?;
+        }
+        if config.package_check_enabled(PackageCheck::Desktop) {
+            lint_desktop_files(&pkgdir, &mut warnings)?;
+        }
+        if config.package_check_enabled(PackageCheck::PythonBytecode) {
+            lint_python_bytecode(&pkgdir, &mut warnings)?;
+        }
+        if config.package_check_enabled(PackageCheck::FileNames) {
+            lint_file_names(&pkgdir, &mut warnings)?;
+        }
+
+        for warning in &warnings {
+            self.log(
+                LogLevel::Warning,
+                LogMessage::PackageWarning(&pkg.pkgname, warning),
+            )?;
+        }
+
+        Ok(())
+    }
+
+    /// Warns about packaged files that contain the path of srcdir or pkgdir.
+    fn lint_build_references(&self, dirs: &PkgbuildDirs, pkg: &Package) -> Result<()> {
+        let pkgdir = dirs.pkgdir(pkg);
+
+        for dir in [&dirs.srcdir, &dirs.pkgdir] {
+            let files = files_containing(&pkgdir, dir)?;
+            if !files.is_empty() {
+                self.log(
+                    LogLevel::Warning,
+                    LogMessage::PackageContainsReference(&pkg.pkgname, dir, &files),
+                )?;
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Warns about backup entries that are not regular files in the package.
+    fn lint_backup_files(
+        &self,
+        dirs: &PkgbuildDirs,
+        pkgbuild: &Pkgbuild,
+        pkg: &Package,
+    ) -> Result<()> {
+        let pkgdir = dirs.pkgdir(pkg);
+
+        let mut lints = Vec::new();
+        for backup in &pkg.backup {
+            let path = pkgdir.join(backup.trim_start_matches('/'));
+            let is_file = path
+                .symlink_metadata()
+                .map(|m| m.file_type().is_file())
+                .unwrap_or(false);
+            if !is_file {
+                lints.push(LintKind::BackupFileMissing(
+                    pkg.pkgname.clone(),
+                    backup.clone(),
+                ));
+            }
+        }
+
+        self.report_lints(pkgbuild, lints)
+    }
+
+    /// Warns about setuid, setgid and world writable files, dotfiles in the package root
+    /// and packages that contain no files.
+    fn lint_package_hygiene(&self, dirs: &PkgbuildDirs, pkg: &Package) -> Result<()> {
+        let pkgdir = dirs.pkgdir(pkg);
+        let mut issues: Vec<(FileIssue, Vec<PathBuf>)> = Vec::new();
+        let mut empty = true;
+
+        let mut add = |issue, path: &Path| {
+            let path = path.strip_prefix(&pkgdir).unwrap_or(path).to_path_buf();
+            match issues.iter_mut().find(|i| i.0 == issue) {
+                Some((_, files)) => files.push(path),
+                None => issues.push((issue, vec![path])),
+            }
+        };
+
+        for file in package_files(WalkDir::new(&pkgdir)) {
+            let file = file.context(
+                Context::GetPackageFiles,
+                IOContext::ReadDir(pkgdir.to_path_buf()),
+            )?;
+            let path = file.path();
+
+            if file.depth() == 1 && file.file_name().as_bytes().starts_with(b".") {
+                add(FileIssue::Dotfile, path);
+            }
+            if !file.file_type().is_dir() {
+                empty = false;
+            }
+            if file.file_type().is_symlink() {
+                continue;
+            }
+
+            let mode = file
+                .metadata()
+                .context(Context::GetPackageFiles, IOContext::Stat(path.into()))?
+                .permissions()
+                .mode();
+
+            if mode & 0o4000 != 0 {
+                add(FileIssue::Setuid, path);
+            }
+            if mode & 0o2000 != 0 {
+                add(FileIssue::Setgid, path);
+            }
+            // sticky directories such as tmp are meant to be world writable
+            if mode & 0o002 != 0 && !(file.file_type().is_dir() && mode & 0o1000 != 0) {
+                add(FileIssue::WorldWritable, path);
+            }
+        }
+
+        issues.sort();
+        for (issue, files) in &issues {
+            self.log(
+                LogLevel::Warning,
+                LogMessage::PackageFileIssue(&pkg.pkgname, *issue, files),
+            )?;
+        }
+        if empty {
+            self.log(LogLevel::Warning, LogMessage::EmptyPackage(&pkg.pkgname))?;
+        }
+
+        Ok(())
+    }
+}
+
+// returns the paths relative to pkgdir
+fn files_containing(pkgdir: &Path, needle: &Path) -> Result<Vec<PathBuf>> {
+    let needle = needle.as_os_str().as_bytes();
+    let mut files = Vec::new();
+
+    for file in package_files(WalkDir::new(pkgdir)) {
+        let file = file.context(
+            Context::GetPackageFiles,
+            IOContext::ReadDir(pkgdir.to_path_buf()),
+        )?;
+        if !file.file_type().is_file() {
+            continue;
+        }
+
+        let path = file.path();
+        let f = File::open(path).context(Context::GetPackageFiles, IOContext::Open(path.into()))?;
+        if contains(BufReader::new(f), needle)
+            .context(Context::GetPackageFiles, IOContext::Read(path.into()))?
+        {
+            let rel = path.strip_prefix(pkgdir).unwrap_or(path);
+            files.push(rel.to_path_buf());
+        }
+    }
+
+    Ok(files)
+}
+
+// searches in chunks, keeping the tail of the previous chunk so matches that straddle
+// chunks are found
+fn contains<R: Read>(mut r: R, needle: &[u8]) -> std::io::Result<bool> {
+    if needle.is_empty() {
+        return Ok(false);
+    }
+
+    let mut buf = vec![0; 64 * 1024 + needle.len()];
+    let mut kept = 0;
+
+    loop {
+        let n = r.read(&mut buf[kept..])?;
+        if n == 0 {
+            return Ok(false);
+        }
+        let len = kept + n;
+
+        if buf[..len].windows(needle.len()).any(|w| w == needle) {
+            return Ok(true);
+        }
+
+        kept = (needle.len() - 1).min(len);
+        buf.copy_within(len - kept..len, 0);
+    }
+}
+
+// walks pkgdir skipping pkgdir itself and the metadata makepkg generates
+pub(crate) fn package_files(
+    walk: WalkDir,
+) -> impl Iterator<Item = walkdir::Result<walkdir::DirEntry>> {
+    walk.min_depth(1).into_iter().filter_entry(|e| {
+        e.depth() != 1
+            || !METADATA_FILES
+                .iter()
+                .any(|m| e.file_name().as_bytes() == m.as_bytes())
+    })
+}
+
+pub(crate) fn warn(
+    check: PackageCheck,
+    path: Option<&Path>,
+    message: impl Into<String>,
+) -> PackageWarning {
+    PackageWarning {
+        check,
+        path: path.map(|p| p.to_path_buf()),
+        message: message.into(),
+    }
+}
+
+fn lint_fhs(pkgdir: &Path, warnings: &mut Vec<PackageWarning>) -> Result<()> {
+    for file in package_files(WalkDir::new(pkgdir).max_depth(2)) {
+        let file = file.context(
+            Context::GetPackageFiles,
+            IOContext::ReadDir(pkgdir.to_path_buf()),
+        )?;
+        let rel = file.path().strip_prefix(pkgdir).unwrap_or(file.path());
+        let name = file.file_name().to_string_lossy();
+
+        if file.depth() == 1 {
+            // dotfiles are reported by the hygiene check
+            if !name.starts_with('.') && !ROOT_DIRS.contains(&name.as_ref()) {
+                warnings.push(warn(
+                    PackageCheck::Fhs,
+                    Some(rel),
+                    "file or directory in a non standard location",
+                ));
+            }
+        } else if rel.starts_with("usr") {
+            if name == "local" {
+                warnings.push(warn(
+                    PackageCheck::Fhs,
+                    Some(rel),
+                    "/usr/local is reserved for the local administrator",
+                ));
+            } else if !USR_DIRS.contains(&name.as_ref()) {
+                warnings.push(warn(
+                    PackageCheck::Fhs,
+                    Some(rel),
+                    "file or directory in a non standard location",
+                ));
+            }
+        }
+    }
+
+    Ok(())
+}
+
+fn lint_license(pkgdir: &Path, pkg: &Package, warnings: &mut Vec<PackageWarning>) -> Result<()> {
+    let pkginfo = pkgdir.join(".PKGINFO");
+    let pkginfo = read_to_string(&pkginfo, Context::GetPackageFiles)?;
+
+    let custom = pkginfo
+        .lines()
+        .filter_map(|l| l.strip_prefix("license = "))
+        .find(|l| l.starts_with("custom") || l.contains("LicenseRef-"));
+
+    if let Some(license) = custom {
+        let dir = Path::new("usr/share/licenses").join(&pkg.pkgname);
+        let installed = pkgdir
+            .join(&dir)
+            .read_dir()
+            .map(|mut d| d.next().is_some())
+            .unwrap_or(false);
+        if !installed {
+            warnings.push(warn(
+                PackageCheck::License,
+                Some(&dir),
+                format!("custom license '{}' is not installed", license),
+            ));
+        }
+    }
+
+    Ok(())
+}
+
+fn lint_desktop_files(pkgdir: &Path, warnings: &mut Vec<PackageWarning>) -> Result<()> {
+    let apps = pkgdir.join("usr/share/applications");
+    if !apps.exists() {
+        return Ok(());
+    }
+
+    for file in WalkDir::new(&apps) {
+        let file = file.context(Context::GetPackageFiles, IOContext::ReadDir(apps.clone()))?;
+        let path = file.path();
+        if !file.file_type().is_file() || path.extension().is_none_or(|e| e != "desktop") {
+            continue;
+        }
+
+        let rel = path.strip_prefix(pkgdir).unwrap_or(path);
+        let mut f =
+            File::open(path).context(Context::GetPackageFiles, IOContext::Open(path.into()))?;
+        let mut data = Vec::new();
+        f.read_to_end(&mut data)
+            .context(Context::GetPackageFiles, IOContext::Read(path.into()))?;
+
+        match String::from_utf8(data) {
+            Ok(data) => {
+                for problem in desktop_file_problems(&data) {
+                    warnings.push(warn(PackageCheck::Desktop, Some(rel), problem));
+                }
+            }
+            Err(_) => warnings.push(warn(PackageCheck::Desktop, Some(rel), "not valid UTF-8")),
+        }
+    }
+
+    Ok(())
+}
+
+fn desktop_file_problems(data: &str) -> Vec<String> {
+    let mut problems = Vec::new();
+    let mut group = None;
+    let mut keys = Vec::new();
+
+    for (n, line) in data.lines().enumerate() {
+        let line = line.trim();
+        if line.is_empty() || line.starts_with('#') {
+            continue;
+        }
+
+        if let Some(name) = line.strip_prefix('[').and_then(|l| l.strip_suffix(']')) {
+            if group.is_none() && name != "Desktop Entry" {
+                problems.push("first group is not [Desktop Entry]".to_string());
+            }
+            group = Some(name);
+            continue;
+        }
+
+        let Some(group) = group else {
+            problems.push(format!("line {}: entry before the first group", n + 1));
+            continue;
+        };
+        let Some((key, value)) = line.split_once('=') else {
+            problems.push(format!("line {}: not a key=value entry", n + 1));
+            continue;
+        };
+        if group == "Desktop Entry" {
+            keys.push((key.trim(), value.trim()));
+        }
+    }
+
+    let get = |k: &str| keys.iter().find(|e| e.0 == k).map(|e| e.1);
+    for key in ["Type", "Name"] {
+        if get(key).is_none() {
+            problems.push(format!("missing required key {}", key));
+        }
+    }
+    if get("Type") == Some("Application")
+        && get("Exec").is_none()
+        && get("DBusActivatable") != Some("true")
+    {
+        problems.push("application is missing an Exec key".to_string());
+    }
+
+    problems
+}
+
+fn lint_python_bytecode(pkgdir: &Path, warnings: &mut Vec<PackageWarning>) -> Result<()> {
+    for file in package_files(WalkDir::new(pkgdir)) {
+        let file = file.context(
+            Context::GetPackageFiles,
+            IOContext::ReadDir(pkgdir.to_path_buf()),
+        )?;
+        let path = file.path();
+        let bytecode = path.extension().is_some_and(|e| e == "pyc" || e == "pyo");
+        let cached = path
+            .parent()
+            .and_then(|p| p.file_name())
+            .is_some_and(|p| p == "__pycache__");
+
+        if file.file_type().is_file() && bytecode && !cached {
+            let rel = path.strip_prefix(pkgdir).unwrap_or(path);
+            warnings.push(warn(
+                PackageCheck::PythonBytecode,
+                Some(rel),
+                "python bytecode outside of __pycache__",
+            ));
+        }
+    }
+
+    Ok(())
+}
@@ -1,28 +1,255 @@
-use std::process::Command;
+use std::{
+    ffi::OsStr,
+    path::{Path, PathBuf},
+    process::Command,
+};
+
+use nix::unistd::Uid;
 
 use crate::{
     callback::CommandKind,
-    error::{CommandOutputExt, Context, Result},
+    error::{CommandError, CommandErrorExt, CommandOutputExt, Context, Result},
     pkgbuild::Pkgbuild,
     run::CommandOutput,
-    Makepkg,
+    Makepkg, Options,
 };
 
-/*
-pub fn deptest<'a, I: Iterator<Item = &'a str>>(pkgs: I) -> Result<Vec<String>> {
-    read_pacman(&["-T"], pkgs)
+/// Returns the dependencies that are not satisfied by the installed packages.
+pub fn deptest<'a, I: IntoIterator<Item = &'a str>>(
+    makepkg: &Makepkg,
+    deps: I,
+) -> Result<Vec<String>> {
+    let mut deps = deps.into_iter().peekable();
+    if deps.peek().is_none() {
+        return Ok(Vec::new());
+    }
+
+    let mut command = makepkg.pacman(["-T", "--"]);
+    command.args(deps);
+    let output = command.output();
+
+    // pacman -T exits 127 and lists the missing deps if any are not satisfied
+    match output {
+        Ok(output) if output.status.code() == Some(127) => {
+            let missing =
+                String::from_utf8(output.stdout).cmd_context(&command, Context::QueryPacman)?;
+            Ok(missing.lines().map(|l| l.to_string()).collect())
+        }
+        Ok(output) if output.status.success() => Ok(Vec::new()),
+        Ok(output) => {
+            Err(CommandError::exit(&command, output.status.code(), Context::QueryPacman).into())
+        }
+        Err(e) => Err(CommandError::exec(e, &command, Context::QueryPacman).into()),
+    }
+}
+
+fn installed(makepkg: &Makepkg, pkgbuild: &Pkgbuild) -> Result<Vec<String>> {
+    read_pacman(makepkg, pkgbuild, &["-Qq"], None.into_iter())
+}
+
+impl Makepkg {
+    /// Creates a pacman command with the given arguments.
+    ///
+    /// Operations that change the system are run through
+    /// [`Config::pacman_auth`](crate::config::Config::pacman_auth) when not running as root,
+    /// falling back to sudo or su if it is not set. `%c` in the auth command is replaced by
+    /// the quoted pacman command, otherwise the pacman command is appended.
+    pub fn pacman<I, S>(&self, args: I) -> Command
+    where
+        I: IntoIterator<Item = S>,
+        S: AsRef<OsStr>,
+    {
+        let config = &self.config;
+        let args = args
+            .into_iter()
+            .map(|a| a.as_ref().to_string_lossy().into_owned())
+            .collect::<Vec<_>>();
+        let query = args
+            .first()
+            .is_some_and(|op| op.starts_with("-T") || op.starts_with("-Q"));
+
+        if query || Uid::effective().is_root() {
+            let mut command = Command::new(&config.pacman);
+            command.args(args);
+            return command;
+        }
+
+        let mut cmd = vec![config.pacman.clone()];
+        cmd.extend(args);
+        self.as_root(cmd)
+    }
+
+    /// Creates a command that runs as root using the same method as [`Makepkg::pacman`].
+    pub(crate) fn as_root(&self, cmd: Vec<String>) -> Command {
+        let config = &self.config;
+
+        if Uid::effective().is_root() {
+            let mut command = Command::new(&cmd[0]);
+            command.args(&cmd[1..]);
+            return command;
+        }
+
+        let escaped = cmd
+            .iter()
+            .map(|a| shell_quote(a))
+            .collect::<Vec<_>>()
+            .join(" ");
+
+        let auth = if !config.pacman_auth.is_empty() {
+            if config.pacman_auth.iter().any(|a| a.contains("%c")) {
+                config
+                    .pacman_auth
+                    .iter()
+                    .map(|a| a.replace("%c", &escaped))
+                    .collect()
+            } else {
+                config.pacman_auth.iter().cloned().chain(cmd).collect()
+            }
+        } else if which("sudo") {
+            ["sudo".to_string()].into_iter().chain(cmd).collect()
+        } else {
+            vec!["su".into(), "root".into(), "-c".into(), escaped]
+        };
+
+        let mut command = Command::new(&auth[0]);
+        command.args(&auth[1..]);
+        command
+    }
+
+    /// The package that owns a file on the system.
+    pub(crate) fn file_owner(&self, path: &Path) -> Option<String> {
+        let output = self.pacman(["-Qqo"]).arg("--").arg(path).output().ok()?;
+        if !output.status.success() {
+            return None;
+        }
+        let owner = String::from_utf8(output.stdout).ok()?;
+        Some(owner.trim().to_string())
+    }
+
+    /// The files an installed package owns.
+    pub(crate) fn installed_files(&self, pkg: &str) -> Option<Vec<PathBuf>> {
+        let output = self.pacman(["-Qlq"]).arg("--").arg(pkg).output().ok()?;
+        if !output.status.success() {
+            return None;
+        }
+        let files = String::from_utf8(output.stdout).ok()?;
+        Some(files.lines().map(PathBuf::from).collect())
+    }
+
+    /// Installs the missing dependencies of a PKGBUILD.
+    ///
+    /// Returns the packages that were installed so they can be removed after the build.
+    pub(crate) fn sync_deps(&self, options: &Options, pkgbuild: &Pkgbuild) -> Result<Vec<String>> {
+        let report = self.check_deps(pkgbuild)?;
+        let missing = report
+            .unsatisfied()
+            .map(|d| d.dep.as_str())
+            .collect::<Vec<_>>();
+        if missing.is_empty() {
+            return Ok(Vec::new());
+        }
+
+        let before = installed(self, pkgbuild)?;
+
+        let mut args = vec!["-S", "--asdeps"];
+        if options.no_confirm {
+            args.push("--noconfirm");
+        }
+        args.push("--");
+        args.extend(missing);
+        self.run_pacman(args)?;
+
+        let after = installed(self, pkgbuild)?;
+        Ok(after.into_iter().filter(|p| !before.contains(p)).collect())
+    }
+
+    /// Removes dependencies installed by [`Makepkg::sync_deps`].
+    pub(crate) fn remove_deps(&self, options: &Options, deps: &[String]) -> Result<()> {
+        if deps.is_empty() {
+            return Ok(());
+        }
+
+        let mut args = vec!["-Rnu"];
+        if options.no_confirm {
+            args.push("--noconfirm");
+        }
+        args.push("--");
+        args.extend(deps.iter().map(|d| d.as_str()));
+        self.run_pacman(args)
+    }
+
+    /// Installs the packages built from a PKGBUILD, if any were built.
+    pub(crate) fn install_packages(&self, options: &Options, pkgbuild: &Pkgbuild) -> Result<()> {
+        let pkgs = self.config.package_list(pkgbuild)?;
+        let pkgs = pkgs
+            .into_iter()
+            .filter(|p| p.path.exists())
+            .map(|p| p.path.into_os_string())
+            .collect::<Vec<_>>();
+        if pkgs.is_empty() {
+            return Ok(());
+        }
+
+        let mut args = vec!["-U".into()];
+        if options.as_deps {
+            args.push("--asdeps".into());
+        }
+        if options.needed {
+            args.push("--needed".into());
+        }
+        if options.no_confirm {
+            args.push("--noconfirm".into());
+        }
+        args.push("--".into());
+        args.extend(pkgs);
+        self.run_pacman(args)
+    }
+
+    fn run_pacman<I, S>(&self, args: I) -> Result<()>
+    where
+        I: IntoIterator<Item = S>,
+        S: AsRef<OsStr>,
+    {
+        // pacman may need to prompt so it gets the terminal
+        let mut command = self.pacman(args);
+        command.status().cmd_context(&command, Context::RunPacman)?;
+        Ok(())
+    }
 }
 
-pub fn installed() -> Result<Vec<String>> {
-    let pkgs = read_pacman(&["-Qq"], None.into_iter())?;
-    Ok(pkgs)
+fn which(cmd: &str) -> bool {
+    std::env::var_os("PATH")
+        .is_some_and(|path| std::env::split_paths(&path).any(|dir| dir.join(cmd).is_file()))
 }
-*/
 
+pub(crate) fn shell_quote(s: &str) -> String {
+    format!("'{}'", s.replace('\'', "'\\''"))
+}
+
+/// Lists the installed packages as `name-version-arch` for the `installed` field of
+/// .BUILDINFO.
+///
+/// With the `localdb` feature the local database is read directly when it exists, otherwise
+/// `pacman -Qi` is parsed.
 pub fn buildinfo_installed(makepkg: &Makepkg, pkgbuild: &Pkgbuild) -> Result<Vec<String>> {
+    #[cfg(feature = "localdb")]
+    {
+        let db = crate::localdb::dbpath(&makepkg.config).join("local");
+        if db.is_dir() {
+            return crate::localdb::installed(&db);
+        }
+    }
+    query_installed(makepkg, pkgbuild)
+}
+
+fn query_installed(makepkg: &Makepkg, pkgbuild: &Pkgbuild) -> Result<Vec<String>> {
     let mut installed = Vec::new();
     let mut current = String::new();
-    let pkgs = read_pacman(makepkg, pkgbuild, &["-Qi"], None.into_iter())?;
+    let mut args = vec!["-Qi".to_string()];
+    if let Some(dbpath) = &makepkg.config.pacman_dbpath {
+        args.push(format!("--dbpath={}", dbpath.display()));
+    }
+    let pkgs = read_pacman(makepkg, pkgbuild, &args, None.into_iter())?;
 
     for pkg in pkgs {
         if pkg.starts_with("Name") {
@@ -60,10 +287,7 @@ where
     S: AsRef<str>,
     I: Iterator<Item = &'a str>,
 {
-    let mut command = Command::new("pacman");
-    for arg in args {
-        command.arg(arg.as_ref());
-    }
+    let mut command = makepkg.pacman(args.iter().map(|a| a.as_ref()));
     command.arg("--");
 
     for pkg in pkgs {
@@ -76,14 +300,3 @@ where
 
     Ok(output.lines().map(|l| l.to_string()).collect())
 }
-
-/*
-pub fn run_pacman<'a, I: Iterator<Item = &'a str>>(op: &str, args: &[&str], pkgs: I) -> Result<()> {
-    let mut command = self.command("sudo");
-    command.arg("pacman").arg(op).args(args).arg("--");
-    command.args(pkgs);
-
-    command.st//atus().cmd_context(&command, Context::RunPacman)?;
-    Ok(())
-}
-*/
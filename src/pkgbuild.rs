@@ -1,7 +1,6 @@
 use std::{
     collections::BTreeSet,
     fmt::Display,
-    fs::read_to_string,
     path::{Path, PathBuf},
     result::Result as StdResult,
     str::FromStr,
@@ -14,9 +13,11 @@ use sha2::{Sha224, Sha256, Sha384, Sha512};
 
 use crate::{
     config::{Config, PkgbuildDirs},
-    error::{Context, Error, IOContext, IOErrorExt, LintError, LintKind, Result},
+    error::{Context, Error, LintError, LintKind, LintLevels, Result},
     fs::{resolve_path, Check},
     lint_pkgbuild::check_pkgver,
+    package::PackageKind,
+    pkgbuild_edit::PkgbuildEditor,
     raw::{FunctionVariables, RawPkgbuild, Value, Variable},
     Makepkg,
 };
@@ -64,6 +65,7 @@ impl Function {
 }
 
 #[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize))]
 pub enum ChecksumKind {
     Md5,
     Sha1,
@@ -125,25 +127,25 @@ impl ChecksumKind {
         let name = self.name();
         match self {
             ChecksumKind::Md5 => {
-                makepkg.verify_file_checksum::<Md5>(dirs, p, s, sums, &name, failed)
+                makepkg.verify_file_checksum::<Md5>(dirs, p, s, sums, name, failed)
             }
             ChecksumKind::Sha1 => {
-                makepkg.verify_file_checksum::<Sha1>(dirs, p, s, sums, &name, failed)
+                makepkg.verify_file_checksum::<Sha1>(dirs, p, s, sums, name, failed)
             }
             ChecksumKind::Sha224 => {
-                makepkg.verify_file_checksum::<Sha224>(dirs, p, s, sums, &name, failed)
+                makepkg.verify_file_checksum::<Sha224>(dirs, p, s, sums, name, failed)
             }
             ChecksumKind::Sha256 => {
-                makepkg.verify_file_checksum::<Sha256>(dirs, p, s, sums, &name, failed)
+                makepkg.verify_file_checksum::<Sha256>(dirs, p, s, sums, name, failed)
             }
             ChecksumKind::Sha384 => {
-                makepkg.verify_file_checksum::<Sha384>(dirs, p, s, sums, &name, failed)
+                makepkg.verify_file_checksum::<Sha384>(dirs, p, s, sums, name, failed)
             }
             ChecksumKind::Sha512 => {
-                makepkg.verify_file_checksum::<Sha512>(dirs, p, s, sums, &name, failed)
+                makepkg.verify_file_checksum::<Sha512>(dirs, p, s, sums, name, failed)
             }
             ChecksumKind::Blake2 => {
-                makepkg.verify_file_checksum::<Blake2b512>(dirs, p, s, sums, &name, failed)
+                makepkg.verify_file_checksum::<Blake2b512>(dirs, p, s, sums, name, failed)
             }
         }
     }
@@ -191,6 +193,19 @@ impl<T> ArchVecs<T> {
 }
 
 impl ArchVecs<String> {
+    /// Parses the values of a dependency array such as `depends` or `optdepends`.
+    pub fn depends(&self) -> ArchVecs<Depend> {
+        let values = self
+            .values
+            .iter()
+            .map(|v| ArchVec {
+                arch: v.arch.clone(),
+                values: v.values.iter().map(|d| d.parse().unwrap()).collect(),
+            })
+            .collect();
+        ArchVecs { values }
+    }
+
     pub fn merge(&mut self, other: Variable) -> StdResult<(), LintKind> {
         let other = other.get_arch_array()?;
         if let Some(oldval) = self.values.iter_mut().find(|v| v.arch == other.arch) {
@@ -302,6 +317,7 @@ impl OptionValue {
 }
 
 #[derive(Debug, Clone, PartialEq, Eq, PartialOrd, Ord, Hash)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize))]
 pub enum Fragment {
     Revision(String),
     Branch(String),
@@ -352,6 +368,7 @@ impl Fragment {
 }
 
 #[derive(Debug, Default, Clone, PartialEq, Eq, PartialOrd, Ord, Hash)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize))]
 pub struct Source {
     pub filename_override: Option<String>,
     pub proto_prefix: Option<String>,
@@ -455,6 +472,126 @@ impl Source {
     }
 }
 
+#[derive(Debug, Copy, Clone, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub enum DepOp {
+    Lt,
+    Le,
+    Eq,
+    Ge,
+    Gt,
+}
+
+impl Display for DepOp {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str(self.as_str())
+    }
+}
+
+impl DepOp {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            DepOp::Lt => "<",
+            DepOp::Le => "<=",
+            DepOp::Eq => "=",
+            DepOp::Ge => ">=",
+            DepOp::Gt => ">",
+        }
+    }
+
+    fn new(s: &str) -> Option<Self> {
+        match s {
+            "<" => Some(DepOp::Lt),
+            "<=" => Some(DepOp::Le),
+            "=" => Some(DepOp::Eq),
+            ">=" => Some(DepOp::Ge),
+            ">" => Some(DepOp::Gt),
+            _ => None,
+        }
+    }
+}
+
+/// A parsed entry of a dependency array such as `name>=1.2-3: description`.
+///
+/// The description is only meaningful for optdepends. An entry with an invalid operator such
+/// as `name=>1` is kept whole as the name so it is not mistaken for a different constraint.
+#[derive(Debug, Default, Clone, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct Depend {
+    pub name: String,
+    /// The comparison operator. This is `None` if there is no version constraint.
+    pub op: Option<DepOp>,
+    pub version: Option<String>,
+    pub description: Option<String>,
+}
+
+impl FromStr for Depend {
+    type Err = std::convert::Infallible;
+
+    fn from_str(s: &str) -> StdResult<Self, Self::Err> {
+        let (dep, description) = match s.split_once(": ") {
+            Some((dep, description)) => (dep, Some(description.to_string())),
+            None => (s, None),
+        };
+
+        let Some(idx) = dep.find(['<', '>', '=']) else {
+            return Ok(Depend {
+                name: dep.to_string(),
+                description,
+                ..Default::default()
+            });
+        };
+
+        let (name, rest) = dep.split_at(idx);
+        let version = rest.trim_start_matches(['<', '>', '=']);
+        let Some(op) = DepOp::new(&rest[..rest.len() - version.len()]) else {
+            return Ok(Depend {
+                name: dep.to_string(),
+                description,
+                ..Default::default()
+            });
+        };
+
+        Ok(Depend {
+            name: name.to_string(),
+            op: Some(op),
+            version: Some(version.to_string()),
+            description,
+        })
+    }
+}
+
+impl Depend {
+    /// The name of a dependency array entry, borrowed from the entry.
+    pub fn name_of(dep: &str) -> &str {
+        let Ok(parsed) = dep.parse::<Depend>();
+        &dep[..parsed.name.len()]
+    }
+}
+
+impl Display for Depend {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str(&self.name)?;
+        if let Some(version) = &self.version {
+            f.write_str(self.op.map(|op| op.as_str()).unwrap_or("="))?;
+            f.write_str(version)?;
+        }
+        if let Some(description) = &self.description {
+            write!(f, ": {}", description)?;
+        }
+        Ok(())
+    }
+}
+
+/// A function defined in a PKGBUILD.
+#[derive(Debug, Default, Clone, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct FunctionSource {
+    pub name: String,
+    /// The line the function starts on.
+    pub line: usize,
+    /// The function as printed by `declare -f`. This is normalised by bash so comments and
+    /// formatting are not preserved.
+    pub body: String,
+}
+
 #[derive(Debug, Default, Clone, PartialEq, Eq, PartialOrd, Ord, Hash)]
 pub struct Pkgbuild {
     pub pkgbase: String,
@@ -489,6 +626,7 @@ pub struct Pkgbuild {
     pub options: Options,
     pub packages: Vec<Package>,
     pub functions: Vec<Function>,
+    function_sources: Vec<FunctionSource>,
     pub dir: PathBuf,
     pub(crate) package_functions: Vec<String>,
 }
@@ -519,7 +657,17 @@ impl Pkgbuild {
     }
 
     pub fn has_function(&self, func: Function) -> bool {
-        self.functions.iter().any(|f| *f == func)
+        self.functions.contains(&func)
+    }
+
+    /// The source of every function defined in the PKGBUILD.
+    pub fn function_sources(&self) -> &[FunctionSource] {
+        &self.function_sources
+    }
+
+    /// The source of a function such as `build` or `package_foo`.
+    pub fn function_source(&self, name: &str) -> Option<&FunctionSource> {
+        self.function_sources.iter().find(|f| f.name == name)
     }
 
     pub fn version(&self) -> String {
@@ -547,47 +695,30 @@ impl Pkgbuild {
             return Err(LintError::pkgbuild(lints).into());
         }
 
+        let mut editor = PkgbuildEditor::new(path)?;
         if pkgver != self.pkgver && self.pkgrel != "1" {
-            Pkgbuild::set_var(path, "pkgrel", "1")?;
+            editor.set_string("pkgrel", None, "1");
+            self.pkgrel = "1".to_string();
         }
 
         self.pkgver = pkgver;
-        Pkgbuild::set_var(path, "pkgver", &self.pkgver)?;
-        Ok(())
+        editor.set_string("pkgver", None, &self.pkgver);
+        editor.save()
     }
 
-    fn set_var(path: &Path, name: &str, val: &str) -> Result<()> {
-        let contents = read_to_string(path).context(
-            Context::SetPkgbuildVar("pkgver".to_string()),
-            IOContext::Read(path.to_path_buf()),
-        )?;
-        let mut edited = String::new();
-        let name = format!("{}=", name);
-
-        for line in contents.lines() {
-            if line.starts_with(&name) {
-                let split = line.split_once(char::is_whitespace);
-                edited.push_str("pkgver=");
-                edited.push_str(val);
-                if let Some((_, rest)) = split {
-                    edited.push(' ');
-                    edited.push_str(rest);
-                }
-            } else {
-                edited.push_str(line);
-            }
-            edited.push('\n');
-        }
-
-        std::fs::write(path, edited).context(
-            Context::SetPkgbuildVar("pkgver".to_string()),
-            IOContext::Write(path.to_path_buf()),
-        )?;
-
-        Ok(())
+    /// Parses the PKGBUILD in `dir` failing on any lint.
+    pub fn new<P: Into<PathBuf>>(dir: P) -> Result<Self> {
+        let (pkgbuild, _) = Pkgbuild::with_lint_levels(dir, &LintLevels::default())?;
+        Ok(pkgbuild)
     }
 
-    pub fn new<P: Into<PathBuf>>(dir: P) -> Result<Self> {
+    /// Parses the PKGBUILD in `dir` only failing on lints that are errors in `levels`.
+    ///
+    /// Lints at the warning level are returned alongside the [`Pkgbuild`].
+    pub fn with_lint_levels<P: Into<PathBuf>>(
+        dir: P,
+        levels: &LintLevels,
+    ) -> Result<(Self, Vec<LintKind>)> {
         let dir = dir.into();
         let dir = resolve_path(Context::ReadPkgbuild, dir)?;
         let pkgbuild_path = dir.join(Pkgbuild::file_name());
@@ -624,25 +755,28 @@ impl Pkgbuild {
         pkgbuild.functions = raw
             .functions
             .iter()
-            .filter_map(|f| Function::new(f))
+            .filter_map(|f| Function::new(&f.name))
             .collect();
 
         pkgbuild.package_functions = raw
             .functions
-            .into_iter()
+            .iter()
+            .map(|f| f.name.clone())
             .filter(|f| f.starts_with("package"))
             .collect();
+        pkgbuild.function_sources = raw.functions;
 
         pkgbuild.functions.sort();
         pkgbuild.functions.dedup();
 
         pkgbuild.lint(&mut lints);
 
-        if !lints.is_empty() {
-            return Err(LintError::pkgbuild(lints).into());
+        let (errors, warnings) = levels.split(lints);
+        if !errors.is_empty() {
+            return Err(LintError::pkgbuild(errors).with_locations(&pkgbuild).into());
         }
 
-        Ok(pkgbuild)
+        Ok((pkgbuild, warnings))
     }
 
     pub fn get_checksums(&self, kind: ChecksumKind) -> &ArchVecs<String> {
@@ -819,39 +953,83 @@ fn set_override_flag(package: &mut Package, var: &Variable) {
     });
 }
 
+/// A package file that building a [`Pkgbuild`] creates. See [`Config::package_list`].
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize))]
+pub struct PackagePath {
+    /// The pkgname, `<pkgbase>-debug` for the debug package.
+    pub pkgname: String,
+    pub kind: PackageKind,
+    /// The architecture in the file name, either `any` or CARCH.
+    pub arch: String,
+    pub path: PathBuf,
+}
+
 impl Config {
-    pub fn package_list(&self, pkgbuild: &Pkgbuild) -> Result<Vec<PathBuf>> {
+    /// The package files a [`Pkgbuild`] creates, including the debug package if debug and
+    /// strip are enabled. See [`PkgbuildDirs::package_dest`] for where each one goes.
+    ///
+    /// The debug package is only created when there were symbols to put in it so it may not
+    /// exist after a build.
+    pub fn package_list(&self, pkgbuild: &Pkgbuild) -> Result<Vec<PackagePath>> {
         let dirs = self.pkgbuild_dirs(pkgbuild)?;
-        let pkgbase = &pkgbuild.pkgbase;
-        let version = pkgbuild.version();
+        let version = self.package_version(pkgbuild);
         let mut pkgs = Vec::new();
+        let mut add = |pkgname: String, kind, arch: &[String]| {
+            let arch = self.package_arch(arch).to_string();
+            let filename = format!("{}-{}-{}{}", pkgname, version, arch, self.pkgext);
+            let path = dirs.package_dest(&pkgname, kind).join(filename);
+            pkgs.push(PackagePath {
+                pkgname,
+                kind,
+                arch,
+                path,
+            });
+        };
 
         for p in pkgbuild.packages() {
-            let filename = format!("{}-{}-{}{}", p.pkgname, version, self.arch, self.pkgext);
-            pkgs.push(dirs.pkgdest.join(filename));
-
-            if self.option(pkgbuild, "debug").enabled() && self.option(pkgbuild, "strip").enabled()
-            {
-                let filename = format!(
-                    "{}-{}-{}-{}{}",
-                    pkgbase, "debug", version, self.arch, self.pkgext
-                );
-                pkgs.push(dirs.pkgdest.join(filename));
-            }
+            add(p.pkgname.clone(), PackageKind::Package, &p.arch);
+        }
+        if self.option(pkgbuild, "debug").enabled() && self.option(pkgbuild, "strip").enabled() {
+            let pkgname = format!("{}-debug", pkgbuild.pkgbase);
+            add(pkgname, PackageKind::Debug, &pkgbuild.arch);
         }
 
         Ok(pkgs)
     }
+
+    /// Where a package file or signature with this file name belongs. Files that are not in
+    /// [`Config::package_list`] go to pkgdest.
+    pub(crate) fn package_file_dest(&self, pkgbuild: &Pkgbuild, name: &str) -> Result<PathBuf> {
+        let package = name.strip_suffix(".sig").unwrap_or(name);
+        for p in self.package_list(pkgbuild)? {
+            if p.path.file_name().is_some_and(|n| n == package) {
+                return Ok(p.path.with_file_name(name));
+            }
+        }
+        Ok(self.pkgbuild_dirs(pkgbuild)?.pkgdest.join(name))
+    }
+
+    /// The architecture a package is built for: `any` if the package is architecture
+    /// independent, otherwise CARCH.
+    pub fn package_arch(&self, arch: &[String]) -> &str {
+        if arch.iter().any(|a| a == "any") {
+            "any"
+        } else {
+            &self.arch
+        }
+    }
 }
 
 #[cfg(test)]
+#[allow(unused_variables, clippy::print_with_newline)]
 mod test {
     use super::*;
     use crate::{
         callback::{Callbacks, CommandOutput, Event, LogLevel, LogMessage},
         CommandKind, Makepkg, Options,
     };
-    use ansi_term::Style;
+    use ansi_term::{Color, Style};
     use std::{
         fs::File,
         io::{self, stdout, Write},
@@ -919,6 +1097,7 @@ mod test {
             &mut self,
             id: usize,
             kind: CommandKind,
+            _command: &crate::callback::CommandInfo,
         ) -> io::Result<crate::callback::CommandOutput> {
             print!(" | on new: -> {} <- | {:?}\n", id, kind.pkgbuild().pkgbase);
             Ok(CommandOutput::Callback)
@@ -969,6 +1148,42 @@ mod test {
         }
     }
 
+    #[test]
+    fn parse_depend() {
+        let Ok(dep) = "foo>=1.2-3: for bar".parse::<Depend>();
+        assert_eq!(dep.name, "foo");
+        assert_eq!(dep.op, Some(DepOp::Ge));
+        assert_eq!(dep.version.as_deref(), Some("1.2-3"));
+        assert_eq!(dep.description.as_deref(), Some("for bar"));
+        assert_eq!(dep.to_string(), "foo>=1.2-3: for bar");
+
+        let Ok(dep) = "foo".parse::<Depend>();
+        assert_eq!(dep.name, "foo");
+        assert_eq!(dep.op, None);
+        assert_eq!(dep.version, None);
+
+        for s in ["foo<1", "foo<=1", "foo=1", "foo>1"] {
+            let Ok(dep) = s.parse::<Depend>();
+            assert_eq!(dep.name, "foo");
+            assert_eq!(dep.to_string(), s);
+        }
+    }
+
+    #[test]
+    fn parse_depend_invalid_op() {
+        for s in ["foo=>1", "foo==1", "foo<>1", "foo>=<1"] {
+            let Ok(dep) = s.parse::<Depend>();
+            assert_eq!(dep.name, s);
+            assert_eq!(dep.op, None);
+            assert_eq!(dep.version, None);
+            assert_eq!(dep.to_string(), s);
+        }
+
+        let Ok(dep) = "foo=>1: for bar".parse::<Depend>();
+        assert_eq!(dep.name, "foo=>1");
+        assert_eq!(dep.description.as_deref(), Some("for bar"));
+    }
+
     #[ignore]
     #[test]
     fn geninteg() {
@@ -0,0 +1,752 @@
+use std::{
+    fs::read_to_string,
+    ops::Range,
+    path::{Path, PathBuf},
+};
+
+use crate::{
+    error::{Context, IOContext, IOErrorExt, Result},
+    pkgbuild::Pkgbuild,
+};
+
+/// Edits the top level variables of a PKGBUILD file, keeping the rest of the file, including
+/// comments and the layout of arrays, as written.
+///
+/// Values are written literally, quoted the way the value being replaced was quoted or with
+/// single quotes when a bare word would not do. Arch specific variables such as
+/// `source_x86_64` are edited by passing the architecture.
+///
+/// Setting a variable replaces its last top level assignment and drops any later `+=`
+/// appends to it. A variable that is not set is added after the last top level assignment.
+/// Assignments inside functions are never touched.
+///
+/// Changes are only written to disk by [`PkgbuildEditor::save`].
+#[derive(Debug, Clone)]
+pub struct PkgbuildEditor {
+    path: PathBuf,
+    contents: String,
+    changed: Vec<String>,
+}
+
+impl Pkgbuild {
+    /// Opens the PKGBUILD for editing.
+    pub fn editor(&self) -> Result<PkgbuildEditor> {
+        PkgbuildEditor::new(self.dir.join(Pkgbuild::file_name()))
+    }
+}
+
+impl PkgbuildEditor {
+    /// Reads the PKGBUILD file at `path` for editing.
+    pub fn new<P: Into<PathBuf>>(path: P) -> Result<Self> {
+        let path = path.into();
+        let contents =
+            read_to_string(&path).context(Context::ReadPkgbuild, IOContext::Read(path.clone()))?;
+        Ok(Self::from_contents(path, contents))
+    }
+
+    /// Edits `contents` as if it were read from `path`.
+    pub fn from_contents<P: Into<PathBuf>, S: Into<String>>(path: P, contents: S) -> Self {
+        PkgbuildEditor {
+            path: path.into(),
+            contents: contents.into(),
+            changed: Vec::new(),
+        }
+    }
+
+    pub fn path(&self) -> &Path {
+        &self.path
+    }
+
+    /// The edited file.
+    pub fn contents(&self) -> &str {
+        &self.contents
+    }
+
+    /// Sets a string variable such as `pkgver`.
+    pub fn set_string(&mut self, name: &str, arch: Option<&str>, value: &str) {
+        let name = var_name(name, arch);
+        let target = self.prepare(&name);
+
+        match target {
+            Some(assign) => {
+                let old = &self.contents[assign.value.clone()];
+                let old = match assign.array {
+                    true => array_items(&self.contents, &assign.value)
+                        .into_iter()
+                        .find(|t| !t.comment)
+                        .map(|t| &self.contents[t.span])
+                        .unwrap_or_default(),
+                    false => old,
+                };
+                let new = quote(value, QuoteStyle::of(old));
+                self.contents.replace_range(assign.value, &new);
+            }
+            None => self.insert(&format!("{}={}", name, quote(value, QuoteStyle::Bare))),
+        }
+        self.changed.push(name);
+    }
+
+    /// Sets an array variable such as `depends` or `sha256sums`.
+    ///
+    /// Values that replace an existing value keep its position and any comment after it.
+    /// Extra values are added after the last one using the same separator as the existing
+    /// values.
+    pub fn set_array<S: AsRef<str>>(&mut self, name: &str, arch: Option<&str>, values: &[S]) {
+        let name = var_name(name, arch);
+        let values = values.iter().map(|v| v.as_ref()).collect::<Vec<_>>();
+        let target = self.prepare(&name);
+
+        match target {
+            Some(assign) if assign.array => {
+                let edited = edit_array(&self.contents, &assign.value, &values);
+                self.contents.replace_range(assign.value, &edited);
+            }
+            Some(assign) => {
+                let style = QuoteStyle::of(&self.contents[assign.value.clone()]);
+                let values = values
+                    .iter()
+                    .map(|v| quote(v, style))
+                    .collect::<Vec<_>>()
+                    .join(" ");
+                self.contents
+                    .replace_range(assign.value, &format!("({})", values));
+            }
+            None => self.insert(&format_array(&name, &values)),
+        }
+        self.changed.push(name);
+    }
+
+    /// Removes every top level assignment of a variable.
+    pub fn remove(&mut self, name: &str, arch: Option<&str>) {
+        let name = var_name(name, arch);
+        let assigns = assignments(&self.contents);
+        for assign in assigns.iter().rev().filter(|a| a.name == name) {
+            self.remove_assignment(assign);
+        }
+        self.changed.push(name);
+    }
+
+    /// Writes the edited PKGBUILD back to its path.
+    pub fn save(&self) -> Result<()> {
+        std::fs::write(&self.path, &self.contents).context(
+            Context::SetPkgbuildVar(self.changed.join(" ")),
+            IOContext::Write(self.path.clone()),
+        )?;
+        Ok(())
+    }
+
+    // removes appends after the last plain assignment and returns that assignment
+    fn prepare(&mut self, name: &str) -> Option<Assignment> {
+        let assigns = assignments(&self.contents);
+        let last = assigns.iter().rposition(|a| a.name == name && !a.append);
+        let appends = assigns
+            .iter()
+            .enumerate()
+            .filter(|(i, a)| a.name == name && a.append && last.is_none_or(|l| *i > l))
+            .map(|(_, a)| a)
+            .collect::<Vec<_>>();
+
+        match last {
+            Some(last) => {
+                for append in appends.iter().rev() {
+                    self.remove_assignment(append);
+                }
+                Some(assigns[last].clone())
+            }
+            None => {
+                // with no plain assignment the first append becomes one
+                let first = appends.first().map(|a| (*a).clone());
+                for append in appends.iter().skip(1).rev() {
+                    self.remove_assignment(append);
+                }
+                let mut first = first?;
+                let plus = first.name_end;
+                self.contents.replace_range(plus..plus + 1, "");
+                first.value = first.value.start - 1..first.value.end - 1;
+                Some(first)
+            }
+        }
+    }
+
+    fn remove_assignment(&mut self, assign: &Assignment) {
+        let line_start = self.contents[..assign.start]
+            .rfind('\n')
+            .map(|i| i + 1)
+            .unwrap_or(0);
+        let line_end = self.contents[assign.value.end..]
+            .find('\n')
+            .map(|i| assign.value.end + i + 1)
+            .unwrap_or(self.contents.len());
+
+        let before = &self.contents[line_start..assign.start];
+        let after = self.contents[assign.value.end..line_end].trim_start_matches([' ', '\t']);
+        let ends_line = after.is_empty() || after.starts_with('\n') || after.starts_with('#');
+
+        if before.trim().is_empty() && ends_line {
+            self.contents.replace_range(line_start..line_end, "");
+        } else if ends_line {
+            // drop the separator from the previous command on the line instead
+            let start = line_start + before.trim_end_matches([' ', '\t', ';']).len();
+            self.contents.replace_range(start..assign.value.end, "");
+        } else {
+            let mut end = assign.value.end;
+            let rest = &self.contents[end..];
+            end += rest.len() - rest.trim_start_matches([' ', '\t', ';']).len();
+            self.contents.replace_range(assign.start..end, "");
+        }
+    }
+
+    // inserts a new assignment on its own line after the last top level assignment
+    fn insert(&mut self, text: &str) {
+        let pos = assignments(&self.contents)
+            .last()
+            .map(|a| {
+                self.contents[a.value.end..]
+                    .find('\n')
+                    .map(|i| a.value.end + i + 1)
+                    .unwrap_or(self.contents.len())
+            })
+            .unwrap_or(0);
+
+        let mut text = format!("{}\n", text);
+        if pos != 0 && !self.contents[..pos].ends_with('\n') {
+            text.insert(0, '\n');
+        }
+        self.contents.insert_str(pos, &text);
+    }
+}
+
+fn var_name(name: &str, arch: Option<&str>) -> String {
+    match arch {
+        Some(arch) => format!("{}_{}", name, arch),
+        None => name.to_string(),
+    }
+}
+
+/// Formats an array as `name=('a'` with each further value on its own line aligned under the
+/// first.
+pub(crate) fn format_array<S: AsRef<str>>(name: &str, values: &[S]) -> String {
+    let pad = format!("\n{}", " ".repeat(name.len() + 2));
+    let values = values
+        .iter()
+        .map(|v| quote(v.as_ref(), QuoteStyle::Single))
+        .collect::<Vec<_>>();
+    format!("{}=({})", name, values.join(&pad))
+}
+
+/// Formats a string assignment, only quoting the value if it needs it.
+pub(crate) fn format_string(name: &str, value: &str) -> String {
+    format!("{}={}", name, quote(value, QuoteStyle::Bare))
+}
+
+/// Formats an array on one line as `name=(a b)`, only quoting values that need it.
+pub(crate) fn format_words<S: AsRef<str>>(name: &str, values: &[S]) -> String {
+    let values = values
+        .iter()
+        .map(|v| quote(v.as_ref(), QuoteStyle::Bare))
+        .collect::<Vec<_>>();
+    format!("{}=({})", name, values.join(" "))
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum QuoteStyle {
+    Bare,
+    Single,
+    Double,
+}
+
+impl QuoteStyle {
+    fn of(word: &str) -> Self {
+        match word.chars().next() {
+            Some('\'') => QuoteStyle::Single,
+            Some('"') => QuoteStyle::Double,
+            _ => QuoteStyle::Bare,
+        }
+    }
+}
+
+fn quote(value: &str, style: QuoteStyle) -> String {
+    let bare = !value.is_empty()
+        && value
+            .chars()
+            .all(|c| c.is_ascii_alphanumeric() || "-_.+:/@,%=".contains(c));
+
+    match style {
+        QuoteStyle::Bare if bare => value.to_string(),
+        QuoteStyle::Bare | QuoteStyle::Single => format!("'{}'", value.replace('\'', "'\\''")),
+        QuoteStyle::Double => {
+            let mut out = String::from("\"");
+            for c in value.chars() {
+                if matches!(c, '"' | '\\' | '$' | '`') {
+                    out.push('\\');
+                }
+                out.push(c);
+            }
+            out.push('"');
+            out
+        }
+    }
+}
+
+#[derive(Debug, Clone)]
+struct Assignment {
+    name: String,
+    append: bool,
+    start: usize,
+    // the position of the `=`, or the `+` for appends
+    name_end: usize,
+    // the value including the parens of arrays
+    value: Range<usize>,
+    array: bool,
+}
+
+#[derive(Debug, Clone)]
+struct Token {
+    span: Range<usize>,
+    comment: bool,
+}
+
+// rewrites the array value at `span` in `s`, returning the new value
+fn edit_array(s: &str, span: &Range<usize>, values: &[&str]) -> String {
+    let tokens = array_items(s, span);
+    let items = tokens.iter().filter(|t| !t.comment).collect::<Vec<_>>();
+    let body = span.start + 1..span.end - 1;
+
+    if items.is_empty() {
+        let line_start = s[..span.start].rfind('\n').map(|i| i + 1).unwrap_or(0);
+        let name = &s[line_start..span.start - 1];
+        let array = format_array(name, values);
+        return format!("{}{})", &array[name.len() + 1..array.len() - 1], &s[body]);
+    }
+
+    let style = QuoteStyle::of(&s[items[0].span.clone()]);
+    let mut edits = Vec::new();
+
+    for (i, item) in items.iter().enumerate() {
+        match values.get(i) {
+            Some(value) => {
+                let style = QuoteStyle::of(&s[item.span.clone()]);
+                edits.push((item.span.clone(), quote(value, style)));
+            }
+            None => {
+                // drop the value, the space before it and a comment after it on the same line
+                let idx = tokens.iter().position(|t| t.span == item.span).unwrap();
+                let start = match idx {
+                    0 => body.start,
+                    // never remove the newline ending a comment or the value would join it
+                    _ if tokens[idx - 1].comment => {
+                        let end = tokens[idx - 1].span.end;
+                        end + s[end..].find('\n').map_or(0, |i| i + 1)
+                    }
+                    _ => tokens[idx - 1].span.end,
+                };
+                let mut end = item.span.end;
+                if let Some(next) = tokens.get(idx + 1) {
+                    if next.comment && !s[end..next.span.start].contains('\n') {
+                        end = next.span.end;
+                    }
+                }
+                // keep the first value's position when dropping it
+                let start = if i == 0 { item.span.start } else { start };
+                edits.push((start..end, String::new()));
+            }
+        }
+    }
+
+    if values.len() > items.len() {
+        let last = items[items.len() - 1];
+        let sep = if items.len() >= 2 {
+            let between = &s[items[items.len() - 2].span.end..last.span.start];
+            if between.contains('\n') {
+                let line_start = s[..last.span.start].rfind('\n').map(|i| i + 1).unwrap_or(0);
+                format!("\n{}", &s[line_start..last.span.start])
+            } else {
+                " ".to_string()
+            }
+        } else if s[body.clone()].contains('\n') {
+            let line_start = s[..last.span.start].rfind('\n').map(|i| i + 1).unwrap_or(0);
+            let indent = &s[line_start..last.span.start];
+            match indent.trim().is_empty() {
+                true => format!("\n{}", indent),
+                false => " ".to_string(),
+            }
+        } else {
+            " ".to_string()
+        };
+
+        // don't put values inside a comment trailing the last value
+        let idx = tokens.iter().position(|t| t.span == last.span).unwrap();
+        let mut pos = last.span.end;
+        if let Some(next) = tokens.get(idx + 1) {
+            if next.comment && !s[pos..next.span.start].contains('\n') {
+                pos = next.span.end;
+            }
+        }
+        let sep = if pos != last.span.end && !sep.starts_with('\n') {
+            let line_start = s[..last.span.start].rfind('\n').map(|i| i + 1).unwrap_or(0);
+            format!("\n{}", " ".repeat(last.span.start - line_start))
+        } else {
+            sep
+        };
+
+        let extra = values[items.len()..]
+            .iter()
+            .map(|v| format!("{}{}", sep, quote(v, style)))
+            .collect::<String>();
+        edits.push((pos..pos, extra));
+    }
+
+    let mut out = s[span.clone()].to_string();
+    edits.sort_by_key(|(r, _)| std::cmp::Reverse(r.start));
+    for (range, text) in edits {
+        out.replace_range(range.start - span.start..range.end - span.start, &text);
+    }
+    out
+}
+
+// the values and comments of the array at `span`
+fn array_items(s: &str, span: &Range<usize>) -> Vec<Token> {
+    let b = s.as_bytes();
+    let mut tokens = Vec::new();
+    let mut i = span.start + 1;
+    let end = span.end - 1;
+
+    while i < end {
+        match b[i] {
+            b' ' | b'\t' | b'\n' => i += 1,
+            b'#' => {
+                let stop = s[i..end].find('\n').map(|n| i + n).unwrap_or(end);
+                tokens.push(Token {
+                    span: i..stop,
+                    comment: true,
+                });
+                i = stop;
+            }
+            _ => {
+                let stop = word_end(s, i).min(end);
+                tokens.push(Token {
+                    span: i..stop,
+                    comment: false,
+                });
+                i = stop.max(i + 1);
+            }
+        }
+    }
+
+    tokens
+}
+
+// the end of the shell word starting at `start`
+fn word_end(s: &str, start: usize) -> usize {
+    let b = s.as_bytes();
+    let mut i = start;
+
+    while i < b.len() {
+        match b[i] {
+            b'\\' => i += 2,
+            b'\'' => i = s[i + 1..].find('\'').map(|n| i + n + 2).unwrap_or(b.len()),
+            b'"' => i = quoted_end(s, i),
+            b'$' if matches!(b.get(i + 1), Some(b'(' | b'{')) => i = group_end(s, i + 1),
+            b' ' | b'\t' | b'\n' | b';' | b'&' | b'|' | b'(' | b')' | b'<' | b'>' => break,
+            _ => i += 1,
+        }
+    }
+
+    i.min(b.len())
+}
+
+// the end of the double quoted string starting at `start`
+fn quoted_end(s: &str, start: usize) -> usize {
+    let b = s.as_bytes();
+    let mut i = start + 1;
+
+    while i < b.len() {
+        match b[i] {
+            b'\\' => i += 2,
+            b'"' => return i + 1,
+            b'$' if matches!(b.get(i + 1), Some(b'(' | b'{')) => i = group_end(s, i + 1),
+            _ => i += 1,
+        }
+    }
+
+    b.len()
+}
+
+// the end of the `(...)` or `{...}` starting at `start`, used for `$(...)` and `${...}`
+fn group_end(s: &str, start: usize) -> usize {
+    let b = s.as_bytes();
+    let (open, close) = match b[start] {
+        b'(' => (b'(', b')'),
+        _ => (b'{', b'}'),
+    };
+    let mut depth = 0;
+    let mut i = start;
+
+    while i < b.len() {
+        match b[i] {
+            b'\\' => i += 1,
+            b'\'' if open == b'(' => {
+                i = s[i + 1..].find('\'').map(|n| i + n + 1).unwrap_or(b.len())
+            }
+            b'"' => i = quoted_end(s, i) - 1,
+            c if c == open => depth += 1,
+            c if c == close => {
+                depth -= 1;
+                if depth == 0 {
+                    return i + 1;
+                }
+            }
+            _ => (),
+        }
+        i += 1;
+    }
+
+    b.len()
+}
+
+// the end of the array value whose `(` is at `start`
+fn array_end(s: &str, start: usize) -> usize {
+    let b = s.as_bytes();
+    let mut i = start + 1;
+
+    while i < b.len() {
+        match b[i] {
+            b')' => return i + 1,
+            b' ' | b'\t' | b'\n' => i += 1,
+            b'#' => i = s[i..].find('\n').map(|n| i + n).unwrap_or(b.len()),
+            _ => i = word_end(s, i).max(i + 1),
+        }
+    }
+
+    b.len()
+}
+
+// every top level variable assignment in order
+fn assignments(s: &str) -> Vec<Assignment> {
+    let b = s.as_bytes();
+    let mut assigns = Vec::new();
+    let mut heredocs = Vec::<(String, bool)>::new();
+    let mut braces = 0i32;
+    let mut parens = 0i32;
+    let mut command_start = true;
+    let mut i = 0;
+
+    while i < b.len() {
+        let word_start = i == 0 || matches!(b[i - 1], b' ' | b'\t' | b'\n' | b';' | b'&' | b'|');
+
+        match b[i] {
+            b'\n' => {
+                i += 1;
+                command_start = true;
+                for (delim, strip) in heredocs.drain(..) {
+                    i = heredoc_end(s, i, &delim, strip);
+                }
+            }
+            b';' | b'&' | b'|' => {
+                i += 1;
+                command_start = true;
+            }
+            b' ' | b'\t' => i += 1,
+            b'#' if word_start => i = s[i..].find('\n').map(|n| i + n).unwrap_or(b.len()),
+            b'(' => {
+                parens += 1;
+                i += 1;
+                command_start = true;
+            }
+            b')' => {
+                parens -= 1;
+                i += 1;
+            }
+            b'{' | b'}'
+                if word_start
+                    && matches!(b.get(i + 1), None | Some(b' ' | b'\t' | b'\n' | b';')) =>
+            {
+                braces += if b[i] == b'{' { 1 } else { -1 };
+                i += 1;
+                command_start = true;
+            }
+            b'<' if b.get(i + 1) == Some(&b'<') && b.get(i + 2) != Some(&b'<') => {
+                let strip = b.get(i + 2) == Some(&b'-');
+                let mut j = i + if strip { 3 } else { 2 };
+                while matches!(b.get(j), Some(b' ' | b'\t')) {
+                    j += 1;
+                }
+                let end = word_end(s, j);
+                let delim = s[j..end].replace(['\'', '"', '\\'], "");
+                heredocs.push((delim, strip));
+                i = end;
+            }
+            _ => {
+                let top = braces <= 0 && parens <= 0;
+                match assignment(s, i).filter(|_| command_start && top) {
+                    Some(assign) => {
+                        i = assign.value.end;
+                        assigns.push(assign);
+                    }
+                    None => {
+                        i = word_end(s, i).max(i + 1);
+                        command_start = false;
+                    }
+                }
+            }
+        }
+    }
+
+    assigns
+}
+
+// skips the body of a heredoc starting at the line at `start`
+fn heredoc_end(s: &str, start: usize, delim: &str, strip: bool) -> usize {
+    let mut i = start;
+    while i < s.len() {
+        let end = s[i..].find('\n').map(|n| i + n + 1).unwrap_or(s.len());
+        let line = s[i..end].trim_end_matches('\n');
+        let line = if strip {
+            line.trim_start_matches('\t')
+        } else {
+            line
+        };
+        i = end;
+        if line == delim {
+            break;
+        }
+    }
+    i
+}
+
+// parses `name=value` or `name+=value` at `start`
+fn assignment(s: &str, start: usize) -> Option<Assignment> {
+    let b = s.as_bytes();
+    let mut i = start;
+
+    if !b[i].is_ascii_alphabetic() && b[i] != b'_' {
+        return None;
+    }
+    while i < b.len() && (b[i].is_ascii_alphanumeric() || b[i] == b'_') {
+        i += 1;
+    }
+
+    let name_end = i;
+    let append = b.get(i) == Some(&b'+');
+    if append {
+        i += 1;
+    }
+    if b.get(i) != Some(&b'=') {
+        return None;
+    }
+    i += 1;
+
+    let array = b.get(i) == Some(&b'(');
+    let end = if array {
+        array_end(s, i)
+    } else {
+        word_end(s, i)
+    };
+
+    Some(Assignment {
+        name: s[start..name_end].to_string(),
+        append,
+        start,
+        name_end,
+        value: i..end,
+        array,
+    })
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    fn edit(contents: &str, f: impl FnOnce(&mut PkgbuildEditor)) -> String {
+        let mut editor = PkgbuildEditor::from_contents("PKGBUILD", contents);
+        f(&mut editor);
+        editor.contents().to_string()
+    }
+
+    #[test]
+    fn set_string() {
+        let pkgbuild = "pkgname=foo\npkgver=1.0 # version\npkgrel='2'\n";
+        let edited = edit(pkgbuild, |e| {
+            e.set_string("pkgver", None, "1.1");
+            e.set_string("pkgrel", None, "1");
+            e.set_string("pkgdesc", None, "a package");
+        });
+        assert_eq!(
+            edited,
+            "pkgname=foo\npkgver=1.1 # version\npkgrel='1'\npkgdesc='a package'\n"
+        );
+    }
+
+    #[test]
+    fn set_array() {
+        let pkgbuild = "depends=('a' # first\n         'b')\nmakedepends=(x)\n";
+        let edited = edit(pkgbuild, |e| {
+            e.set_array("depends", None, &["c", "d", "e"]);
+            e.set_array("makedepends", None, &[] as &[&str]);
+            e.set_array("provides", None, &["foo"]);
+        });
+        assert_eq!(
+            edited,
+            "depends=('c' # first\n         'd'\n         'e')\nmakedepends=()\nprovides=('foo')\n"
+        );
+
+        let edited = edit(pkgbuild, |e| e.set_array("depends", None, &["c"]));
+        assert_eq!(edited, "depends=('c' # first\n)\nmakedepends=(x)\n");
+    }
+
+    #[test]
+    fn remove() {
+        let pkgbuild = "pkgname=foo\ndepends=(a)\npkgver=1; depends+=(b)\n";
+        let edited = edit(pkgbuild, |e| e.remove("depends", None));
+        assert_eq!(edited, "pkgname=foo\npkgver=1\n");
+    }
+
+    #[test]
+    fn arch_suffix() {
+        let pkgbuild = "source=(a.tar.gz)\nsource_x86_64=(b.tar.gz)\n";
+        let edited = edit(pkgbuild, |e| {
+            e.set_array("source", Some("x86_64"), &["c.tar.gz"]);
+            e.set_array("source", Some("aarch64"), &["d.tar.gz"]);
+        });
+        assert_eq!(
+            edited,
+            "source=(a.tar.gz)\nsource_x86_64=(c.tar.gz)\nsource_aarch64=('d.tar.gz')\n"
+        );
+    }
+
+    #[test]
+    fn append() {
+        let pkgbuild = "depends=(a)\ndepends+=(b)\npkgver=1\n";
+        let edited = edit(pkgbuild, |e| e.set_array("depends", None, &["c"]));
+        assert_eq!(edited, "depends=(c)\npkgver=1\n");
+
+        let pkgbuild = "pkgver=1\ndepends+=(a)\ndepends+=(b)\n";
+        let edited = edit(pkgbuild, |e| e.set_array("depends", None, &["c"]));
+        assert_eq!(edited, "pkgver=1\ndepends=(c)\n");
+    }
+
+    #[test]
+    fn skips_heredocs_and_functions() {
+        let pkgbuild = "\
+pkgver=1
+build() {
+  pkgver=2
+  cat <<EOF
+pkgver=3
+EOF
+}
+cat <<-'END' > file
+	pkgver=4
+	END
+pkgrel=1
+";
+        let edited = edit(pkgbuild, |e| e.set_string("pkgver", None, "5"));
+        assert_eq!(edited, pkgbuild.replacen("pkgver=1", "pkgver=5", 1));
+
+        let edited = edit(pkgbuild, |e| e.set_string("pkgdesc", None, "x"));
+        assert_eq!(
+            edited,
+            pkgbuild.replace("pkgrel=1\n", "pkgrel=1\npkgdesc=x\n")
+        );
+    }
+}
@@ -0,0 +1,120 @@
+use std::{collections::BTreeSet, path::PathBuf};
+
+use crate::{
+    config::{PackagingBackend, PkgbuildDirs},
+    error::Result,
+    pkgbuild::{PackagePath, Pkgbuild, Source},
+    Makepkg,
+};
+
+// Sources are downloaded, extracted, built and then packaged. Assume each step needs
+// roughly as much space as the sources themselves.
+pub(crate) const DISK_USAGE_FACTOR: u64 = 4;
+
+/// A source that is not yet in [`srcdest`](crate::config::PkgbuildDirs::srcdest).
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct PlannedDownload {
+    pub source: Source,
+    /// Where the source will be downloaded to.
+    pub path: PathBuf,
+    /// The size of the download if the server reported one.
+    pub size: Option<u64>,
+}
+
+/// What building a PKGBUILD is expected to produce and require.
+///
+/// Returned by [`Makepkg::estimate_build`].
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct BuildPlan {
+    /// The package files that will be created.
+    pub packages: Vec<PackagePath>,
+    /// Dependencies, make dependencies and check dependencies that are not installed.
+    pub missing_depends: Vec<String>,
+    /// Sources that need to be downloaded.
+    pub downloads: Vec<PlannedDownload>,
+    /// External programs the build will run.
+    pub tools: BTreeSet<String>,
+    /// A rough estimate of the disk space the build will use in bytes.
+    ///
+    /// This is `None` when the size of no source is known.
+    pub disk_usage: Option<u64>,
+}
+
+impl BuildPlan {
+    /// The combined size of all downloads with a known size.
+    pub fn download_size(&self) -> u64 {
+        self.downloads.iter().filter_map(|d| d.size).sum()
+    }
+}
+
+impl Makepkg {
+    /// Works out what building a PKGBUILD will do without doing it.
+    ///
+    /// This queries pacman for missing dependencies and may make network requests to find
+    /// the size of sources that have not been downloaded yet.
+    pub fn estimate_build(&self, pkgbuild: &Pkgbuild) -> Result<BuildPlan> {
+        let config = &self.config;
+        let dirs = self.pkgbuild_dirs(pkgbuild)?;
+        let mut plan = BuildPlan {
+            packages: config.package_list(pkgbuild)?,
+            missing_depends: self.missing_depends(pkgbuild)?,
+            ..Default::default()
+        };
+        let mut source_size = None;
+
+        for source in pkgbuild.source.enabled(&config.arch) {
+            let path = dirs.download_path(source);
+            let size = self.source_size(&dirs, source);
+            if let Some(size) = size {
+                *source_size.get_or_insert(0) += size;
+            }
+
+            if let Some(vcs) = source.vcs_kind() {
+                plan.tools.insert(vcs.name().to_string());
+            } else if let Some(tool) = self.get_download_tool(source).filter(|_| !path.exists()) {
+                plan.tools.insert(tool.command.clone());
+            }
+
+            if !path.exists() {
+                plan.downloads.push(PlannedDownload {
+                    source: source.clone(),
+                    path,
+                    size,
+                });
+            }
+        }
+
+        plan.tools.insert("bash".to_string());
+        plan.tools
+            .insert(self.shell().to_string_lossy().into_owned());
+        plan.tools.insert("bsdtar".to_string());
+        if config.packaging_backend == PackagingBackend::Fakeroot {
+            plan.tools.insert("fakeroot".to_string());
+        }
+        if let Some(compress) = config.compress_args(config.pkgext.compress()).first() {
+            plan.tools.insert(compress.clone());
+        }
+
+        plan.disk_usage = source_size.map(|s| s * DISK_USAGE_FACTOR);
+        Ok(plan)
+    }
+
+    /// Estimates the disk space a build will use from the size of its downloaded sources.
+    pub(crate) fn estimate_disk_usage(
+        &self,
+        dirs: &PkgbuildDirs,
+        pkgbuild: &Pkgbuild,
+    ) -> Option<u64> {
+        pkgbuild
+            .source
+            .enabled(&self.config.arch)
+            .filter_map(|s| self.local_source_size(dirs, s))
+            .reduce(|a, b| a + b)
+            .map(|s| s * DISK_USAGE_FACTOR)
+    }
+
+    fn missing_depends(&self, pkgbuild: &Pkgbuild) -> Result<Vec<String>> {
+        let report = self.check_deps(pkgbuild)?;
+        Ok(report.unsatisfied().map(|d| d.dep.clone()).collect())
+    }
+}
@@ -0,0 +1,109 @@
+//! Python bindings for PKGBUILD parsing, linting, .SRCINFO generation and checksum
+//! verification.
+//!
+//! Build the crate as an extension module with the `python` feature, for example with
+//! `maturin build --features python,pyo3/extension-module`, and `import makepkg`.
+
+use std::path::PathBuf;
+
+use pyo3::{create_exception, exceptions::PyException, prelude::*};
+
+use crate::{
+    config::Config, error::LintLevels, options::Options, pkgbuild::Pkgbuild as RsPkgbuild, Makepkg,
+};
+
+create_exception!(makepkg, MakepkgError, PyException);
+
+fn err<E: std::fmt::Display>(e: E) -> PyErr {
+    MakepkgError::new_err(e.to_string())
+}
+
+/// A parsed PKGBUILD.
+#[pyclass(name = "Pkgbuild", module = "makepkg")]
+struct Pkgbuild {
+    inner: RsPkgbuild,
+    lints: Vec<String>,
+}
+
+#[pymethods]
+impl Pkgbuild {
+    /// Parses the PKGBUILD in `dir`. Lints that are errors raise MakepkgError, the rest are
+    /// available from `lints`.
+    #[new]
+    fn new(dir: PathBuf) -> PyResult<Self> {
+        let (inner, lints) =
+            RsPkgbuild::with_lint_levels(dir, &LintLevels::default()).map_err(err)?;
+        let lints = lints.iter().map(|l| l.to_string()).collect();
+        Ok(Pkgbuild { inner, lints })
+    }
+
+    #[getter]
+    fn pkgbase(&self) -> &str {
+        &self.inner.pkgbase
+    }
+
+    #[getter]
+    fn pkgver(&self) -> &str {
+        &self.inner.pkgver
+    }
+
+    #[getter]
+    fn pkgrel(&self) -> &str {
+        &self.inner.pkgrel
+    }
+
+    #[getter]
+    fn epoch(&self) -> Option<&str> {
+        self.inner.epoch.as_deref()
+    }
+
+    /// The full version including epoch and pkgrel.
+    #[getter]
+    fn version(&self) -> String {
+        self.inner.version()
+    }
+
+    #[getter]
+    fn pkgnames(&self) -> Vec<String> {
+        self.inner.packages().map(|p| p.pkgname.clone()).collect()
+    }
+
+    /// Warnings found while parsing the PKGBUILD.
+    #[getter]
+    fn lints(&self) -> Vec<String> {
+        self.lints.clone()
+    }
+
+    /// Generates the .SRCINFO for the PKGBUILD.
+    fn srcinfo(&self) -> String {
+        self.inner.srcinfo()
+    }
+
+    /// Verifies the checksums of the already downloaded sources using the makepkg.conf at
+    /// `config` or the system one. Raises MakepkgError if any do not match.
+    #[pyo3(signature = (config=None))]
+    fn verify_checksums(&self, config: Option<PathBuf>) -> PyResult<()> {
+        let config = match config {
+            Some(path) => Config::from_path(path),
+            None => Config::new(),
+        }
+        .map_err(err)?;
+        let makepkg = Makepkg::from_config(config);
+        let mut options = Options::new();
+        options.no_signatures = true;
+        makepkg
+            .check_integ(&options, &self.inner, false)
+            .map_err(err)
+    }
+
+    fn __repr__(&self) -> String {
+        format!("<Pkgbuild {} {}>", self.inner.pkgbase, self.inner.version())
+    }
+}
+
+#[pymodule]
+fn makepkg(m: &Bound<'_, PyModule>) -> PyResult<()> {
+    m.add_class::<Pkgbuild>()?;
+    m.add("MakepkgError", m.py().get_type::<MakepkgError>())?;
+    Ok(())
+}
@@ -49,7 +49,8 @@
 //! variable_decl   = "VAR" global_var variable
 //!                 | "VAR" function_var variable
 //!
-//! function_decl   = "FUNCTION" function_name
+//! line            = digit { digit }
+//! function_decl   = "FUNCTION" function_name line string
 //!
 //! statement        = variable_declration
 //!                 | function_declaration
@@ -67,7 +68,7 @@
 //! VAR GLOBAL ARRAY depends_x86_64 "gdb"
 //! VAR FUNCTION package STRING pkgdesc "does something"
 //! VAR FUNCTION package ARRAY depends
-//! FUNCTION package
+//! FUNCTION package 12 "package () \n{ \n    make DESTDIR=\"$pkgdir\" install\n}"
 
 use std::{
     collections::HashMap,
@@ -81,7 +82,8 @@ use crate::{
         CommandErrorExt, CommandOutputExt, Context, Error, IOContext, IOError, LintKind,
         ParseError, ParseErrorKind, Result,
     },
-    pkgbuild::ArchVec,
+    location::{add_spans, locate_dump_line},
+    pkgbuild::{ArchVec, FunctionSource},
     FileKind,
 };
 
@@ -96,11 +98,27 @@ pub enum Value {
     Map(HashMap<String, String>),
 }
 
+/// Where a variable is assigned in the PKGBUILD.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Span {
+    /// The line the assignment starts on starting from 1.
+    pub line: usize,
+    /// The line the assignment ends on.
+    pub end_line: usize,
+    /// The unexpanded right hand side of the assignment as written.
+    pub raw: String,
+}
+
 #[derive(Debug, Clone, PartialEq, Eq)]
 pub struct Variable {
     pub name: String,
     pub arch: Option<String>,
     pub value: Value,
+    /// Where the variable is assigned if it could be found in the PKGBUILD.
+    ///
+    /// Variables assigned in ways other than a plain `name=value` such as through `eval`
+    /// will not have a span.
+    pub span: Option<Span>,
 }
 
 impl Variable {
@@ -208,7 +226,7 @@ impl RawConfig {
 pub struct RawPkgbuild {
     pub variables: Vec<Variable>,
     pub function_variables: Vec<FunctionVariables>,
-    pub functions: Vec<String>,
+    pub functions: Vec<FunctionSource>,
 }
 
 impl RawPkgbuild {
@@ -227,16 +245,23 @@ impl RawPkgbuild {
         })?;
 
         let output = bash_output(Some(parent), &[&path], "dump")?;
-        let pkgbuild: RawPkgbuild =
-            RawPkgbuild::parse_processed_output(&output, FileKind::Pkgbuild)?;
+        let mut pkgbuild = RawPkgbuild::parse_dump(path, &output)?;
+        if let Ok(contents) = std::fs::read_to_string(path) {
+            add_spans(&contents, &mut pkgbuild);
+        }
         Ok(pkgbuild)
     }
 
-    fn parse_processed_output(s: &str, file_kind: FileKind) -> Result<Self> {
+    fn parse_dump(path: &Path, s: &str) -> Result<Self> {
         let mut data = Self::default();
 
         for line in s.lines() {
-            parse_line(&mut data, line, file_kind)?;
+            if let Err(mut err) = parse_line(&mut data, line, FileKind::Pkgbuild) {
+                if let Error::Parse(e) = &mut err {
+                    e.location = locate_dump_line(path, line);
+                }
+                return Err(err);
+            }
         }
 
         Ok(data)
@@ -418,7 +443,12 @@ fn parse_line(data: &mut RawPkgbuild, line: &str, file_kind: FileKind) -> Result
                 w => return Err(unexpected_word(line, w, file_kind)),
             };
 
-            let variable = Variable { name, arch, value };
+            let variable = Variable {
+                name,
+                arch,
+                value,
+                span: None,
+            };
 
             if let Some(function) = function {
                 match data
@@ -450,8 +480,17 @@ fn parse_function<I: Iterator<Item = String>>(
     line: &str,
     file_kind: FileKind,
     words: &mut I,
-) -> Result<String> {
-    let word = next_word(line, file_kind, words)?;
+) -> Result<FunctionSource> {
+    let name = next_word(line, file_kind, words)?;
+    let lineno = next_word(line, file_kind, words)?;
+    let lineno = lineno
+        .parse()
+        .map_err(|_| unexpected_word(line, &lineno, file_kind))?;
+    let body = next_word(line, file_kind, words)?;
     end_of_words(line, file_kind, words)?;
-    Ok(word)
+    Ok(FunctionSource {
+        name,
+        line: lineno,
+        body,
+    })
 }
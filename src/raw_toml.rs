@@ -0,0 +1,219 @@
+use std::{ffi::OsStr, path::Path};
+
+use toml::{Table, Value as TomlValue};
+
+use crate::{
+    error::{Context, LintKind, ParseError, ParseErrorKind, Result},
+    fs::read_to_string,
+    raw::{RawConfig, Value, Variable},
+    FileKind,
+};
+
+impl RawConfig {
+    /// Loads config files in order with later files overriding earlier ones. Files ending in
+    /// `.toml` are parsed directly and everything else is sourced with bash.
+    pub fn from_config_files<P: AsRef<Path>>(
+        paths: &[P],
+        lints: &mut Vec<LintKind>,
+    ) -> Result<Self> {
+        let mut variables = Vec::new();
+        let mut bash = Vec::new();
+
+        for path in paths {
+            let path = path.as_ref();
+            if path.extension() != Some(OsStr::new("toml")) {
+                bash.push(path);
+                continue;
+            }
+            if !bash.is_empty() {
+                variables.extend(RawConfig::from_paths(&bash)?.variables);
+                bash.clear();
+            }
+            variables.extend(RawConfig::from_toml(path, lints)?.variables);
+        }
+        if !bash.is_empty() {
+            variables.extend(RawConfig::from_paths(&bash)?.variables);
+        }
+
+        Ok(RawConfig { variables })
+    }
+
+    /// Parses a makepkg.toml. Keys are the same as the variables in makepkg.conf.
+    ///
+    /// Numbers and booleans are converted to strings and tables become arrays of
+    /// `key=value` entries as used by `PKGDEST_OVERRIDES` and `LINT_LEVELS`.
+    pub fn from_toml<P: AsRef<Path>>(path: P, lints: &mut Vec<LintKind>) -> Result<Self> {
+        let path = path.as_ref();
+        let contents = read_to_string(path, Context::ReadConfig)?;
+        let table = contents.parse::<Table>().map_err(|e| {
+            let kind = ParseErrorKind::Toml(format!("{}: {}", path.display(), e.message()));
+            ParseError::new(path.display().to_string(), FileKind::Config, kind)
+        })?;
+
+        let mut variables = Vec::new();
+        for (name, value) in table {
+            let value = match value {
+                TomlValue::Array(arr) => arr
+                    .into_iter()
+                    .filter_map(|v| toml_string(&name, v, lints))
+                    .collect::<Vec<_>>()
+                    .into(),
+                TomlValue::Table(table) => table
+                    .into_iter()
+                    .filter_map(|(k, v)| Some(format!("{}={}", k, toml_string(&name, v, lints)?)))
+                    .collect::<Vec<_>>()
+                    .into(),
+                value => match toml_string(&name, value, lints) {
+                    Some(s) => Value::String(s),
+                    None => continue,
+                },
+            };
+
+            variables.push(Variable {
+                name,
+                arch: None,
+                value,
+                span: None,
+            });
+        }
+
+        Ok(RawConfig { variables })
+    }
+}
+
+impl From<Vec<String>> for Value {
+    fn from(value: Vec<String>) -> Self {
+        Value::Array(value)
+    }
+}
+
+fn toml_string(name: &str, value: TomlValue, lints: &mut Vec<LintKind>) -> Option<String> {
+    match value {
+        TomlValue::String(s) => Some(s),
+        TomlValue::Integer(n) => Some(n.to_string()),
+        TomlValue::Float(n) => Some(n.to_string()),
+        TomlValue::Boolean(b) => Some(b.to_string()),
+        TomlValue::Datetime(d) => Some(d.to_string()),
+        value => {
+            lints.push(LintKind::WrongValueType(
+                name.into(),
+                "string".into(),
+                value.type_str().into(),
+            ));
+            None
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use std::fs;
+
+    fn values<'a>(config: &'a RawConfig, name: &str) -> Vec<&'a Value> {
+        config
+            .variables
+            .iter()
+            .filter(|v| v.name == name)
+            .map(|v| &v.value)
+            .collect()
+    }
+
+    fn string(s: &str) -> Value {
+        Value::String(s.to_string())
+    }
+
+    #[test]
+    fn from_toml() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("makepkg.toml");
+        let toml = r#"
+            PACKAGER = "Jane <jane@example.com>"
+            VCS_RETRIES = 3
+            RATIO = 1.5
+            COLOR = true
+            DATE = 1979-05-27
+            BUILDENV = ["!distcc", "color", 2]
+            LINT_LEVELS = { "pkgdesc" = "error", "url" = false }
+        "#;
+        fs::write(&path, toml).unwrap();
+
+        let mut lints = Vec::new();
+        let config = RawConfig::from_toml(&path, &mut lints).unwrap();
+        assert!(lints.is_empty());
+        assert_eq!(
+            values(&config, "PACKAGER"),
+            [&string("Jane <jane@example.com>")]
+        );
+        assert_eq!(values(&config, "VCS_RETRIES"), [&string("3")]);
+        assert_eq!(values(&config, "RATIO"), [&string("1.5")]);
+        assert_eq!(values(&config, "COLOR"), [&string("true")]);
+        assert_eq!(values(&config, "DATE"), [&string("1979-05-27")]);
+        assert_eq!(
+            values(&config, "BUILDENV"),
+            [&Value::Array(vec![
+                "!distcc".into(),
+                "color".into(),
+                "2".into()
+            ])]
+        );
+        assert_eq!(
+            values(&config, "LINT_LEVELS"),
+            [&Value::Array(vec![
+                "pkgdesc=error".into(),
+                "url=false".into()
+            ])]
+        );
+    }
+
+    #[test]
+    fn wrong_value_type() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("makepkg.toml");
+        let toml = "OPTIONS = [\"strip\", [\"debug\"]]\n\
+                    [PKGDEST]\n\
+                    nested = { a = 1 }\n";
+        fs::write(&path, toml).unwrap();
+
+        let mut lints = Vec::new();
+        let config = RawConfig::from_toml(&path, &mut lints).unwrap();
+        assert_eq!(
+            values(&config, "OPTIONS"),
+            [&Value::Array(vec!["strip".into()])]
+        );
+        assert_eq!(values(&config, "PKGDEST"), [&Value::Array(Vec::new())]);
+        assert_eq!(lints.len(), 2);
+        assert!(matches!(
+            &lints[0],
+            LintKind::WrongValueType(name, want, got)
+                if name == "OPTIONS" && want == "string" && got == "array"
+        ));
+        assert!(matches!(
+            &lints[1],
+            LintKind::WrongValueType(name, _, got) if name == "PKGDEST" && got == "table"
+        ));
+    }
+
+    #[test]
+    fn from_config_files_order() {
+        let dir = tempfile::tempdir().unwrap();
+        let first = dir.path().join("makepkg.conf");
+        let toml = dir.path().join("makepkg.toml");
+        let last = dir.path().join("user.conf");
+        fs::write(&first, "PKGDEST=/first\nSRCDEST=/first\n").unwrap();
+        fs::write(&toml, "PKGDEST = \"/toml\"\nSRCDEST = \"/toml\"\n").unwrap();
+        fs::write(&last, "SRCDEST=/last\n").unwrap();
+
+        let mut lints = Vec::new();
+        let config = RawConfig::from_config_files(&[&first, &toml, &last], &mut lints).unwrap();
+        // later variables override earlier ones
+        assert_eq!(
+            values(&config, "PKGDEST"),
+            [&string("/first"), &string("/toml")]
+        );
+        assert_eq!(
+            values(&config, "SRCDEST"),
+            [&string("/first"), &string("/toml"), &string("/last")]
+        );
+    }
+}
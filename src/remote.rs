@@ -0,0 +1,214 @@
+use std::{path::PathBuf, process::Command};
+
+use crate::{
+    callback::{CommandKind, Event},
+    config::PkgbuildDirs,
+    error::{CommandErrorExt, CommandOutputExt, Context, Result},
+    fs::{mkdir, rename},
+    pacman::shell_quote,
+    pkgbuild::Pkgbuild,
+    run::CommandOutput,
+    Makepkg,
+};
+
+/// A host to build packages on over SSH.
+///
+/// The remote host needs makepkg, tar and a POSIX shell. Everything else about the build,
+/// such as makepkg.conf, comes from the remote host.
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub struct RemoteBuilder {
+    /// The destination passed to ssh, e.g. `user@host`.
+    pub host: String,
+    /// Extra arguments passed to ssh.
+    pub ssh_args: Vec<String>,
+    /// The makepkg command to run on the remote host.
+    pub makepkg: String,
+    /// Extra arguments passed to the remote makepkg.
+    pub args: Vec<String>,
+}
+
+impl RemoteBuilder {
+    pub fn new<S: Into<String>>(host: S) -> Self {
+        RemoteBuilder {
+            host: host.into(),
+            ssh_args: Vec::new(),
+            makepkg: "makepkg".to_string(),
+            args: Vec::new(),
+        }
+    }
+
+    fn ssh(&self, command: &str) -> Command {
+        let mut ssh = Command::new("ssh");
+        ssh.args(&self.ssh_args)
+            .arg("--")
+            .arg(&self.host)
+            .arg(command);
+        ssh
+    }
+}
+
+impl Makepkg {
+    /// Builds a PKGBUILD on a remote host over SSH.
+    ///
+    /// startdir and any sources already in SRCDEST are copied to a temporary directory on
+    /// the remote host and makepkg is run there. Output of the remote build is relayed
+    /// through the callbacks. Once done the built packages are copied to PKGDEST and the
+    /// logs to LOGDEST and the temporary directory is removed.
+    ///
+    /// Returns the paths of the retrieved packages.
+    pub fn build_remote(
+        &self,
+        pkgbuild: &Pkgbuild,
+        remote: &RemoteBuilder,
+    ) -> Result<Vec<PathBuf>> {
+        let dirs = self.pkgbuild_dirs(pkgbuild)?;
+        let context = Context::RemoteBuild(remote.host.clone());
+
+        self.event(Event::BuildingRemote(&pkgbuild.pkgbase, &remote.host))?;
+
+        let mut command = remote.ssh("mktemp -d");
+        let tmp = command
+            .process_read(self, CommandKind::RemoteBuild(pkgbuild))
+            .read(&command, context.clone())?;
+        let tmp = tmp.trim();
+
+        let res = self.build_remote_in(&dirs, pkgbuild, remote, tmp, &context);
+
+        let mut command = remote.ssh(&format!("rm -rf -- {}", shell_quote(tmp)));
+        command
+            .process_spawn(self, CommandKind::RemoteBuild(pkgbuild))
+            .cmd_context(&command, context)?;
+
+        res
+    }
+
+    fn build_remote_in(
+        &self,
+        dirs: &PkgbuildDirs,
+        pkgbuild: &Pkgbuild,
+        remote: &RemoteBuilder,
+        tmp: &str,
+        context: &Context,
+    ) -> Result<Vec<PathBuf>> {
+        let qtmp = shell_quote(tmp);
+
+        let mut tar = Command::new("tar");
+        tar.arg("-C")
+            .arg(&dirs.startdir)
+            .arg("--exclude=./src")
+            .arg("--exclude=./pkg")
+            .arg("-cf")
+            .arg("-")
+            .arg(".");
+        self.remote_upload(pkgbuild, remote, tar, &qtmp, context)?;
+
+        let sources = pkgbuild
+            .source
+            .all()
+            .map(|s| s.file_name())
+            .filter(|f| dirs.srcdest != dirs.startdir && dirs.srcdest.join(f).exists())
+            .collect::<Vec<_>>();
+        if !sources.is_empty() {
+            let mut tar = Command::new("tar");
+            tar.arg("-C")
+                .arg(&dirs.srcdest)
+                .arg("-cf")
+                .arg("-")
+                .arg("--")
+                .args(&sources);
+            self.remote_upload(pkgbuild, remote, tar, &qtmp, context)?;
+        }
+
+        let args = remote
+            .args
+            .iter()
+            .map(|a| shell_quote(a))
+            .collect::<Vec<_>>()
+            .join(" ");
+        let mut command = remote.ssh(&format!(
+            "cd {0} && mkdir -p .out/pkg .out/log && \
+             PKGDEST={0}/.out/pkg LOGDEST={0}/.out/log {1} {2}",
+            qtmp, remote.makepkg, args
+        ));
+        command
+            .process_spawn(self, CommandKind::RemoteBuild(pkgbuild))
+            .cmd_context(&command, context.clone())?;
+
+        let mut command = remote.ssh(&format!("ls -1 {}/.out/pkg", qtmp));
+        let files = command
+            .process_read(self, CommandKind::RemoteBuild(pkgbuild))
+            .read(&command, context.clone())?;
+
+        self.remote_fetch(
+            pkgbuild,
+            remote,
+            &format!("{}/.out/pkg", qtmp),
+            dirs.pkgdest.clone(),
+            context,
+        )?;
+        self.remote_fetch(
+            pkgbuild,
+            remote,
+            &format!("{}/.out/log", qtmp),
+            dirs.logdest.clone(),
+            context,
+        )?;
+
+        let mut pkgs = Vec::new();
+        for file in files.lines() {
+            let fetched = dirs.pkgdest.join(file);
+            let dest = self.config.package_file_dest(pkgbuild, file)?;
+            if dest != fetched {
+                if let Some(dir) = dest.parent().filter(|d| !d.exists()) {
+                    mkdir(dir, context.clone())?;
+                }
+                rename(&fetched, &dest, context.clone())?;
+            }
+            pkgs.push(dest);
+        }
+        Ok(pkgs)
+    }
+
+    fn remote_upload(
+        &self,
+        pkgbuild: &Pkgbuild,
+        remote: &RemoteBuilder,
+        mut tar: Command,
+        qtmp: &str,
+        context: &Context,
+    ) -> Result<()> {
+        let mut ssh = remote.ssh(&format!("tar -C {} -xf -", qtmp));
+        tar.process_pipe(
+            self,
+            CommandKind::RemoteBuild(pkgbuild),
+            &[],
+            &mut ssh,
+            None,
+        )
+        .cmd_context(&tar, context.clone())?;
+        Ok(())
+    }
+
+    fn remote_fetch(
+        &self,
+        pkgbuild: &Pkgbuild,
+        remote: &RemoteBuilder,
+        qdir: &str,
+        dest: PathBuf,
+        context: &Context,
+    ) -> Result<()> {
+        mkdir(&dest, context.clone())?;
+        let mut ssh = remote.ssh(&format!("tar -C {} -cf - .", qdir));
+        let mut tar = Command::new("tar");
+        tar.arg("-C").arg(dest).arg("-xf").arg("-");
+        ssh.process_pipe(
+            self,
+            CommandKind::RemoteBuild(pkgbuild),
+            &[],
+            &mut tar,
+            None,
+        )
+        .cmd_context(&ssh, context.clone())?;
+        Ok(())
+    }
+}
@@ -0,0 +1,109 @@
+use std::{
+    fmt::Display,
+    time::{Duration, Instant},
+};
+
+use crate::{error::Result, package::BuiltPackage, pkgbuild::Pkgbuild, Makepkg};
+
+/// A step of [`Makepkg::build`] that is timed in the [`BuildReport`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize))]
+pub enum BuildPhase {
+    Download,
+    /// Checking the checksums and signatures of sources.
+    Verify,
+    Extract,
+    Prepare,
+    Build,
+    Check,
+    Package,
+    /// Creating the package archives.
+    Archive,
+}
+
+impl BuildPhase {
+    pub fn name(&self) -> &'static str {
+        match self {
+            BuildPhase::Download => "download",
+            BuildPhase::Verify => "verify",
+            BuildPhase::Extract => "extract",
+            BuildPhase::Prepare => "prepare",
+            BuildPhase::Build => "build",
+            BuildPhase::Check => "check",
+            BuildPhase::Package => "package",
+            BuildPhase::Archive => "archive",
+        }
+    }
+}
+
+impl Display for BuildPhase {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str(self.name())
+    }
+}
+
+/// How long each phase of a build took and the packages it produced.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize))]
+pub struct BuildReport {
+    /// The phases that ran in the order they ran.
+    pub phases: Vec<(BuildPhase, Duration)>,
+    /// Wall-clock time of the whole build, including the time spent outside of any phase.
+    pub total: Duration,
+    /// The package files that were created or restored from the artifact cache. Empty when
+    /// no archives were made.
+    pub packages: Vec<BuiltPackage>,
+}
+
+impl BuildReport {
+    /// The time spent in a phase or [`None`] if it did not run.
+    pub fn phase(&self, phase: BuildPhase) -> Option<Duration> {
+        self.phases
+            .iter()
+            .filter(|p| p.0 == phase)
+            .map(|p| p.1)
+            .reduce(|a, b| a + b)
+    }
+}
+
+impl Display for BuildReport {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "Finished in {:.1}s", self.total.as_secs_f64())?;
+        for (i, (phase, time)) in self.phases.iter().enumerate() {
+            let sep = if i == 0 { " (" } else { ", " };
+            write!(f, "{}{} {:.1}s", sep, phase, time.as_secs_f64())?;
+        }
+        if !self.phases.is_empty() {
+            write!(f, ")")?;
+        }
+        Ok(())
+    }
+}
+
+impl Makepkg {
+    /// Runs `f` and records how long it took in the report of the current build if there is
+    /// one.
+    pub(crate) fn time_phase<T>(
+        &self,
+        phase: BuildPhase,
+        f: impl FnOnce() -> Result<T>,
+    ) -> Result<T> {
+        #[cfg(feature = "tracing")]
+        let _span = tracing::info_span!("phase", phase = phase.name()).entered();
+        let start = Instant::now();
+        let res = f();
+        if let Some(report) = &mut *self.report.borrow_mut() {
+            report.phases.push((phase, start.elapsed()));
+        }
+        res
+    }
+
+    /// Adds the packages in pkgdest to the report of the current build.
+    pub(crate) fn record_built_packages(&self, pkgbuild: &Pkgbuild) -> Result<()> {
+        let packages = self.built_packages(pkgbuild)?;
+        if let Some(report) = &mut *self.report.borrow_mut() {
+            report.packages = packages;
+        }
+        Ok(())
+    }
+}
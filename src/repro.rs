@@ -0,0 +1,189 @@
+use std::{
+    collections::BTreeSet,
+    fmt::Display,
+    path::{Path, PathBuf},
+};
+
+use sha2::Sha256;
+
+use crate::{
+    callback::Event,
+    error::{Context, Result},
+    fs::{mkdir, rename, rm_all},
+    integ::hash_file,
+    options::Options,
+    package_contents::read_package,
+    pkgbuild::Pkgbuild,
+    Makepkg,
+};
+
+/// The outcome of [`Makepkg::check_reproducible`].
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize))]
+pub struct ReproducibilityReport {
+    pub packages: Vec<PackageReproducibility>,
+}
+
+impl ReproducibilityReport {
+    /// Whether both builds produced byte identical packages.
+    pub fn is_reproducible(&self) -> bool {
+        self.packages.iter().all(|p| p.identical)
+    }
+}
+
+impl Display for ReproducibilityReport {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        for package in &self.packages {
+            writeln!(f, "{}", package)?;
+        }
+        Ok(())
+    }
+}
+
+/// How one package file compared between the two builds.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize))]
+pub struct PackageReproducibility {
+    /// The file name of the package.
+    pub file: String,
+    /// The archives are byte for byte the same.
+    pub identical: bool,
+    /// Members that are missing from one build or differ in content, type, mode or mtime.
+    pub differences: Vec<PathBuf>,
+}
+
+impl Display for PackageReproducibility {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        if self.identical {
+            write!(f, "{}: reproducible", self.file)
+        } else if self.differences.is_empty() {
+            write!(f, "{}: archives differ but their members match", self.file)
+        } else {
+            write!(f, "{}: not reproducible, differences in:", self.file)?;
+            for member in &self.differences {
+                write!(f, "\n    {}", member.display())?;
+            }
+            Ok(())
+        }
+    }
+}
+
+impl Makepkg {
+    /// Builds the packages twice, the second time from a clean srcdir at the same path, and
+    /// compares the results.
+    ///
+    /// Both builds share the configuration's SOURCE_DATE_EPOCH. The artifact cache is not
+    /// used and the packages of the second build are left in pkgdest.
+    pub fn check_reproducible(
+        &mut self,
+        options: &Options,
+        pkgbuild: &mut Pkgbuild,
+    ) -> Result<ReproducibilityReport> {
+        let mut options = options.clone();
+        options.rebuild = true;
+        options.install = false;
+        options.resume = false;
+        options.skip_unchanged = false;
+        options.no_archive = false;
+
+        let cache = self.config.artifact_cache.take();
+        let res = self.check_reproducible_inner(&options, pkgbuild);
+        self.config.artifact_cache = cache;
+        res
+    }
+
+    fn check_reproducible_inner(
+        &mut self,
+        options: &Options,
+        pkgbuild: &mut Pkgbuild,
+    ) -> Result<ReproducibilityReport> {
+        self.build(options, pkgbuild)?;
+
+        let dirs = self.pkgbuild_dirs(pkgbuild)?;
+        let work = dirs.builddir.join(".makepkg-repro");
+        if work.exists() {
+            rm_all(&work, Context::CheckReproducible)?;
+        }
+        let first = work.join("first");
+        mkdir(&first, Context::CheckReproducible)?;
+
+        let packages = self
+            .config
+            .package_list(pkgbuild)?
+            .into_iter()
+            .map(|p| p.path)
+            .filter(|p| p.exists())
+            .collect::<Vec<_>>();
+        for package in &packages {
+            rename(
+                package,
+                first.join(file_name(package)),
+                Context::CheckReproducible,
+            )?;
+        }
+
+        // packages often embed their srcdir so the second build runs at the same path, from a
+        // clean srcdir
+        self.event(Event::CheckingReproducibility)?;
+        let selected = self.builddir.borrow().clone();
+        let builddir = match selected {
+            Some(dir) => self.config.builddir.replace(dir),
+            None => self.config.builddir.clone(),
+        };
+        let candidates = std::mem::take(&mut self.config.builddir_candidates);
+        let tmpfs = self.config.tmpfs_builddir.take();
+        let mut options = options.clone();
+        options.clean_build = true;
+        let res = self.build(&options, pkgbuild);
+        self.config.builddir = builddir;
+        self.config.builddir_candidates = candidates;
+        self.config.tmpfs_builddir = tmpfs;
+        res?;
+
+        let mut report = ReproducibilityReport::default();
+        for package in &packages {
+            let name = file_name(package);
+            report
+                .packages
+                .push(compare_packages(&first.join(name), package)?);
+        }
+
+        rm_all(&work, Context::CheckReproducible)?;
+        Ok(report)
+    }
+}
+
+fn compare_packages(first: &Path, second: &Path) -> Result<PackageReproducibility> {
+    let file = file_name(second).to_string();
+
+    if hash_file::<Sha256>(first)? == hash_file::<Sha256>(second)? {
+        return Ok(PackageReproducibility {
+            file,
+            identical: true,
+            differences: Vec::new(),
+        });
+    }
+
+    let a = read_package(first, Context::CheckReproducible)?;
+    let b = read_package(second, Context::CheckReproducible)?;
+    let differences = a
+        .keys()
+        .chain(b.keys())
+        .collect::<BTreeSet<_>>()
+        .into_iter()
+        .filter(|member| a.get(*member) != b.get(*member))
+        .cloned()
+        .collect();
+
+    Ok(PackageReproducibility {
+        file,
+        identical: false,
+        differences,
+    })
+}
+
+fn file_name(path: &Path) -> &str {
+    path.file_name()
+        .and_then(|n| n.to_str())
+        .unwrap_or_default()
+}
@@ -1,23 +1,39 @@
 use std::{
+    collections::BTreeMap,
     fs::File,
-    io::{self, stdout, Empty, ErrorKind, Read, Write},
+    io::{self, stdout, Empty, ErrorKind, IsTerminal, Read, Write},
     net::Shutdown,
     ops::Deref,
     os::{
-        fd::{AsFd, OwnedFd},
-        unix::net::UnixStream,
+        fd::{AsFd, AsRawFd, OwnedFd},
+        unix::{net::UnixStream, process::CommandExt},
     },
     path::Path,
-    process::{Command, ExitStatus, Output, Stdio},
+    process::{Child, Command, ExitStatus, Output, Stdio},
     result::Result as StdResult,
+    thread::sleep,
+    time::{Duration, Instant},
 };
 
-use mio::{Events, Interest, Poll, Token};
+use mio::{event::Source, unix::SourceFd, Events, Interest, Poll, Registry, Token};
+use nix::{
+    errno::Errno,
+    fcntl::{fcntl, FcntlArg, FdFlag, OFlag},
+    ioctl_read_bad,
+    libc::TIOCGWINSZ,
+    pty::{openpty, Winsize},
+    sys::{
+        signal::{kill, killpg, Signal},
+        termios::{tcgetattr, tcsetattr, OutputFlags, SetArg},
+        wait::waitpid,
+    },
+    unistd::{dup2, Pid},
+};
 
 use crate::{
-    callback::{self, CommandKind, Event},
+    callback::{self, Callbacks, CommandInfo, CommandKind, Event},
     config::PkgbuildDirs,
-    error::{CommandErrorExt, Context, IOContext, IOError, Result},
+    error::{CommandErrorExt, Context, IOContext, IOError, Result, TimeoutError},
     fs::open,
     installation_variables::FAKEROOT_LIBDIRS,
     makepkg::FakeRoot,
@@ -27,7 +43,23 @@ use crate::{
     Makepkg,
 };
 
+/// Extra control over how a command is run.
+#[derive(Debug, Default, Clone, Copy)]
+pub(crate) struct RunOptions {
+    /// Kill the command and fail with [`ErrorKind::TimedOut`] if it is still running at this
+    /// point. The command should be in its own process group.
+    pub deadline: Option<Instant>,
+    /// Give the command a pseudo-terminal for its output instead of a pipe.
+    pub pty: bool,
+    /// Let the command read from our stdin if [`Callbacks::command_stdin`] allows it.
+    pub stdin: bool,
+}
+
+/// Called periodically while a command runs so long running commands can report progress.
+pub(crate) type Progress<'a> = &'a mut dyn FnMut(&mut dyn Callbacks) -> io::Result<()>;
+
 pub(crate) trait CommandOutput {
+    #[allow(clippy::too_many_arguments)]
     fn process_inner<W: Write>(
         &mut self,
         makepkg: &Makepkg,
@@ -37,6 +69,8 @@ pub(crate) trait CommandOutput {
         ignore_stdout: bool,
         pipe_into: Option<&mut Command>,
         logfile: Option<&mut File>,
+        progress: Option<Progress>,
+        run: RunOptions,
     ) -> StdResult<ExitStatus, io::Error>;
     fn process_pipe(
         &mut self,
@@ -44,8 +78,19 @@ pub(crate) trait CommandOutput {
         kind: CommandKind,
         input: &[u8],
         pipe_into: &mut Command,
+        progress: Option<Progress>,
     ) -> StdResult<ExitStatus, io::Error> {
-        self.process_inner::<Empty>(makepkg, kind, input, None, true, Some(pipe_into), None)
+        self.process_inner::<Empty>(
+            makepkg,
+            kind,
+            input,
+            None,
+            true,
+            Some(pipe_into),
+            None,
+            progress,
+            RunOptions::default(),
+        )
     }
     fn process_function(
         &mut self,
@@ -54,18 +99,11 @@ pub(crate) trait CommandOutput {
         input: &[u8],
         pkgver: Option<&mut Vec<u8>>,
         logfile: Option<&mut File>,
+        run: RunOptions,
     ) -> StdResult<ExitStatus, io::Error> {
-        self.process_inner(makepkg, kind, input, pkgver, false, None, logfile)
-    }
-    fn process_input_output<W: Write>(
-        &mut self,
-        makepkg: &Makepkg,
-        kind: CommandKind,
-        input: &[u8],
-        output: Option<&mut W>,
-    ) -> StdResult<ExitStatus, io::Error> {
-        let ignore_stdout = output.is_some();
-        self.process_inner(makepkg, kind, input, output, ignore_stdout, None, None)
+        self.process_inner(
+            makepkg, kind, input, pkgver, false, None, logfile, None, run,
+        )
     }
     fn process_write_output<W: Write>(
         &mut self,
@@ -73,14 +111,34 @@ pub(crate) trait CommandOutput {
         kind: CommandKind,
         output: &mut W,
     ) -> StdResult<ExitStatus, io::Error> {
-        self.process_inner(makepkg, kind, &[], Some(output), true, None, None)
+        self.process_inner(
+            makepkg,
+            kind,
+            &[],
+            Some(output),
+            true,
+            None,
+            None,
+            None,
+            RunOptions::default(),
+        )
     }
     fn process_spawn(
         &mut self,
         makepkg: &Makepkg,
         kind: CommandKind,
     ) -> StdResult<ExitStatus, io::Error> {
-        self.process_inner::<Empty>(makepkg, kind, &[], None, false, None, None)
+        self.process_inner::<Empty>(
+            makepkg,
+            kind,
+            &[],
+            None,
+            false,
+            None,
+            None,
+            None,
+            RunOptions::default(),
+        )
     }
     fn process_read(
         &mut self,
@@ -89,7 +147,17 @@ pub(crate) trait CommandOutput {
     ) -> StdResult<Output, io::Error> {
         let mut output = Vec::new();
         let output = Output {
-            status: self.process_inner(makepkg, kind, &[], Some(&mut output), true, None, None)?,
+            status: self.process_inner(
+                makepkg,
+                kind,
+                &[],
+                Some(&mut output),
+                true,
+                None,
+                None,
+                None,
+                RunOptions::default(),
+            )?,
             stdout: output,
             stderr: Vec::new(),
         };
@@ -112,8 +180,20 @@ impl CommandOutput for Command {
         ignore_stdout: bool,
         pipe_into: Option<&mut Command>,
         mut logfile: Option<&mut File>,
+        mut progress: Option<Progress>,
+        run: RunOptions,
     ) -> StdResult<ExitStatus, io::Error> {
+        let deadline = run.deadline;
+        #[cfg(feature = "tracing")]
+        let _span = tracing::debug_span!(
+            "command",
+            pkgbase = kind.pkgbuild().pkgbase,
+            program = ?self.get_program(),
+            args = ?self.get_args().collect::<Vec<_>>(),
+        )
+        .entered();
         let mut callbacks = makepkg.callbacks.borrow_mut();
+        let mut timed_out = false;
         let ignore_stdout = ignore_stdout || pipe_into.is_some();
         let has_pipe = pipe_into.is_some();
 
@@ -131,8 +211,9 @@ impl CommandOutput for Command {
         struct CommandData {
             id: usize,
             how_output: callback::CommandOutput,
-            outsock: Option<mio::net::UnixStream>,
-            errsock: Option<mio::net::UnixStream>,
+            stdin: bool,
+            outsock: Option<Pipe>,
+            errsock: Option<Pipe>,
         }
 
         let mut setup_out = |command: &mut Command,
@@ -141,22 +222,40 @@ impl CommandOutput for Command {
          -> StdResult<CommandData, io::Error> {
             let mut outsock = None;
             let mut errsock = None;
-            let cap_out = (output.is_some() || logfile.is_some()) && !has_pipe;
+            // output being captured has to go through a pipe even when it is forwarded to an
+            // inherited, null or file sink
+            let cap_out = ((output.is_some() || logfile.is_some()) && !has_pipe)
+                || makepkg.captured_output.borrow().is_some();
 
             let mut id = makepkg.id.borrow_mut();
             *id += 1;
             let id = *id - 1;
 
             let how_output = if let Some(callbacks) = &mut *callbacks {
-                callbacks.command_new(id, kind)?
+                callbacks.command_new(id, kind, &CommandInfo::new(command))?
             } else {
                 Default::default()
             };
-
-            if matches!(how_output, callback::CommandOutput::Callback) || cap_out {
-                let (r, w) = UnixStream::pair()?;
-                r.set_nonblocking(true)?;
-                let mut r = mio::net::UnixStream::from_std(r);
+            let stdin = run.stdin
+                && !is_proc2
+                && match &mut *callbacks {
+                    Some(callbacks) => callbacks.command_stdin(id, kind)?,
+                    None => true,
+                };
+
+            let pty = run.pty && !is_proc2;
+            if matches!(how_output, callback::CommandOutput::Callback)
+                || cap_out
+                || (pty && matches!(how_output, callback::CommandOutput::Inherit))
+            {
+                let (mut r, w) = if pty {
+                    open_pty()?
+                } else {
+                    let (r, w) = UnixStream::pair()?;
+                    r.set_nonblocking(true)?;
+                    let r = mio::net::UnixStream::from_std(r);
+                    (Pipe::Socket(r), OwnedFd::from(w))
+                };
 
                 if output.is_some() {
                     let (r2, w2) = UnixStream::pair()?;
@@ -166,10 +265,9 @@ impl CommandOutput for Command {
                     poll.registry()
                         .register(&mut r2, token_out, Interest::READABLE)?;
                     *open |= token_out.0;
-                    outsock = Some(r2);
+                    outsock = Some(Pipe::Socket(r2));
                 } else if !ignore_stdout {
-                    let w2 = w.try_clone()?;
-                    command.stdout(OwnedFd::from(w2));
+                    command.stdout(w.try_clone()?);
                 }
 
                 let token = if is_proc2 { token_err2 } else { token_err };
@@ -177,7 +275,7 @@ impl CommandOutput for Command {
                 *open |= token.0;
                 poll.registry()
                     .register(&mut r, token, Interest::READABLE)?;
-                command.stderr(OwnedFd::from(w));
+                command.stderr(w);
                 errsock = Some(r);
             } else if let callback::CommandOutput::File(ref file) = how_output {
                 if !ignore_stdout {
@@ -194,6 +292,7 @@ impl CommandOutput for Command {
             let data = CommandData {
                 id,
                 how_output,
+                stdin,
                 outsock,
                 errsock,
             };
@@ -220,17 +319,43 @@ impl CommandOutput for Command {
                 .register(&mut w, token_in, Interest::WRITABLE)?;
             open |= token_in.0;
             insock = Some(w);
+        } else if data1.stdin {
+            self.stdin(Stdio::inherit());
         } else {
             self.stdin(Stdio::null());
         }
 
+        // stdin is already taken by the script so hand our stdin over as fd 3 and let
+        // the script redirect it to the function
+        let _stdin_fd = if data1.stdin && !input.is_empty() {
+            let fd = io::stdin().as_fd().try_clone_to_owned()?;
+            let raw = fd.as_raw_fd();
+            self.env("MAKEPKG_STDIN_FD", "3");
+            unsafe {
+                self.pre_exec(move || {
+                    if raw == 3 {
+                        fcntl(3, FcntlArg::F_SETFD(FdFlag::empty()))?;
+                    } else {
+                        dup2(raw, 3)?;
+                    }
+                    Ok(())
+                });
+            }
+            Some(fd)
+        } else {
+            None
+        };
+
         let mut child = self.spawn()?;
         let mut child2 = None;
+        let mut running = KillOnDrop(vec![child.id()]);
 
         if let Some(command) = pipe_into {
             data2 = setup_out(command, true, &mut open)?;
             command.stdin(child.stdout.take().unwrap());
-            child2 = Some(command.spawn()?);
+            let spawned = command.spawn()?;
+            running.0.push(spawned.id());
+            child2 = Some(spawned);
             command.stderr(Stdio::null());
         }
 
@@ -240,7 +365,20 @@ impl CommandOutput for Command {
         let mut ends_with_nl = true;
 
         while open != 0 {
-            poll.poll(&mut events, None)?;
+            let mut timeout = progress.as_ref().map(|_| PROGRESS_INTERVAL);
+            if let (Some(deadline), false) = (deadline, timed_out) {
+                let remaining = deadline.saturating_duration_since(Instant::now());
+                timeout = Some(timeout.map_or(remaining, |t| t.min(remaining)));
+            }
+            poll.poll(&mut events, timeout)?;
+            if deadline.is_some_and(|d| !timed_out && Instant::now() >= d) {
+                kill_group(&child, child2.as_ref());
+                timed_out = true;
+            }
+            if let (Some(progress), Some(callbacks)) = (&mut progress, &mut *callbacks) {
+                progress(callbacks.as_mut())?;
+            }
+
             //println!("open={open}");
             //println!("{events:#?}");
 
@@ -297,7 +435,16 @@ impl CommandOutput for Command {
                                         if let Some(ref mut logfile) = logfile {
                                             logfile.write_all(&buff[..n])?
                                         }
+                                        if let Some(captured) =
+                                            &mut *makepkg.captured_output.borrow_mut()
+                                        {
+                                            captured.extend_from_slice(&buff[..n]);
+                                        }
                                         if event.token() != token_out || !ignore_stdout {
+                                            if let Some(log) = &mut *makepkg.build_log.borrow_mut()
+                                            {
+                                                log.write_output(&buff[..n])?;
+                                            }
                                             ends_with_nl = buff[n - 1] == b'\n';
                                             match how_output {
                                                 callback::CommandOutput::Inherit => {
@@ -331,15 +478,15 @@ impl CommandOutput for Command {
 
                         if !ends_with_nl && event.token() == token_err {
                             match how_output {
-                                callback::CommandOutput::Inherit => stdout().write_all(&[b'\n'])?,
+                                callback::CommandOutput::Inherit => stdout().write_all(b"\n")?,
                                 callback::CommandOutput::Null => (),
                                 callback::CommandOutput::Callback => {
                                     if let Some(callbacks) = &mut *callbacks {
-                                        callbacks.command_output(data.id, kind, &[b'\n'])?;
+                                        callbacks.command_output(data.id, kind, b"\n")?;
                                     }
                                 }
                                 callback::CommandOutput::File(ref mut file) => {
-                                    file.write_all(&[b'\n'])?
+                                    file.write_all(b"\n")?
                                 }
                             }
                         }
@@ -348,22 +495,162 @@ impl CommandOutput for Command {
             }
         }
 
-        if let Some(callbacks) = &mut *callbacks {
-            callbacks.command_exit(data1.id, kind)?;
-        }
-
         if let Some(mut child2) = child2 {
-            let status = child2.wait()?;
+            let status = wait(&mut child2, &mut progress, &mut callbacks, deadline)?;
+            running.0.retain(|&pid| pid != child2.id());
             if let Some(callbacks) = &mut *callbacks {
-                callbacks.command_exit(data2.id, kind)?;
+                callbacks.command_exit(data2.id, kind, status)?;
             }
-            if !status.success() {
-                return Ok(status);
+            match status {
+                Some(status) if !status.success() => return Ok(status),
+                Some(_) => (),
+                None => timed_out = true,
             }
         }
 
-        let status = child.wait()?;
-        Ok(status)
+        let status = wait(&mut child, &mut progress, &mut callbacks, deadline)?;
+        running.0.clear();
+        if let Some(callbacks) = &mut *callbacks {
+            callbacks.command_exit(data1.id, kind, status)?;
+        }
+        #[cfg(feature = "tracing")]
+        tracing::debug!(status = ?status, timed_out, "command exited");
+        if timed_out || status.is_none() {
+            return Err(io::Error::from(ErrorKind::TimedOut));
+        }
+        Ok(status.unwrap())
+    }
+}
+
+// where the output of a command is read from
+#[derive(Debug)]
+enum Pipe {
+    Socket(mio::net::UnixStream),
+    /// The master side of a pseudo-terminal.
+    Pty(File),
+}
+
+impl Read for Pipe {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        match self {
+            Pipe::Socket(sock) => sock.read(buf),
+            // reading the master fails with EIO instead of returning EOF once the command exits
+            Pipe::Pty(file) => match file.read(buf) {
+                Err(e) if e.raw_os_error() == Some(Errno::EIO as i32) => Ok(0),
+                res => res,
+            },
+        }
+    }
+}
+
+impl Source for Pipe {
+    fn register(
+        &mut self,
+        registry: &Registry,
+        token: Token,
+        interests: Interest,
+    ) -> io::Result<()> {
+        match self {
+            Pipe::Socket(sock) => sock.register(registry, token, interests),
+            Pipe::Pty(file) => SourceFd(&file.as_raw_fd()).register(registry, token, interests),
+        }
+    }
+
+    fn reregister(
+        &mut self,
+        registry: &Registry,
+        token: Token,
+        interests: Interest,
+    ) -> io::Result<()> {
+        match self {
+            Pipe::Socket(sock) => sock.reregister(registry, token, interests),
+            Pipe::Pty(file) => SourceFd(&file.as_raw_fd()).reregister(registry, token, interests),
+        }
+    }
+
+    fn deregister(&mut self, registry: &Registry) -> io::Result<()> {
+        match self {
+            Pipe::Socket(sock) => sock.deregister(registry),
+            Pipe::Pty(file) => SourceFd(&file.as_raw_fd()).deregister(registry),
+        }
+    }
+}
+
+ioctl_read_bad!(get_winsize, TIOCGWINSZ, Winsize);
+
+// opens a pseudo-terminal the size of our own terminal, returns the master to read from and the
+// slave to give to the command
+fn open_pty() -> io::Result<(Pipe, OwnedFd)> {
+    let mut size = Winsize {
+        ws_row: 24,
+        ws_col: 80,
+        ws_xpixel: 0,
+        ws_ypixel: 0,
+    };
+    if stdout().is_terminal() {
+        let _ = unsafe { get_winsize(stdout().as_raw_fd(), &mut size) };
+    }
+
+    let pty = openpty(&size, None)?;
+
+    // keep plain \n line endings so the output is the same as from a pipe
+    let mut termios = tcgetattr(&pty.slave)?;
+    termios.output_flags.remove(OutputFlags::ONLCR);
+    tcsetattr(&pty.slave, SetArg::TCSANOW, &termios)?;
+    fcntl(pty.master.as_raw_fd(), FcntlArg::F_SETFL(OFlag::O_NONBLOCK))?;
+
+    Ok((Pipe::Pty(File::from(pty.master)), pty.slave))
+}
+
+// kills and reaps the listed processes if a command is abandoned part way through, such as when
+// a callback returns an error or cancels the build
+struct KillOnDrop(Vec<u32>);
+
+impl Drop for KillOnDrop {
+    fn drop(&mut self) {
+        for &pid in &self.0 {
+            let pid = Pid::from_raw(pid as i32);
+            let _ = killpg(pid, Signal::SIGKILL);
+            let _ = kill(pid, Signal::SIGKILL);
+            let _ = waitpid(pid, None);
+        }
+    }
+}
+
+// commands run with a deadline are put in their own process group so everything they spawned
+// can be killed with them
+fn kill_group(child: &Child, child2: Option<&Child>) {
+    for child in [Some(child), child2].into_iter().flatten() {
+        let _ = killpg(Pid::from_raw(child.id() as i32), Signal::SIGKILL);
+    }
+}
+
+const PROGRESS_INTERVAL: Duration = Duration::from_millis(250);
+
+// returns None if the deadline passed and the child had to be killed
+fn wait(
+    child: &mut Child,
+    progress: &mut Option<Progress>,
+    callbacks: &mut Option<Box<dyn Callbacks>>,
+    deadline: Option<Instant>,
+) -> io::Result<Option<ExitStatus>> {
+    if progress.is_none() && deadline.is_none() {
+        return child.wait().map(Some);
+    }
+
+    loop {
+        if let Some(status) = child.try_wait()? {
+            return Ok(Some(status));
+        }
+        if deadline.is_some_and(|d| Instant::now() >= d) {
+            kill_group(child, None);
+            child.wait()?;
+            return Ok(None);
+        }
+        if let (Some(progress), Some(callbacks)) = (&mut *progress, &mut *callbacks) {
+            progress(callbacks.as_mut())?;
+        }
+        sleep(PROGRESS_INTERVAL);
     }
 }
 
@@ -442,27 +729,32 @@ impl Makepkg {
         let pkgdir = &dirs.pkgdir.join(pkgname.unwrap_or(pkgbase));
         let mut output = Vec::new();
 
-        let mut command = Command::new("bash");
+        let mut command = self.shell_command()?;
         command
-            .arg("--noprofile")
-            .arg("--norc")
-            .arg("-s")
-            .arg("-")
             .arg("run")
             .arg(&dirs.pkgbuild)
             .arg(workingdir)
             .arg(function)
-            .env("CARCH", &self.config.arch)
-            .env("startdir", &dirs.startdir)
-            .env("srcdir", &dirs.srcdir)
-            .env("pkgdir", pkgdir)
+            .envs(self.function_env(dirs, pkgdir))
             .current_dir(&dirs.startdir);
 
         if matches!(function, "build" | "check") || function.starts_with("package") {
             self.build_env(dirs, pkgbuild, &mut command);
+            self.isolate_network(options, &mut command);
+        } else {
+            let mut env = BTreeMap::new();
+            self.color_env(&mut env);
+            command.envs(env);
         }
         if function.starts_with("package") {
-            self.fakeroot_env(&mut command)?;
+            self.fakeroot_env(options, &mut command)?;
+            self.confine_package(
+                options,
+                &mut command,
+                function,
+                &[&dirs.pkgdir, &dirs.srcdir],
+                Context::RunFunction(function.into()),
+            )?;
         }
         if let Some(pkgname) = pkgname {
             command.arg(pkgname);
@@ -488,15 +780,34 @@ impl Makepkg {
             None
         };
 
-        command
-            .process_function(
-                self,
-                CommandKind::PkgbuildFunction(pkgbuild),
-                PKGBUILD_SCRIPT.as_bytes(),
-                command_output,
-                logfile.as_mut(),
-            )
-            .cmd_context(&command, Context::RunFunction(function.into()))?;
+        let deadline = options.timeout.map(|timeout| Instant::now() + timeout);
+        let pty = options.pty && matches!(function, "build" | "check");
+        if deadline.is_some() {
+            command.process_group(0);
+        }
+
+        let status = command.process_function(
+            self,
+            CommandKind::PkgbuildFunction(pkgbuild),
+            PKGBUILD_SCRIPT.as_bytes(),
+            command_output,
+            logfile.as_mut(),
+            RunOptions {
+                deadline,
+                pty,
+                stdin: options.interactive,
+            },
+        );
+        if let (Err(e), Some(timeout)) = (&status, options.timeout) {
+            if e.kind() == ErrorKind::TimedOut {
+                return Err(TimeoutError {
+                    function: function.to_string(),
+                    timeout,
+                }
+                .into());
+            }
+        }
+        status.cmd_context(&command, Context::RunFunction(function.into()))?;
 
         let output = String::from_utf8(output)
             .cmd_context(&command, Context::RunFunction(function.into()))?;
@@ -0,0 +1,157 @@
+use std::{path::Path, process::Command};
+
+use crate::{
+    error::{Context, IOContext, IOError, Result},
+    options::Options,
+    Makepkg,
+};
+
+impl Makepkg {
+    /// Confines a helper process (download agent, extractor, compressor) so that it may only
+    /// write beneath `writable`.
+    ///
+    /// This is a no-op unless [`Options::sandbox`] is set. The rules are applied in the child
+    /// right before exec so they never affect the calling process.
+    pub(crate) fn sandbox(
+        &self,
+        options: &Options,
+        command: &mut Command,
+        writable: &[&Path],
+        context: Context,
+    ) -> Result<()> {
+        if !options.sandbox {
+            return Ok(());
+        }
+
+        imp::sandbox(command, writable)
+            .map_err(|e| IOError::new(context, IOContext::Sandbox(command_name(command)), e))?;
+        Ok(())
+    }
+
+    /// Confines a packaging function so that it may only write beneath `writable`.
+    ///
+    /// This is a no-op unless [`Options::confine_package`] is set. Unlike
+    /// [`sandbox`](Self::sandbox) no syscalls are filtered, only filesystem access is
+    /// restricted so that stray writes outside of pkgdir fail instead of silently ending up on
+    /// the host.
+    pub(crate) fn confine_package(
+        &self,
+        options: &Options,
+        command: &mut Command,
+        function: &str,
+        writable: &[&Path],
+        context: Context,
+    ) -> Result<()> {
+        if !options.confine_package {
+            return Ok(());
+        }
+
+        imp::confine(command, writable)
+            .map_err(|e| IOError::new(context, IOContext::Sandbox(function.to_string()), e))?;
+        Ok(())
+    }
+}
+
+fn command_name(command: &Command) -> String {
+    command.get_program().to_string_lossy().to_string()
+}
+
+#[cfg(feature = "sandbox")]
+mod imp {
+    use std::{io, os::unix::process::CommandExt, path::Path, process::Command};
+
+    use landlock::{
+        path_beneath_rules, Access, AccessFs, Ruleset, RulesetAttr, RulesetCreated,
+        RulesetCreatedAttr, ABI,
+    };
+    use nix::libc;
+    use seccompiler::{BpfProgram, SeccompAction, SeccompFilter, TargetArch};
+
+    // Syscalls no download agent or compressor has any business making.
+    const DENIED_SYSCALLS: &[libc::c_long] = &[
+        libc::SYS_ptrace,
+        libc::SYS_process_vm_readv,
+        libc::SYS_process_vm_writev,
+        libc::SYS_mount,
+        libc::SYS_umount2,
+        libc::SYS_pivot_root,
+        libc::SYS_swapon,
+        libc::SYS_swapoff,
+        libc::SYS_reboot,
+        libc::SYS_kexec_load,
+        libc::SYS_init_module,
+        libc::SYS_finit_module,
+        libc::SYS_delete_module,
+        libc::SYS_bpf,
+    ];
+
+    fn ruleset(writable: &[&Path]) -> io::Result<RulesetCreated> {
+        let abi = ABI::V2;
+        Ruleset::default()
+            .handle_access(AccessFs::from_all(abi))
+            .and_then(|r| r.create())
+            .and_then(|r| r.add_rules(path_beneath_rules(["/"], AccessFs::from_read(abi))))
+            .and_then(|r| r.add_rules(path_beneath_rules(["/dev"], AccessFs::from_all(abi))))
+            .and_then(|r| r.add_rules(path_beneath_rules(writable, AccessFs::from_all(abi))))
+            .map_err(io::Error::other)
+    }
+
+    pub(super) fn sandbox(command: &mut Command, writable: &[&Path]) -> io::Result<()> {
+        let mut ruleset = Some(ruleset(writable)?);
+
+        let rules = DENIED_SYSCALLS.iter().map(|&s| (s, Vec::new())).collect();
+        let arch = TargetArch::try_from(std::env::consts::ARCH).map_err(io::Error::other)?;
+        let filter = SeccompFilter::new(
+            rules,
+            SeccompAction::Allow,
+            SeccompAction::Errno(libc::EPERM as u32),
+            arch,
+        )
+        .map_err(io::Error::other)?;
+        let filter: BpfProgram = filter.try_into().map_err(io::Error::other)?;
+
+        // Everything is allocated up front, the child only makes syscalls.
+        unsafe {
+            command.pre_exec(move || {
+                if let Some(ruleset) = ruleset.take() {
+                    ruleset.restrict_self().map_err(io::Error::other)?;
+                }
+                seccompiler::apply_filter(&filter).map_err(io::Error::other)?;
+                Ok(())
+            });
+        }
+
+        Ok(())
+    }
+
+    pub(super) fn confine(command: &mut Command, writable: &[&Path]) -> io::Result<()> {
+        let mut ruleset = Some(ruleset(writable)?);
+
+        unsafe {
+            command.pre_exec(move || {
+                if let Some(ruleset) = ruleset.take() {
+                    ruleset.restrict_self().map_err(io::Error::other)?;
+                }
+                Ok(())
+            });
+        }
+
+        Ok(())
+    }
+}
+
+#[cfg(not(feature = "sandbox"))]
+mod imp {
+    use std::{io, path::Path, process::Command};
+
+    pub(super) fn sandbox(_command: &mut Command, _writable: &[&Path]) -> io::Result<()> {
+        Err(io::Error::new(
+            io::ErrorKind::Unsupported,
+            "built without the sandbox feature",
+        ))
+    }
+
+    pub(super) fn confine(command: &mut Command, writable: &[&Path]) -> io::Result<()> {
+        sandbox(command, writable)
+    }
+}
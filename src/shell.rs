@@ -0,0 +1,87 @@
+use std::{
+    path::Path,
+    process::{Command, Stdio},
+};
+
+use crate::{
+    error::{CommandError, Context, Result, UnsupportedShellError},
+    Makepkg,
+};
+
+// features of bash the function runner relies on
+static SHELL_FEATURES: &[(&str, &str)] = &[
+    ("arrays", "a=(1 2); [[ ${#a[@]} == 2 ]]"),
+    ("associative arrays", "declare -A a; a[x]=1"),
+    ("namerefs", "x=1; declare -n r=x; [[ $r == 1 ]]"),
+    ("local -I", "f() { local -I x; }; f"),
+    ("shopt", "shopt -s extglob"),
+    (
+        "declare -F with extdebug",
+        "shopt -s extdebug; f() { :; }; declare -F f >/dev/null",
+    ),
+    (
+        "process substitution",
+        "read -r x < <(echo y); [[ $x == y ]]",
+    ),
+];
+
+impl Makepkg {
+    /// The shell PKGBUILD functions are run with.
+    pub fn shell(&self) -> &Path {
+        self.config
+            .shell
+            .as_deref()
+            .unwrap_or_else(|| Path::new("bash"))
+    }
+
+    /// Creates a command running [`shell`](Makepkg::shell) that reads its script from stdin.
+    ///
+    /// The first time this is called for a shell other than bash the shell is checked for
+    /// the features makepkg needs.
+    pub(crate) fn shell_command(&self) -> Result<Command> {
+        let shell = self.shell();
+
+        if !self.shell_checked.get() {
+            if shell != Path::new("bash") {
+                check_shell(shell)?;
+            }
+            self.shell_checked.set(true);
+        }
+
+        let mut command = Command::new(shell);
+        if is_bash(shell) {
+            command.arg("--noprofile").arg("--norc");
+        }
+        command.arg("-s").arg("-");
+        Ok(command)
+    }
+}
+
+fn is_bash(shell: &Path) -> bool {
+    shell.file_name().is_some_and(|n| n == "bash")
+}
+
+fn check_shell(shell: &Path) -> Result<()> {
+    for (feature, probe) in SHELL_FEATURES {
+        let mut command = Command::new(shell);
+        command
+            .arg("-c")
+            .arg(probe)
+            .stdin(Stdio::null())
+            .stdout(Stdio::null())
+            .stderr(Stdio::null());
+        let status = command
+            .status()
+            .map_err(|e| CommandError::exec(e, &command, Context::CheckShell))?;
+
+        if !status.success() {
+            return Err(UnsupportedShellError {
+                shell: shell.to_path_buf(),
+                feature,
+            }
+            .into());
+        }
+    }
+
+    Ok(())
+}
@@ -0,0 +1,133 @@
+use std::{
+    os::unix::fs::MetadataExt,
+    path::{Path, PathBuf},
+    process::Command,
+};
+
+use sha2::Sha256;
+
+use crate::{
+    build_dirs::DIR_MARKER,
+    callback::{CommandKind, Event},
+    config::PkgbuildDirs,
+    error::{CommandErrorExt, Context, Result},
+    fs::{read_to_string, rm_all, write},
+    integ::hash,
+    options::Options,
+    pkgbuild::Pkgbuild,
+    run::CommandOutput,
+    Makepkg,
+};
+
+impl Makepkg {
+    /// Restores srcdir from the snapshot taken after the last extraction.
+    ///
+    /// This only happens when [`Options::srcdir_snapshot`] is set, srcdir is empty and the
+    /// snapshot was taken from the same source files. Returns whether srcdir was restored.
+    pub(crate) fn restore_srcdir_snapshot(
+        &self,
+        options: &Options,
+        dirs: &PkgbuildDirs,
+        pkgbuild: &Pkgbuild,
+    ) -> Result<bool> {
+        if !options.srcdir_snapshot || !srcdir_is_empty(&dirs.srcdir) {
+            return Ok(false);
+        }
+        let Some(key) = self.snapshot_key(dirs, pkgbuild)? else {
+            return Ok(false);
+        };
+
+        let snapshot = snapshot_dir(dirs);
+        let key_file = snapshot.join("key");
+        let tree = snapshot.join("tree");
+        if !tree.exists() || !key_file.exists() {
+            return Ok(false);
+        }
+        if read_to_string(&key_file, Context::ExtractSources)? != key {
+            return Ok(false);
+        }
+
+        self.event(Event::UsingSrcdirSnapshot)?;
+        copy_tree(self, pkgbuild, &tree, &dirs.srcdir)?;
+        Ok(true)
+    }
+
+    /// Snapshots the freshly extracted srcdir so later clean builds can restore it with
+    /// [`Makepkg::restore_srcdir_snapshot`] instead of extracting again.
+    pub(crate) fn save_srcdir_snapshot(
+        &self,
+        options: &Options,
+        dirs: &PkgbuildDirs,
+        pkgbuild: &Pkgbuild,
+    ) -> Result<()> {
+        if !options.srcdir_snapshot {
+            return Ok(());
+        }
+        let Some(key) = self.snapshot_key(dirs, pkgbuild)? else {
+            return Ok(());
+        };
+
+        let snapshot = snapshot_dir(dirs);
+        if snapshot.exists() {
+            rm_all(&snapshot, Context::ExtractSources)?;
+        }
+        let tree = snapshot.join("tree");
+        self.create_build_dir(&tree, Context::ExtractSources)?;
+        copy_tree(self, pkgbuild, &dirs.srcdir, &tree)?;
+        write(snapshot.join("key"), key, Context::ExtractSources)?;
+        Ok(())
+    }
+
+    // identifies the source files a snapshot was extracted from. VCS sources are updated in
+    // place so they can not be snapshotted.
+    fn snapshot_key(&self, dirs: &PkgbuildDirs, pkgbuild: &Pkgbuild) -> Result<Option<String>> {
+        let mut key = String::new();
+
+        for source in pkgbuild.source.enabled(&self.config.arch) {
+            if source.vcs_kind().is_some() {
+                return Ok(None);
+            }
+            let path = dirs.download_path(source);
+            let Ok(metadata) = path.metadata() else {
+                return Ok(None);
+            };
+            key.push_str(&format!(
+                "{} {} {}\n",
+                source.file_name(),
+                metadata.size(),
+                metadata.mtime()
+            ));
+        }
+        for noextract in &pkgbuild.noextract {
+            key.push_str(&format!("!{}\n", noextract));
+        }
+
+        let path = snapshot_dir(dirs).join("key");
+        hash::<Sha256, _>(&path, &mut key.as_bytes()).map(Some)
+    }
+}
+
+fn snapshot_dir(dirs: &PkgbuildDirs) -> PathBuf {
+    dirs.srcdir.with_file_name(".srcdir-snapshot")
+}
+
+// srcdir only contains the marker left by create_build_dir
+fn srcdir_is_empty(srcdir: &Path) -> bool {
+    srcdir
+        .read_dir()
+        .is_ok_and(|mut d| d.all(|e| e.is_ok_and(|e| e.file_name() == DIR_MARKER)))
+}
+
+// reflinks make this near instant on filesystems that support them
+fn copy_tree(makepkg: &Makepkg, pkgbuild: &Pkgbuild, src: &Path, dest: &Path) -> Result<()> {
+    let mut command = Command::new("cp");
+    command
+        .arg("-a")
+        .arg("--reflink=auto")
+        .arg("--")
+        .arg(src.join("."))
+        .arg(dest)
+        .process_spawn(makepkg, CommandKind::BuildingPackage(pkgbuild))
+        .cmd_context(&command, Context::ExtractSources)?;
+    Ok(())
+}
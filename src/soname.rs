@@ -0,0 +1,122 @@
+use std::{fs::File, io::Read, path::Path, process::Command};
+
+use crate::{
+    callback::CommandKind,
+    config::PkgbuildDirs,
+    error::{CommandOutputExt, Context, IOContext, IOErrorExt, Result},
+    pkgbuild::{Depend, Package, Pkgbuild},
+    run::CommandOutput,
+    Makepkg,
+};
+
+pub(crate) const ET_EXEC: u8 = 2;
+pub(crate) const ET_DYN: u8 = 3;
+
+impl Makepkg {
+    /// Generates `libfoo.so=1-64` style provides for the shared libraries in a package.
+    ///
+    /// Only done when the `sonameprovides` option is enabled. Libraries the package already
+    /// provides are skipped.
+    pub(crate) fn soname_provides(
+        &self,
+        dirs: &PkgbuildDirs,
+        pkgbuild: &Pkgbuild,
+        pkg: &Package,
+    ) -> Result<Vec<String>> {
+        let config = &self.config;
+        if !config.option(pkgbuild, "sonameprovides").enabled() {
+            return Ok(Vec::new());
+        }
+
+        let pkgdir = dirs.pkgdir(pkg);
+        let mut provides = Vec::new();
+
+        for file in walkdir::WalkDir::new(&pkgdir) {
+            let file =
+                file.context(Context::GetPackageFiles, IOContext::ReadDir(pkgdir.clone()))?;
+            if !file.file_type().is_file() {
+                continue;
+            }
+
+            let Some(bits) = elf_class(file.path(), ET_DYN) else {
+                continue;
+            };
+            let Some(soname) = self.soname(pkgbuild, file.path())? else {
+                continue;
+            };
+            // unversioned sonames can't be depended on meaningfully
+            let Some((name, version)) = soname.split_once(".so.") else {
+                continue;
+            };
+
+            let name = format!("{}.so", name);
+            let already_provided = pkg.provides.enabled(&config.arch).any(|p| {
+                let Ok(dep) = p.parse::<Depend>();
+                dep.name == name
+            });
+
+            let provide = format!("{}={}-{}", name, version, bits);
+            if !already_provided && !provides.contains(&provide) {
+                provides.push(provide);
+            }
+        }
+
+        Ok(provides)
+    }
+
+    fn soname(&self, pkgbuild: &Pkgbuild, path: &Path) -> Result<Option<String>> {
+        let dynamic = self.elf_dynamic(pkgbuild, path)?;
+        let soname = dynamic_entries(&dynamic, "Library soname").next();
+        Ok(soname)
+    }
+
+    /// The dynamic section of an ELF file as printed by `readelf -d`.
+    pub(crate) fn elf_dynamic(&self, pkgbuild: &Pkgbuild, path: &Path) -> Result<String> {
+        let mut command = Command::new("readelf");
+        command
+            .arg("-d")
+            .arg(path)
+            .env("LC_ALL", "C")
+            .process_read(self, CommandKind::BuildingPackage(pkgbuild))
+            .read(&command, Context::GeneratePackageFile(".PKGINFO".into()))
+            .map_err(Into::into)
+    }
+}
+
+/// Finds entries such as `Shared library: [libc.so.6]` in `readelf -d` output.
+pub(crate) fn dynamic_entries<'a>(
+    dynamic: &'a str,
+    name: &'a str,
+) -> impl Iterator<Item = String> + 'a {
+    dynamic.lines().filter_map(move |l| {
+        let (_, val) = l.split_once(name)?;
+        let val = val.strip_prefix(": [")?;
+        val.strip_suffix(']').map(|s| s.to_string())
+    })
+}
+
+/// Returns 32 or 64 if the file is an ELF file of the given type.
+pub(crate) fn elf_class(path: &Path, kind: u8) -> Option<u32> {
+    let mut header = [0; 18];
+    File::open(path).ok()?.read_exact(&mut header).ok()?;
+
+    if &header[..4] != b"\x7fELF" {
+        return None;
+    }
+
+    // e_type is little or big endian depending on EI_DATA
+    let e_type = if header[5] == 2 {
+        header[17]
+    } else {
+        header[16]
+    };
+    if e_type != kind {
+        return None;
+    }
+
+    match header[4] {
+        1 => Some(32),
+        2 => Some(64),
+        _ => None,
+    }
+}
@@ -1,26 +1,103 @@
 use std::{
+    cmp::Reverse,
+    collections::{HashMap, VecDeque},
     fs::File,
-    io::{Seek, SeekFrom, Write},
+    io::{self, Seek, SeekFrom, Write},
     mem::replace,
-    path::PathBuf,
+    path::{Path, PathBuf},
     result::Result as StdResult,
     time::Duration,
 };
 
 use curl::{
-    easy::{Easy2, Handler, WriteError},
+    easy::{Easy2, Handler, List, WriteError},
     multi::{Easy2Handle, Multi},
 };
 
 use crate::{
     callback::Event,
-    config::PkgbuildDirs,
+    config::{Config, DownloadOrder, DownloadPolicy, PkgbuildDirs},
     error::{Context, DownloadError, IOContext, IOErrorExt, Result},
-    fs::{open, rename},
+    fs::{open, rename, rm_file, write},
+    options::Options,
     pkgbuild::{Pkgbuild, Source},
     Download, DownloadEvent, Makepkg,
 };
 
+#[derive(Debug, Default, Clone, PartialEq, Eq)]
+pub(crate) struct Validators {
+    pub url: String,
+    pub etag: Option<String>,
+    pub last_modified: Option<String>,
+}
+
+impl Validators {
+    pub(crate) fn load(path: &Path, source: &Source) -> Result<Option<Validators>> {
+        let contents = match std::fs::read_to_string(path) {
+            Err(e) if e.kind() == io::ErrorKind::NotFound => return Ok(None),
+            res => res.context(Context::RetrieveSources, IOContext::Read(path.into()))?,
+        };
+
+        let mut validators = Validators::default();
+        for line in contents.lines() {
+            match line.split_once(": ") {
+                Some(("url", url)) => validators.url = url.to_string(),
+                Some(("etag", etag)) => validators.etag = Some(etag.to_string()),
+                Some(("last-modified", date)) => validators.last_modified = Some(date.to_string()),
+                _ => (),
+            }
+        }
+
+        if validators.url != source.url || validators.is_empty() {
+            return Ok(None);
+        }
+
+        Ok(Some(validators))
+    }
+
+    fn save(&self, path: &Path) -> Result<()> {
+        if self.is_empty() {
+            if path.exists() {
+                rm_file(path, Context::RetrieveSources)?;
+            }
+            return Ok(());
+        }
+
+        let mut contents = format!("url: {}\n", self.url);
+        if let Some(etag) = &self.etag {
+            contents.push_str(&format!("etag: {}\n", etag));
+        }
+        if let Some(date) = &self.last_modified {
+            contents.push_str(&format!("last-modified: {}\n", date));
+        }
+        write(path, contents, Context::RetrieveSources)
+    }
+
+    fn is_empty(&self) -> bool {
+        self.etag.is_none() && self.last_modified.is_none()
+    }
+
+    fn header(&mut self, data: &[u8]) {
+        let Ok(line) = std::str::from_utf8(data) else {
+            return;
+        };
+        let line = line.trim_end();
+
+        // a new response begins after each redirect
+        if line.starts_with("HTTP/") {
+            self.etag = None;
+            self.last_modified = None;
+        } else if let Some((key, value)) = line.split_once(':') {
+            let value = value.trim().to_string();
+            if key.eq_ignore_ascii_case("etag") {
+                self.etag = Some(value);
+            } else if key.eq_ignore_ascii_case("last-modified") {
+                self.last_modified = Some(value);
+            }
+        }
+    }
+}
+
 pub struct Handle<'a> {
     makepkg: &'a Makepkg,
     pkgbuild: &'a Pkgbuild,
@@ -28,9 +105,18 @@ pub struct Handle<'a> {
     file: File,
     temp_path: PathBuf,
     final_path: PathBuf,
+    validators_path: Option<PathBuf>,
+    validators: Validators,
+    conditional: bool,
+    host: String,
+    done: bool,
     err: Result<()>,
 }
 
+struct Head;
+
+impl Handler for Head {}
+
 impl<'a> Handler for Handle<'a> {
     fn write(&mut self, data: &[u8]) -> StdResult<usize, WriteError> {
         let err = self.file.write_all(data).context(
@@ -45,6 +131,13 @@ impl<'a> Handler for Handle<'a> {
         }
     }
 
+    fn header(&mut self, data: &[u8]) -> bool {
+        if self.validators_path.is_some() {
+            self.validators.header(data);
+        }
+        true
+    }
+
     fn progress(&mut self, dltotal: f64, dlnow: f64, _ultotal: f64, _ulnow: f64) -> bool {
         let event = DownloadEvent::Progress(self.download, dlnow, dltotal);
         if let Err(e) = self.makepkg.download(self.pkgbuild, event) {
@@ -73,11 +166,13 @@ impl Makepkg {
     pub(crate) fn download_curl_sources(
         &self,
         dirs: &PkgbuildDirs,
+        options: &Options,
         pkgbuild: &Pkgbuild,
-        mut sources: Vec<&Source>,
+        sources: Vec<&Source>,
     ) -> Result<()> {
         let curlm = Multi::new();
-        let max_downloads = 8;
+        let policy = &self.config.download_policy;
+        let max_downloads = policy.max_downloads.max(1) as u32;
         let mut handles = Vec::new();
         let mut running = 0;
         let total = sources.len();
@@ -87,17 +182,20 @@ impl Makepkg {
         }
 
         self.download(pkgbuild, DownloadEvent::DownloadStart(total))?;
+        let mut sources = order_downloads(&self.config, policy.order, sources);
 
         while running > 0 || !sources.is_empty() {
-            while running < max_downloads && !sources.is_empty() {
-                if let Some(source) = sources.pop() {
-                    let curl =
-                        self.make_payload(dirs, pkgbuild, source, total - sources.len(), total)?;
-                    self.event(Event::DownloadingCurl(source.file_name()))?;
-                    let handle = curlm.add2(curl)?;
-                    handles.push(handle);
-                    running += 1;
-                }
+            while running < max_downloads {
+                let Some(pos) = next_download(policy, &sources, &handles) else {
+                    break;
+                };
+                let source = sources.remove(pos).unwrap();
+                let n = total - sources.len();
+                let curl = self.make_payload(dirs, options, pkgbuild, source, n, total)?;
+                self.event(Event::DownloadingCurl(source.file_name()))?;
+                let handle = curlm.add2(curl)?;
+                handles.push(handle);
+                running += 1;
             }
 
             running = curlm.perform()?;
@@ -122,6 +220,7 @@ impl Makepkg {
     fn make_payload<'a>(
         &'a self,
         dirs: &'a PkgbuildDirs,
+        options: &Options,
         pkgbuild: &'a Pkgbuild,
         source: &'a Source,
         current: usize,
@@ -137,8 +236,17 @@ impl Makepkg {
         } else {
             temp_path.set_extension("part");
         }
+
+        let validators_path = options
+            .conditional_download
+            .then(|| dirs.validators_path(source));
+        let cached = match &validators_path {
+            Some(path) if final_path.exists() => Validators::load(path, source)?,
+            _ => None,
+        };
+
         let mut file = File::options();
-        file.create(true).write(true);
+        file.create(true).write(true).truncate(cached.is_some());
         let mut file = open(&file, &temp_path, Context::RetrieveSources)?;
         let len = file
             .seek(SeekFrom::End(0))
@@ -155,11 +263,30 @@ impl Makepkg {
             file,
             temp_path,
             final_path,
+            validators_path,
+            validators: Validators {
+                url: source.url.clone(),
+                ..Default::default()
+            },
+            conditional: cached.is_some(),
+            host: host(&source.url).to_string(),
+            done: false,
             err: Ok(()),
         });
         self.download(pkgbuild, DownloadEvent::Init(download))?;
-        curl_set_ops(&mut curl, source)?;
-        curl.resume_from(len)?;
+        let mut headers = Vec::new();
+        if let Some(cached) = &cached {
+            if let Some(etag) = &cached.etag {
+                headers.push(format!("If-None-Match: {}", etag));
+            }
+            if let Some(date) = &cached.last_modified {
+                headers.push(format!("If-Modified-Since: {}", date));
+            }
+        }
+        curl_set_ops(&mut curl, &self.config, source, &headers)?;
+        if cached.is_none() {
+            curl.resume_from(len)?;
+        }
         Ok(curl)
     }
 }
@@ -170,11 +297,25 @@ fn handle_messages(makepkg: &Makepkg, curlm: &Multi, handles: &mut [Easy2Handle<
             if let Some(res) = m.result_for2(handle) {
                 let response = handle.response_code().unwrap_or(0);
                 let context = handle.get_mut();
+                context.done = true;
 
                 if let Err(e) = res {
                     context.err = Err(e.into());
                     return;
                 }
+                if context.conditional && response == 304 {
+                    if let Err(err) = rm_file(&context.temp_path, Context::RetrieveSources) {
+                        context.err = Err(err);
+                        return;
+                    }
+                    if let Err(err) = makepkg.download(
+                        context.pkgbuild,
+                        DownloadEvent::NotModified(context.download),
+                    ) {
+                        context.err = Err(err);
+                    }
+                    return;
+                }
                 if !(200..300).contains(&response) {
                     if let Err(err) = makepkg.download(
                         context.pkgbuild,
@@ -199,6 +340,13 @@ fn handle_messages(makepkg: &Makepkg, curlm: &Multi, handles: &mut [Easy2Handle<
                     return;
                 }
 
+                if let Some(path) = &context.validators_path {
+                    if let Err(err) = context.validators.save(path) {
+                        context.err = Err(err);
+                        return;
+                    }
+                }
+
                 if let Err(err) =
                     makepkg.download(context.pkgbuild, DownloadEvent::Completed(context.download))
                 {
@@ -210,7 +358,85 @@ fn handle_messages(makepkg: &Makepkg, curlm: &Multi, handles: &mut [Easy2Handle<
     });
 }
 
-fn curl_set_ops<T>(curl: &mut Easy2<T>, source: &Source) -> Result<()> {
+fn host(url: &str) -> &str {
+    let url = url.split_once("://").map(|u| u.1).unwrap_or(url);
+    let authority = url.split(['/', '?', '#']).next().unwrap_or(url);
+    authority.rsplit('@').next().unwrap_or(authority)
+}
+
+fn next_download(
+    policy: &DownloadPolicy,
+    sources: &VecDeque<&Source>,
+    handles: &[Easy2Handle<Handle>],
+) -> Option<usize> {
+    let Some(max) = policy.max_downloads_per_host else {
+        return (!sources.is_empty()).then_some(0);
+    };
+
+    let mut active = HashMap::new();
+    for handle in handles.iter().map(|h| h.get_ref()).filter(|h| !h.done) {
+        *active.entry(handle.host.as_str()).or_insert(0) += 1;
+    }
+
+    sources
+        .iter()
+        .position(|s| active.get(host(&s.url)).copied().unwrap_or(0) < max.max(1))
+}
+
+fn order_downloads<'a>(
+    config: &Config,
+    order: DownloadOrder,
+    sources: Vec<&'a Source>,
+) -> VecDeque<&'a Source> {
+    match order {
+        DownloadOrder::Listed => sources.into(),
+        DownloadOrder::LargestFirst => {
+            let mut sized = sources
+                .into_iter()
+                .map(|s| (remote_size(config, s), s))
+                .collect::<Vec<_>>();
+            // unknown sizes sort last
+            sized.sort_by_key(|s| Reverse(s.0));
+            sized.into_iter().map(|(_, s)| s).collect()
+        }
+        DownloadOrder::InterleaveHosts => {
+            let mut hosts: Vec<(&str, VecDeque<&Source>)> = Vec::new();
+            for source in sources {
+                let host = host(&source.url);
+                match hosts.iter_mut().find(|h| h.0 == host) {
+                    Some((_, queue)) => queue.push_back(source),
+                    None => hosts.push((host, VecDeque::from([source]))),
+                }
+            }
+
+            let mut ordered = VecDeque::new();
+            while hosts.iter().any(|h| !h.1.is_empty()) {
+                for (_, queue) in &mut hosts {
+                    ordered.extend(queue.pop_front());
+                }
+            }
+            ordered
+        }
+    }
+}
+
+pub(crate) fn remote_size(config: &Config, source: &Source) -> Option<u64> {
+    let mut curl = Easy2::new(Head);
+    curl_set_ops(&mut curl, config, source, &[]).ok()?;
+    curl.nobody(true).ok()?;
+    curl.progress(false).ok()?;
+    curl.timeout(Duration::from_secs(10)).ok()?;
+    curl.perform().ok()?;
+    let size = curl.content_length_download().ok()?;
+    (size >= 0.0).then_some(size as u64)
+}
+
+fn curl_set_ops<T>(
+    curl: &mut Easy2<T>,
+    config: &Config,
+    source: &Source,
+    extra_headers: &[String],
+) -> Result<()> {
     curl.useragent(&format!(
         "{}/{}",
         env!("CARGO_PKG_NAME"),
@@ -223,5 +449,20 @@ fn curl_set_ops<T>(curl: &mut Easy2<T>, source: &Source) -> Result<()> {
     curl.tcp_keepidle(Duration::from_secs(1))?;
     curl.url(&source.url)?;
     curl.get(true)?;
+
+    let mut headers = List::new();
+    let mut any = false;
+    let configured = config
+        .download_headers
+        .iter()
+        .filter(|h| h.matches(source))
+        .map(|h| &h.header);
+    for header in configured.chain(extra_headers) {
+        headers.append(header)?;
+        any = true;
+    }
+    if any {
+        curl.http_headers(headers)?;
+    }
     Ok(())
 }
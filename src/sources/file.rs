@@ -5,6 +5,7 @@ use crate::{
     config::{DownloadAgent, PkgbuildDirs},
     error::{CommandErrorExt, Context, Result},
     fs::{make_link, rename, rm_file},
+    options::Options,
     pkgbuild::{Pkgbuild, Source},
     run::CommandOutput,
     CommandKind, Makepkg,
@@ -14,6 +15,7 @@ impl Makepkg {
     pub(crate) fn download_file(
         &self,
         dirs: &PkgbuildDirs,
+        options: &Options,
         pkgbuild: &Pkgbuild,
         downloads: &BTreeMap<&DownloadAgent, Vec<&Source>>,
     ) -> Result<()> {
@@ -36,6 +38,12 @@ impl Makepkg {
 
                 self.event(Event::Downloading(source.file_name()))?;
                 let mut command = Command::new(&agent.command);
+                self.sandbox(
+                    options,
+                    &mut command,
+                    &[&dirs.srcdest],
+                    Context::RetrieveSources,
+                )?;
                 command
                     .args(&args)
                     .current_dir(&dirs.srcdest)
@@ -51,6 +59,7 @@ impl Makepkg {
     pub(crate) fn extract_file(
         &self,
         dirs: &PkgbuildDirs,
+        options: &Options,
         pkgbuild: &Pkgbuild,
         source: &Source,
     ) -> Result<()> {
@@ -76,9 +85,21 @@ impl Makepkg {
             .map(|s| s.status.success())
             .unwrap_or(false);
 
-        if supported {
+        if !supported {
+            return Ok(());
+        }
+
+        if let Some(cache) = &self.config.extract_cache {
+            self.extract_cached(dirs, options, pkgbuild, source, cache)?;
+        } else {
             self.event(Event::Extacting(source.file_name()))?;
             let mut command = Command::new("bsdtar");
+            self.sandbox(
+                options,
+                &mut command,
+                &[&dirs.srcdir],
+                Context::ExtractSources,
+            )?;
             command
                 .arg("-xf")
                 .arg(&srcfile)
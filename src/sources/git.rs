@@ -29,17 +29,20 @@ impl Makepkg {
                 .map(|v| v.split_whitespace().collect::<Vec<_>>());
             let flags = flags.as_deref().unwrap_or(["--mirror"].as_slice());
 
-            let mut command = Command::new("git");
-            command
-                .arg("clone")
-                .arg("--origin=origin")
-                .args(flags)
-                .arg("--")
-                .arg(&source.url)
-                .arg(path)
-                .env("GIT_TERMINAL_PROMPT", "0")
-                .process_spawn(self, CommandKind::DownloadSources(pkgbuild, source))
-                .download_context(source, &command, Context::None)?;
+            self.update_mirror(options, VCSKind::Git, &path, || {
+                let mut command = Command::new("git");
+                command
+                    .arg("clone")
+                    .arg("--origin=origin")
+                    .args(flags)
+                    .arg("--")
+                    .arg(&source.url)
+                    .arg(&path)
+                    .env("GIT_TERMINAL_PROMPT", "0")
+                    .process_spawn(self, CommandKind::DownloadSources(pkgbuild, source))
+                    .download_context(source, &command, Context::None)?;
+                Ok(())
+            })?;
         } else if !options.hold_ver {
             let mut command = Command::new("git");
             let remote_url = command
@@ -58,15 +61,18 @@ impl Makepkg {
 
             self.event(Event::UpdatingVCS(VCSKind::Git, source))?;
 
-            let mut command = Command::new("git");
-            command
-                .arg("fetch")
-                .arg("--all")
-                .arg("-p")
-                .env("GIT_TERMINAL_PROMPT", "0")
-                .current_dir(dirs.download_path(source))
-                .process_spawn(self, CommandKind::DownloadSources(pkgbuild, source))
-                .download_context(source, &command, Context::None)?;
+            self.update_mirror(options, VCSKind::Git, &path, || {
+                let mut command = Command::new("git");
+                command
+                    .arg("fetch")
+                    .arg("--all")
+                    .arg("-p")
+                    .env("GIT_TERMINAL_PROMPT", "0")
+                    .current_dir(&path)
+                    .process_spawn(self, CommandKind::DownloadSources(pkgbuild, source))
+                    .download_context(source, &command, Context::None)?;
+                Ok(())
+            })?;
         }
 
         Ok(())
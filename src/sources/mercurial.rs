@@ -27,24 +27,30 @@ impl Makepkg {
         if !repopath.exists() {
             self.event(Event::DownloadingVCS(VCSKind::Mercurial, source))?;
 
-            let mut command = Command::new("hg");
-            command
-                .arg("clone")
-                .arg("-U")
-                .arg(&url)
-                .arg(&repopath)
-                .current_dir(&dirs.srcdest)
-                .process_spawn(self, CommandKind::DownloadSources(pkgbuild, source))
-                .download_context(source, &command, Context::None)?;
+            self.update_mirror(options, VCSKind::Mercurial, &repopath, || {
+                let mut command = Command::new("hg");
+                command
+                    .arg("clone")
+                    .arg("-U")
+                    .arg(&url)
+                    .arg(&repopath)
+                    .current_dir(&dirs.srcdest)
+                    .process_spawn(self, CommandKind::DownloadSources(pkgbuild, source))
+                    .download_context(source, &command, Context::None)?;
+                Ok(())
+            })?;
         } else if !options.hold_ver {
             self.event(Event::UpdatingVCS(VCSKind::Mercurial, source))?;
 
-            let mut command = Command::new("hg");
-            command
-                .arg("pull")
-                .current_dir(repopath)
-                .process_spawn(self, CommandKind::DownloadSources(pkgbuild, source))
-                .download_context(source, &command, Context::None)?;
+            self.update_mirror(options, VCSKind::Mercurial, &repopath, || {
+                let mut command = Command::new("hg");
+                command
+                    .arg("pull")
+                    .current_dir(&repopath)
+                    .process_spawn(self, CommandKind::DownloadSources(pkgbuild, source))
+                    .download_context(source, &command, Context::None)?;
+                Ok(())
+            })?;
         }
 
         Ok(())
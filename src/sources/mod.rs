@@ -2,6 +2,8 @@ use std::collections::BTreeMap;
 
 pub use vcs::*;
 
+use self::curl::Validators;
+
 type SourceMap<'a, T> = BTreeMap<T, Vec<&'a Source>>;
 
 use crate::{
@@ -11,6 +13,7 @@ use crate::{
     fs::{mkdir, set_time},
     options::Options,
     pkgbuild::{Function, Pkgbuild, Source},
+    report::BuildPhase,
     Makepkg,
 };
 
@@ -36,10 +39,10 @@ impl Makepkg {
         mkdir(&dirs.srcdest, Context::RetrieveSources)?;
 
         let (downloads, vcs_downloads, curl_downloads) =
-            self.get_downloads(pkgbuild, &dirs, all)?;
+            self.get_downloads(options, pkgbuild, &dirs, all)?;
 
-        self.download_curl_sources(&dirs, pkgbuild, curl_downloads)?;
-        self.download_file(&dirs, pkgbuild, &downloads)?;
+        self.download_curl_sources(&dirs, options, pkgbuild, curl_downloads)?;
+        self.download_file(&dirs, options, pkgbuild, &downloads)?;
         self.download_vcs(&dirs, options, pkgbuild, &vcs_downloads)?;
 
         Ok(())
@@ -47,25 +50,36 @@ impl Makepkg {
 
     pub fn extract_sources(&self, options: &Options, pkgbuild: &Pkgbuild, all: bool) -> Result<()> {
         let dirs = self.pkgbuild_dirs(pkgbuild)?;
-        if !options.no_extract {
+        let snapshot = options.srcdir_snapshot && !all && !options.no_extract;
+        let restored = snapshot && self.restore_srcdir_snapshot(options, &dirs, pkgbuild)?;
+        if !options.no_extract && !restored {
             self.event(Event::ExtractingSources)?;
 
-            for source in &pkgbuild.source.values {
-                if !all && !source.enabled(&self.config.arch) {
-                    continue;
-                }
+            self.time_phase(BuildPhase::Extract, || {
+                for source in &pkgbuild.source.values {
+                    if !all && !source.enabled(&self.config.arch) {
+                        continue;
+                    }
 
-                for source in &source.values {
-                    match source.vcs_kind() {
-                        Some(vcs) => self.extract_vcs(&dirs, pkgbuild, vcs, source)?,
-                        _ => self.extract_file(&dirs, pkgbuild, source)?,
+                    for source in &source.values {
+                        match source.vcs_kind() {
+                            Some(vcs) => self.extract_vcs(&dirs, pkgbuild, vcs, source)?,
+                            _ => self.extract_file(&dirs, options, pkgbuild, source)?,
+                        }
                     }
                 }
+                Ok(())
+            })?;
+
+            if snapshot {
+                self.save_srcdir_snapshot(options, &dirs, pkgbuild)?;
             }
         }
 
         if !options.no_prepare {
-            self.run_function(options, pkgbuild, Function::Prepare)?
+            self.time_phase(BuildPhase::Prepare, || {
+                self.run_function(options, pkgbuild, Function::Prepare)
+            })?
         }
         if self.config().reproducible {
             for file in walkdir::WalkDir::new(&dirs.srcdir) {
@@ -84,12 +98,13 @@ impl Makepkg {
 
     fn get_downloads<'a>(
         &'a self,
+        options: &Options,
         pkgbuild: &'a Pkgbuild,
         dirs: &PkgbuildDirs,
         all: bool,
     ) -> Result<(
-        SourceMap<&'a DownloadAgent>,
-        SourceMap<VCSKind>,
+        SourceMap<'a, &'a DownloadAgent>,
+        SourceMap<'a, VCSKind>,
         Vec<&'a Source>,
     )> {
         let mut downloads: SourceMap<&DownloadAgent> = BTreeMap::new();
@@ -115,12 +130,16 @@ impl Makepkg {
             if let Some(tool) = source.vcs_kind() {
                 vcs_downloads.entry(tool).or_default().push(source);
             } else if path.exists() {
-                self.event(Event::FoundSource(source.file_name()))?;
+                if self.revalidate(options, dirs, source)? {
+                    curl.push(source);
+                } else {
+                    self.event(Event::FoundSource(source.file_name()))?;
+                }
                 continue;
             } else if !source.is_remote() {
                 return Err(DownloadError::SourceMissing(source.clone()).into());
             } else if let Some(tool) = self.get_download_tool(source) {
-                if tool.command.rsplit('/').next().unwrap() == "curl" {
+                if tool.is_curl() {
                     curl.push(source);
                 } else {
                     downloads.entry(tool).or_default().push(source);
@@ -135,6 +154,57 @@ impl Makepkg {
         Ok((downloads, vcs_downloads, curl))
     }
 
+    fn revalidate(&self, options: &Options, dirs: &PkgbuildDirs, source: &Source) -> Result<bool> {
+        if !options.conditional_download || !source.is_remote() {
+            return Ok(false);
+        }
+
+        // revalidating goes through libcurl, only do so when that would not drop arguments
+        // from the configured agent
+        let curl = match self.get_download_tool(source) {
+            Some(tool) => tool.is_stock_curl(),
+            None => self.curl_supports(source),
+        };
+        if !curl {
+            return Ok(false);
+        }
+
+        let validators = Validators::load(&dirs.validators_path(source), source)?;
+        Ok(validators.is_some())
+    }
+
+    /// Gets the size of a source, either from the file already on disk or by asking the server.
+    ///
+    /// Returns `None` for VCS sources and when the size can not be determined.
+    pub(crate) fn source_size(&self, dirs: &PkgbuildDirs, source: &Source) -> Option<u64> {
+        if source.vcs_kind().is_some() {
+            return None;
+        }
+
+        if let Some(size) = self.local_source_size(dirs, source) {
+            return Some(size);
+        }
+
+        let curl = match self.get_download_tool(source) {
+            Some(tool) => tool.is_curl(),
+            None => self.curl_supports(source),
+        };
+        if source.is_remote() && curl {
+            curl::remote_size(&self.config, source)
+        } else {
+            None
+        }
+    }
+
+    /// Gets the size of a source that has already been downloaded without touching the network.
+    pub(crate) fn local_source_size(&self, dirs: &PkgbuildDirs, source: &Source) -> Option<u64> {
+        if source.vcs_kind().is_some() {
+            return None;
+        }
+        let metadata = dirs.download_path(source).metadata().ok()?;
+        Some(metadata.len())
+    }
+
     fn curl_supports(&self, source: &Source) -> bool {
         let Some(protocol) = source.protocol() else {
             return false;
@@ -143,11 +213,7 @@ impl Makepkg {
         ::curl::Version::get().protocols().any(|p| p == protocol)
     }
 
-    fn get_download_tool(&self, source: &Source) -> Option<&DownloadAgent> {
-        let download_proto = source.protocol()?;
-        self.config
-            .dl_agents
-            .iter()
-            .find(|a| a.protocol == download_proto)
+    pub(crate) fn get_download_tool(&self, source: &Source) -> Option<&DownloadAgent> {
+        self.config.dl_agent(source.protocol()?)
     }
 }
@@ -1,13 +1,24 @@
-use std::{collections::BTreeMap, fmt::Display, str::FromStr};
+use std::{
+    collections::BTreeMap,
+    fmt::Display,
+    fs::{read_dir, read_link},
+    path::{Path, PathBuf},
+    str::FromStr,
+    thread::sleep,
+    time::Duration,
+};
 
 use crate::{
+    callback::{Event, LogLevel, LogMessage},
     config::PkgbuildDirs,
-    error::{Result, VCSClientError},
+    error::{CommandErrorKind, Context, DownloadError, Error, Result, VCSClientError},
+    fs::{rm_all, rm_file},
     pkgbuild::{Pkgbuild, Source},
     Makepkg, Options,
 };
 
 #[derive(Debug, Copy, Clone, PartialEq, Eq, PartialOrd, Ord, Hash)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize))]
 pub enum VCSKind {
     Git,
     Svn,
@@ -23,6 +34,34 @@ impl Display for VCSKind {
 }
 
 impl VCSKind {
+    /// Whether a failed download may succeed if tried again.
+    ///
+    /// The VCS tools don't tell network errors apart from other errors by their exit code, so
+    /// a failure with the tool's generic error code is only treated as transient when the
+    /// tool's output mentions a known network failure. Authentication failures, missing
+    /// repositories, failing to run the tool at all or being killed by a signal are not.
+    pub(crate) fn is_transient(self, err: &Error, output: &[u8]) -> bool {
+        let code = match self {
+            VCSKind::Git => 128,
+            VCSKind::Svn => 1,
+            VCSKind::Mercurial => 255,
+            VCSKind::Fossil => 1,
+            VCSKind::Bzr => 3,
+        };
+
+        let generic = matches!(
+            err,
+            Error::Download(DownloadError::Command(_, e))
+                if matches!(e.kind, CommandErrorKind::ExitCode(Some(c)) if c == code)
+        );
+        if !generic {
+            return false;
+        }
+
+        let output = String::from_utf8_lossy(output).to_ascii_lowercase();
+        NETWORK_ERRORS.iter().any(|e| output.contains(e))
+    }
+
     pub fn name(&self) -> &'static str {
         match self {
             VCSKind::Git => "git",
@@ -34,6 +73,29 @@ impl VCSKind {
     }
 }
 
+// output from the VCS tools (mostly via libcurl, ssh and the OS) that means the network failed
+// rather than the request
+const NETWORK_ERRORS: &[&str] = &[
+    "could not resolve host",
+    "temporary failure in name resolution",
+    "name or service not known",
+    "failed to connect",
+    "could not connect",
+    "connection refused",
+    "connection reset",
+    "timed out",
+    "network is unreachable",
+    "no route to host",
+    "early eof",
+    "gnutls_handshake",
+    "ssl_connect",
+    "ssl_read",
+    "http/2 stream",
+    "502 bad gateway",
+    "503 service unavailable",
+    "504 gateway",
+];
+
 impl FromStr for VCSKind {
     type Err = VCSClientError;
 
@@ -79,17 +141,159 @@ impl Makepkg {
         pkgbuild: &Pkgbuild,
         sources: &BTreeMap<VCSKind, Vec<&Source>>,
     ) -> Result<()> {
-        for (vcs, sources) in sources {
+        for (&vcs, sources) in sources {
             for &source in sources {
-                match vcs {
-                    VCSKind::Git => self.download_git(dirs, pkgbuild, options, source)?,
-                    VCSKind::Svn => self.download_svn(dirs, pkgbuild, options, source)?,
-                    VCSKind::Mercurial => self.download_hg(dirs, pkgbuild, options, source)?,
-                    VCSKind::Fossil => self.download_fossil(dirs, pkgbuild, options, source)?,
-                    VCSKind::Bzr => self.download_bzr(dirs, pkgbuild, options, source)?,
+                let mut attempt = 0;
+
+                loop {
+                    *self.captured_output.borrow_mut() = Some(Vec::new());
+                    let res = match vcs {
+                        VCSKind::Git => self.download_git(dirs, pkgbuild, options, source),
+                        VCSKind::Svn => self.download_svn(dirs, pkgbuild, options, source),
+                        VCSKind::Mercurial => self.download_hg(dirs, pkgbuild, options, source),
+                        VCSKind::Fossil => self.download_fossil(dirs, pkgbuild, options, source),
+                        VCSKind::Bzr => self.download_bzr(dirs, pkgbuild, options, source),
+                    };
+                    let output = self.captured_output.borrow_mut().take().unwrap_or_default();
+
+                    match res {
+                        Err(e)
+                            if attempt < self.config.vcs_retries
+                                && vcs.is_transient(&e, &output) =>
+                        {
+                            attempt += 1;
+                            let delay = retry_delay(attempt);
+                            self.event(Event::RetryingVCS(vcs, source, attempt, delay))?;
+                            sleep(delay);
+                        }
+                        res => break res?,
+                    }
                 }
             }
         }
         Ok(())
     }
+
+    /// Runs a clone or update of a VCS mirror, cleaning up after it if it fails or is killed.
+    ///
+    /// A failed clone has its partial mirror removed. If [`Options::clean_vcs_locks`] is set,
+    /// lock files that were left behind by earlier interrupted updates and have not been
+    /// touched for an hour are removed before updating and again if the update fails.
+    pub(crate) fn update_mirror<F>(
+        &self,
+        options: &Options,
+        vcs: VCSKind,
+        path: &Path,
+        update: F,
+    ) -> Result<()>
+    where
+        F: FnOnce() -> Result<()>,
+    {
+        let existed = path.exists();
+
+        if existed && options.clean_vcs_locks {
+            self.clean_stale_locks(vcs, path)?;
+        }
+
+        let res = update();
+
+        if res.is_err() {
+            if !existed && path.exists() {
+                rm_all(path, Context::RetrieveSources)?;
+            } else if existed && options.clean_vcs_locks {
+                self.clean_stale_locks(vcs, path)?;
+            }
+        }
+
+        res
+    }
+
+    fn clean_stale_locks(&self, vcs: VCSKind, path: &Path) -> Result<()> {
+        let locks = lock_files(vcs, path)
+            .into_iter()
+            .filter(|l| is_old(l))
+            .collect::<Vec<_>>();
+        if locks.is_empty() || repo_in_use(path) {
+            return Ok(());
+        }
+
+        for lock in locks {
+            self.log(LogLevel::Warning, LogMessage::RemovingStaleLock(&lock))?;
+            rm_file(&lock, Context::RetrieveSources)?;
+        }
+
+        Ok(())
+    }
+}
+
+// 1s, 2s, 4s ... up to a minute
+fn retry_delay(attempt: u32) -> Duration {
+    Duration::from_secs(1 << attempt.saturating_sub(1).min(6))
+}
+
+fn lock_files(vcs: VCSKind, path: &Path) -> Vec<PathBuf> {
+    match vcs {
+        VCSKind::Git => {
+            // mirrors are bare unless GITFLAGS says otherwise
+            let dot_git = path.join(".git");
+            let git_dir = if dot_git.is_dir() { &dot_git } else { path };
+
+            let refs = walkdir::WalkDir::new(git_dir.join("refs"))
+                .into_iter()
+                .filter_map(|e| e.ok())
+                .filter(|e| e.file_type().is_file())
+                .map(|e| e.into_path())
+                .filter(|p| p.extension().is_some_and(|e| e == "lock"));
+
+            ["index.lock", "HEAD.lock", "packed-refs.lock", "gc.pid"]
+                .iter()
+                .map(|l| git_dir.join(l))
+                .filter(|l| l.is_file())
+                .chain(refs)
+                .collect()
+        }
+        VCSKind::Mercurial => [".hg/wlock", ".hg/store/lock"]
+            .iter()
+            .map(|l| path.join(l))
+            .filter(|l| l.symlink_metadata().is_ok())
+            .collect(),
+        _ => Vec::new(),
+    }
+}
+
+// how long a lock has to be left untouched before it's considered stale
+const STALE_LOCK_AGE: Duration = Duration::from_secs(60 * 60);
+
+/// Checks whether a lock file was last modified long enough ago that whatever created it is
+/// assumed to be gone.
+fn is_old(lock: &Path) -> bool {
+    lock.symlink_metadata()
+        .and_then(|m| m.modified())
+        .ok()
+        .and_then(|m| m.elapsed().ok())
+        .is_some_and(|age| age >= STALE_LOCK_AGE)
+}
+
+/// Checks whether any process visible to us has its working directory inside of a
+/// repository.
+///
+/// This is only a best effort check on top of the lock's age. The VCS tools are not always
+/// run from inside the repository and the working directories of other users' processes
+/// can't be read.
+fn repo_in_use(path: &Path) -> bool {
+    let Ok(procs) = read_dir("/proc") else {
+        // can't tell, assume the worst
+        return true;
+    };
+
+    procs
+        .filter_map(|p| p.ok())
+        .filter(|p| {
+            p.file_name()
+                .to_string_lossy()
+                .bytes()
+                .all(|b| b.is_ascii_digit())
+        })
+        .filter_map(|p| read_link(p.path().join("cwd")).ok())
+        .any(|cwd| cwd.starts_with(path))
 }
@@ -3,6 +3,7 @@ use std::io::Write;
 
 use crate::{
     error::{Context, IOContext, IOErrorExt, Result},
+    fs::{rename, write},
     pkgbuild::{ArchVecs, Package, Pkgbuild},
 };
 
@@ -13,90 +14,88 @@ macro_rules! writeln {
     };
 }
 
-impl Pkgbuild {
-    fn write_arch_arrays<W, D>(&self, name: &str, arrs: &ArchVecs<D>, w: &mut W) -> Result<()>
-    where
-        W: Write,
-        D: Display,
-    {
-        for arr in &arrs.values {
-            self.write_arch_val(name, arr.arch.as_deref(), &arr.values, w)?;
-        }
-        Ok(())
+fn write_arch_arrays<W, D>(name: &str, arrs: &ArchVecs<D>, w: &mut W) -> Result<()>
+where
+    W: Write,
+    D: Display,
+{
+    for arr in &arrs.values {
+        write_arch_val(name, arr.arch.as_deref(), &arr.values, w)?;
     }
+    Ok(())
+}
 
-    fn write_arch_val<W, D, I>(&self, n: &str, arch: Option<&str>, arr: I, w: &mut W) -> Result<()>
-    where
-        W: Write,
-        D: Display,
-        I: IntoIterator<Item = D>,
-    {
-        for val in arr {
-            if let Some(arch) = arch {
-                writeln!(w, "\t{}_{} = {}", n, arch, val)?;
-            } else {
-                writeln!(w, "\t{} = {}", n, val)?;
-            }
+fn write_arch_val<W, D, I>(n: &str, arch: Option<&str>, arr: I, w: &mut W) -> Result<()>
+where
+    W: Write,
+    D: Display,
+    I: IntoIterator<Item = D>,
+{
+    for val in arr {
+        if let Some(arch) = arch {
+            writeln!(w, "\t{}_{} = {}", n, arch, val)?;
+        } else {
+            writeln!(w, "\t{} = {}", n, val)?;
         }
-        Ok(())
     }
+    Ok(())
+}
 
-    fn write_val<W, D, I>(&self, name: &str, arr: I, w: &mut W) -> Result<()>
-    where
-        W: Write,
-        D: Display,
-        I: IntoIterator<Item = D>,
-    {
-        self.write_arch_val(name, None, arr, w)
-    }
-
-    fn write_arch_array_overriddes<W: Write, D: Display>(
-        &self,
-        package: &Package,
-        name: &str,
-        arrs: &ArchVecs<D>,
-        w: &mut W,
-    ) -> Result<()> {
-        for arr in &arrs.values {
-            if !package.is_overridden(name, arr.arch.as_deref()) {
-                continue;
-            }
-            let mut arrs = arrs.values.iter().peekable();
-            if arrs.peek().is_none() {
-                writeln!(w, "\t{} =", name)?;
-                break;
-            }
-            self.write_arch_val(name, arr.arch.as_deref(), &arr.values, w)?;
-        }
-
-        Ok(())
-    }
+fn write_val<W, D, I>(name: &str, arr: I, w: &mut W) -> Result<()>
+where
+    W: Write,
+    D: Display,
+    I: IntoIterator<Item = D>,
+{
+    write_arch_val(name, None, arr, w)
+}
 
-    fn write_overriddes<W: Write, D: Display, I: IntoIterator<Item = D>>(
-        &self,
-        package: &Package,
-        name: &str,
-        vals: I,
-        w: &mut W,
-    ) -> Result<()>
-    where
-        I::IntoIter: ExactSizeIterator,
-    {
-        if !package.is_overridden(name, None) {
-            return Ok(());
+fn write_arch_array_overriddes<W: Write, D: Display>(
+    package: &Package,
+    name: &str,
+    arrs: &ArchVecs<D>,
+    w: &mut W,
+) -> Result<()> {
+    for arr in &arrs.values {
+        if !package.is_overridden(name, arr.arch.as_deref()) {
+            continue;
         }
-        let mut vals = vals.into_iter().peekable();
-        if vals.peek().is_none() {
+        let mut arrs = arrs.values.iter().peekable();
+        if arrs.peek().is_none() {
             writeln!(w, "\t{} =", name)?;
-            return Ok(());
+            break;
         }
+        write_arch_val(name, arr.arch.as_deref(), &arr.values, w)?;
+    }
 
-        for val in vals {
-            writeln!(w, "\t{} = {}", name, val)?;
-        }
-        Ok(())
+    Ok(())
+}
+
+fn write_overriddes<W: Write, D: Display, I: IntoIterator<Item = D>>(
+    package: &Package,
+    name: &str,
+    vals: I,
+    w: &mut W,
+) -> Result<()>
+where
+    I::IntoIter: ExactSizeIterator,
+{
+    if !package.is_overridden(name, None) {
+        return Ok(());
+    }
+    let mut vals = vals.into_iter().peekable();
+    if vals.peek().is_none() {
+        writeln!(w, "\t{} =", name)?;
+        return Ok(());
     }
 
+    for val in vals {
+        writeln!(w, "\t{} = {}", name, val)?;
+    }
+    Ok(())
+}
+
+impl Pkgbuild {
     fn write_functions<W: Write>(&self, w: &mut W) -> Result<()> {
         // makepkg doesn'tdo this but i think its useful information to have
         for func in &self.functions {
@@ -111,63 +110,98 @@ impl Pkgbuild {
         String::from_utf8(s).unwrap()
     }
 
+    /// Whether the .SRCINFO next to the PKGBUILD matches the one generated from it.
+    pub fn srcinfo_is_current(&self) -> bool {
+        std::fs::read_to_string(self.dir.join(".SRCINFO")).is_ok_and(|s| s == self.srcinfo())
+    }
+
+    /// Writes .SRCINFO next to the PKGBUILD unless it is already up to date and returns
+    /// whether it was written.
+    ///
+    /// The new file is renamed over the old one so it is never seen half written.
+    pub fn update_srcinfo_file(&self) -> Result<bool> {
+        if self.srcinfo_is_current() {
+            return Ok(false);
+        }
+        let path = self.dir.join(".SRCINFO");
+        let tmp = self.dir.join(".SRCINFO.tmp");
+        write(&tmp, self.srcinfo(), Context::WriteSrcinfo)?;
+        if let Err(e) = rename(&tmp, &path, Context::WriteSrcinfo) {
+            let _ = std::fs::remove_file(&tmp);
+            return Err(e);
+        }
+        Ok(true)
+    }
+
     pub fn write_srcinfo<W: Write>(&self, w: &mut W) -> Result<()> {
+        self.write_srcinfo_base(w)?;
+
+        for package in &self.packages {
+            std::writeln!(w).context(Context::GenerateSrcinfo, IOContext::WriteBuffer)?;
+            package.write_srcinfo_fragment(w)?;
+        }
+
+        Ok(())
+    }
+
+    /// Writes the `pkgbase` section of the .SRCINFO without any of the `pkgname` sections.
+    pub fn write_srcinfo_base<W: Write>(&self, w: &mut W) -> Result<()> {
         writeln!(w, "pkgbase = {}", self.pkgbase)?;
-        self.write_val("pkgdesc", &self.pkgdesc, w)?;
+        write_val("pkgdesc", &self.pkgdesc, w)?;
         writeln!(w, "\tpkgver = {}", self.pkgver)?;
         writeln!(w, "\tpkgrel = {}", self.pkgrel)?;
-        self.write_val("epoch", &self.epoch, w)?;
-        self.write_val("url", &self.url, w)?;
-        self.write_val("install", &self.install, w)?;
-        self.write_val("changelog", &self.changelog, w)?;
-        self.write_val("arch", &self.arch, w)?;
-        self.write_val("groups", &self.groups, w)?;
-        self.write_val("license", &self.license, w)?;
-        self.write_arch_arrays("checkdepends", &self.checkdepends, w)?;
-        self.write_arch_arrays("makedepends", &self.makedepends, w)?;
-        self.write_arch_arrays("depends", &self.depends, w)?;
-        self.write_arch_arrays("optdepends", &self.optdepends, w)?;
-        self.write_arch_arrays("provides", &self.provides, w)?;
-        self.write_arch_arrays("conflicts", &self.conflicts, w)?;
-        self.write_arch_arrays("replaces", &self.replaces, w)?;
-        self.write_val("noextract", &self.noextract, w)?;
-        self.write_val("options", &self.options.values, w)?;
-        self.write_val("backup", &self.backup, w)?;
-        self.write_arch_arrays("source", &self.source, w)?;
-        self.write_val("validpgpkeys", &self.validpgpkeys, w)?;
-        self.write_arch_arrays("md5sums", &self.md5sums, w)?;
-        self.write_arch_arrays("sha1sums", &self.sha1sums, w)?;
-        self.write_arch_arrays("sha224sums", &self.sha224sums, w)?;
-        self.write_arch_arrays("sha256sums", &self.sha256sums, w)?;
-        self.write_arch_arrays("sha384sums", &self.sha384sums, w)?;
-        self.write_arch_arrays("sha512sums", &self.sha512sums, w)?;
-        self.write_arch_arrays("b2sums", &self.b2sums, w)?;
+        write_val("epoch", &self.epoch, w)?;
+        write_val("url", &self.url, w)?;
+        write_val("install", &self.install, w)?;
+        write_val("changelog", &self.changelog, w)?;
+        write_val("arch", &self.arch, w)?;
+        write_val("groups", &self.groups, w)?;
+        write_val("license", &self.license, w)?;
+        write_arch_arrays("checkdepends", &self.checkdepends, w)?;
+        write_arch_arrays("makedepends", &self.makedepends, w)?;
+        write_arch_arrays("depends", &self.depends, w)?;
+        write_arch_arrays("optdepends", &self.optdepends, w)?;
+        write_arch_arrays("provides", &self.provides, w)?;
+        write_arch_arrays("conflicts", &self.conflicts, w)?;
+        write_arch_arrays("replaces", &self.replaces, w)?;
+        write_val("noextract", &self.noextract, w)?;
+        write_val("options", &self.options.values, w)?;
+        write_val("backup", &self.backup, w)?;
+        write_arch_arrays("source", &self.source, w)?;
+        write_val("validpgpkeys", &self.validpgpkeys, w)?;
+        write_arch_arrays("md5sums", &self.md5sums, w)?;
+        write_arch_arrays("sha1sums", &self.sha1sums, w)?;
+        write_arch_arrays("sha224sums", &self.sha224sums, w)?;
+        write_arch_arrays("sha256sums", &self.sha256sums, w)?;
+        write_arch_arrays("sha384sums", &self.sha384sums, w)?;
+        write_arch_arrays("sha512sums", &self.sha512sums, w)?;
+        write_arch_arrays("b2sums", &self.b2sums, w)?;
 
         self.write_functions(w)?;
-
-        for package in &self.packages {
-            self.write_srcinfo_pkg(package, w)?;
-        }
-
         Ok(())
     }
+}
 
-    fn write_srcinfo_pkg<W: Write>(&self, pkg: &Package, w: &mut W) -> Result<()> {
-        writeln!(w, "\npkgname = {}", pkg.pkgname)?;
-        self.write_overriddes(pkg, "pkgdesc", &pkg.pkgdesc, w)?;
-        self.write_overriddes(pkg, "url", &pkg.url, w)?;
-        self.write_overriddes(pkg, "install", &pkg.install, w)?;
-        self.write_overriddes(pkg, "changelog", &pkg.changelog, w)?;
-        self.write_overriddes(pkg, "arch", &pkg.arch, w)?;
-        self.write_overriddes(pkg, "groups", &pkg.groups, w)?;
-        self.write_overriddes(pkg, "license", &pkg.license, w)?;
-        self.write_arch_array_overriddes(pkg, "depends", &pkg.depends, w)?;
-        self.write_arch_array_overriddes(pkg, "optdepends", &pkg.optdepends, w)?;
-        self.write_arch_array_overriddes(pkg, "provides", &pkg.provides, w)?;
-        self.write_arch_array_overriddes(pkg, "conflicts", &pkg.conflicts, w)?;
-        self.write_arch_array_overriddes(pkg, "replaces", &pkg.replaces, w)?;
-        self.write_overriddes(pkg, "options", &pkg.options.values, w)?;
-        self.write_overriddes(pkg, "backup", &pkg.backup, w)?;
+impl Package {
+    /// Writes the `pkgname` section of the .SRCINFO for this package.
+    ///
+    /// Only values the package overrides from the `pkgbase` section are written.
+    pub fn write_srcinfo_fragment<W: Write>(&self, w: &mut W) -> Result<()> {
+        writeln!(w, "pkgname = {}", self.pkgname)?;
+        write_overriddes(self, "pkgdesc", &self.pkgdesc, w)?;
+        write_overriddes(self, "url", &self.url, w)?;
+        write_overriddes(self, "install", &self.install, w)?;
+        write_overriddes(self, "changelog", &self.changelog, w)?;
+        write_overriddes(self, "arch", &self.arch, w)?;
+        write_overriddes(self, "groups", &self.groups, w)?;
+        write_overriddes(self, "license", &self.license, w)?;
+        write_arch_array_overriddes(self, "depends", &self.depends, w)?;
+        write_arch_array_overriddes(self, "optdepends", &self.optdepends, w)?;
+        write_arch_array_overriddes(self, "provides", &self.provides, w)?;
+        write_arch_array_overriddes(self, "conflicts", &self.conflicts, w)?;
+        write_arch_array_overriddes(self, "replaces", &self.replaces, w)?;
+        write_overriddes(self, "options", &self.options.values, w)?;
+        write_overriddes(self, "backup", &self.backup, w)?;
         Ok(())
     }
 }
@@ -0,0 +1,93 @@
+use std::{
+    collections::BTreeSet,
+    path::{Path, PathBuf},
+    process::Command,
+};
+
+use crate::{
+    error::{CommandOutputExt, Context, Error, Result},
+    pkgbuild::Pkgbuild,
+    Makepkg,
+};
+
+/// What [`Makepkg::update_srcinfos`] did to each changed PKGBUILD.
+#[derive(Debug, Default)]
+pub struct SrcinfoReport {
+    /// Directories whose .SRCINFO was written.
+    pub updated: Vec<PathBuf>,
+    /// Directories whose .SRCINFO was already up to date.
+    pub unchanged: Vec<PathBuf>,
+    /// Directories whose PKGBUILD was deleted.
+    pub removed: Vec<PathBuf>,
+    /// Directories whose PKGBUILD could not be loaded.
+    pub failed: Vec<(PathBuf, Error)>,
+}
+
+impl Makepkg {
+    /// Regenerates .SRCINFO for every PKGBUILD under `root` that changed since the git
+    /// revision `since`, including uncommitted and untracked PKGBUILDs.
+    ///
+    /// A PKGBUILD that fails to load is recorded in the report instead of stopping the
+    /// update.
+    pub fn update_srcinfos<P: AsRef<Path>>(&self, root: P, since: &str) -> Result<SrcinfoReport> {
+        let root = root.as_ref();
+        let mut report = SrcinfoReport::default();
+
+        for dir in changed_pkgbuilds(root, since)? {
+            let dir = root.join(dir);
+            if !dir.join(Pkgbuild::file_name()).exists() {
+                report.removed.push(dir);
+                continue;
+            }
+
+            let pkgbuild = match self.load_pkgbuild(&dir) {
+                Ok(pkgbuild) => pkgbuild,
+                Err(e) => {
+                    report.failed.push((dir, e));
+                    continue;
+                }
+            };
+
+            if pkgbuild.update_srcinfo_file()? {
+                report.updated.push(dir);
+            } else {
+                report.unchanged.push(dir);
+            }
+        }
+
+        Ok(report)
+    }
+}
+
+// directories relative to root containing a PKGBUILD that differs from the revision
+fn changed_pkgbuilds(root: &Path, since: &str) -> Result<BTreeSet<PathBuf>> {
+    let mut diff = Command::new("git");
+    diff.arg("-C")
+        .arg(root)
+        .arg("diff")
+        .arg("--name-only")
+        .arg("--relative")
+        .arg(since)
+        .arg("--");
+    let diff = diff.output().read(&diff, Context::UpdateSrcinfo)?;
+
+    let mut untracked = Command::new("git");
+    untracked
+        .arg("-C")
+        .arg(root)
+        .arg("ls-files")
+        .arg("--others")
+        .arg("--exclude-standard");
+    let untracked = untracked
+        .output()
+        .read(&untracked, Context::UpdateSrcinfo)?;
+
+    let dirs = diff
+        .lines()
+        .chain(untracked.lines())
+        .map(Path::new)
+        .filter(|p| p.file_name().is_some_and(|f| f == Pkgbuild::file_name()))
+        .map(|p| p.parent().unwrap_or(Path::new("")).to_path_buf())
+        .collect();
+    Ok(dirs)
+}
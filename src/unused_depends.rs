@@ -0,0 +1,129 @@
+use std::{collections::BTreeSet, ffi::OsString, path::Path, time::SystemTime};
+
+use walkdir::WalkDir;
+
+use crate::{
+    callback::Event,
+    config::PkgbuildDirs,
+    error::{Context, IOContext, IOErrorExt, Result},
+    pkgbuild::{Depend, Pkgbuild},
+    soname::{dynamic_entries, elf_class, ET_DYN, ET_EXEC},
+    Makepkg,
+};
+
+// directories holding commands the PKGBUILD may call by name
+static BIN_DIRS: &[&str] = &["usr/bin/", "usr/sbin/", "bin/", "sbin/"];
+
+impl Makepkg {
+    /// Suggests makedepends and checkdepends that do not look to have been used by the build.
+    ///
+    /// Nothing traces what the build actually runs so this is a heuristic. A dependency counts
+    /// as used if any of its files were accessed since `started`, its name or one of its
+    /// commands is mentioned in the PKGBUILD's functions or a packaged binary links against
+    /// one of its libraries.
+    pub(crate) fn suggest_unused_depends(
+        &self,
+        dirs: &PkgbuildDirs,
+        pkgbuild: &Pkgbuild,
+        started: SystemTime,
+        checked: bool,
+    ) -> Result<()> {
+        let arch = &self.config.arch;
+        let depends = pkgbuild
+            .packages()
+            .flat_map(|p| p.depends.enabled(arch))
+            .map(|d| Depend::name_of(d))
+            .collect::<BTreeSet<_>>();
+        let words = pkgbuild
+            .function_sources()
+            .iter()
+            .flat_map(|f| {
+                f.body
+                    .split(|c: char| !c.is_alphanumeric() && !"_-.+".contains(c))
+                    .filter(|w| !w.is_empty())
+            })
+            .collect::<BTreeSet<_>>();
+
+        let mut vars = vec![("makedepends", &pkgbuild.makedepends)];
+        if checked {
+            vars.push(("checkdepends", &pkgbuild.checkdepends));
+        }
+
+        let mut linked = None;
+
+        for (var, deps) in vars {
+            for dep in deps.enabled(arch) {
+                let name = Depend::name_of(dep);
+                if depends.contains(name) || words.contains(name) {
+                    continue;
+                }
+                let Some(files) = self.installed_files(name) else {
+                    continue;
+                };
+                if files
+                    .iter()
+                    .any(|f| is_command(f, &words) || accessed(f, started))
+                {
+                    continue;
+                }
+
+                let linked = match &mut linked {
+                    Some(linked) => linked,
+                    None => linked.insert(self.linked_libs(dirs, pkgbuild)?),
+                };
+                if files
+                    .iter()
+                    .filter_map(|f| f.file_name())
+                    .any(|f| linked.contains(f))
+                {
+                    continue;
+                }
+
+                self.event(Event::PossiblyUnusedDepend(var, dep))?;
+            }
+        }
+
+        Ok(())
+    }
+
+    // the libraries every packaged binary links against
+    fn linked_libs(&self, dirs: &PkgbuildDirs, pkgbuild: &Pkgbuild) -> Result<BTreeSet<OsString>> {
+        let mut libs = BTreeSet::new();
+
+        for file in WalkDir::new(&dirs.pkgdir) {
+            let file = file.context(
+                Context::GetPackageFiles,
+                IOContext::ReadDir(dirs.pkgdir.clone()),
+            )?;
+            if !file.file_type().is_file()
+                || elf_class(file.path(), ET_DYN)
+                    .or_else(|| elf_class(file.path(), ET_EXEC))
+                    .is_none()
+            {
+                continue;
+            }
+
+            let dynamic = self.elf_dynamic(pkgbuild, file.path())?;
+            libs.extend(dynamic_entries(&dynamic, "Shared library").map(OsString::from));
+        }
+
+        Ok(libs)
+    }
+}
+
+fn is_command(path: &Path, words: &BTreeSet<&str>) -> bool {
+    let path = path.to_string_lossy();
+    let path = path.trim_start_matches('/');
+    BIN_DIRS.iter().any(|dir| {
+        path.strip_prefix(dir)
+            .is_some_and(|cmd| !cmd.is_empty() && !cmd.contains('/') && words.contains(cmd))
+    })
+}
+
+fn accessed(path: &Path, started: SystemTime) -> bool {
+    path.symlink_metadata()
+        .ok()
+        .filter(|m| m.is_file())
+        .and_then(|m| m.accessed().ok())
+        .is_some_and(|t| t >= started)
+}
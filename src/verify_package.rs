@@ -0,0 +1,415 @@
+use std::{
+    collections::{BTreeMap, HashMap},
+    fmt::Display,
+    fs::File,
+    path::{Path, PathBuf},
+    process::Command,
+};
+
+use crate::{
+    callback::{Event, SigFailedKind},
+    error::{CommandErrorExt, Context, Result},
+    fs::{open, read_to_string, rm_all_writable, temp_dir},
+    integ::{SigStatus, Verifier},
+    package_contents::{extract_package, parse_pkginfo, read_members, Member, MemberKind},
+    pkgbuild::Pkgbuild,
+    run::CommandOutput,
+    Makepkg,
+};
+
+/// The outcome of [`Makepkg::verify_package`].
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize))]
+pub struct PackageVerification {
+    pub path: PathBuf,
+    /// The package has a detached signature next to it.
+    pub signed: bool,
+    pub problems: Vec<PackageProblem>,
+}
+
+impl PackageVerification {
+    /// Whether no problems were found.
+    pub fn is_ok(&self) -> bool {
+        self.problems.is_empty()
+    }
+}
+
+impl Display for PackageVerification {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let file = self.path.file_name().unwrap_or_default().to_string_lossy();
+        if self.is_ok() {
+            return write!(f, "{}: ok", file);
+        }
+        write!(f, "{}:", file)?;
+        for problem in &self.problems {
+            write!(f, "\n    {}", problem)?;
+        }
+        Ok(())
+    }
+}
+
+/// Something wrong with a package file found by [`Makepkg::verify_package`].
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize))]
+pub enum PackageProblem {
+    /// The package has no .PKGINFO.
+    MissingPkginfo,
+    /// The package has no .MTREE.
+    MissingMtree,
+    /// The pkgname in .PKGINFO is not built by the PKGBUILD.
+    UnknownPackage(String),
+    /// A .PKGINFO field does not match the PKGBUILD.
+    Pkginfo {
+        key: String,
+        expected: Vec<String>,
+        found: Vec<String>,
+    },
+    /// A file listed in .MTREE is not in the archive.
+    MissingFile(PathBuf),
+    /// A file in the archive is not listed in .MTREE.
+    UnlistedFile(PathBuf),
+    /// A .MTREE keyword does not match the file in the archive.
+    Mtree {
+        path: PathBuf,
+        key: String,
+        expected: String,
+        found: String,
+    },
+    /// The detached signature did not verify.
+    Signature { fingerprint: String, reason: String },
+}
+
+impl Display for PackageProblem {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            PackageProblem::MissingPkginfo => f.write_str("missing .PKGINFO"),
+            PackageProblem::MissingMtree => f.write_str("missing .MTREE"),
+            PackageProblem::UnknownPackage(name) => {
+                write!(f, "{} is not a package of this PKGBUILD", name)
+            }
+            PackageProblem::Pkginfo {
+                key,
+                expected,
+                found,
+            } => write!(
+                f,
+                ".PKGINFO {} is '{}' but should be '{}'",
+                key,
+                found.join(" "),
+                expected.join(" ")
+            ),
+            PackageProblem::MissingFile(path) => {
+                write!(f, "{} is in .MTREE but not the package", path.display())
+            }
+            PackageProblem::UnlistedFile(path) => {
+                write!(f, "{} is in the package but not .MTREE", path.display())
+            }
+            PackageProblem::Mtree {
+                path,
+                key,
+                expected,
+                found,
+            } => write!(
+                f,
+                "{} has {} {} but .MTREE says {}",
+                path.display(),
+                key,
+                found,
+                expected
+            ),
+            PackageProblem::Signature {
+                fingerprint,
+                reason,
+            } => write!(f, "signature from {} failed ({})", fingerprint, reason),
+        }
+    }
+}
+
+impl Makepkg {
+    /// Checks a built package file against the [`Pkgbuild`] it was built from.
+    ///
+    /// The .PKGINFO fields are compared with what the PKGBUILD would produce, every .MTREE
+    /// entry is compared with the archive's contents and the detached `.sig` is verified if
+    /// there is one. Problems are returned in the [`PackageVerification`], errors are only
+    /// returned if the package could not be read.
+    pub fn verify_package(&self, pkgbuild: &Pkgbuild, path: &Path) -> Result<PackageVerification> {
+        let file = path.file_name().unwrap_or_default().to_string_lossy();
+        self.event(Event::VerifyingPackage(&file))?;
+
+        let tmp = temp_dir("makepkg-verify-", Context::VerifyPackage)?;
+        let res = self.verify_extracted(pkgbuild, path, tmp.path());
+        rm_all_writable(tmp.path(), Context::VerifyPackage)?;
+        res
+    }
+
+    fn verify_extracted(
+        &self,
+        pkgbuild: &Pkgbuild,
+        path: &Path,
+        tmp: &Path,
+    ) -> Result<PackageVerification> {
+        extract_package(path, tmp, Context::VerifyPackage)?;
+        let mut members = read_members(tmp, Context::VerifyPackage)?;
+        members.remove(Path::new(".MTREE"));
+
+        let mut verification = PackageVerification {
+            path: path.to_path_buf(),
+            ..Default::default()
+        };
+        let problems = &mut verification.problems;
+
+        let pkginfo = tmp.join(".PKGINFO");
+        if pkginfo.exists() {
+            let pkginfo = parse_pkginfo(&read_to_string(&pkginfo, Context::VerifyPackage)?);
+            self.check_pkginfo(pkgbuild, &pkginfo, problems);
+        } else {
+            problems.push(PackageProblem::MissingPkginfo);
+        }
+
+        let mtree = tmp.join(".MTREE");
+        if mtree.exists() {
+            let mut command = Command::new("gzip");
+            let output = command
+                .arg("-dc")
+                .arg(&mtree)
+                .process_output()
+                .cmd_context(&command, Context::VerifyPackage)?;
+            let entries = parse_mtree(&String::from_utf8_lossy(&output.stdout));
+            check_mtree(&entries, &members, problems);
+        } else {
+            problems.push(PackageProblem::MissingMtree);
+        }
+
+        let mut sig = path.as_os_str().to_os_string();
+        sig.push(".sig");
+        let sig = PathBuf::from(sig);
+        if sig.exists() {
+            verification.signed = true;
+            self.check_package_sig(path, &sig, &mut verification.problems)?;
+        }
+
+        Ok(verification)
+    }
+
+    fn check_pkginfo(
+        &self,
+        pkgbuild: &Pkgbuild,
+        pkginfo: &BTreeMap<String, Vec<String>>,
+        problems: &mut Vec<PackageProblem>,
+    ) {
+        let config = &self.config;
+        let arch = &config.arch;
+        let mut expected = vec![
+            ("pkgbase", vec![pkgbuild.pkgbase.clone()]),
+            ("pkgver", vec![config.package_version(pkgbuild)]),
+        ];
+
+        let pkgname = pkginfo
+            .get("pkgname")
+            .and_then(|n| n.first())
+            .cloned()
+            .unwrap_or_default();
+        let debug = format!("{}-debug", pkgbuild.pkgbase);
+        if let Some(pkg) = pkgbuild.packages().find(|p| p.pkgname == pkgname) {
+            let strings = |v: &[String]| v.to_vec();
+            expected.extend([
+                ("arch", vec![config.package_arch(&pkg.arch).to_string()]),
+                ("pkgdesc", pkg.pkgdesc.iter().cloned().collect()),
+                ("url", pkg.url.iter().cloned().collect()),
+                ("license", strings(&pkg.license)),
+                ("replaces", pkg.replaces.enabled(arch).cloned().collect()),
+                ("group", strings(&pkg.groups)),
+                ("conflict", pkg.conflicts.enabled(arch).cloned().collect()),
+                ("backup", strings(&pkg.backup)),
+                ("depend", pkg.depends.enabled(arch).cloned().collect()),
+                ("optdepend", pkg.optdepends.enabled(arch).cloned().collect()),
+                (
+                    "makedepend",
+                    pkgbuild.makedepends.enabled(arch).cloned().collect(),
+                ),
+                (
+                    "checkdepend",
+                    pkgbuild.checkdepends.enabled(arch).cloned().collect(),
+                ),
+            ]);
+
+            // soname provides are added to the ones in the PKGBUILD
+            let provides = pkginfo.get("provides").cloned().unwrap_or_default();
+            let wanted = pkg.provides.enabled(arch).cloned().collect::<Vec<_>>();
+            if wanted.iter().any(|p| !provides.contains(p)) {
+                problems.push(PackageProblem::Pkginfo {
+                    key: "provides".to_string(),
+                    expected: wanted,
+                    found: provides,
+                });
+            }
+        } else if pkgname == debug {
+            let arch = config.package_arch(&pkgbuild.arch).to_string();
+            expected.push(("arch", vec![arch]));
+        } else {
+            problems.push(PackageProblem::UnknownPackage(pkgname));
+            return;
+        }
+
+        for (key, mut expected) in expected {
+            let mut found = pkginfo.get(key).cloned().unwrap_or_default();
+            expected.sort();
+            found.sort();
+            if expected != found {
+                problems.push(PackageProblem::Pkginfo {
+                    key: key.to_string(),
+                    expected,
+                    found,
+                });
+            }
+        }
+    }
+
+    fn check_package_sig(
+        &self,
+        path: &Path,
+        sig: &Path,
+        problems: &mut Vec<PackageProblem>,
+    ) -> Result<()> {
+        let file = path.file_name().unwrap_or_default().to_string_lossy();
+        let mut gpg = Verifier::new(&self.config)?;
+        let sig = open(File::options().read(true), sig, Context::VerifyPackage)?;
+        let data = open(File::options().read(true), path, Context::VerifyPackage)?;
+
+        for sig in gpg.verify_detached(&file, sig.into(), data.into())? {
+            let reason = match &sig.status {
+                SigStatus::Good(true) => continue,
+                SigStatus::Good(false) => SigFailedKind::NotTrusted,
+                SigStatus::KeyMissing => SigFailedKind::UnknownPublicKey,
+                SigStatus::Revoked => SigFailedKind::Revoked,
+                SigStatus::Expired => SigFailedKind::Expired,
+                SigStatus::Bad(e) => SigFailedKind::Other(e),
+            };
+            problems.push(PackageProblem::Signature {
+                fingerprint: sig.fingerprint.clone(),
+                reason: reason.to_string(),
+            });
+        }
+        Ok(())
+    }
+}
+
+// the keywords of every path in an mtree file with /set defaults applied
+fn parse_mtree(mtree: &str) -> BTreeMap<PathBuf, HashMap<String, String>> {
+    let mut defaults = HashMap::new();
+    let mut entries = BTreeMap::new();
+
+    for line in mtree.lines() {
+        let mut words = line.split_whitespace();
+        let Some(first) = words.next() else {
+            continue;
+        };
+        let keywords = words.filter_map(|w| w.split_once('='));
+        match first {
+            "/set" => {
+                defaults.extend(keywords.map(|(k, v)| (k.to_string(), v.to_string())));
+            }
+            "/unset" => {
+                for word in line.split_whitespace().skip(1) {
+                    defaults.remove(word);
+                }
+            }
+            _ if first.starts_with('#') => (),
+            _ => {
+                let path = unescape(first);
+                let path = path.strip_prefix("./").unwrap_or(&path);
+                if path == "." {
+                    continue;
+                }
+                let mut entry = defaults.clone();
+                entry.extend(keywords.map(|(k, v)| (k.to_string(), v.to_string())));
+                entries.insert(PathBuf::from(path), entry);
+            }
+        }
+    }
+
+    entries
+}
+
+// mtree escapes unusual characters as \ and three octal digits
+fn unescape(s: &str) -> String {
+    let bytes = s.as_bytes();
+    let mut out = Vec::with_capacity(bytes.len());
+    let mut i = 0;
+    while i < bytes.len() {
+        if bytes[i] == b'\\' {
+            if let Some(c) = bytes
+                .get(i + 1..i + 4)
+                .and_then(|o| std::str::from_utf8(o).ok())
+                .and_then(|o| u8::from_str_radix(o, 8).ok())
+            {
+                out.push(c);
+                i += 4;
+                continue;
+            }
+        }
+        out.push(bytes[i]);
+        i += 1;
+    }
+    String::from_utf8_lossy(&out).into_owned()
+}
+
+fn check_mtree(
+    entries: &BTreeMap<PathBuf, HashMap<String, String>>,
+    members: &BTreeMap<PathBuf, Member>,
+    problems: &mut Vec<PackageProblem>,
+) {
+    for (path, entry) in entries {
+        let Some(member) = members.get(path) else {
+            problems.push(PackageProblem::MissingFile(path.clone()));
+            continue;
+        };
+
+        let (kind, digest, link) = match &member.kind {
+            MemberKind::File(sum) => ("file", Some(sum.clone()), None),
+            MemberKind::Dir => ("dir", None, None),
+            MemberKind::Symlink(target) => ("link", None, Some(target.display().to_string())),
+        };
+        let mut found = vec![
+            ("type", kind.to_string()),
+            ("mode", format!("{:o}", member.mode)),
+            ("time", member.mtime.to_string()),
+        ];
+        if let Some(digest) = digest {
+            found.push(("size", member.size.to_string()));
+            found.push(("sha256digest", digest));
+        }
+        if let Some(link) = link {
+            found.push(("link", link));
+        }
+
+        for (key, found) in found {
+            let Some(expected) = entry.get(key) else {
+                continue;
+            };
+            let expected = match key {
+                // only whole seconds are kept when extracting
+                "time" => expected.split('.').next().unwrap_or_default().to_string(),
+                "mode" => u32::from_str_radix(expected, 8)
+                    .map(|m| format!("{:o}", m))
+                    .unwrap_or_else(|_| expected.clone()),
+                "link" => unescape(expected),
+                _ => expected.clone(),
+            };
+            if expected != found {
+                problems.push(PackageProblem::Mtree {
+                    path: path.clone(),
+                    key: key.to_string(),
+                    expected,
+                    found,
+                });
+            }
+        }
+    }
+
+    for path in members.keys() {
+        if !entries.contains_key(path) {
+            problems.push(PackageProblem::UnlistedFile(path.clone()));
+        }
+    }
+}